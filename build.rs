@@ -0,0 +1,38 @@
+//! Embeds every ROM in `assets/roms` so the start menu can offer them
+//! without any files next to the binary.
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    let dir = Path::new("assets/roms");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .expect("assets/roms should exist")
+        .map(|entry| entry.expect("assets/roms should be readable").path())
+        .filter(|path| path.is_file() && path.extension().is_none_or(|ext| ext != "md"))
+        .collect();
+    paths.sort();
+
+    let mut seen: Vec<Vec<u8>> = vec![];
+    let mut roms = String::from("pub const ROMS: &[(&str, &[u8])] = &[\n");
+    for path in paths {
+        let bytes = fs::read(&path).expect("ROM should be readable");
+        // some ROMs are checked in twice under different names
+        if bytes.is_empty() || seen.contains(&bytes) {
+            continue;
+        }
+        seen.push(bytes);
+
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let full_path = fs::canonicalize(&path).unwrap();
+        roms.push_str(&format!(
+            "    ({:?}, include_bytes!({:?})),\n",
+            name, full_path
+        ));
+    }
+    roms.push_str("];\n");
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("roms.rs");
+    fs::write(out, roms).unwrap();
+}
@@ -0,0 +1,65 @@
+//! How fast sprite-heavy programs run: a loop that does nothing but draw
+//! font sprites, the worst case for DRW. Also what reading the display
+//! each frame costs, unpacked by `pixels` or packed by `rows`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use chip8_core::{
+    interpreter::{is_lit, HIRES_SCREEN_HEIGHT},
+    Interpreter, SCREEN_WIDTH,
+};
+
+/// draw every hex digit of the font across the screen, then jump back to the start
+fn sprite_loop() -> Vec<u8> {
+    let mut rom = vec![];
+    for digit in 0..16u8 {
+        // LD V0, digit; LD F, V0; LD V1, digit * 4; DRW V1, V2, 5
+        rom.extend([0x60, digit, 0xF0, 0x29, 0x61, digit * 4, 0xD1, 0x25]);
+    }
+    rom.extend([0x12, 0x00]); // JP 0x200
+    rom
+}
+
+fn drw(c: &mut Criterion) {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_program(&sprite_loop()).unwrap();
+    c.bench_function("sprite loop, 1000 instructions", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                interpreter.step().unwrap();
+            }
+        })
+    });
+}
+
+/// count the lit pixels the way a frontend visits them when drawing a frame
+fn read_display(c: &mut Criterion) {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_program(&sprite_loop()).unwrap();
+    for _ in 0..1000 {
+        interpreter.step().unwrap();
+    }
+    let mut group = c.benchmark_group("reading the display");
+    group.bench_function("pixels", |b| {
+        b.iter(|| {
+            black_box(interpreter.pixels())
+                .iter()
+                .filter(|on| **on)
+                .count()
+        })
+    });
+    group.bench_function("rows", |b| {
+        b.iter(|| {
+            let rows = black_box(interpreter.rows());
+            (0..SCREEN_WIDTH * HIRES_SCREEN_HEIGHT)
+                .filter(|idx| is_lit(rows, idx % SCREEN_WIDTH, idx / SCREEN_WIDTH))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, drw, read_display);
+criterion_main!(benches);
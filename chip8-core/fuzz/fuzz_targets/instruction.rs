@@ -0,0 +1,10 @@
+//! One instruction, run with arbitrary quirks, registers, and I.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (u8, u16, [u8; 16], u16)| {
+    let (flags, word, registers, index) = input;
+    chip8_core::fuzz::run_instruction(flags, word, registers, index);
+});
@@ -0,0 +1,8 @@
+//! Like `rom`, but with MegaChip on and the ROM ending at the end of the
+//! 64 KiB the pc can reach.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| chip8_core::fuzz::run_large_rom(data));
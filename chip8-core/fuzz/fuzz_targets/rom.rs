@@ -0,0 +1,7 @@
+//! A byte of quirk flags and two of held keys, then a ROM to run.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| chip8_core::fuzz::run_rom(data));
@@ -0,0 +1,127 @@
+//! What can go wrong while loading or running a program.
+
+use std::{error::Error, fmt, io};
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// the word at `addr` isn't an instruction this interpreter knows
+    InvalidOpcode {
+        addr: u16,
+        word: u16,
+    },
+    /// CALL at `addr` with every stack level in use; `stack` is the call stack at the time
+    StackOverflow {
+        addr: u16,
+        stack: Vec<u16>,
+    },
+    /// RET at `addr` with nothing to return to
+    StackUnderflow {
+        addr: u16,
+    },
+    /// the instruction at `addr` reached past the end of memory, to `target`
+    MemoryOutOfBounds {
+        addr: u16,
+        target: usize,
+    },
+    /// a ROM of `size` bytes doesn't fit in the `max` bytes after the program start
+    RomTooLarge {
+        size: usize,
+        max: usize,
+    },
+    /// in strict mode, the instruction `word` at `addr` isn't portable
+    Strict {
+        addr: u16,
+        word: u16,
+        violation: StrictViolation,
+    },
+    Io(io::Error),
+}
+
+/// Why strict mode stopped on an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrictViolation {
+    /// SYS to machine code at `target`, which only the original hardware could run
+    Sys { target: u16 },
+    /// a write to `first..=last`, below the program start, with I at `i`
+    ReservedWrite { first: u16, last: u16, i: u16 },
+    /// a word no documented interpreter gives a meaning
+    Undocumented,
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::InvalidOpcode { addr, word } => {
+                write!(f, "invalid opcode {:04X} at {:#05x}", word, addr)
+            }
+            Chip8Error::StackOverflow { addr, stack } => {
+                write!(f, "stack overflow at {:#05x}, call stack:", addr)?;
+                for frame in stack {
+                    write!(f, " {:#05x}", frame)?;
+                }
+                Ok(())
+            }
+            Chip8Error::StackUnderflow { addr } => {
+                write!(f, "return with an empty stack at {:#05x}", addr)
+            }
+            Chip8Error::MemoryOutOfBounds { addr, target } => {
+                write!(
+                    f,
+                    "access to {:#x} past the end of memory at {:#05x}",
+                    target, addr
+                )
+            }
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, but only {} fit in memory", size, max)
+            }
+            Chip8Error::Strict {
+                addr,
+                word,
+                violation,
+            } => {
+                write!(f, "strict: {:04X} at {:#05x} ", word, addr)?;
+                match violation {
+                    StrictViolation::Sys { target } => write!(
+                        f,
+                        "calls machine code at {:#05x} (SYS), which only the COSMAC VIP could run; \
+                         every other interpreter ignores it",
+                        target
+                    ),
+                    StrictViolation::ReservedWrite { first, last, i } => {
+                        let range = if first == last {
+                            format!("{:#05x}", first)
+                        } else {
+                            format!("{:#05x}-{:#05x}", first, last)
+                        };
+                        write!(
+                            f,
+                            "writes to {} with I at {:#05x}, in the memory below 0x200 \
+                             that interpreters keep for themselves",
+                            range, i
+                        )
+                    }
+                    StrictViolation::Undocumented => write!(
+                        f,
+                        "isn't a documented instruction, so interpreters disagree on what it does"
+                    ),
+                }
+            }
+            Chip8Error::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
@@ -0,0 +1,186 @@
+//! Entry points for the `cargo fuzz` targets in `fuzz/`, which turn
+//! arbitrary bytes into ROMs and interpreter settings and run them. Bad
+//! input may fail with a [`Chip8Error`](crate::Chip8Error) but must never
+//! panic. Not part of the supported API.
+
+use crate::{interpreter::PROGRAM_START, Interpreter, Quirks};
+
+/// most instructions a ROM runs for, so looping ROMs still finish
+const MAX_STEPS: usize = 4096;
+/// instructions between timer ticks
+const STEPS_PER_TICK: usize = 16;
+/// most bytes of a large ROM the input decides; the rest leads up to them
+const MAX_TAIL: usize = 0x1000;
+
+/// Quirks and other settings from a byte, one flag per bit.
+fn interpreter_for(flags: u8) -> Interpreter {
+    let bit = |n: u8| flags & (1 << n) != 0;
+    let mut interpreter = Interpreter::with_quirks(Quirks {
+        shift_uses_vy: bit(0),
+        load_store_increments_i: bit(1),
+        jump_uses_vx: bit(2),
+        wrap_sprites: bit(3),
+        logic_resets_vf: bit(4),
+        key_wait_tone: bit(5),
+        display_wait: bit(6),
+        // out of bits, so these share with the quirks above
+        memory_wraps: bit(3),
+        add_i_sets_vf: bit(4),
+        i_12_bits: bit(4),
+    });
+    interpreter.set_megachip(bit(7));
+    interpreter
+}
+
+/// Load and run `data` as a ROM, after a byte of quirk flags and two of
+/// held keys, for up to a few thousand instructions.
+pub fn run_rom(data: &[u8]) {
+    let [flags, keys_high, keys_low, rom @ ..] = data else {
+        return;
+    };
+    let mut interpreter = interpreter_for(*flags);
+    interpreter.set_lenient(flags & 1 != 0);
+    if interpreter.load_program(rom).is_err() {
+        return;
+    }
+    let keys = u16::from_be_bytes([*keys_high, *keys_low]);
+    run(&mut interpreter, keys, MAX_STEPS);
+}
+
+/// Like [`run_rom`], but with MegaChip on and the ROM ending at 0xFFFF,
+/// the last address the pc reaches: LD V0, 0s lead up to the last few KiB
+/// of `data`, which run off the end of memory unless they jump away.
+pub fn run_large_rom(data: &[u8]) {
+    let [flags, keys_high, keys_low, tail @ ..] = data else {
+        return;
+    };
+    let tail = &tail[tail.len().saturating_sub(MAX_TAIL)..];
+    let lead_up = (0x10000 - PROGRAM_START - tail.len()) / 2;
+    let mut rom = [0x60, 0x00].repeat(lead_up);
+    rom.extend_from_slice(tail);
+
+    let mut interpreter = interpreter_for(*flags);
+    interpreter.set_lenient(flags & 1 != 0);
+    interpreter.set_megachip(true);
+    if interpreter.load_program(&rom).is_err() {
+        return;
+    }
+    let keys = u16::from_be_bytes([*keys_high, *keys_low]);
+    run(&mut interpreter, keys, lead_up + MAX_STEPS);
+}
+
+/// step `interpreter` up to `steps` times, holding `keys` on and off, until it fails
+fn run(interpreter: &mut Interpreter, keys: u16, steps: usize) {
+    for step in 0..steps {
+        if step % STEPS_PER_TICK == 0 {
+            interpreter.tick_60hz();
+            // let go of every key now and then, so key waits finish
+            let held = if step % (STEPS_PER_TICK * 4) == 0 {
+                0
+            } else {
+                keys
+            };
+            for key in 0..16 {
+                interpreter.set_key(key, held & (1 << key) != 0);
+            }
+        }
+        let _ = interpreter.next_instruction();
+        if interpreter.step().is_err() {
+            break;
+        }
+    }
+    let _ = interpreter.pixels();
+    let snapshot = interpreter.snapshot();
+    interpreter.restore(&snapshot);
+}
+
+/// Instructions that leave every register holding `registers`' value for
+/// it and I at `index`. LD I only reaches 0xFFF, so ADD I, VF takes it the
+/// rest of the way.
+fn setup(registers: [u8; 16], index: u16) -> Vec<u8> {
+    let mut rom = Vec::new();
+    rom.extend_from_slice(&(0xA000 | (index & 0xFFF)).to_be_bytes());
+    // 0x1000 at a time: 16 lots of 0xFF, then 0x10
+    for _ in 0..index >> 12 {
+        rom.extend_from_slice(&[0x6F, 0xFF]);
+        for _ in 0..16 {
+            rom.extend_from_slice(&[0xFF, 0x1E]);
+        }
+        rom.extend_from_slice(&[0x6F, 0x10, 0xFF, 0x1E]);
+    }
+    for (x, value) in registers.iter().enumerate() {
+        rom.extend_from_slice(&[0x60 | x as u8, *value]);
+    }
+    rom
+}
+
+/// Execute the instruction `word` from the state [`setup`] leaves, with
+/// quirks from `flags` as for [`run_rom`].
+pub fn run_instruction(flags: u8, word: u16, registers: [u8; 16], index: u16) {
+    let mut rom = setup(registers, index);
+    rom.extend_from_slice(&word.to_be_bytes());
+
+    let mut interpreter = interpreter_for(flags);
+    interpreter.set_lenient(true);
+    if interpreter.load_program(&rom).is_err() {
+        return;
+    }
+    // one for each instruction of the setup, then `word` itself
+    for _ in 0..rom.len() / 2 {
+        if interpreter.step().is_err() {
+            return;
+        }
+    }
+    let _ = interpreter.pixels();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn test_setup() {
+        let registers = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 0xFF];
+        let rom = setup(registers, 0xABCD);
+        let mut interpreter = interpreter_for(0);
+        interpreter.set_megachip(true);
+        interpreter.load_program(&rom).unwrap();
+        for _ in 0..rom.len() / 2 {
+            interpreter.step().unwrap();
+        }
+        assert_eq!(interpreter.index_register(), 0xABCD);
+        assert_eq!(interpreter.registers(), &registers);
+    }
+
+    #[test]
+    fn test_large_roms_never_panic() {
+        // straight to the end of memory, then skipping past it
+        run_large_rom(&[0, 0, 0]);
+        run_large_rom(&[0, 0, 0, 0x30, 0x00, 0x60, 0x00]);
+        let mut rng = Rng::with_seed(64);
+        for _ in 0..20 {
+            let len = rng.next_u8() as usize;
+            let data: Vec<u8> = (0..len).map(|_| rng.next_u8()).collect();
+            run_large_rom(&data);
+        }
+    }
+
+    /// a cheap stand-in for the fuzzer, over a few thousand random inputs
+    #[test]
+    fn test_random_roms_never_panic() {
+        let mut rng = Rng::with_seed(64);
+        for _ in 0..1000 {
+            let len = rng.next_u8() as usize;
+            let data: Vec<u8> = (0..len).map(|_| rng.next_u8()).collect();
+            run_rom(&data);
+        }
+        for _ in 0..2000 {
+            let byte = |rng: &mut Rng| rng.next_u8();
+            let word = u16::from_be_bytes([byte(&mut rng), byte(&mut rng)]);
+            let registers = [0; 16].map(|_: u8| byte(&mut rng));
+            let index = u16::from_be_bytes([byte(&mut rng), byte(&mut rng)]);
+            run_instruction(byte(&mut rng), word, registers, index);
+        }
+    }
+}
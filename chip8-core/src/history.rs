@@ -0,0 +1,114 @@
+//! A record of the last few instructions executed, for seeing how a
+//! program got to where it stopped.
+
+use std::{
+    collections::{vec_deque, VecDeque},
+    fmt,
+};
+
+use crate::interpreter::Op;
+
+/// A register an instruction changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// one of V0-VF
+    V(u8),
+    I,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterDelta {
+    pub register: Register,
+    pub old: u16,
+    pub new: u16,
+}
+
+impl fmt::Display for RegisterDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.register {
+            Register::V(x) => write!(f, "V{:X} {:02X}->{:02X}", x, self.old, self.new),
+            Register::I => write!(f, "I {:03X}->{:03X}", self.old, self.new),
+        }
+    }
+}
+
+/// One executed instruction, with the registers as they were before and
+/// after it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// address the instruction was fetched from
+    pub pc: u16,
+    pub word: u16,
+    pub op: Op,
+    pub registers_before: [u8; 16],
+    pub registers_after: [u8; 16],
+    pub i_before: u16,
+    pub i_after: u16,
+}
+
+impl HistoryEntry {
+    /// the instruction form, e.g. "LD Vx, byte"
+    pub fn mnemonic(&self) -> &'static str {
+        self.op.name()
+    }
+
+    /// the registers the instruction changed, V0-VF first and then I
+    pub fn deltas(&self) -> impl Iterator<Item = RegisterDelta> + '_ {
+        let registers = self
+            .registers_before
+            .iter()
+            .zip(&self.registers_after)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(x, (old, new))| RegisterDelta {
+                register: Register::V(x as u8),
+                old: *old as u16,
+                new: *new as u16,
+            });
+        let i = (self.i_before != self.i_after).then_some(RegisterDelta {
+            register: Register::I,
+            old: self.i_before,
+            new: self.i_after,
+        });
+        registers.chain(i)
+    }
+}
+
+/// The last `capacity` entries, dropping the oldest as new ones come in.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl History {
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// whether anything is being kept, so `step` can skip recording otherwise
+    pub(crate) fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub(crate) fn push(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn iter(&self) -> vec_deque::Iter<'_, HistoryEntry> {
+        self.entries.iter()
+    }
+}
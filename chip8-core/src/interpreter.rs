@@ -0,0 +1,2721 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    ops::Range,
+};
+
+use crate::{
+    error::{Chip8Error, StrictViolation},
+    font::FONT,
+    history::{History, HistoryEntry},
+    megachip::{Blend, DigitizedSound, MegaChip, MegaOp, MEGA_MEMORY_SIZE},
+    observer::Observer,
+    quirks::Quirks,
+    rng::Rng,
+    sys::{SysHandler, SysOutcome},
+    watch::{accesses, Access, WatchHit, WatchKind, WatchTarget},
+};
+
+// wrap u8 for now
+type U4 = u8;
+
+// wrap u16 for now
+type U8 = u16;
+
+/// A decoded instruction, with its operands pulled out of the word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Cls,
+    Ret,
+    Sys,
+    /// SUPER-CHIP: stop the program
+    Exit,
+    Jp {
+        nnn: U8,
+    },
+    Call {
+        nnn: U8,
+    },
+    Se {
+        x: U4,
+        nn: u8,
+    },
+    Sne {
+        x: U4,
+        nn: u8,
+    },
+    SeVxVy {
+        x: U4,
+        y: U4,
+    },
+    Ld {
+        x: U4,
+        nn: u8,
+    },
+    Add {
+        x: U4,
+        nn: u8,
+    },
+    LdVxVy {
+        x: U4,
+        y: U4,
+    },
+    OrVxVy {
+        x: U4,
+        y: U4,
+    },
+    AndVxVy {
+        x: U4,
+        y: U4,
+    },
+    XorVxVy {
+        x: U4,
+        y: U4,
+    },
+    AddVxVy {
+        x: U4,
+        y: U4,
+    },
+    SubVxVy {
+        x: U4,
+        y: U4,
+    },
+    ShrVxVy {
+        x: U4,
+        y: U4,
+    },
+    SubnVxVy {
+        x: U4,
+        y: U4,
+    },
+    ShlVxVy {
+        x: U4,
+        y: U4,
+    },
+    SneVxVy {
+        x: U4,
+        y: U4,
+    },
+    LdI {
+        nnn: U8,
+    },
+    JpV0 {
+        nnn: U8,
+    },
+    Rnd {
+        x: U4,
+        nn: u8,
+    },
+    Drw {
+        x: U4,
+        y: U4,
+        n: U4,
+    },
+    Skp {
+        x: U4,
+    },
+    Sknp {
+        x: U4,
+    },
+    LdVxDt {
+        x: U4,
+    },
+    LdVxK {
+        x: U4,
+    },
+    LdDtVx {
+        x: U4,
+    },
+    LdStVx {
+        x: U4,
+    },
+    AddIVx {
+        x: U4,
+    },
+    LdFVx {
+        x: U4,
+    },
+    LdBVx {
+        x: U4,
+    },
+    LdIVx {
+        x: U4,
+    },
+    LdVxI {
+        x: U4,
+    },
+    /// SUPER-CHIP: save registers to the RPL user flags
+    LdRVx {
+        x: U4,
+    },
+    /// SUPER-CHIP: load registers from the RPL user flags
+    LdVxR {
+        x: U4,
+    },
+    /// MegaChip, ignored like SYS unless `Interpreter::set_megachip` is on
+    Mega(MegaOp),
+    Invalid,
+}
+
+impl Op {
+    /// instruction form in Cowgod's notation, e.g. "LD Vx, byte"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Cls => "CLS",
+            Op::Ret => "RET",
+            Op::Sys => "SYS addr",
+            Op::Exit => "EXIT",
+            Op::Jp { .. } => "JP addr",
+            Op::Call { .. } => "CALL addr",
+            Op::Se { .. } => "SE Vx, byte",
+            Op::Sne { .. } => "SNE Vx, byte",
+            Op::SeVxVy { .. } => "SE Vx, Vy",
+            Op::Ld { .. } => "LD Vx, byte",
+            Op::Add { .. } => "ADD Vx, byte",
+            Op::LdVxVy { .. } => "LD Vx, Vy",
+            Op::OrVxVy { .. } => "OR Vx, Vy",
+            Op::AndVxVy { .. } => "AND Vx, Vy",
+            Op::XorVxVy { .. } => "XOR Vx, Vy",
+            Op::AddVxVy { .. } => "ADD Vx, Vy",
+            Op::SubVxVy { .. } => "SUB Vx, Vy",
+            Op::ShrVxVy { .. } => "SHR Vx, Vy",
+            Op::SubnVxVy { .. } => "SUBN Vx, Vy",
+            Op::ShlVxVy { .. } => "SHL Vx, Vy",
+            Op::SneVxVy { .. } => "SNE Vx, Vy",
+            Op::LdI { .. } => "LD I, addr",
+            Op::JpV0 { .. } => "JP V0, addr",
+            Op::Rnd { .. } => "RND Vx, byte",
+            Op::Drw { .. } => "DRW Vx, Vy, nibble",
+            Op::Skp { .. } => "SKP Vx",
+            Op::Sknp { .. } => "SKNP Vx",
+            Op::LdVxDt { .. } => "LD Vx, DT",
+            Op::LdVxK { .. } => "LD Vx, K",
+            Op::LdDtVx { .. } => "LD DT, Vx",
+            Op::LdStVx { .. } => "LD ST, Vx",
+            Op::AddIVx { .. } => "ADD I, Vx",
+            Op::LdFVx { .. } => "LD F, Vx",
+            Op::LdBVx { .. } => "LD B, Vx",
+            Op::LdIVx { .. } => "LD [I], Vx",
+            Op::LdVxI { .. } => "LD Vx, [I]",
+            Op::LdRVx { .. } => "LD R, Vx",
+            Op::LdVxR { .. } => "LD Vx, R",
+            Op::Mega(op) => op.name(),
+            Op::Invalid => "INVALID",
+        }
+    }
+}
+
+pub const MEMORY_SIZE: usize = 4096;
+
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+/// the height of the two-page display hires CHIP-8 ROMs use
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
+
+/// how many RPL user flags FX75 and FX85 can save and load, as on the HP 48
+pub const RPL_FLAG_COUNT: usize = 8;
+
+/// The display as one bool per pixel, row by row, with room for the hires
+/// display. Only the first `Interpreter::screen_height` rows are in use.
+pub type Pixels = [bool; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+
+/// The display packed one row per `u64`, with each row's leftmost pixel in
+/// its top bit, so DRW can work a whole sprite row at a time. Like
+/// `Pixels`, it has room for the hires display.
+pub type Rows = [u64; HIRES_SCREEN_HEIGHT];
+
+/// the bit for column `x` within a row of `Rows`
+fn column_bit(x: usize) -> u64 {
+    1 << (SCREEN_WIDTH - 1 - x)
+}
+
+/// whether the pixel at column `x` of row `y` is lit in `rows`
+pub fn is_lit(rows: &Rows, x: usize, y: usize) -> bool {
+    rows[y] & column_bit(x) != 0
+}
+
+/// light or clear the pixel at column `x` of row `y` in `rows`
+pub fn set_lit(rows: &mut Rows, x: usize, y: usize, on: bool) {
+    if on {
+        rows[y] |= column_bit(x);
+    } else {
+        rows[y] &= !column_bit(x);
+    }
+}
+
+/// the index of every pixel, as in `Interpreter::pixels`, that is lit in
+/// one of `old` and `new` but not the other, in order
+pub fn dirty_pixels(old: &Rows, new: &Rows) -> Vec<usize> {
+    let mut dirty = vec![];
+    for (y, (old, new)) in old.iter().zip(new).enumerate() {
+        let changed = old ^ new;
+        if changed != 0 {
+            dirty.extend(
+                (0..SCREEN_WIDTH)
+                    .filter(|x| changed & column_bit(*x) != 0)
+                    .map(|x| y * SCREEN_WIDTH + x),
+            );
+        }
+    }
+    dirty
+}
+
+/// unpack `rows` into one bool per pixel
+fn rows_to_pixels(rows: &Rows) -> Pixels {
+    let mut pixels = [false; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+    for (idx, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = is_lit(rows, idx % SCREEN_WIDTH, idx / SCREEN_WIDTH);
+    }
+    pixels
+}
+
+/// pack one bool per pixel into `Rows`
+fn pixels_to_rows(pixels: &Pixels) -> Rows {
+    let mut rows = [0; HIRES_SCREEN_HEIGHT];
+    for (idx, _) in pixels.iter().enumerate().filter(|(_, on)| **on) {
+        rows[idx / SCREEN_WIDTH] |= column_bit(idx % SCREEN_WIDTH);
+    }
+    rows
+}
+
+pub struct Interpreter {
+    memory_map: [u8; MEMORY_SIZE],
+    /// where programs are loaded and start running, usually `PROGRAM_START`
+    program_start: usize,
+    _program_size: usize,
+    program_counter: u16,
+
+    stack_pointer: u8,
+    stack: [u16; 16],
+
+    registers: [u8; 16], // also called Vx
+    // TODO: Is it possible in rust to make `vf` look like a field, even though it would be a method that maps back to these registers? e.g. (self.vf = 5) or (x = self.vf) would both work
+    index_register: u16, // usually only stores lowest 12 bits, for memory addresses
+
+    delay_timer: u8,
+    sound_timer: u8,
+    /// SUPER-CHIP's persistent storage, which outlives the program on the HP 48
+    rpl_flags: [u8; RPL_FLAG_COUNT],
+
+    /// "hardware" abstractions
+    /// input: for the keyboard. represents whether key i is pressed
+    keys: [bool; 16],
+    /// keys pressed and released between two frames, which read as held
+    /// until the next tick so the program still sees them
+    tapped: [bool; 16],
+    key_down_ld_vx_k: Option<u8>, // track the key we are waiting to release
+    /// a DRW under the display wait quirk is holding execution until the next tick
+    waiting_for_vblank: bool,
+    /// running a two-page hires ROM, on a 64x64 display
+    hires: bool,
+    rows: Rows,
+    /// the display as of the last `take_dirty_pixels`
+    taken_rows: Rows,
+
+    quirks: Quirks,
+    rng: Rng,
+    /// skip invalid opcodes instead of stopping on them
+    lenient: bool,
+    /// stop on instructions that won't behave the same on other interpreters
+    strict: bool,
+
+    breakpoints: BTreeSet<u16>,
+    /// the breakpoint `step` last stopped at, which the next step runs past
+    stopped_at: Option<u16>,
+    watchpoints: BTreeMap<WatchTarget, WatchKind>,
+    /// the most recently executed instructions, when `set_history_len` asks for them
+    history: History,
+    run_state: RunState,
+    /// MegaChip's extra memory and display, when it's supported
+    megachip: Option<Box<MegaChip>>,
+    /// what SYS does, rather than nothing
+    sys_handler: Option<Box<dyn SysHandler>>,
+    observers: Vec<Box<dyn Observer>>,
+}
+
+/// What a call to `Interpreter::step` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Executed,
+    /// Stopped before executing the instruction at this breakpoint. The
+    /// next `step` executes it.
+    Breakpoint(u16),
+    /// Executed an instruction that touched a watched register or address.
+    Watchpoint(WatchHit),
+}
+
+impl fmt::Display for StepOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StepOutcome::Executed => write!(f, "executed"),
+            StepOutcome::Breakpoint(addr) => write!(f, "breakpoint {:#05x}", addr),
+            StepOutcome::Watchpoint(hit) => write!(f, "watchpoint, {}", hit),
+        }
+    }
+}
+
+/// Whether the program is still going, as of the last `step`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    /// LD Vx, K is waiting for a key to be pressed and released
+    WaitingForKey,
+    /// The program has stopped making progress. Stepping goes on as
+    /// usual, e.g. running the jump again or retrying the failed instruction.
+    Halted {
+        reason: HaltReason,
+    },
+    /// The program ran EXIT, so stepping does nothing until it's reset.
+    Exited,
+}
+
+/// Why a program is `RunState::Halted`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HaltReason {
+    /// a jump at `addr` to itself, which is how many programs end
+    JumpToSelf { addr: u16 },
+    /// a loop of the instruction at `addr` and a jump back to it, where
+    /// the instruction can't leave the loop or read the keys
+    IdleLoop { addr: u16 },
+    /// the program counter went past the end of the loaded program
+    EndOfProgram,
+    /// the last step failed with this error
+    Error(String),
+}
+
+impl fmt::Display for RunState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunState::Running => write!(f, "running"),
+            RunState::WaitingForKey => write!(f, "waiting for a key"),
+            RunState::Halted { reason } => write!(f, "halted, {}", reason),
+            RunState::Exited => write!(f, "exited"),
+        }
+    }
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HaltReason::JumpToSelf { addr } => write!(f, "jumping to itself at {:#05x}", addr),
+            HaltReason::IdleLoop { addr } => write!(f, "idling in a loop at {:#05x}", addr),
+            HaltReason::EndOfProgram => write!(f, "past the end of the program"),
+            HaltReason::Error(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Whether `op`, followed by a jump back to it, loops forever without
+/// reading the keys: it always goes on to the next instruction, and
+/// doesn't write memory that could change the loop.
+fn stays_in_loop(op: &Op) -> bool {
+    !matches!(
+        op,
+        Op::Ret
+            | Op::Sys
+            | Op::Exit
+            | Op::Jp { .. }
+            | Op::Call { .. }
+            | Op::Se { .. }
+            | Op::Sne { .. }
+            | Op::SeVxVy { .. }
+            | Op::SneVxVy { .. }
+            | Op::JpV0 { .. }
+            | Op::Skp { .. }
+            | Op::Sknp { .. }
+            | Op::LdVxK { .. }
+            | Op::LdBVx { .. }
+            | Op::LdIVx { .. }
+            | Op::Mega(_)
+            | Op::Invalid
+    )
+}
+
+/// A copy of the complete interpreter state, e.g. for saving and restoring a game.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Snapshot {
+    pub memory: [u8; MEMORY_SIZE],
+    pub program_size: usize,
+    pub program_counter: u16,
+    pub stack_pointer: u8,
+    pub stack: [u16; 16],
+    pub registers: [u8; 16],
+    pub index_register: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keys: [bool; 16],
+    pub key_down_ld_vx_k: Option<u8>,
+    pub pixels: Pixels,
+    /// whether this is a hires ROM, on the 64x64 display
+    pub hires: bool,
+    /// where the random number generator is in its sequence, or 0 to start from the default seed
+    pub rng_state: u64,
+}
+
+pub const FONT_START: usize = 0x50;
+/// where programs normally load and start
+pub const PROGRAM_START: usize = 512;
+/// where ETI-660 programs load and start
+pub const ETI_660_PROGRAM_START: usize = 0x600;
+/// Where hires ROMs' CHIP-8 code starts. They begin with a jump to 0x260,
+/// where machine code patches the VIP's interpreter for the 64x64 display,
+/// which is emulated instead of run.
+const HIRES_PROGRAM_START: u16 = 0x2C0;
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_quirks(Quirks::default())
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        // initialize memory map
+        let mut memory_map = [0; 4096];
+        // write font
+        for (idx, c) in FONT.iter().enumerate() {
+            memory_map[FONT_START + idx] = *c;
+        }
+
+        Interpreter {
+            memory_map,
+            program_start: PROGRAM_START,
+            _program_size: 0,
+
+            stack: [0; 16],
+
+            registers: [0; 16],
+            index_register: 0,
+
+            program_counter: PROGRAM_START as u16,
+            stack_pointer: 0,
+
+            delay_timer: 0,
+            sound_timer: 0,
+            rpl_flags: [0; RPL_FLAG_COUNT],
+
+            hires: false,
+            rows: [0; HIRES_SCREEN_HEIGHT],
+            taken_rows: [0; HIRES_SCREEN_HEIGHT],
+
+            keys: [false; 16],
+            tapped: [false; 16],
+            key_down_ld_vx_k: None,
+            waiting_for_vblank: false,
+
+            quirks,
+            rng: Rng::new(),
+            lenient: false,
+            strict: false,
+
+            breakpoints: BTreeSet::new(),
+            stopped_at: None,
+            watchpoints: BTreeMap::new(),
+            history: History::default(),
+            run_state: RunState::Running,
+            megachip: None,
+            sys_handler: None,
+            observers: vec![],
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// change quirks mid-run, e.g. to try a ROM with another preset without restarting it
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Restart RND's sequence from `seed`. Without a seed every run uses
+    /// the same default one, so seeding only matters to vary the sequence.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::with_seed(seed);
+    }
+
+    /// Treat invalid opcodes as NOPs rather than failing `step` with
+    /// `Chip8Error::InvalidOpcode`. Off by default.
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// Fail `step` with `Chip8Error::Strict` on SYS, on writes to the
+    /// interpreter's memory below 0x200, and on undocumented opcodes, even
+    /// when lenient. Off by default.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn set_key(&mut self, key_idx: usize, is_down: bool) {
+        self.keys[key_idx] = is_down;
+    }
+
+    /// Register a key that was pressed and released too quickly for
+    /// `set_key` to see it, e.g. within one rendered frame. It reads as
+    /// held until the next `tick_60hz`, so SKP, SKNP, and LD Vx, K treat it
+    /// as a short press.
+    pub fn press_and_release(&mut self, key_idx: usize) {
+        self.tapped[key_idx] = true;
+    }
+
+    /// whether key `key_idx` is held or was tapped since the last tick
+    fn key_down(&self, key_idx: usize) -> bool {
+        self.keys[key_idx] || self.tapped[key_idx]
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Stop after any instruction that accesses `target` in a way matching `kind`.
+    pub fn add_watchpoint(&mut self, target: WatchTarget, kind: WatchKind) {
+        self.watchpoints.insert(target, kind);
+    }
+
+    pub fn remove_watchpoint(&mut self, target: WatchTarget) {
+        self.watchpoints.remove(&target);
+    }
+
+    /// Keep the last `len` instructions executed for `history`, or none
+    /// with 0. Off by default, since recording slows every step down.
+    pub fn set_history_len(&mut self, len: usize) {
+        self.history.set_capacity(len);
+    }
+
+    /// the most recently executed instructions, oldest first
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &HistoryEntry> + ExactSizeIterator {
+        self.history.iter()
+    }
+
+    fn watched_value(&self, target: WatchTarget) -> u8 {
+        match target {
+            WatchTarget::Register(x) => self.registers[x as usize & 0xF],
+            WatchTarget::Memory(addr) if (addr as usize) < self.memory_len() => {
+                self.read(addr as usize)
+            }
+            WatchTarget::Memory(_) => 0,
+        }
+    }
+
+    /// the machine code address of `op`, if it runs as SYS
+    fn sys_target(&self, op: &Op, instruction: u16) -> Option<u16> {
+        match op {
+            Op::Sys => Some(instruction & 0xFFF),
+            Op::Mega(_) if self.megachip.is_none() => Some(instruction & 0xFFF),
+            _ => None,
+        }
+    }
+
+    /// give SYS `addr` to the handler, if there is one
+    fn run_sys_handler(&mut self, addr: u16) -> SysOutcome {
+        let Some(mut handler) = self.sys_handler.take() else {
+            return SysOutcome::Continue;
+        };
+        let outcome = handler.sys(addr, self);
+        // unless the handler replaced itself
+        if self.sys_handler.is_none() {
+            self.sys_handler = Some(handler);
+        }
+        outcome
+    }
+
+    /// why strict mode stops on `op`, if it's on and does
+    fn strict_violation(&self, op: &Op, instruction: u16) -> Option<StrictViolation> {
+        if !self.strict {
+            return None;
+        }
+        if let Some(target) = self.sys_target(op, instruction) {
+            return Some(StrictViolation::Sys { target });
+        }
+        if matches!(op, Op::Invalid) {
+            return Some(StrictViolation::Undocumented);
+        }
+        let reserved: Vec<u16> = accesses(op, self.index_register, &self.quirks)
+            .into_iter()
+            .filter_map(|(target, access)| match (target, access) {
+                (WatchTarget::Memory(addr), Access::Write) if addr < 0x200 => Some(addr),
+                _ => None,
+            })
+            .collect();
+        Some(StrictViolation::ReservedWrite {
+            first: *reserved.iter().min()?,
+            last: *reserved.iter().max()?,
+            i: self.index_register,
+        })
+    }
+
+    pub fn step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        if self.run_state == RunState::Exited {
+            return Ok(StepOutcome::Executed);
+        }
+        if !self.can_continue() {
+            self.run_state = RunState::Halted {
+                reason: HaltReason::EndOfProgram,
+            };
+            return Ok(StepOutcome::Executed);
+        }
+        if self.waiting_for_vblank {
+            return Ok(StepOutcome::Executed);
+        }
+
+        let pc = self.program_counter;
+        if self.breakpoints.contains(&pc) && self.stopped_at != Some(pc) {
+            self.stopped_at = Some(pc);
+            return Ok(StepOutcome::Breakpoint(pc));
+        }
+        self.stopped_at = None;
+
+        log::debug!("pc: {:?}", self.program_counter);
+        let instruction = self.fetch(pc).map_err(|e| self.halt(e))?;
+        let op = match decode(instruction) {
+            // the hires interpreter's own routine for clearing its bigger display
+            Op::Mega(_) if self.hires && instruction == 0x0230 => Op::Cls,
+            op => op,
+        };
+        log::debug!("op: {:?}", op);
+        if let Some(violation) = self.strict_violation(&op, instruction) {
+            self.program_counter = pc;
+            return Err(self.halt(Chip8Error::Strict {
+                addr: pc,
+                word: instruction,
+                violation,
+            }));
+        }
+        if matches!(op, Op::Invalid) && !self.lenient {
+            // leave the pc on the bad word, so stepping again fails the same way
+            self.program_counter = pc;
+            return Err(self.halt(Chip8Error::InvalidOpcode {
+                addr: pc,
+                word: instruction,
+            }));
+        }
+        let watched: Vec<(WatchTarget, Access, u8)> =
+            accesses(&op, self.index_register, &self.quirks)
+                .into_iter()
+                .filter(|(target, access)| {
+                    self.watchpoints
+                        .get(target)
+                        .is_some_and(|kind| kind.matches(*access))
+                })
+                .map(|(target, access)| (target, access, self.watched_value(target)))
+                .collect();
+        log::debug!("registers (before): {:?}", self.registers);
+        let before = self
+            .history
+            .enabled()
+            .then_some((self.registers, self.index_register));
+        let observed =
+            (!self.observers.is_empty()).then_some((self.registers, self.should_play_sound()));
+        if let Err(e) = self.execute(op) {
+            // back on the failing instruction, for the debugger to show
+            self.program_counter = pc;
+            return Err(self.halt(e));
+        }
+        let sys_exit = self
+            .sys_target(&op, instruction)
+            .is_some_and(|addr| self.run_sys_handler(addr) == SysOutcome::Exit);
+        log::debug!("registers (after):  {:?}", self.registers);
+
+        // LD Vx, K doesn't write anything until a key has been pressed and released
+        let still_waiting = matches!(op, Op::LdVxK { .. }) && self.program_counter == pc;
+        let was_waiting = self.run_state == RunState::WaitingForKey;
+        let idling = matches!(
+            self.run_state,
+            RunState::Halted { reason: HaltReason::IdleLoop { addr } } if addr == pc
+        );
+        self.run_state = match op {
+            Op::Exit => RunState::Exited,
+            _ if sys_exit => RunState::Exited,
+            _ if still_waiting => RunState::WaitingForKey,
+            Op::Jp { nnn } if nnn == pc => RunState::Halted {
+                reason: HaltReason::JumpToSelf { addr: pc },
+            },
+            Op::Jp { nnn }
+                if nnn + 2 == pc
+                    && stays_in_loop(&decode(self.fetch_instruction_at(nnn as usize))) =>
+            {
+                RunState::Halted {
+                    reason: HaltReason::IdleLoop { addr: nnn },
+                }
+            }
+            // the other half of the idle loop, which stays halted
+            _ if idling => self.run_state.clone(),
+            _ => RunState::Running,
+        };
+        if let Some((registers, was_playing)) = observed {
+            self.notify_step(pc, op, &registers, still_waiting && !was_waiting);
+            self.notify_sound(was_playing);
+        }
+        // a wait for a key would otherwise fill the history with itself
+        if let Some((registers, index_register)) = before.filter(|_| !still_waiting) {
+            self.history.push(HistoryEntry {
+                pc,
+                word: instruction,
+                op,
+                registers_before: registers,
+                registers_after: self.registers,
+                i_before: index_register,
+                i_after: self.index_register,
+            });
+        }
+        let hit = watched
+            .into_iter()
+            .find(|(_, access, _)| !(still_waiting && *access == Access::Write));
+        if let Some((target, access, old)) = hit {
+            return Ok(StepOutcome::Watchpoint(WatchHit {
+                pc,
+                target,
+                access,
+                old,
+                new: self.watched_value(target),
+            }));
+        }
+
+        Ok(StepOutcome::Executed)
+    }
+
+    /// record `error` as the reason the program halted, and hand it back
+    fn halt(&mut self, error: Chip8Error) -> Chip8Error {
+        self.run_state = RunState::Halted {
+            reason: HaltReason::Error(error.to_string()),
+        };
+        error
+    }
+
+    /// whether the program is running, waiting for a key, halted, or exited
+    pub fn state(&self) -> &RunState {
+        &self.run_state
+    }
+
+    /// Count the delay and sound timers down, and let go of keys from
+    /// `press_and_release`. Call this 60 times per second of emulated
+    /// time, independently of how many instructions run; `step` never
+    /// touches the timers.
+    pub fn tick_60hz(&mut self) {
+        let was_playing = self.should_play_sound();
+        self.end_vblank_wait();
+        self.tapped = [false; 16];
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+        self.notify_sound(was_playing);
+    }
+
+    /// tell the observers if the buzzer started or stopped since `was_playing`
+    fn notify_sound(&mut self, was_playing: bool) {
+        let playing = self.should_play_sound();
+        for observer in &mut self.observers {
+            match (was_playing, playing) {
+                (false, true) => observer.on_sound_start(),
+                (true, false) => observer.on_sound_stop(),
+                _ => (),
+            }
+        }
+    }
+
+    /// tell the observers what `op` at `addr` just did, given the registers it started with
+    fn notify_step(&mut self, addr: u16, op: Op, registers: &[u8; 16], key_wait_started: bool) {
+        let collided = self.registers[0xF] == 1;
+        let pc = self.program_counter;
+        for observer in &mut self.observers {
+            match op {
+                Op::Cls => observer.on_clear(addr),
+                Op::Drw { x, y, n } => observer.on_draw(
+                    addr,
+                    registers[x as usize],
+                    registers[y as usize],
+                    n,
+                    collided,
+                ),
+                Op::LdVxK { x } if key_wait_started => observer.on_key_wait(addr, x),
+                Op::Call { nnn } => observer.on_call(addr, nnn),
+                Op::Ret => observer.on_ret(addr, pc),
+                _ => (),
+            }
+        }
+    }
+
+    /// whether a DRW is holding execution until the next `tick_60hz`,
+    /// under the display wait quirk; `step` does nothing until then
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    /// let execution continue after a display wait without ticking the timers
+    pub fn end_vblank_wait(&mut self) {
+        self.waiting_for_vblank = false;
+    }
+
+    pub fn should_play_sound(&self) -> bool {
+        self.sound_timer > 0 || (self.quirks.key_wait_tone && self.key_down_ld_vx_k.is_some())
+    }
+
+    /// Load programs at `addr`, and start running there, instead of at
+    /// `PROGRAM_START`, e.g. `ETI_660_PROGRAM_START` for ETI-660 programs.
+    /// Call it before `load_program`. Addresses past the end of memory are
+    /// clamped to it, leaving no room for a program.
+    pub fn set_program_start(&mut self, addr: u16) {
+        self.program_start = (addr as usize).min(MEMORY_SIZE);
+        self.program_counter = addr;
+    }
+
+    pub fn program_start(&self) -> u16 {
+        self.program_start as u16
+    }
+
+    /// The RPL user flags FX75 saved, for a frontend to keep between runs.
+    /// They're not part of a `Snapshot`, since on the HP 48 they belong to
+    /// the calculator rather than the program.
+    pub fn rpl_flags(&self) -> &[u8; RPL_FLAG_COUNT] {
+        &self.rpl_flags
+    }
+
+    /// restore the RPL user flags from an earlier run, for FX85 to load
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAG_COUNT]) {
+        self.rpl_flags = flags;
+    }
+
+    /// Understand MegaChip's instructions, and give programs up to 16 MiB
+    /// of memory through 24-bit addressing. Call it before `load_program`,
+    /// since it also lets larger ROMs load. Off by default; without it
+    /// MegaChip instructions are ignored like SYS.
+    pub fn set_megachip(&mut self, megachip: bool) {
+        self.megachip = megachip.then(|| Box::new(MegaChip::new(MEGA_MEMORY_SIZE - MEMORY_SIZE)));
+    }
+
+    /// Run SYS 0NNN with `handler`, which otherwise does nothing, as on
+    /// every interpreter after the COSMAC VIP. Without MegaChip, its
+    /// instructions count as SYS too.
+    pub fn set_sys_handler(&mut self, handler: impl SysHandler + 'static) {
+        self.sys_handler = Some(Box::new(handler));
+    }
+
+    /// go back to ignoring SYS
+    pub fn clear_sys_handler(&mut self) {
+        self.sys_handler = None;
+    }
+
+    /// Tell `observer` what the program does from now on, after any
+    /// observers added before it.
+    pub fn add_observer(&mut self, observer: impl Observer + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    /// MegaChip's state, if `set_megachip` turned it on
+    pub fn megachip(&self) -> Option<&MegaChip> {
+        self.megachip.as_deref()
+    }
+
+    pub fn megachip_mut(&mut self) -> Option<&mut MegaChip> {
+        self.megachip.as_deref_mut()
+    }
+
+    /// whether a ROM has entered Mega mode, and draws with MegaChip's display
+    fn mega_mode(&self) -> bool {
+        self.megachip.as_ref().is_some_and(|mega| mega.enabled())
+    }
+
+    /// all the memory programs can address, which MegaChip extends past 4 KiB
+    fn memory_len(&self) -> usize {
+        match &self.megachip {
+            Some(_) => MEGA_MEMORY_SIZE,
+            None => MEMORY_SIZE,
+        }
+    }
+
+    /// the byte at `addr`, where MegaChip's memory reads as 0 past its end and
+    /// 4 KiB of memory repeats every 4 KiB, as the VIP's did
+    fn read(&self, addr: usize) -> u8 {
+        match &self.megachip {
+            Some(mega) if addr >= MEMORY_SIZE => {
+                mega.memory.get(addr - MEMORY_SIZE).copied().unwrap_or(0)
+            }
+            _ => self.memory_map[addr % MEMORY_SIZE],
+        }
+    }
+
+    /// set the byte at `addr`, where writes past the end of MegaChip's memory
+    /// are dropped and 4 KiB of memory repeats as for `read`
+    fn write(&mut self, addr: usize, value: u8) {
+        match &mut self.megachip {
+            Some(mega) if addr >= MEMORY_SIZE => {
+                if let Some(byte) = mega.memory.get_mut(addr - MEMORY_SIZE) {
+                    *byte = value;
+                }
+            }
+            _ => self.memory_map[addr % MEMORY_SIZE] = value,
+        }
+    }
+
+    /// I, for an instruction about to access the `len` bytes from I, or
+    /// an error if they reach past the end of memory and it doesn't wrap
+    fn i_for(&self, len: usize) -> Result<usize, Chip8Error> {
+        let i = self.i();
+        if !self.quirks.memory_wraps && i + len > self.memory_len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                addr: self.program_counter - 2,
+                target: i + len - 1,
+            });
+        }
+        Ok(i)
+    }
+
+    fn read_bytes(&self, addr: usize, len: usize) -> Vec<u8> {
+        (0..len).map(|idx| self.read(addr + idx)).collect()
+    }
+
+    /// I as an address, including the top 8 bits MegaChip's LDHI sets
+    fn i(&self) -> usize {
+        let high = self
+            .megachip
+            .as_ref()
+            .map_or(0, |mega| mega.i_high as usize);
+        (high << 16) | self.index_register as usize
+    }
+
+    /// move I on by `by`, wrapping at 12 or 16 bits as the quirk says, and
+    /// return whether it passed 0xFFF
+    fn add_to_i(&mut self, by: u16) -> bool {
+        let sum = self.index_register as u32 + by as u32;
+        let mask = if self.quirks.i_12_bits { 0xFFF } else { 0xFFFF };
+        self.index_register = (sum & mask) as u16;
+        sum > 0xFFF
+    }
+
+    /// set I to a 16-bit value, clearing any top bits from LDHI
+    fn set_i(&mut self, value: u16) {
+        self.index_register = value;
+        if let Some(mega) = &mut self.megachip {
+            mega.i_high = 0;
+        }
+    }
+
+    pub fn read_program_from_file(&mut self, p: &str) -> Result<(), Chip8Error> {
+        self.load_program(&std::fs::read(p)?)
+    }
+
+    /// copy a ROM into memory at the program start
+    pub fn load_program(&mut self, buffer: &[u8]) -> Result<(), Chip8Error> {
+        let max = self.memory_len() - self.program_start;
+        if buffer.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: buffer.len(),
+                max,
+            });
+        }
+        self._program_size = buffer.len();
+        // anything past 4 KiB goes in MegaChip's memory
+        let (low, high) = buffer.split_at(buffer.len().min(MEMORY_SIZE - self.program_start));
+        self.memory_map[self.program_start..self.program_start + low.len()].copy_from_slice(low);
+        if let Some(mega) = &mut self.megachip {
+            mega.memory[..high.len()].copy_from_slice(high);
+        }
+        // the boot sequence of hires ROMs, which only works loaded in the usual place
+        self.hires = self.program_start == PROGRAM_START
+            && self.megachip.is_none()
+            && buffer.starts_with(&[0x12, 0x60]);
+        if self.hires {
+            self.program_counter = HIRES_PROGRAM_START;
+        }
+        self.run_state = RunState::Running;
+        Ok(())
+    }
+
+    /// Start the loaded program over, as the VIP's reset switch did:
+    /// registers, the stack, timers, and the display are cleared, while
+    /// memory is left as the program last wrote it, and the history is
+    /// emptied. Quirks, breakpoints, watchpoints, and RPL flags stay as
+    /// they are.
+    pub fn reset(&mut self) {
+        self.program_counter = if self.hires {
+            HIRES_PROGRAM_START
+        } else {
+            self.program_start as u16
+        };
+        self.stack_pointer = 0;
+        self.stack = [0; 16];
+        self.registers = [0; 16];
+        self.index_register = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.key_down_ld_vx_k = None;
+        self.tapped = [false; 16];
+        self.waiting_for_vblank = false;
+        self.rows = [0; HIRES_SCREEN_HEIGHT];
+        self.stopped_at = None;
+        self.history.clear();
+        self.run_state = RunState::Running;
+        if let Some(mega) = &mut self.megachip {
+            mega.reset();
+        }
+    }
+
+    /// whether the loaded ROM is a two-page hires one, shown on a 64x64 display
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    /// how many rows of `pixels` and `rows` are in use
+    pub fn screen_height(&self) -> usize {
+        if self.hires {
+            HIRES_SCREEN_HEIGHT
+        } else {
+            SCREEN_HEIGHT
+        }
+    }
+
+    /// The display as one bool per pixel, row by row, unpacked from `rows`.
+    /// A convenience for tests and tools: it copies 4096 bools, so a
+    /// frontend drawing every frame should read `rows` with `is_lit`.
+    pub fn pixels(&self) -> Pixels {
+        rows_to_pixels(&self.rows)
+    }
+
+    /// the display as it's stored, one packed `u64` per row
+    pub fn rows(&self) -> &Rows {
+        &self.rows
+    }
+
+    /// The indices into `pixels()` of every pixel that differs from the
+    /// last call (or from a blank display, on the first), in order, so a
+    /// frontend can redraw just those.
+    pub fn take_dirty_pixels(&mut self) -> Vec<usize> {
+        let dirty = dirty_pixels(&self.taken_rows, &self.rows);
+        self.taken_rows = self.rows;
+        dirty
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory_map,
+            program_size: self._program_size,
+            program_counter: self.program_counter,
+            stack_pointer: self.stack_pointer,
+            stack: self.stack,
+            registers: self.registers,
+            index_register: self.index_register,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            keys: self.keys,
+            key_down_ld_vx_k: self.key_down_ld_vx_k,
+            pixels: self.pixels(),
+            hires: self.hires,
+            rng_state: self.rng.state(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.memory_map = snapshot.memory;
+        self._program_size = snapshot.program_size;
+        self.program_counter = snapshot.program_counter;
+        self.stack_pointer = snapshot.stack_pointer;
+        self.stack = snapshot.stack;
+        self.registers = snapshot.registers;
+        self.index_register = snapshot.index_register;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.keys = snapshot.keys;
+        self.tapped = [false; 16];
+        self.key_down_ld_vx_k = snapshot.key_down_ld_vx_k;
+        self.rows = pixels_to_rows(&snapshot.pixels);
+        self.hires = snapshot.hires;
+        self.rng = Rng::from_state(snapshot.rng_state);
+        // what ran before doesn't lead up to the restored state
+        self.history.clear();
+        self.run_state = RunState::Running;
+    }
+
+    pub fn memory(&self) -> &[u8; MEMORY_SIZE] {
+        &self.memory_map
+    }
+
+    /// Edit registers, timers, and memory, as a debugger does while the
+    /// program is stopped. Nothing can run while the edits are being made.
+    pub fn poke(&mut self) -> Poke<'_> {
+        Poke { interpreter: self }
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    /// return addresses of the active calls, oldest first
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[1..=self.stack_pointer as usize]
+    }
+
+    /// how deep calls can nest before the next CALL overflows the stack
+    pub fn max_call_depth(&self) -> usize {
+        self.stack.len() - 1
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// the instruction the next `step` will execute, if the program counter is within memory
+    pub fn next_instruction(&self) -> Option<u16> {
+        let pc = self.program_counter as usize;
+        (pc + 1 < self.memory_len()).then(|| self.fetch_instruction_at(pc))
+    }
+
+    /// where the built-in font lives in memory
+    pub fn font_range(&self) -> Range<usize> {
+        FONT_START..FONT_START + FONT.len()
+    }
+
+    /// where the loaded program lives in memory
+    pub fn program_range(&self) -> Range<usize> {
+        self.program_start..self.program_start + self._program_size
+    }
+
+    fn can_continue(&self) -> bool {
+        let is_within_memory = (self.program_counter as usize) < self.memory_len();
+        let is_in_program =
+            self.program_counter as usize <= self.program_start + self._program_size;
+
+        is_within_memory && is_in_program
+    }
+
+    fn fetch_instruction_at(&self, pc: usize) -> u16 {
+        let first = self.read(pc);
+        let second = self.read(pc + 1);
+
+        ((first as u16) << 8) | second as u16
+    }
+
+    /// the next word, moving the pc past it, for the instruction at `addr`
+    fn fetch(&mut self, addr: u16) -> Result<u16, Chip8Error> {
+        let instruction = self.fetch_instruction_at(self.program_counter as usize);
+        self.skip_word(addr)?;
+        Ok(instruction)
+    }
+
+    /// move the pc past a word for the instruction at `addr`, failing
+    /// rather than wrapping past the 64 KiB it can address
+    fn skip_word(&mut self, addr: u16) -> Result<(), Chip8Error> {
+        self.program_counter =
+            self.program_counter
+                .checked_add(2)
+                .ok_or(Chip8Error::MemoryOutOfBounds {
+                    addr,
+                    target: self.program_counter as usize + 2,
+                })?;
+        Ok(())
+    }
+
+    fn execute(&mut self, op: Op) -> Result<(), Chip8Error> {
+        match op {
+            Op::Cls => match &mut self.megachip {
+                Some(mega) if mega.enabled() => mega.flip(),
+                _ => self.rows = [0; HIRES_SCREEN_HEIGHT],
+            },
+            Op::Ret => {
+                if self.stack_pointer == 0 {
+                    return Err(Chip8Error::StackUnderflow {
+                        addr: self.program_counter - 2,
+                    });
+                }
+                self.program_counter = self.stack[self.stack_pointer as usize];
+                self.stack_pointer -= 1;
+            }
+            // `step` marks the program as exited
+            Op::Sys | Op::Exit => (),
+            Op::Jp { nnn: addr } => {
+                self.program_counter = addr;
+            }
+            Op::Call { nnn: addr } => {
+                // slot 0 is never used, so the last slot is the deepest call
+                if self.stack_pointer as usize == self.stack.len() - 1 {
+                    return Err(Chip8Error::StackOverflow {
+                        addr: self.program_counter - 2,
+                        stack: self.call_stack().to_vec(),
+                    });
+                }
+                self.stack_pointer += 1;
+                self.stack[self.stack_pointer as usize] = self.program_counter;
+                self.program_counter = addr;
+            }
+            Op::Se { x, nn: byte } => {
+                let vx = self.registers[x as usize];
+                if vx == byte {
+                    self.skip_word(self.program_counter - 2)?;
+                }
+            }
+            Op::Sne { x, nn: byte } => {
+                let vx = self.registers[x as usize];
+                if vx != byte {
+                    self.skip_word(self.program_counter - 2)?;
+                }
+            }
+            Op::SeVxVy { x, y } => {
+                let vx = self.registers[x as usize];
+                let vy = self.registers[y as usize];
+                if vx == vy {
+                    self.skip_word(self.program_counter - 2)?;
+                }
+            }
+            Op::Ld { x, nn: byte } => self.registers[x as usize] = byte,
+            Op::Add { x, nn: byte } => {
+                let vx = self.registers[x as usize];
+                let (total, _) = vx.overflowing_add(byte);
+                // NOTE: This instruction does NOT set the overflow register (vf)
+                self.registers[x as usize] = total;
+            }
+            Op::LdVxVy { x, y } => self.registers[x as usize] = self.registers[y as usize],
+            Op::OrVxVy { x, y } => {
+                self.registers[x as usize] |= self.registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xf] = 0;
+                }
+            }
+            Op::AndVxVy { x, y } => {
+                self.registers[x as usize] &= self.registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xf] = 0;
+                }
+            }
+            Op::XorVxVy { x, y } => {
+                self.registers[x as usize] ^= self.registers[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xf] = 0;
+                }
+            }
+            Op::AddVxVy { x, y } => {
+                let vx = self.registers[x as usize];
+                let vy = self.registers[y as usize];
+                let (total, overflow) = vx.overflowing_add(vy);
+
+                self.registers[x as usize] = total;
+                self.registers[0xf] = overflow as u8;
+            }
+            Op::SubVxVy { x, y } => {
+                let vx = self.registers[x as usize];
+                let vy = self.registers[y as usize];
+
+                let (total, overflow) = vx.overflowing_sub(vy);
+                self.registers[x as usize] = total;
+                self.registers[0xf] = !overflow as u8;
+            }
+            Op::ShrVxVy { x, y } => {
+                if self.quirks.shift_uses_vy {
+                    self.registers[x as usize] = self.registers[y as usize];
+                }
+                let vx = self.registers[x as usize];
+                let lsb_is_1 = (vx & 0b00000001).count_ones() == 1;
+                self.registers[x as usize] = vx >> 1;
+                self.registers[0xf] = if lsb_is_1 { 0x1 } else { 0x0 };
+            }
+            Op::SubnVxVy { x, y } => {
+                let vx = self.registers[x as usize];
+                let vy = self.registers[y as usize];
+
+                let (total, overflow) = vy.overflowing_sub(vx);
+                self.registers[x as usize] = total;
+                self.registers[0xf] = !overflow as u8;
+            }
+            Op::ShlVxVy { x, y } => {
+                if self.quirks.shift_uses_vy {
+                    self.registers[x as usize] = self.registers[y as usize];
+                }
+                let vx = self.registers[x as usize];
+                let msb_is_1 = (vx & 0b10000000).count_ones() == 1;
+                self.registers[x as usize] = vx << 1;
+                self.registers[0xf] = if msb_is_1 { 0x1 } else { 0x0 };
+            }
+            Op::SneVxVy { x, y } => {
+                if self.registers[x as usize] != self.registers[y as usize] {
+                    self.skip_word(self.program_counter - 2)?;
+                }
+            }
+            Op::LdI { nnn: addr } => self.set_i(addr),
+            Op::JpV0 { nnn: addr } => {
+                let offset_register = if self.quirks.jump_uses_vx {
+                    (addr >> 8) as usize
+                } else {
+                    0
+                };
+                self.program_counter = addr + self.registers[offset_register] as u16;
+            }
+            Op::Rnd { x, nn: byte } => {
+                let r = self.rng.next_u8();
+                self.registers[x as usize] = r & byte;
+            }
+            Op::Drw { x, y, .. } if self.mega_mode() => {
+                let (vx, vy) = (self.registers[x as usize], self.registers[y as usize]);
+                let len = self.megachip.as_ref().map_or(0, |mega| mega.sprite_len());
+                let sprite = self.read_bytes(self.i_for(len)?, len);
+                let collision = self
+                    .megachip
+                    .as_mut()
+                    .is_some_and(|mega| mega.draw(vx as usize, vy as usize, &sprite));
+                self.registers[0xf] = collision as u8;
+            }
+            Op::Drw { x, y, n: nibble } => {
+                let vx = self.registers[x as usize];
+                let vy = self.registers[y as usize];
+
+                // read the sprite's rows from memory at I, into a fixed buffer since n is at most 15
+                let mut sprite = [0; 16];
+                let start = self.i_for(nibble as usize)?;
+                for (i, b) in sprite[..nibble as usize].iter_mut().enumerate() {
+                    *b = self.read(start + i);
+                }
+
+                // the start position always wraps; the rest of the sprite
+                // clips at the edges unless the wrap quirk is on
+                let left = vx as usize % SCREEN_WIDTH;
+                let height = self.screen_height();
+                let top = vy as usize % height;
+                let mut collision_flag = false;
+                for (row, b) in sprite[..nibble as usize].iter().enumerate() {
+                    let mut py = top + row;
+                    if py >= height {
+                        if !self.quirks.wrap_sprites {
+                            break;
+                        }
+                        py %= height;
+                    }
+                    // line the byte up at the left edge, then move it into place:
+                    // rotating wraps the bits past the right edge, shifting drops them
+                    let at_left_edge = (*b as u64) << (SCREEN_WIDTH - 8);
+                    let sprite_row = if self.quirks.wrap_sprites {
+                        at_left_edge.rotate_right(left as u32)
+                    } else {
+                        at_left_edge >> left
+                    };
+                    collision_flag |= self.rows[py] & sprite_row != 0;
+                    self.rows[py] ^= sprite_row;
+                }
+
+                if collision_flag {
+                    // TODO: When does the overflow flag get set to false? Should I set to false if there's no overflow?
+                    self.registers[0xf] = 0x1; // true
+                } else {
+                    self.registers[0xf] = 0x0; // false
+                }
+                // the VIP drew during the display interrupt, so nothing else ran until the next frame
+                self.waiting_for_vblank = self.quirks.display_wait;
+            }
+            // only the low nibble of VX picks the key
+            Op::Skp { x } => {
+                let is_key_pressed = self.key_down(self.registers[x as usize] as usize & 0xF);
+                if is_key_pressed {
+                    self.skip_word(self.program_counter - 2)?;
+                }
+            }
+            Op::Sknp { x } => {
+                // skip if key not pressed
+                let is_key_pressed = self.key_down(self.registers[x as usize] as usize & 0xF);
+                if !is_key_pressed {
+                    self.skip_word(self.program_counter - 2)?;
+                }
+            }
+            Op::LdVxDt { x } => self.registers[x as usize] = self.delay_timer,
+            Op::LdVxK { x } => {
+                // if a key was down before...
+                if let Some(key_idx) = self.key_down_ld_vx_k {
+                    // but now is up...
+                    if !self.key_down(key_idx as usize) {
+                        self.registers[x as usize] = key_idx;
+                        self.key_down_ld_vx_k = None;
+                    } else {
+                        // still held, keep waiting for the release
+                        self.program_counter -= 2;
+                    }
+                } else {
+                    // check if a key is down, then record it
+                    if let Some(found) = (0..16).find(|key_idx| self.key_down(*key_idx)) {
+                        self.key_down_ld_vx_k = Some(found as u8);
+                    }
+                    // keep waiting... for key press or key release
+                    self.program_counter -= 2;
+                }
+            }
+            Op::LdDtVx { x } => self.delay_timer = self.registers[x as usize],
+            Op::LdStVx { x } => self.sound_timer = self.registers[x as usize],
+            Op::AddIVx { x } => {
+                let overflowed = self.add_to_i(self.registers[x as usize] as u16);
+                if self.quirks.add_i_sets_vf {
+                    self.registers[0xF] = overflowed as u8;
+                }
+            }
+            Op::LdFVx { x } => {
+                let bytes_per_letter = 5;
+                // only the low nibble of VX picks the digit
+                let digit = (self.registers[x as usize] & 0xF) as u16;
+                self.set_i(FONT_START as u16 + digit * bytes_per_letter);
+            }
+            Op::LdBVx { x } => {
+                let vx = self.registers[x as usize];
+                let i = self.i_for(3)?;
+                self.write(i, (vx / 100) % 10);
+                self.write(i + 1, (vx / 10) % 10);
+                self.write(i + 2, vx % 10);
+            }
+            Op::LdIVx { x } => {
+                let i = self.i_for(x as usize + 1)?;
+                for idx in 0..=x {
+                    self.write(i + idx as usize, self.registers[idx as usize]);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.add_to_i(x as u16 + 1);
+                }
+            }
+            Op::LdVxI { x } => {
+                let i = self.i_for(x as usize + 1)?;
+                for idx in 0..=x {
+                    self.registers[idx as usize] = self.read(i + idx as usize);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.add_to_i(x as u16 + 1);
+                }
+            }
+            Op::LdRVx { x } => {
+                // registers past the last flag aren't saved
+                let count = (x as usize + 1).min(RPL_FLAG_COUNT);
+                self.rpl_flags[..count].copy_from_slice(&self.registers[..count]);
+            }
+            Op::LdVxR { x } => {
+                let count = (x as usize + 1).min(RPL_FLAG_COUNT);
+                self.registers[..count].copy_from_slice(&self.rpl_flags[..count]);
+            }
+            Op::Mega(op) => self.execute_mega(op)?,
+            // only reached in lenient mode, where it's a NOP
+            Op::Invalid => log::warn!("skipping invalid opcode"),
+        }
+
+        Ok(())
+    }
+
+    fn execute_mega(&mut self, op: MegaOp) -> Result<(), Chip8Error> {
+        if self.megachip.is_none() {
+            return Ok(());
+        }
+        let i = self.i();
+        // read what the instruction needs before borrowing the MegaChip state
+        let data = match op {
+            // the low 16 bits are the next word
+            MegaOp::LdIHi { .. } => self.fetch(self.program_counter - 2)?.to_be_bytes().to_vec(),
+            MegaOp::LdPal { nn } => self.read_bytes(i, 4 * nn as usize),
+            MegaOp::DigiSnd { .. } => {
+                // after a 16-bit sample rate come a 24-bit length and a reserved byte, then the samples
+                let header = self.read_bytes(i, 6);
+                let len = u32::from_be_bytes([0, header[2], header[3], header[4]]) as usize;
+                [&header[..2], &self.read_bytes(i + 6, len)].concat()
+            }
+            _ => vec![],
+        };
+        let Some(mega) = &mut self.megachip else {
+            return Ok(());
+        };
+        match op {
+            MegaOp::Off => mega.set_enabled(false),
+            MegaOp::On => mega.set_enabled(true),
+            MegaOp::LdIHi { nn } => {
+                mega.i_high = nn;
+                self.index_register = u16::from_be_bytes([data[0], data[1]]);
+            }
+            MegaOp::LdPal { .. } => mega.load_palette(&data),
+            MegaOp::SprW { nn } => mega.set_sprite_width(nn),
+            MegaOp::SprH { nn } => mega.set_sprite_height(nn),
+            MegaOp::Alpha { nn } => mega.set_alpha(nn),
+            MegaOp::DigiSnd { n } => mega.play(DigitizedSound {
+                sample_rate: u16::from_be_bytes([data[0], data[1]]),
+                samples: data[2..].to_vec(),
+                looping: n != 1,
+            }),
+            MegaOp::StopSnd => mega.stop_sound(),
+            MegaOp::BlendMode { n } => mega.set_blend(Blend::from_n(n)),
+            MegaOp::CollisionColor { nn } => mega.set_collision_color(nn),
+            MegaOp::ScrollUp { n } => mega.scroll_up(n as usize),
+        }
+        Ok(())
+    }
+}
+
+/// Changes to an [`Interpreter`]'s state from outside the program, made
+/// through [`Interpreter::poke`]. They don't trigger watchpoints, and the
+/// program picks up from the changed state when it next steps.
+pub struct Poke<'a> {
+    interpreter: &'a mut Interpreter,
+}
+
+impl Poke<'_> {
+    /// set Vx, for `x` from 0 to F
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.interpreter.registers[x & 0xF] = value;
+    }
+
+    pub fn set_index_register(&mut self, value: u16) {
+        self.interpreter.set_i(value);
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.interpreter.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.interpreter.sound_timer = value;
+    }
+
+    /// set the byte at `addr`, which wraps around 4 KiB of memory as the program's own writes do
+    pub fn write_memory(&mut self, addr: usize, value: u8) {
+        self.interpreter.write(addr, value);
+    }
+}
+
+/// Decode one instruction word. This only looks at the word, so it works
+/// without an interpreter, e.g. for disassembling; words that aren't
+/// instructions decode to `Op::Invalid`.
+pub fn decode(instruction: u16) -> Op {
+    let first_nibble: U4 = ((0xF000_u16 & instruction) >> 12) as U4;
+    let x = ((0x0F00_u16 & instruction) >> 8) as U4;
+    let y = ((0x00F0_u16 & instruction) >> 4) as U4;
+    let n = (0x000F_u16 & instruction) as U4;
+
+    let nnn: U8 = 0x0FFF_u16 & instruction;
+    let nn = (0x00FF_u16 & instruction) as u8;
+    log::debug!(
+        "instruction: {:#06x}, as nibbles: {:#03x} {:#03x} {:#03x} {:#03x}, nn: {:#04x}, nnn: {:#05x}",
+        instruction, first_nibble, x, y, n, nn, nnn
+    );
+    match first_nibble {
+        0 => match instruction {
+            0x00E0 => Op::Cls,
+            0x00EE => Op::Ret,
+            0x00FD => Op::Exit,
+            _ => MegaOp::decode(instruction).map_or(Op::Sys, Op::Mega),
+        },
+        1 => Op::Jp { nnn },
+        2 => Op::Call { nnn },
+        3 => Op::Se { x, nn },
+        4 => Op::Sne { x, nn },
+        5 => {
+            if n != 0 {
+                return Op::Invalid;
+            }
+            Op::SeVxVy { x, y }
+        }
+        6 => Op::Ld { x, nn },
+        7 => Op::Add { x, nn },
+        8 => match n {
+            0 => Op::LdVxVy { x, y },
+            1 => Op::OrVxVy { x, y },
+            2 => Op::AndVxVy { x, y },
+            3 => Op::XorVxVy { x, y },
+            4 => Op::AddVxVy { x, y },
+            5 => Op::SubVxVy { x, y },
+            6 => Op::ShrVxVy { x, y },
+            7 => Op::SubnVxVy { x, y },
+            0xE => Op::ShlVxVy { x, y },
+            _ => Op::Invalid,
+        },
+        9 => {
+            if n != 0 {
+                return Op::Invalid;
+            }
+
+            Op::SneVxVy { x, y }
+        }
+        0xA => Op::LdI { nnn },
+        0xB => Op::JpV0 { nnn },
+        0xC => Op::Rnd { x, nn },
+        0xD => Op::Drw { x, y, n },
+        0xE => match nn {
+            0x9E => Op::Skp { x },
+            0xA1 => Op::Sknp { x },
+            _ => Op::Invalid,
+        },
+        0xF => match nn {
+            0x07 => Op::LdVxDt { x },
+            0x0A => Op::LdVxK { x },
+            0x15 => Op::LdDtVx { x },
+            0x18 => Op::LdStVx { x },
+            0x1E => Op::AddIVx { x },
+            0x29 => Op::LdFVx { x },
+            0x33 => Op::LdBVx { x },
+            0x55 => Op::LdIVx { x },
+            0x65 => Op::LdVxI { x },
+            0x75 => Op::LdRVx { x },
+            0x85 => Op::LdVxR { x },
+            _ => Op::Invalid,
+        },
+        _ => Op::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod test_support;
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::{op_test, NEXT, SKIPPED};
+    use super::*;
+    use crate::{megachip::MEGA_WIDTH, quirks::Quirks};
+
+    const START: u16 = PROGRAM_START as u16;
+
+    op_test!(test_op_cls, 0x00E0, { pixel(5), pixel(100) } => |vm| {
+        assert!(vm.pixels().iter().all(|p| !p));
+    });
+    op_test!(test_op_ret, 0x00EE, { call_stack(0x300) } => |vm| {
+        assert_eq!(vm.program_counter, 0x300);
+        assert_eq!(vm.stack_pointer, 0);
+    });
+    op_test!(test_op_sys_is_ignored, 0x0123, {} => |vm| assert_eq!(vm.program_counter, NEXT));
+    op_test!(test_op_jp, 0x1234, {} => |vm| assert_eq!(vm.program_counter, 0x234));
+    op_test!(test_op_call, 0x2345, {} => |vm| {
+        assert_eq!(vm.program_counter, 0x345);
+        assert_eq!(vm.stack_pointer, 1);
+        assert_eq!(vm.stack[1], NEXT);
+    });
+    op_test!(test_op_se_skips_when_equal, 0x3A42, { reg(0xA, 0x42) } => |vm| {
+        assert_eq!(vm.program_counter, SKIPPED);
+    });
+    op_test!(test_op_se_does_not_skip, 0x3A42, { reg(0xA, 0x41) } => |vm| {
+        assert_eq!(vm.program_counter, NEXT);
+    });
+    op_test!(test_op_sne_skips_when_not_equal, 0x4A42, { reg(0xA, 0x41) } => |vm| {
+        assert_eq!(vm.program_counter, SKIPPED);
+    });
+    op_test!(test_op_sne_does_not_skip, 0x4A42, { reg(0xA, 0x42) } => |vm| {
+        assert_eq!(vm.program_counter, NEXT);
+    });
+    op_test!(test_op_se_vx_vy, 0x5AB0, { reg(0xA, 7), reg(0xB, 7) } => |vm| {
+        assert_eq!(vm.program_counter, SKIPPED);
+    });
+    op_test!(test_op_ld_vx_byte, 0x6A42, {} => |vm| assert_eq!(vm.registers[0xA], 0x42));
+    op_test!(test_op_add_vx_byte_wraps_without_carry, 0x7AFF, { reg(0xA, 2), reg(0xF, 5) } => |vm| {
+        assert_eq!(vm.registers[0xA], 1);
+        assert_eq!(vm.registers[0xF], 5);
+    });
+    op_test!(test_op_ld_vx_vy, 0x8AB0, { reg(0xB, 9) } => |vm| assert_eq!(vm.registers[0xA], 9));
+    op_test!(test_op_or, 0x8AB1, { reg(0xA, 0b1100), reg(0xB, 0b1010), reg(0xF, 1) } => |vm| {
+        assert_eq!(vm.registers[0xA], 0b1110);
+        assert_eq!(vm.registers[0xF], 0);
+    });
+    op_test!(test_op_and, 0x8AB2, { reg(0xA, 0b1100), reg(0xB, 0b1010) } => |vm| {
+        assert_eq!(vm.registers[0xA], 0b1000);
+    });
+    op_test!(test_op_xor, 0x8AB3, { reg(0xA, 0b1100), reg(0xB, 0b1010) } => |vm| {
+        assert_eq!(vm.registers[0xA], 0b0110);
+    });
+    op_test!(test_op_add_vx_vy_sets_carry, 0x8014, { reg(0, 0xFF), reg(1, 2) } => |vm| {
+        assert_eq!(vm.registers[0], 1);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_op_add_vx_vy_clears_carry, 0x8014, { reg(0, 1), reg(1, 2), reg(0xF, 1) } => |vm| {
+        assert_eq!(vm.registers[0], 3);
+        assert_eq!(vm.registers[0xF], 0);
+    });
+    op_test!(test_op_sub_without_borrow, 0x8015, { reg(0, 5), reg(1, 3) } => |vm| {
+        assert_eq!(vm.registers[0], 2);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_op_sub_with_borrow, 0x8015, { reg(0, 3), reg(1, 5) } => |vm| {
+        assert_eq!(vm.registers[0], 0xFE);
+        assert_eq!(vm.registers[0xF], 0);
+    });
+    op_test!(test_op_shr_shifts_vy, 0x8016, { reg(0, 0xF0), reg(1, 0b11) } => |vm| {
+        assert_eq!(vm.registers[0], 0b1);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_op_subn, 0x8017, { reg(0, 3), reg(1, 5) } => |vm| {
+        assert_eq!(vm.registers[0], 2);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_op_shl_shifts_vy, 0x801E, { reg(0, 0x0F), reg(1, 0x81) } => |vm| {
+        assert_eq!(vm.registers[0], 0x02);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_op_sne_vx_vy, 0x9AB0, { reg(0xA, 1), reg(0xB, 2) } => |vm| {
+        assert_eq!(vm.program_counter, SKIPPED);
+    });
+    op_test!(test_op_ld_i, 0xA123, {} => |vm| assert_eq!(vm.index_register, 0x123));
+    op_test!(test_op_jp_v0, 0xB300, { reg(0, 4) } => |vm| assert_eq!(vm.program_counter, 0x304));
+    op_test!(test_op_rnd_is_masked, 0xC000, { reg(0, 0xFF) } => |vm| assert_eq!(vm.registers[0], 0));
+    op_test!(test_op_drw, 0xD011, { reg(0, 1), reg(1, 2), i(0x300), mem(0x300, &[0x80]) } => |vm| {
+        assert!(vm.pixels()[2 * SCREEN_WIDTH + 1]);
+        assert_eq!(vm.pixels().iter().filter(|p| **p).count(), 1);
+        assert_eq!(vm.registers[0xF], 0);
+    });
+    op_test!(test_op_drw_collision, 0xD011, { reg(0, 1), reg(1, 2), i(0x300), mem(0x300, &[0x80]), pixel(2 * SCREEN_WIDTH + 1) } => |vm| {
+        assert!(!vm.pixels()[2 * SCREEN_WIDTH + 1]);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_op_skp, 0xE09E, { reg(0, 5), key(5) } => |vm| {
+        assert_eq!(vm.program_counter, SKIPPED);
+    });
+    op_test!(test_op_sknp, 0xE0A1, { reg(0, 5) } => |vm| {
+        assert_eq!(vm.program_counter, SKIPPED);
+    });
+    op_test!(test_op_ld_vx_dt, 0xF007, { dt(9) } => |vm| assert_eq!(vm.registers[0], 9));
+    op_test!(test_op_ld_vx_k_waits_for_press, 0xF00A, {} => |vm| {
+        assert_eq!(vm.program_counter, START);
+    });
+    op_test!(test_op_ld_vx_k_waits_for_release, 0xF00A, { key(3) } => |vm| {
+        assert_eq!(vm.program_counter, START);
+        assert_eq!(vm.key_down_ld_vx_k, Some(3));
+    });
+    op_test!(test_op_ld_vx_k_waits_while_held, 0xF00A, { waiting_for_key(3), key(3) } => |vm| {
+        assert_eq!(vm.program_counter, START);
+        assert_eq!(vm.registers[0], 0);
+        assert!(vm.should_play_sound());
+    });
+    op_test!(test_op_ld_vx_k_stores_released_key, 0xF00A, { waiting_for_key(3) } => |vm| {
+        assert_eq!(vm.program_counter, NEXT);
+        assert_eq!(vm.registers[0], 3);
+    });
+    op_test!(test_op_ld_dt_vx, 0xF015, { reg(0, 7) } => |vm| assert_eq!(vm.delay_timer, 7));
+    op_test!(test_op_ld_st_vx, 0xF018, { reg(0, 7), st(3) } => |vm| assert_eq!(vm.sound_timer, 7));
+    op_test!(test_op_add_i_vx, 0xF01E, { i(0x100), reg(0, 0x10) } => |vm| {
+        assert_eq!(vm.index_register, 0x110);
+    });
+    op_test!(test_op_add_i_vx_wraps, 0xF01E, { i(0xFFFF), reg(0, 2) } => |vm| {
+        assert_eq!(vm.index_register, 1);
+    });
+    op_test!(test_op_ld_f_vx, 0xF029, { reg(0, 0xA) } => |vm| {
+        assert_eq!(vm.index_register as usize, FONT_START + 0xA * 5);
+    });
+    op_test!(test_op_ld_f_vx_uses_low_nibble, 0xF029, { reg(0, 0x5A) } => |vm| {
+        assert_eq!(vm.index_register as usize, FONT_START + 0xA * 5);
+    });
+    op_test!(test_op_skp_uses_low_nibble, 0xE09E, { reg(0, 0x13), key(3) } => |vm| {
+        assert_eq!(vm.program_counter, SKIPPED);
+    });
+    op_test!(test_op_ld_b_vx, 0xF033, { reg(0, 123), i(0x300) } => |vm| {
+        assert_eq!(vm.memory_map[0x300..0x303], [1, 2, 3]);
+    });
+    op_test!(test_op_ld_b_vx_wraps_past_end_of_memory, 0xF033, { reg(0, 123), i(0xFFE) } => |vm| {
+        assert_eq!(vm.memory_map[0xFFE..], [1, 2]);
+        assert_eq!(vm.memory_map[0], 3);
+    });
+    op_test!(test_op_ld_i_vx, 0xF255, { reg(0, 1), reg(1, 2), reg(2, 3), i(0x300) } => |vm| {
+        assert_eq!(vm.memory_map[0x300..0x304], [1, 2, 3, 0]);
+        assert_eq!(vm.index_register, 0x303);
+    });
+    op_test!(test_op_ld_vx_i, 0xF265, { i(0x300), mem(0x300, &[4, 5, 6, 7]) } => |vm| {
+        assert_eq!(vm.registers[0..4], [4, 5, 6, 0]);
+        assert_eq!(vm.index_register, 0x303);
+    });
+    op_test!(test_op_ld_r_vx, 0xF175, { reg(0, 1), reg(1, 2), reg(2, 3) } => |vm| {
+        assert_eq!(vm.rpl_flags, [1, 2, 0, 0, 0, 0, 0, 0]);
+    });
+    op_test!(test_op_ld_r_vx_saves_at_most_8, 0xFF75, { reg(7, 8), reg(8, 9) } => |vm| {
+        assert_eq!(vm.rpl_flags[7], 8);
+    });
+    op_test!(test_op_ld_vx_r, 0xF185, { rpl_flags([4, 5, 6, 0, 0, 0, 0, 0]) } => |vm| {
+        assert_eq!(vm.registers[0..3], [4, 5, 0]);
+    });
+
+    #[test]
+    fn test_addvxvy_carry_bit_should_be_set_last() -> Result<(), Chip8Error> {
+        let mut vm = Interpreter::new();
+
+        // 2+2 = 4, but should be overriden to 0 in flag Vf
+        vm.registers[0xf] = 2;
+        vm.execute(Op::AddVxVy { x: 0xf, y: 0xf })?;
+        assert_eq!(vm.registers[0xf], 0);
+
+        // 0xff + 0xff = 0xfe with wrapping overflow, but should be overriden to 1 in flag Vf
+        vm.registers[0xf] = 0xff;
+        vm.execute(Op::AddVxVy { x: 0xf, y: 0xf })?;
+        assert_eq!(vm.registers[0xf], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_some_ops_should_reset_vf() -> Result<(), Chip8Error> {
+        let mut vm = Interpreter::new();
+
+        vm.registers[0xf] = 1;
+        vm.execute(Op::AndVxVy { x: 0, y: 1 })?;
+        assert_eq!(vm.registers[0xf], 0);
+
+        vm.registers[0xf] = 1;
+        vm.execute(Op::OrVxVy { x: 0, y: 1 })?;
+        assert_eq!(vm.registers[0xf], 0);
+
+        vm.registers[0xf] = 1;
+        vm.execute(Op::XorVxVy { x: 0, y: 1 })?;
+        assert_eq!(vm.registers[0xf], 0);
+
+        Ok(())
+    }
+
+    op_test!(test_debug_accessors, 0x2300, { mem(0x300, &[0x6A, 0x42]) } => |vm| {
+        assert_eq!(vm.call_stack(), &[NEXT]);
+        assert_eq!(vm.next_instruction(), Some(0x6A42));
+    });
+
+    #[test]
+    fn test_breakpoint_stops_once() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        // LD V0, 1; LD V0, 2; JP 0x202
+        interpreter.load_program(&[0x60, 0x01, 0x60, 0x02, 0x12, 0x02])?;
+        interpreter.add_breakpoint(0x202);
+
+        assert_eq!(interpreter.step()?, StepOutcome::Executed);
+        assert_eq!(interpreter.step()?, StepOutcome::Breakpoint(0x202));
+        assert_eq!(interpreter.registers[0], 1);
+        assert_eq!(interpreter.step()?, StepOutcome::Executed);
+        assert_eq!(interpreter.registers[0], 2);
+
+        // the jump comes back around to the breakpoint
+        interpreter.step()?;
+        assert_eq!(interpreter.step()?, StepOutcome::Breakpoint(0x202));
+
+        interpreter.remove_breakpoint(0x202);
+        assert_eq!(interpreter.breakpoints().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_watchpoints() -> Result<(), Chip8Error> {
+        use crate::watch::{Access, WatchHit, WatchKind, WatchTarget};
+
+        let mut interpreter = Interpreter::new();
+        // LD V3, 5; LD I, 0x300; LD [I], V3; LD I, 0x300; LD V3, [I]
+        interpreter.load_program(&[0x63, 0x05, 0xA3, 0x00, 0xF3, 0x55, 0xA3, 0x00, 0xF3, 0x65])?;
+        interpreter.add_watchpoint(WatchTarget::Register(3), WatchKind::Write);
+        interpreter.add_watchpoint(WatchTarget::Memory(0x303), WatchKind::Read);
+
+        assert_eq!(
+            interpreter.step()?,
+            StepOutcome::Watchpoint(WatchHit {
+                pc: 0x200,
+                target: WatchTarget::Register(3),
+                access: Access::Write,
+                old: 0,
+                new: 5,
+            })
+        );
+        assert_eq!(interpreter.step()?, StepOutcome::Executed);
+        // writing 0x303 and reading V3 aren't watched
+        assert_eq!(interpreter.step()?, StepOutcome::Executed);
+        assert_eq!(interpreter.step()?, StepOutcome::Executed);
+        // the read of 0x303 comes before the write to V3, so it's reported
+        let StepOutcome::Watchpoint(hit) = interpreter.step()? else {
+            panic!("expected a watchpoint");
+        };
+        assert_eq!(
+            (hit.target, hit.access),
+            (WatchTarget::Memory(0x303), Access::Read)
+        );
+        assert_eq!(hit.to_string(), "[0x303] read at 0x208: 0x05 -> 0x05");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seeded_rnd_is_repeatable() -> Result<(), Chip8Error> {
+        let rolls = |seed: Option<u64>| -> Result<Vec<u8>, Chip8Error> {
+            let mut interpreter = Interpreter::new();
+            if let Some(seed) = seed {
+                interpreter.set_seed(seed);
+            }
+            // RND V0, 0xFF, forever
+            interpreter.load_program(&[0xC0, 0xFF, 0x12, 0x00])?;
+            let mut rolls = vec![];
+            for _ in 0..8 {
+                interpreter.step()?;
+                interpreter.step()?;
+                rolls.push(interpreter.registers[0]);
+            }
+            Ok(rolls)
+        };
+
+        assert_eq!(rolls(Some(7))?, rolls(Some(7))?);
+        assert_ne!(rolls(Some(7))?, rolls(Some(8))?);
+        assert_eq!(rolls(None)?, rolls(None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_resumes_rnd_sequence() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_seed(1);
+        interpreter.load_program(&[0xC0, 0xFF])?;
+        let snapshot = interpreter.snapshot();
+        interpreter.step()?;
+        let first = interpreter.registers[0];
+
+        interpreter.restore(&snapshot);
+        interpreter.step()?;
+        assert_eq!(interpreter.registers[0], first);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_program_rejects_large_roms() {
+        let mut interpreter = Interpreter::new();
+        let rom = vec![0; MEMORY_SIZE - PROGRAM_START + 1];
+        let err = interpreter.load_program(&rom).unwrap_err();
+        assert!(matches!(
+            err,
+            Chip8Error::RomTooLarge {
+                size: 3585,
+                max: 3584
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "ROM is 3585 bytes, but only 3584 fit in memory"
+        );
+        assert!(interpreter.load_program(&rom[1..]).is_ok());
+    }
+
+    #[test]
+    fn test_timers_only_count_down_on_ticks() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        // LD V0, 2; LD DT, V0; LD ST, V0; then loop forever
+        interpreter.load_program(&[0x60, 0x02, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06])?;
+        for _ in 0..100 {
+            interpreter.step()?;
+        }
+        assert_eq!(interpreter.delay_timer(), 2);
+        assert!(interpreter.should_play_sound());
+        interpreter.tick_60hz();
+        interpreter.tick_60hz();
+        interpreter.tick_60hz();
+        assert_eq!(interpreter.delay_timer(), 0);
+        assert!(!interpreter.should_play_sound());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ret_with_empty_stack() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x00, 0xEE])?;
+        let err = interpreter.step().unwrap_err();
+        assert!(matches!(err, Chip8Error::StackUnderflow { addr: 0x200 }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_with_full_stack() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        // calls itself forever
+        interpreter.load_program(&[0x22, 0x00])?;
+        for _ in 0..15 {
+            interpreter.step()?;
+        }
+        let Err(Chip8Error::StackOverflow { addr, stack }) = interpreter.step() else {
+            panic!("expected a stack overflow");
+        };
+        assert_eq!(addr, 0x200);
+        assert_eq!(stack, [0x202; 15]);
+        assert_eq!(interpreter.call_stack(), [0x202; 15]);
+        assert_eq!(interpreter.program_counter(), 0x200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_opcode() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x60, 0x01, 0x5A, 0xB1])?;
+        interpreter.step()?;
+        let err = interpreter.step().unwrap_err();
+        assert!(matches!(
+            err,
+            Chip8Error::InvalidOpcode {
+                addr: 0x202,
+                word: 0x5AB1
+            }
+        ));
+        assert_eq!(err.to_string(), "invalid opcode 5AB1 at 0x202");
+        assert_eq!(interpreter.program_counter(), 0x202);
+
+        interpreter.set_lenient(true);
+        interpreter.step()?;
+        assert_eq!(interpreter.program_counter(), 0x204);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict() -> Result<(), Chip8Error> {
+        let strict = |program: &[u8]| -> Result<Chip8Error, Chip8Error> {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_strict(true);
+            interpreter.set_lenient(true);
+            interpreter.load_program(program)?;
+            let err = interpreter.step().unwrap_err();
+            assert_eq!(interpreter.program_counter(), 0x200);
+            Ok(err)
+        };
+        let err = strict(&[0x03, 0x40])?;
+        assert!(matches!(
+            err,
+            Chip8Error::Strict {
+                addr: 0x200,
+                word: 0x0340,
+                violation: StrictViolation::Sys { target: 0x340 }
+            }
+        ));
+        assert!(err
+            .to_string()
+            .starts_with("strict: 0340 at 0x200 calls machine code at 0x340"));
+        let err = strict(&[0x5A, 0xB1])?;
+        assert!(matches!(
+            err,
+            Chip8Error::Strict {
+                violation: StrictViolation::Undocumented,
+                ..
+            }
+        ));
+
+        // LD I, 0x1FE; LD [I], V3 reaches from 0x1FE to 0x201
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict(true);
+        interpreter.load_program(&[0xA1, 0xFE, 0xF3, 0x55])?;
+        interpreter.step()?;
+        let err = interpreter.step().unwrap_err();
+        assert!(matches!(
+            err,
+            Chip8Error::Strict {
+                addr: 0x202,
+                violation: StrictViolation::ReservedWrite {
+                    first: 0x1FE,
+                    last: 0x1FF,
+                    i: 0x1FE
+                },
+                ..
+            }
+        ));
+        assert!(err
+            .to_string()
+            .contains("writes to 0x1fe-0x1ff with I at 0x1fe"));
+
+        // reading the font and writing above it are both fine
+        let mut interpreter = Interpreter::new();
+        interpreter.set_strict(true);
+        interpreter.load_program(&[0xA0, 0x00, 0xF3, 0x65, 0xA3, 0x00, 0xF3, 0x55])?;
+        for _ in 0..4 {
+            interpreter.step()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sys_handler() -> Result<(), Chip8Error> {
+        use std::{cell::RefCell, rc::Rc};
+
+        // SYS 0xA01 prints V0, and SYS 0xA00 ends the run
+        let printed = Rc::new(RefCell::new(vec![]));
+        let log = printed.clone();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_sys_handler(move |addr: u16, interpreter: &mut Interpreter| {
+            log.borrow_mut().push((addr, interpreter.registers()[0]));
+            interpreter.poke().set_register(1, 0xFF);
+            match addr {
+                0xA00 => SysOutcome::Exit,
+                _ => SysOutcome::Continue,
+            }
+        });
+        // LD V0, 7; SYS 0xA01; 0x0123 is MegaChip's LD I, but SYS without it; SYS 0xA00; LD V0, 8
+        interpreter.load_program(&[0x60, 0x07, 0x0A, 0x01, 0x01, 0x23, 0x0A, 0x00, 0x60, 0x08])?;
+        for _ in 0..5 {
+            interpreter.step()?;
+        }
+        assert_eq!(*printed.borrow(), [(0xA01, 7), (0x123, 7), (0xA00, 7)]);
+        assert_eq!(interpreter.registers()[1], 0xFF);
+        assert_eq!(interpreter.state(), &RunState::Exited);
+        assert_eq!(interpreter.program_counter(), 0x208);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_sys_handler(|_, _: &mut Interpreter| SysOutcome::Exit);
+        interpreter.clear_sys_handler();
+        interpreter.load_program(&[0x0A, 0x00, 0x0A, 0x00])?;
+        interpreter.step()?;
+        assert_eq!(interpreter.state(), &RunState::Running);
+        Ok(())
+    }
+
+    #[test]
+    fn test_observer() -> Result<(), Chip8Error> {
+        use std::{cell::RefCell, rc::Rc};
+
+        struct Log(Rc<RefCell<Vec<String>>>);
+        impl Observer for Log {
+            fn on_draw(&mut self, addr: u16, x: u8, y: u8, n: u8, collided: bool) {
+                let event = format!("draw {:#05x} ({}, {}) {} {}", addr, x, y, n, collided);
+                self.0.borrow_mut().push(event);
+            }
+            fn on_clear(&mut self, addr: u16) {
+                self.0.borrow_mut().push(format!("clear {:#05x}", addr));
+            }
+            fn on_sound_start(&mut self) {
+                self.0.borrow_mut().push(String::from("sound start"));
+            }
+            fn on_sound_stop(&mut self) {
+                self.0.borrow_mut().push(String::from("sound stop"));
+            }
+            fn on_key_wait(&mut self, addr: u16, x: u8) {
+                self.0
+                    .borrow_mut()
+                    .push(format!("key wait {:#05x} V{}", addr, x));
+            }
+            fn on_call(&mut self, addr: u16, target: u16) {
+                let event = format!("call {:#05x} -> {:#05x}", addr, target);
+                self.0.borrow_mut().push(event);
+            }
+            fn on_ret(&mut self, addr: u16, target: u16) {
+                let event = format!("ret {:#05x} -> {:#05x}", addr, target);
+                self.0.borrow_mut().push(event);
+            }
+        }
+
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut vm = Interpreter::new();
+        vm.add_observer(Log(log.clone()));
+        vm.load_program(&[
+            0x00, 0xE0, // 0x200: CLS
+            0x22, 0x0A, // CALL 0x20A
+            0xF1, 0x0A, // LD V1, K
+            0x12, 0x06, // JP 0x206
+            0x00, 0x00, //
+            0x60, 0x03, // 0x20A: LD V0, 3
+            0xF0, 0x18, // LD ST, V0
+            0xA2, 0x00, // LD I, 0x200
+            0xD0, 0x05, // DRW V0, V0, 5
+            0xD0, 0x05, // DRW V0, V0, 5
+            0x00, 0xEE, // RET
+        ])?;
+        for _ in 0..10 {
+            vm.step()?;
+        }
+        for _ in 0..3 {
+            vm.tick_60hz();
+        }
+        assert_eq!(
+            *log.borrow(),
+            [
+                "clear 0x200",
+                "call 0x202 -> 0x20a",
+                "sound start",
+                "draw 0x210 (3, 3) 5 false",
+                "draw 0x212 (3, 3) 5 true",
+                "ret 0x214 -> 0x204",
+                "key wait 0x204 V1",
+                "sound stop",
+            ]
+        );
+
+        vm.clear_observers();
+        vm.set_key(2, true);
+        vm.step()?;
+        assert_eq!(log.borrow().len(), 8);
+        Ok(())
+    }
+
+    const SCHIP: Quirks = Quirks::SUPER_CHIP;
+    const WRAP: Quirks = Quirks {
+        wrap_sprites: true,
+        ..Quirks::COSMAC_VIP
+    };
+    op_test!(test_quirk_logic_keeps_vf, 0x8AB1, { quirks(SCHIP), reg(0xF, 1) } => |vm| {
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_quirk_shift_in_place, 0x8016, { quirks(SCHIP), reg(0, 0b101), reg(1, 0xF0) } => |vm| {
+        assert_eq!(vm.registers[0], 0b10);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    #[test]
+    fn test_quirk_memory_out_of_bounds() -> Result<(), Chip8Error> {
+        let strict = Quirks {
+            memory_wraps: false,
+            ..Quirks::COSMAC_VIP
+        };
+        // FX33, FX55, FX65, and DXYN with I too near the end for them
+        for (word, i, target) in [
+            (0xF033, 0xFFE, 0x1000),
+            (0xF255, 0xFFE, 0x1000),
+            (0xF365, 0xFFD, 0x1000),
+            (0xD005, 0xFFC, 0x1000),
+        ] {
+            let mut interpreter = Interpreter::with_quirks(strict);
+            interpreter.load_program(&u16::to_be_bytes(word))?;
+            interpreter.index_register = i;
+            let err = interpreter.step().unwrap_err();
+            assert!(
+                matches!(err, Chip8Error::MemoryOutOfBounds { addr: 0x200, target: t } if t == target),
+                "{:04X}: {}",
+                word,
+                err
+            );
+            assert_eq!(interpreter.program_counter(), START);
+        }
+        assert_eq!(
+            Chip8Error::MemoryOutOfBounds {
+                addr: 0x200,
+                target: 0x1000
+            }
+            .to_string(),
+            "access to 0x1000 past the end of memory at 0x200"
+        );
+
+        // right up to the end is fine
+        let mut interpreter = Interpreter::with_quirks(strict);
+        interpreter.load_program(&[0xF1, 0x65])?;
+        interpreter.index_register = 0xFFE;
+        interpreter.poke().write_memory(0xFFF, 7);
+        interpreter.step()?;
+        assert_eq!(interpreter.registers()[1], 7);
+        Ok(())
+    }
+
+    op_test!(test_quirk_add_i_sets_vf, 0xF01E, { quirks(Quirks::AMIGA), i(0xFFE), reg(0, 3), reg(0xF, 0) } => |vm| {
+        assert_eq!(vm.index_register, 0x001);
+        assert_eq!(vm.registers[0xF], 1);
+    });
+    op_test!(test_quirk_add_i_clears_vf, 0xF01E, { quirks(Quirks::AMIGA), i(0x300), reg(0, 3), reg(0xF, 1) } => |vm| {
+        assert_eq!(vm.index_register, 0x303);
+        assert_eq!(vm.registers[0xF], 0);
+    });
+    op_test!(test_quirk_i_12_bits_on_store, 0xF155, { quirks(Quirks::AMIGA), i(0xFFF) } => |vm| {
+        assert_eq!(vm.index_register, 0x001);
+    });
+    #[test]
+    fn test_quirk_display_wait() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::with_quirks(Quirks::COSMAC_VIP);
+        // DRW V0, V0, 1; LD V0, 1
+        interpreter.load_program(&[0xD0, 0x01, 0x60, 0x01])?;
+        interpreter.step()?;
+        assert!(interpreter.waiting_for_vblank());
+        interpreter.step()?;
+        assert_eq!(interpreter.program_counter(), START + 2);
+        interpreter.tick_60hz();
+        interpreter.step()?;
+        assert_eq!(interpreter.registers()[0], 1);
+
+        let mut interpreter = Interpreter::with_quirks(SCHIP);
+        interpreter.load_program(&[0xD0, 0x01])?;
+        interpreter.step()?;
+        assert!(!interpreter.waiting_for_vblank());
+        Ok(())
+    }
+    #[test]
+    fn test_program_start() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_program_start(ETI_660_PROGRAM_START as u16);
+        // LD V0, 1; JP 0x600
+        interpreter.load_program(&[0x60, 0x01, 0x16, 0x00])?;
+        assert_eq!(interpreter.program_range(), 0x600..0x604);
+        interpreter.step()?;
+        assert_eq!(interpreter.registers()[0], 1);
+        interpreter.step()?;
+        assert_eq!(interpreter.program_counter(), 0x600);
+
+        let too_large = vec![0; MEMORY_SIZE - ETI_660_PROGRAM_START + 1];
+        assert!(matches!(
+            interpreter.load_program(&too_large),
+            Err(Chip8Error::RomTooLarge { max: 0xA00, .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_megachip() -> Result<(), Chip8Error> {
+        let rom = [
+            0x00, 0x11, // MEGAON
+            0x01, 0x01, 0x00, 0x00, // LDHI I, 0x10000
+            0x60, 0x2A, // LD V0, 0x2A
+            0xF0, 0x55, // LD [I], V0
+            0x03, 0x01, // SPRW 1
+            0x04, 0x01, // SPRH 1
+            0xD0, 0x00, // DRW V0, V0, 0, with the byte just stored as its color
+            0x00, 0xE0, // CLS, showing the frame
+        ];
+        // leave I on the stored byte
+        let quirks = Quirks {
+            load_store_increments_i: false,
+            ..Quirks::default()
+        };
+        let mut interpreter = Interpreter::with_quirks(quirks);
+        interpreter.set_megachip(true);
+        // pad past 4 KiB, which only fits with MegaChip's memory
+        let mut padded = rom.to_vec();
+        padded.resize(MEMORY_SIZE, 0);
+        interpreter.load_program(&padded)?;
+        for _ in 0..8 {
+            interpreter.step()?;
+        }
+        assert_eq!(interpreter.read(0x10000), 0x2A);
+        let mega = interpreter.megachip().unwrap();
+        assert!(mega.enabled());
+        let at = 0x2A * MEGA_WIDTH + 0x2A;
+        assert_eq!(mega.screen()[at], 0xFF2A_2A2A);
+
+        // without MegaChip support its instructions do nothing
+        let mut interpreter = Interpreter::with_quirks(quirks);
+        interpreter.load_program(&rom)?;
+        for _ in 0..8 {
+            interpreter.step()?;
+        }
+        assert!(interpreter.megachip().is_none());
+        // I stays 0 without LDHI, and its low word runs as a SYS
+        assert_eq!(interpreter.memory()[0], 0x2A);
+        assert!(interpreter.load_program(&padded).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_megachip_runs_to_the_end_of_memory() -> Result<(), Chip8Error> {
+        // LD V0, 0 up to 0xFFFE, the last word the pc can reach
+        let rom: Vec<u8> = [0x60, 0x00].repeat(0xFE00 / 2);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_megachip(true);
+        interpreter.load_program(&rom)?;
+        let err = loop {
+            if let Err(e) = interpreter.step() {
+                break e;
+            }
+        };
+        assert!(matches!(
+            err,
+            Chip8Error::MemoryOutOfBounds {
+                addr: 0xFFFE,
+                target: 0x10000
+            }
+        ));
+        assert_eq!(interpreter.program_counter(), 0xFFFE);
+
+        // SE V0, 0 skipping past the end
+        let mut rom = rom;
+        rom[0xFDFC..0xFDFE].copy_from_slice(&[0x30, 0x00]);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_megachip(true);
+        interpreter.load_program(&rom)?;
+        let err = loop {
+            if let Err(e) = interpreter.step() {
+                break e;
+            }
+        };
+        assert!(matches!(
+            err,
+            Chip8Error::MemoryOutOfBounds {
+                addr: 0xFFFC,
+                target: 0x10000
+            }
+        ));
+        assert_eq!(interpreter.program_counter(), 0xFFFC);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(0x00E0), Op::Cls);
+        assert_eq!(decode(0x00EE), Op::Ret);
+        assert_eq!(decode(0x00FD), Op::Exit);
+        assert_eq!(decode(0x0A23), Op::Sys);
+        assert_eq!(decode(0x0123), Op::Mega(MegaOp::LdIHi { nn: 0x23 }));
+        assert_eq!(decode(0x2ABC), Op::Call { nnn: 0xABC });
+        assert_eq!(decode(0x7A42), Op::Add { x: 0xA, nn: 0x42 });
+        assert_eq!(decode(0x8ABE), Op::ShlVxVy { x: 0xA, y: 0xB });
+        assert_eq!(decode(0xD125), Op::Drw { x: 1, y: 2, n: 5 });
+        assert_eq!(decode(0xF329), Op::LdFVx { x: 3 });
+        for invalid in [0x5AB1, 0x8AB8, 0x9AB1, 0xE0FF, 0xF0FF] {
+            assert_eq!(decode(invalid), Op::Invalid);
+        }
+    }
+    #[test]
+    fn test_hires() -> Result<(), Chip8Error> {
+        let mut rom = vec![0; 0xC0];
+        rom[..2].copy_from_slice(&[0x12, 0x60]);
+        rom.extend([
+            0x60, 0x28, // LD V0, 40
+            0xF1, 0x29, // LD F, V1
+            0xD1, 0x05, // DRW V1, V0, 5, below the bottom of the usual display
+            0x02, 0x30, // the hires clear screen
+        ]);
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&rom)?;
+        assert!(interpreter.hires());
+        assert_eq!(interpreter.screen_height(), HIRES_SCREEN_HEIGHT);
+        assert_eq!(interpreter.program_counter(), 0x2C0);
+        for _ in 0..3 {
+            interpreter.step()?;
+        }
+        assert_eq!(interpreter.rows()[40], 0xF0 << 56);
+        interpreter.step()?;
+        assert!(interpreter.rows().iter().all(|row| *row == 0));
+
+        // only a jump to 0x260 at the very start marks a hires ROM
+        interpreter.load_program(&[0x00, 0xE0, 0x12, 0x60])?;
+        assert!(!interpreter.hires());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[
+            0x60, 0x05, // LD V0, 5
+            0xF0, 0x15, // LD DT, V0
+            0xF0, 0x29, // LD F, V0
+            0xD0, 0x05, // DRW V0, V0, 5
+            0x22, 0x00, // CALL 0x200
+        ])?;
+        for _ in 0..5 {
+            interpreter.step()?;
+        }
+        interpreter.reset();
+        assert_eq!(interpreter.program_counter(), 0x200);
+        assert_eq!(interpreter.registers(), &[0; 16]);
+        assert_eq!(interpreter.index_register(), 0);
+        assert_eq!(interpreter.delay_timer(), 0);
+        assert_eq!(interpreter.snapshot().stack_pointer, 0);
+        assert!(interpreter.rows().iter().all(|row| *row == 0));
+        // the program is still there to run again
+        interpreter.step()?;
+        assert_eq!(interpreter.registers()[0], 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_poke() -> Result<(), Chip8Error> {
+        // ADD V3, 1, then an invalid opcode poked into LD B, V3
+        let mut vm = Interpreter::new();
+        vm.load_program(&[0x73, 0x01, 0xFF, 0xFF])?;
+        let mut poke = vm.poke();
+        poke.set_register(3, 0x7F);
+        poke.set_index_register(0x300);
+        poke.set_delay_timer(10);
+        poke.set_sound_timer(20);
+        // wrapping around to 0x202
+        poke.write_memory(0x1202, 0xF3);
+        poke.write_memory(0x203, 0x33);
+        vm.step()?;
+        vm.step()?;
+        assert_eq!(vm.registers()[3], 0x80);
+        assert_eq!(vm.memory()[0x300..0x303], [1, 2, 8]);
+        assert_eq!((vm.delay_timer(), vm.sound_timer()), (10, 20));
+        Ok(())
+    }
+
+    #[test]
+    fn test_history() -> Result<(), Chip8Error> {
+        use crate::history::{Register, RegisterDelta};
+
+        let mut vm = Interpreter::new();
+        vm.load_program(&[
+            0x60, 0x05, // LD V0, 5
+            0xA3, 0x00, // LD I, 0x300
+            0x70, 0x01, // ADD V0, 1
+            0x12, 0x08, // JP 0x208
+            0xFF, 0xFF, // invalid
+        ])?;
+        // nothing is kept until asked for
+        vm.step()?;
+        assert_eq!(vm.history().len(), 0);
+
+        vm.set_history_len(2);
+        for _ in 0..3 {
+            vm.step()?;
+        }
+        let entries: Vec<&HistoryEntry> = vm.history().collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].pc, entries[0].word), (0x204, 0x7001));
+        assert_eq!(entries[0].mnemonic(), "ADD Vx, byte");
+        assert_eq!(
+            entries[0].deltas().collect::<Vec<_>>(),
+            [RegisterDelta {
+                register: Register::V(0),
+                old: 5,
+                new: 6
+            }]
+        );
+        assert_eq!(entries[1].op, Op::Jp { nnn: 0x208 });
+        assert_eq!(entries[1].deltas().count(), 0);
+
+        // an instruction that fails isn't recorded
+        assert!(vm.step().is_err());
+        assert_eq!(vm.history().last().map(|entry| entry.pc), Some(0x206));
+        vm.reset();
+        assert_eq!(vm.history().len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_press_and_release() -> Result<(), Chip8Error> {
+        // LD V1, K sees a tap as a press, then a release at the next tick
+        let mut vm = Interpreter::new();
+        vm.load_program(&[0xF1, 0x0A, 0x12, 0x02])?;
+        vm.press_and_release(5);
+        vm.step()?;
+        vm.step()?;
+        assert_eq!(vm.program_counter(), START);
+        vm.tick_60hz();
+        vm.step()?;
+        assert_eq!(vm.registers()[1], 5);
+        assert_eq!(vm.program_counter(), START + 2);
+
+        // SKP V0 sees it until the tick
+        vm.load_program(&[0xE0, 0x9E, 0xE0, 0x9E])?;
+        vm.reset();
+        vm.press_and_release(0);
+        vm.step()?;
+        assert_eq!(vm.program_counter(), START + 4);
+        vm.reset();
+        vm.press_and_release(0);
+        vm.tick_60hz();
+        vm.step()?;
+        assert_eq!(vm.program_counter(), START + 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_state() -> Result<(), Chip8Error> {
+        let halted = |reason| RunState::Halted { reason };
+        let mut vm = Interpreter::new();
+        // LD V0, K; JP 0x202
+        vm.load_program(&[0xF0, 0x0A, 0x12, 0x02])?;
+        assert_eq!(vm.state(), &RunState::Running);
+        vm.step()?;
+        assert_eq!(vm.state(), &RunState::WaitingForKey);
+        vm.set_key(3, true);
+        vm.step()?;
+        vm.set_key(3, false);
+        vm.step()?;
+        assert_eq!(vm.state(), &RunState::Running);
+        vm.step()?;
+        assert_eq!(vm.state(), &halted(HaltReason::JumpToSelf { addr: 0x202 }));
+        assert_eq!(vm.state().to_string(), "halted, jumping to itself at 0x202");
+
+        // LD V1, DT; JP 0x200 goes on forever, while a key check could leave
+        let idle = halted(HaltReason::IdleLoop { addr: 0x200 });
+        vm.load_program(&[0xF1, 0x07, 0x12, 0x00])?;
+        vm.reset();
+        vm.step()?;
+        assert_eq!(vm.state(), &RunState::Running);
+        for _ in 0..3 {
+            vm.step()?;
+            assert_eq!(vm.state(), &idle);
+        }
+        assert_eq!(vm.state().to_string(), "halted, idling in a loop at 0x200");
+        vm.load_program(&[0xE1, 0x9E, 0x12, 0x00])?;
+        vm.reset();
+        vm.step()?;
+        vm.step()?;
+        assert_eq!(vm.state(), &RunState::Running);
+
+        // ADD V0, 1; EXIT; ADD V0, 1
+        vm.load_program(&[0x70, 0x01, 0x00, 0xFD, 0x70, 0x01])?;
+        vm.reset();
+        assert_eq!(vm.state(), &RunState::Running);
+        for _ in 0..3 {
+            vm.step()?;
+        }
+        assert_eq!(vm.state(), &RunState::Exited);
+        assert_eq!(vm.registers()[0], 1);
+        vm.reset();
+        assert_eq!(vm.state(), &RunState::Running);
+
+        // LD V0, 1, then running off the end
+        let mut vm = Interpreter::new();
+        vm.load_program(&[0x60, 0x01])?;
+        for _ in 0..3 {
+            vm.step()?;
+        }
+        assert_eq!(vm.state(), &halted(HaltReason::EndOfProgram));
+
+        // an error halts until the next step tries again
+        vm.load_program(&[0x00, 0xEE])?;
+        vm.reset();
+        let err = vm.step().unwrap_err();
+        assert_eq!(vm.state(), &halted(HaltReason::Error(err.to_string())));
+        vm.poke().write_memory(0x200, 0x60);
+        vm.step()?;
+        assert_eq!(vm.state(), &RunState::Running);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pixels_round_trip_through_rows() {
+        let mut pixels = [false; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+        pixels[0] = true;
+        pixels[SCREEN_WIDTH + 63] = true;
+        let rows = pixels_to_rows(&pixels);
+        assert_eq!(rows[0], 1 << 63);
+        assert_eq!(rows[1], 1);
+        assert_eq!(rows_to_pixels(&rows), pixels);
+    }
+    #[test]
+    fn test_take_dirty_pixels() -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::new();
+        // LD I, 0x300; then DRW V0, V0, 1 four times
+        interpreter.load_program(&[0xA3, 0x00, 0xD0, 0x01, 0xD0, 0x01, 0xD0, 0x01, 0xD0, 0x01])?;
+        interpreter.memory_map[0x300] = 0b1010_0000;
+        assert!(interpreter.take_dirty_pixels().is_empty());
+        interpreter.step()?;
+        interpreter.step()?;
+        assert_eq!(interpreter.take_dirty_pixels(), [0, 2]);
+        assert!(interpreter.take_dirty_pixels().is_empty());
+        // erased again
+        interpreter.step()?;
+        assert_eq!(interpreter.take_dirty_pixels(), [0, 2]);
+        // drawn and erased between calls, so nothing changed
+        interpreter.step()?;
+        interpreter.step()?;
+        assert!(interpreter.take_dirty_pixels().is_empty());
+        Ok(())
+    }
+    op_test!(test_quirk_key_wait_is_silent, 0xF00A, { quirks(SCHIP), key(3) } => |vm| {
+        assert_eq!(vm.key_down_ld_vx_k, Some(3));
+        assert!(!vm.should_play_sound());
+    });
+    op_test!(test_quirk_jump_uses_vx, 0xB300, { quirks(SCHIP), reg(0, 4), reg(3, 8) } => |vm| {
+        assert_eq!(vm.program_counter, 0x308);
+    });
+    op_test!(test_quirk_load_store_keeps_i, 0xF255, { quirks(SCHIP), i(0x300) } => |vm| {
+        assert_eq!(vm.index_register, 0x300);
+    });
+    op_test!(test_op_drw_clips, 0xD012, { reg(0, 60), reg(1, 63), i(0x300), mem(0x300, &[0xFF, 0xFF]) } => |vm| {
+        // starts at (60, 31) after wrapping the start position, then clips the right half and the second row
+        assert!((60..64).all(|x| vm.pixels()[31 * SCREEN_WIDTH + x]));
+        assert_eq!(vm.rows()[31], 0xF);
+        assert_eq!(vm.pixels().iter().filter(|p| **p).count(), 4);
+    });
+    op_test!(test_quirk_wrap_sprites, 0xD012, { quirks(WRAP), reg(0, 60), reg(1, 31), i(0x300), mem(0x300, &[0xFF, 0xFF]) } => |vm| {
+        // each row wraps onto its own left edge, and the second row onto the top
+        for y in [31, 0] {
+            assert!((60..64).chain(0..4).all(|x| vm.pixels()[y * SCREEN_WIDTH + x]));
+        }
+        assert_eq!(vm.rows()[0], 0xF000_0000_0000_000F);
+        assert_eq!(vm.pixels().iter().filter(|p| **p).count(), 16);
+    });
+}
@@ -0,0 +1,111 @@
+//! Helpers for writing compact per-opcode unit tests.
+//!
+//! ```ignore
+//! op_test!(add_vx_vy_sets_carry, 0x8014, { reg(0, 0xFF), reg(1, 2) } => |vm| {
+//!     assert_eq!(vm.registers[0], 1);
+//!     assert_eq!(vm.registers[0xF], 1);
+//! });
+//! ```
+
+use super::{column_bit, Interpreter, PROGRAM_START, RPL_FLAG_COUNT, SCREEN_WIDTH};
+use crate::quirks::Quirks;
+
+/// Builds an interpreter in a known state, then executes exactly one instruction.
+pub(crate) struct OpTest {
+    vm: Interpreter,
+}
+
+impl OpTest {
+    pub(crate) fn new() -> Self {
+        OpTest {
+            vm: Interpreter::new(),
+        }
+    }
+
+    pub(crate) fn quirks(mut self, quirks: Quirks) -> Self {
+        self.vm.quirks = quirks;
+        self
+    }
+
+    pub(crate) fn reg(mut self, x: usize, value: u8) -> Self {
+        self.vm.registers[x] = value;
+        self
+    }
+
+    pub(crate) fn i(mut self, value: u16) -> Self {
+        self.vm.index_register = value;
+        self
+    }
+
+    pub(crate) fn mem(mut self, addr: usize, bytes: &[u8]) -> Self {
+        self.vm.memory_map[addr..addr + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    pub(crate) fn dt(mut self, value: u8) -> Self {
+        self.vm.delay_timer = value;
+        self
+    }
+
+    pub(crate) fn st(mut self, value: u8) -> Self {
+        self.vm.sound_timer = value;
+        self
+    }
+
+    pub(crate) fn key(mut self, key: usize) -> Self {
+        self.vm.keys[key] = true;
+        self
+    }
+
+    pub(crate) fn rpl_flags(mut self, flags: [u8; RPL_FLAG_COUNT]) -> Self {
+        self.vm.rpl_flags = flags;
+        self
+    }
+
+    pub(crate) fn pixel(mut self, idx: usize) -> Self {
+        self.vm.rows[idx / SCREEN_WIDTH] |= column_bit(idx % SCREEN_WIDTH);
+        self
+    }
+
+    /// as if LD Vx, K had already seen `key` go down
+    pub(crate) fn waiting_for_key(mut self, key: u8) -> Self {
+        self.vm.key_down_ld_vx_k = Some(key);
+        self
+    }
+
+    /// push a return address, as if a CALL had happened
+    pub(crate) fn call_stack(mut self, return_addr: u16) -> Self {
+        self.vm.stack_pointer += 1;
+        self.vm.stack[self.vm.stack_pointer as usize] = return_addr;
+        self
+    }
+
+    /// write `instruction` at the program start and execute it
+    pub(crate) fn run(mut self, instruction: u16) -> Interpreter {
+        self.vm
+            .load_program(&instruction.to_be_bytes())
+            .expect("instruction should fit in memory");
+        self.vm.step().expect("instruction should execute");
+        self.vm
+    }
+}
+
+/// Declares a `#[test]` that runs one encoded instruction against an
+/// `OpTest` setup, binding the resulting interpreter for assertions.
+macro_rules! op_test {
+    ($name:ident, $instruction:expr, { $($setup:ident($($arg:expr),*)),* $(,)? } => |$vm:ident| $check:expr) => {
+        #[test]
+        fn $name() {
+            let $vm = $crate::interpreter::test_support::OpTest::new()
+                $(.$setup($($arg),*))*
+                .run($instruction);
+            $check;
+        }
+    };
+}
+pub(crate) use op_test;
+
+/// program counter after executing the instruction at the program start without skipping
+pub(crate) const NEXT: u16 = PROGRAM_START as u16 + 2;
+/// program counter after executing a skip instruction that skipped
+pub(crate) const SKIPPED: u16 = PROGRAM_START as u16 + 4;
@@ -0,0 +1,55 @@
+//! A CHIP-8 interpreter with no opinions about windowing, audio, or input.
+//!
+//! A frontend owns an [`Interpreter`], feeds it key state with
+//! [`Interpreter::set_key`], calls [`Interpreter::step`] for each
+//! instruction and [`Interpreter::tick_60hz`] at 60 Hz, and draws
+//! [`Interpreter::rows`] however it likes.
+//!
+//! Loading and stepping fail with a [`Chip8Error`] saying what went wrong
+//! and where, rather than panicking.
+//!
+//! An [`Observer`] added with [`Interpreter::add_observer`] hears about
+//! draws, calls, returns, key waits, and the buzzer as they happen.
+//!
+//! SYS 0NNN is ignored unless [`Interpreter::set_sys_handler`] gives it a
+//! meaning, for embedders with host services of their own.
+//!
+//! [`decode`] turns an instruction word into an [`Op`] on its own, for
+//! disassemblers and other tools that don't need an interpreter.
+//!
+//! ```
+//! use chip8_core::Interpreter;
+//!
+//! let mut interpreter = Interpreter::new();
+//! // LD V0, 0x2A; LD ST, V0
+//! interpreter.load_program(&[0x60, 0x2A, 0xF0, 0x18]).unwrap();
+//! interpreter.step().unwrap();
+//! interpreter.step().unwrap();
+//! assert!(interpreter.should_play_sound());
+//! ```
+
+mod error;
+pub mod font;
+#[doc(hidden)]
+pub mod fuzz;
+mod history;
+pub mod interpreter;
+pub mod megachip;
+mod observer;
+mod quirks;
+mod rng;
+mod savestate;
+mod sys;
+pub mod vip_timing;
+mod watch;
+
+pub use error::{Chip8Error, StrictViolation};
+pub use history::{HistoryEntry, Register, RegisterDelta};
+pub use interpreter::{
+    decode, HaltReason, Interpreter, Op, Pixels, Rows, RunState, Snapshot, StepOutcome,
+    SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+pub use observer::Observer;
+pub use quirks::Quirks;
+pub use sys::{SysHandler, SysOutcome};
+pub use watch::{Access, WatchHit, WatchKind, WatchTarget};
@@ -0,0 +1,377 @@
+//! MegaChip: a CHIP-8 extension with a 256x192 display of 256 colors,
+//! sprites of any size blended onto it, 24-bit addressing for up to 16 MiB
+//! of memory, and digitized sound.
+//!
+//! An interpreter only understands these instructions once
+//! `Interpreter::set_megachip` has turned support on, and a ROM then enters
+//! Mega mode with 0011. In Mega mode DRW draws palette indexes onto a back
+//! buffer, which reaches the screen when 00E0 clears it, so each frame is
+//! drawn in full before it's shown.
+//!
+//! None of this is part of a `Snapshot`.
+
+/// MegaChip display width in pixels
+pub const MEGA_WIDTH: usize = 256;
+/// MegaChip display height in pixels
+pub const MEGA_HEIGHT: usize = 192;
+/// all the memory 24-bit addresses reach
+pub const MEGA_MEMORY_SIZE: usize = 1 << 24;
+
+/// A MegaChip instruction, all of which live in the 0nnn space CHIP-8
+/// leaves to machine code routines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MegaOp {
+    /// 0010: leave Mega mode
+    Off,
+    /// 0011: enter Mega mode
+    On,
+    /// 01NN nnnn: I = NNnnnn, with the low 16 bits in the next word
+    LdIHi { nn: u8 },
+    /// 02NN: load NN colors from I into the palette, starting at index 1
+    LdPal { nn: u8 },
+    /// 03NN: sprite width, or 256 for 0
+    SprW { nn: u8 },
+    /// 04NN: sprite height, or 256 for 0
+    SprH { nn: u8 },
+    /// 05NN: opacity of the whole screen
+    Alpha { nn: u8 },
+    /// 060N: play the digitized sound at I, once if N is 1 and on a loop otherwise
+    DigiSnd { n: u8 },
+    /// 0700: stop the digitized sound
+    StopSnd,
+    /// 080N: how sprites blend onto the screen, as `Blend::from_n`
+    BlendMode { n: u8 },
+    /// 09NN: the palette index DRW reports collisions with, or none for 0
+    CollisionColor { nn: u8 },
+    /// 00BN: scroll the screen up N lines
+    ScrollUp { n: u8 },
+}
+
+impl MegaOp {
+    /// the MegaChip instruction `word` encodes, if any
+    pub fn decode(word: u16) -> Option<MegaOp> {
+        let nn = (word & 0xFF) as u8;
+        let n = nn & 0xF;
+        Some(match word >> 8 {
+            0x00 => match nn {
+                0x10 => MegaOp::Off,
+                0x11 => MegaOp::On,
+                0xB0..=0xBF => MegaOp::ScrollUp { n },
+                _ => return None,
+            },
+            0x01 => MegaOp::LdIHi { nn },
+            0x02 => MegaOp::LdPal { nn },
+            0x03 => MegaOp::SprW { nn },
+            0x04 => MegaOp::SprH { nn },
+            0x05 => MegaOp::Alpha { nn },
+            0x06 if nn < 0x10 => MegaOp::DigiSnd { n },
+            0x07 if nn == 0 => MegaOp::StopSnd,
+            0x08 if nn < 0x10 => MegaOp::BlendMode { n },
+            0x09 => MegaOp::CollisionColor { nn },
+            _ => return None,
+        })
+    }
+
+    /// instruction form, as in `Op::name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            MegaOp::Off => "MEGAOFF",
+            MegaOp::On => "MEGAON",
+            MegaOp::LdIHi { .. } => "LDHI I, long",
+            MegaOp::LdPal { .. } => "LDPAL byte",
+            MegaOp::SprW { .. } => "SPRW byte",
+            MegaOp::SprH { .. } => "SPRH byte",
+            MegaOp::Alpha { .. } => "ALPHA byte",
+            MegaOp::DigiSnd { .. } => "DIGISND nibble",
+            MegaOp::StopSnd => "STOPSND",
+            MegaOp::BlendMode { .. } => "BMODE nibble",
+            MegaOp::CollisionColor { .. } => "CCOL byte",
+            MegaOp::ScrollUp { .. } => "SCRU nibble",
+        }
+    }
+}
+
+/// How a sprite's colors combine with what's already on screen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    /// the sprite replaces the screen
+    #[default]
+    Normal,
+    /// 25% sprite
+    Quarter,
+    /// 50% sprite
+    Half,
+    /// 75% sprite
+    ThreeQuarters,
+    /// the channels add, saturating at white
+    Add,
+    /// the channels multiply, darkening
+    Multiply,
+}
+
+impl Blend {
+    /// the blend mode BMODE `n` selects; unknown ones draw normally
+    pub fn from_n(n: u8) -> Blend {
+        match n {
+            1 => Blend::Quarter,
+            2 => Blend::Half,
+            3 => Blend::ThreeQuarters,
+            4 => Blend::Add,
+            5 => Blend::Multiply,
+            _ => Blend::Normal,
+        }
+    }
+
+    /// the opaque ARGB color of `src` drawn over `dst`
+    fn mix(self, dst: u32, src: u32) -> u32 {
+        let channel = |shift: u32| {
+            let d = (dst >> shift) & 0xFF;
+            let s = (src >> shift) & 0xFF;
+            let mixed = match self {
+                Blend::Normal => s,
+                Blend::Quarter => (3 * d + s) / 4,
+                Blend::Half => (d + s) / 2,
+                Blend::ThreeQuarters => (d + 3 * s) / 4,
+                Blend::Add => (d + s).min(0xFF),
+                Blend::Multiply => d * s / 0xFF,
+            };
+            mixed << shift
+        };
+        0xFF00_0000 | channel(16) | channel(8) | channel(0)
+    }
+}
+
+/// Sampled sound for a frontend to play, from DIGISND.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigitizedSound {
+    pub sample_rate: u16,
+    /// 8-bit unsigned mono samples
+    pub samples: Vec<u8>,
+    pub looping: bool,
+}
+
+/// A change to the digitized sound, for a frontend to act on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoundEvent {
+    Play(DigitizedSound),
+    Stop,
+}
+
+/// MegaChip state, kept alongside the usual CHIP-8 state.
+pub struct MegaChip {
+    /// memory past the first 4 KiB
+    pub(crate) memory: Vec<u8>,
+    enabled: bool,
+    /// the top 8 bits of I, set by LDHI
+    pub(crate) i_high: u8,
+    /// ARGB colors, where index 0 is transparent in sprites
+    palette: [u32; 256],
+    sprite_width: usize,
+    sprite_height: usize,
+    alpha: u8,
+    blend: Blend,
+    collision_color: u8,
+    /// palette indexes drawn since the last flip, for collisions
+    indexes: Vec<u8>,
+    /// colors drawn since the last flip
+    back: Vec<u32>,
+    /// what the screen shows, as of the last flip
+    front: Vec<u32>,
+    sound: Option<SoundEvent>,
+}
+
+impl MegaChip {
+    pub(crate) fn new(extra_memory: usize) -> Self {
+        let mut palette = [0; 256];
+        // a grayscale ramp until the ROM loads its own colors
+        for (idx, color) in palette.iter_mut().enumerate() {
+            *color = 0xFF00_0000 | (idx as u32 * 0x0001_0101);
+        }
+        MegaChip {
+            memory: vec![0; extra_memory],
+            enabled: false,
+            i_high: 0,
+            palette,
+            sprite_width: 8,
+            sprite_height: 8,
+            alpha: 0xFF,
+            blend: Blend::Normal,
+            collision_color: 0,
+            indexes: vec![0; MEGA_WIDTH * MEGA_HEIGHT],
+            back: vec![0xFF00_0000; MEGA_WIDTH * MEGA_HEIGHT],
+            front: vec![0xFF00_0000; MEGA_WIDTH * MEGA_HEIGHT],
+            sound: None,
+        }
+    }
+
+    /// back to how `new` left it, but keeping the memory
+    pub(crate) fn reset(&mut self) {
+        *self = MegaChip {
+            memory: std::mem::take(&mut self.memory),
+            ..MegaChip::new(0)
+        };
+    }
+
+    /// whether the ROM is in Mega mode, and the frontend should show `screen`
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// the 256x192 display, row by row, one ARGB color per pixel
+    pub fn screen(&self) -> &[u32] {
+        &self.front
+    }
+
+    /// how opaque the whole screen should be drawn
+    pub fn alpha(&self) -> u8 {
+        self.alpha
+    }
+
+    /// the last sound change since the previous call, if any
+    pub fn take_sound_event(&mut self) -> Option<SoundEvent> {
+        self.sound.take()
+    }
+
+    /// the number of bytes DRW reads for a sprite at the current size
+    pub(crate) fn sprite_len(&self) -> usize {
+        self.sprite_width * self.sprite_height
+    }
+
+    /// show what's been drawn, then start the next frame blank: 00E0 in Mega mode
+    pub(crate) fn flip(&mut self) {
+        self.front.copy_from_slice(&self.back);
+        self.back.fill(0xFF00_0000);
+        self.indexes.fill(0);
+    }
+
+    /// Draw `sprite`, one palette index per pixel, with its top left corner
+    /// at (`x`, `y`). Pixels past the edges are clipped and index 0 is
+    /// transparent. Returns whether any pixel landed on the collision color.
+    pub(crate) fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let mut collision = false;
+        for (row, line) in sprite.chunks(self.sprite_width).enumerate() {
+            let py = y + row;
+            if py >= MEGA_HEIGHT {
+                break;
+            }
+            for (col, &index) in line.iter().enumerate() {
+                let px = x + col;
+                if px >= MEGA_WIDTH {
+                    break;
+                }
+                if index == 0 {
+                    continue;
+                }
+                let at = py * MEGA_WIDTH + px;
+                collision |= self.indexes[at] != 0 && self.indexes[at] == self.collision_color;
+                self.indexes[at] = index;
+                self.back[at] = self.blend.mix(self.back[at], self.palette[index as usize]);
+            }
+        }
+        collision
+    }
+
+    /// move what's been drawn up `lines`, leaving black below
+    pub(crate) fn scroll_up(&mut self, lines: usize) {
+        let shift = lines.min(MEGA_HEIGHT) * MEGA_WIDTH;
+        self.back.copy_within(shift.., 0);
+        self.indexes.copy_within(shift.., 0);
+        let len = self.back.len();
+        self.back[len - shift..].fill(0xFF00_0000);
+        self.indexes[len - shift..].fill(0);
+    }
+
+    /// palette entries from index 1, from 4 bytes each of alpha, red, green and blue
+    pub(crate) fn load_palette(&mut self, colors: &[u8]) {
+        for (entry, argb) in self.palette[1..].iter_mut().zip(colors.chunks_exact(4)) {
+            *entry = u32::from_be_bytes([argb[0], argb[1], argb[2], argb[3]]);
+        }
+    }
+
+    pub(crate) fn set_sprite_width(&mut self, nn: u8) {
+        self.sprite_width = if nn == 0 { 256 } else { nn as usize };
+    }
+
+    pub(crate) fn set_sprite_height(&mut self, nn: u8) {
+        self.sprite_height = if nn == 0 { 256 } else { nn as usize };
+    }
+
+    pub(crate) fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    pub(crate) fn set_blend(&mut self, blend: Blend) {
+        self.blend = blend;
+    }
+
+    pub(crate) fn set_collision_color(&mut self, index: u8) {
+        self.collision_color = index;
+    }
+
+    pub(crate) fn play(&mut self, sound: DigitizedSound) {
+        self.sound = Some(SoundEvent::Play(sound));
+    }
+
+    pub(crate) fn stop_sound(&mut self) {
+        self.sound = Some(SoundEvent::Stop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(MegaOp::decode(0x0011), Some(MegaOp::On));
+        assert_eq!(MegaOp::decode(0x0123), Some(MegaOp::LdIHi { nn: 0x23 }));
+        assert_eq!(MegaOp::decode(0x0601), Some(MegaOp::DigiSnd { n: 1 }));
+        assert_eq!(MegaOp::decode(0x00B4), Some(MegaOp::ScrollUp { n: 4 }));
+        assert_eq!(MegaOp::decode(0x0701), None);
+        assert_eq!(MegaOp::decode(0x0A00), None);
+        assert_eq!(MegaOp::decode(0x00E0), None);
+    }
+
+    #[test]
+    fn test_blend() {
+        let (dst, src) = (0xFF20_4080, 0xFFA0_C0FF);
+        assert_eq!(Blend::Normal.mix(dst, src), src);
+        assert_eq!(Blend::Half.mix(dst, src), 0xFF60_80BF);
+        assert_eq!(Blend::Add.mix(dst, src), 0xFFC0_FFFF);
+        assert_eq!(Blend::Multiply.mix(0xFFFF_8000, 0xFF80_8080), 0xFF80_4000);
+    }
+
+    #[test]
+    fn test_draw_and_flip() {
+        let mut mega = MegaChip::new(0);
+        mega.load_palette(&[0xFF, 0x12, 0x34, 0x56]);
+        mega.set_sprite_width(2);
+        mega.set_sprite_height(2);
+        // a 2x2 sprite with a transparent corner, half off the right edge
+        assert!(!mega.draw(MEGA_WIDTH - 1, 0, &[1, 1, 0, 1]));
+        assert_eq!(mega.screen()[MEGA_WIDTH - 1], 0xFF00_0000);
+
+        mega.flip();
+        assert_eq!(mega.screen()[MEGA_WIDTH - 1], 0xFF12_3456);
+        assert_eq!(mega.screen()[2 * MEGA_WIDTH - 1], 0xFF00_0000);
+        assert_eq!(mega.back[MEGA_WIDTH - 1], 0xFF00_0000);
+
+        mega.set_collision_color(1);
+        assert!(!mega.draw(0, 0, &[1, 0, 0, 0]));
+        assert!(mega.draw(0, 0, &[1, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_scroll_up() {
+        let mut mega = MegaChip::new(0);
+        mega.set_sprite_width(1);
+        mega.draw(0, 3, &[7]);
+        mega.scroll_up(2);
+        assert_eq!(mega.indexes[MEGA_WIDTH], 7);
+        assert_eq!(mega.indexes[3 * MEGA_WIDTH], 0);
+    }
+}
@@ -0,0 +1,66 @@
+//! Hooks for what a program does as it runs, for tracers, debuggers, and
+//! scripting layers that want to follow along without stepping through
+//! its state themselves.
+//!
+//! ```
+//! use std::{cell::Cell, rc::Rc};
+//!
+//! use chip8_core::{Interpreter, Observer};
+//!
+//! /// counts the sprites drawn
+//! struct Draws(Rc<Cell<usize>>);
+//!
+//! impl Observer for Draws {
+//!     fn on_draw(&mut self, _addr: u16, _x: u8, _y: u8, _n: u8, _collided: bool) {
+//!         self.0.set(self.0.get() + 1);
+//!     }
+//! }
+//!
+//! let draws = Rc::new(Cell::new(0));
+//! let mut interpreter = Interpreter::new();
+//! interpreter.add_observer(Draws(draws.clone()));
+//! // DRW V0, V0, 5; DRW V0, V0, 5
+//! interpreter.load_program(&[0xD0, 0x05, 0xD0, 0x05]).unwrap();
+//! interpreter.step().unwrap();
+//! interpreter.step().unwrap();
+//! assert_eq!(draws.get(), 2);
+//! ```
+
+/// Told about the program's instructions as `Interpreter::step` runs
+/// them, once [`Interpreter::add_observer`](crate::Interpreter::add_observer)
+/// registers it. `addr` is where the instruction is. Every method does
+/// nothing unless implemented, and none are called for instructions that
+/// fail.
+pub trait Observer {
+    /// DXYN drew an `n`-row sprite (0 for SUPER-CHIP's 16x16 ones) with its
+    /// top left at (`x`, `y`), and `collided` when it erased a pixel
+    fn on_draw(&mut self, addr: u16, x: u8, y: u8, n: u8, collided: bool) {
+        let _ = (addr, x, y, n, collided);
+    }
+
+    /// 00E0 cleared the display
+    fn on_clear(&mut self, addr: u16) {
+        let _ = addr;
+    }
+
+    /// the buzzer started, from LD ST, Vx or the key wait tone quirk
+    fn on_sound_start(&mut self) {}
+
+    /// the buzzer stopped, as the sound timer ran out or the key was released
+    fn on_sound_stop(&mut self) {}
+
+    /// LD Vx, K started waiting for a key, to put in V`x`
+    fn on_key_wait(&mut self, addr: u16, x: u8) {
+        let _ = (addr, x);
+    }
+
+    /// CALL went to the subroutine at `target`
+    fn on_call(&mut self, addr: u16, target: u16) {
+        let _ = (addr, target);
+    }
+
+    /// RET went back to `target`
+    fn on_ret(&mut self, addr: u16, target: u16) {
+        let _ = (addr, target);
+    }
+}
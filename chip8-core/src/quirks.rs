@@ -0,0 +1,94 @@
+/// Behaviors that differ between CHIP-8 implementations. Most ROMs were
+/// written against one particular interpreter and only run correctly with
+/// its quirks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE copy VY into VX before shifting, rather than shifting VX in place
+    pub shift_uses_vy: bool,
+    /// FX55 and FX65 leave I pointing just past the last register they copied
+    pub load_store_increments_i: bool,
+    /// BNNN jumps to NNN + VX, where X is the top nibble of NNN (BXNN), rather than NNN + V0
+    pub jump_uses_vx: bool,
+    /// sprites that cross a screen edge wrap around to the opposite side rather than being clipped
+    pub wrap_sprites: bool,
+    /// 8XY1, 8XY2, and 8XY3 reset VF to 0
+    pub logic_resets_vf: bool,
+    /// FX0A sounds the buzzer while the key it read is held, until the release that ends the wait
+    pub key_wait_tone: bool,
+    /// DXYN waits for the next 60 Hz frame, so at most one sprite is drawn per frame
+    pub display_wait: bool,
+    /// FX33, FX55, FX65, and DXYN wrap around to the start of memory when they
+    /// reach past its end, as the VIP's 4 KiB repeated, rather than failing
+    /// with `Chip8Error::MemoryOutOfBounds`
+    pub memory_wraps: bool,
+    /// FX1E sets VF to 1 when I + VX passes 0xFFF and to 0 otherwise, as
+    /// the Amiga interpreter did; Spacefight 2091! relies on it
+    pub add_i_sets_vf: bool,
+    /// I is 12 bits, so FX1E and the I increments of FX55 and FX65 wrap
+    /// it past 0xFFF to 0, rather than at 0xFFFF
+    pub i_12_bits: bool,
+}
+
+impl Quirks {
+    /// the original COSMAC VIP interpreter
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        wrap_sprites: false,
+        logic_resets_vf: true,
+        key_wait_tone: true,
+        display_wait: true,
+        memory_wraps: true,
+        add_i_sets_vf: false,
+        i_12_bits: false,
+    };
+
+    /// SUPER-CHIP 1.1 on the HP 48
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        jump_uses_vx: true,
+        wrap_sprites: false,
+        logic_resets_vf: false,
+        key_wait_tone: false,
+        display_wait: false,
+        memory_wraps: true,
+        add_i_sets_vf: false,
+        i_12_bits: false,
+    };
+
+    /// the Amiga interpreter: the VIP's quirks without waiting for the
+    /// display, plus its flag for FX1E overflowing and a 12-bit I
+    pub const AMIGA: Quirks = Quirks {
+        display_wait: false,
+        add_i_sets_vf: true,
+        i_12_bits: true,
+        ..Self::COSMAC_VIP
+    };
+
+    /// named presets, as accepted by `from_name`
+    pub const PRESETS: [(&'static str, Quirks); 3] = [
+        ("vip", Self::COSMAC_VIP),
+        ("schip", Self::SUPER_CHIP),
+        ("amiga", Self::AMIGA),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        Self::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, quirks)| *quirks)
+    }
+}
+
+impl Default for Quirks {
+    /// COSMAC VIP behavior, except that drawing doesn't wait for the
+    /// display, which would slow sprite-heavy games at faster speeds
+    fn default() -> Self {
+        Quirks {
+            display_wait: false,
+            ..Self::COSMAC_VIP
+        }
+    }
+}
@@ -0,0 +1,47 @@
+/// A small xorshift PRNG, so the core doesn't depend on a platform source
+/// of randomness. Like the macroquad generator it replaces, it starts from a
+/// fixed seed unless given one, so every run makes the same RND choices.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+impl Rng {
+    pub(crate) fn new() -> Self {
+        Rng {
+            state: DEFAULT_SEED,
+        }
+    }
+
+    /// A generator for `seed`. Seeds are spread out with splitmix64 first,
+    /// since xorshift gives poor output for a while from small states.
+    pub(crate) fn with_seed(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng::from_state(z ^ (z >> 31))
+    }
+
+    /// resume from `state()`; xorshift is stuck at zero, so zero means the default
+    pub(crate) fn from_state(state: u64) -> Self {
+        if state == 0 {
+            Rng::new()
+        } else {
+            Rng { state }
+        }
+    }
+
+    pub(crate) fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub(crate) fn next_u8(&mut self) -> u8 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
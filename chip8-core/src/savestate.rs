@@ -0,0 +1,255 @@
+//! A versioned binary encoding of [`Snapshot`], for save files.
+//!
+//! All numbers are little-endian:
+//!
+//! | bytes | field                                         |
+//! |-------|-----------------------------------------------|
+//! | 4     | magic `C8ST`                                  |
+//! | 2     | format version (u16)                          |
+//! | 4096  | memory                                        |
+//! | 4     | program size (u32)                            |
+//! | 2     | program counter (u16)                         |
+//! | 1     | stack pointer                                 |
+//! | 32    | stack, 16 x u16                               |
+//! | 16    | registers V0-VF                               |
+//! | 2     | index register (u16)                          |
+//! | 1     | delay timer                                   |
+//! | 1     | sound timer                                   |
+//! | 16    | keys, 0 or 1 each                             |
+//! | 1     | key LD Vx, K is waiting on, or 0xFF for none  |
+//! | 2048  | pixels, 0 or 1 each, row-major                |
+//! | 8     | random number generator state (u64)           |
+//! | 1     | hires, 0 or 1                                 |
+//! | 2048  | pixels of the hires display's bottom half     |
+//!
+//! Any change to the layout must bump `VERSION`. States from earlier
+//! versions still load: version 1 states end before the generator state,
+//! and load with the default one, and version 2 states end before hires.
+
+use std::error::Error;
+
+use crate::interpreter::{Snapshot, HIRES_SCREEN_HEIGHT, MEMORY_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u16 = 3;
+/// pixels in the usual display, and so in the first block of them
+const LORES_PIXELS: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+const NO_KEY: u8 = 0xFF;
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(MEMORY_SIZE + SCREEN_WIDTH * HIRES_SCREEN_HEIGHT + 96);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.memory);
+        bytes.extend_from_slice(&(self.program_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.program_counter.to_le_bytes());
+        bytes.push(self.stack_pointer);
+        for addr in self.stack {
+            bytes.extend_from_slice(&addr.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.registers);
+        bytes.extend_from_slice(&self.index_register.to_le_bytes());
+        bytes.push(self.delay_timer);
+        bytes.push(self.sound_timer);
+        bytes.extend(self.keys.iter().map(|down| *down as u8));
+        bytes.push(self.key_down_ld_vx_k.unwrap_or(NO_KEY));
+        let (lores, rest) = self.pixels.split_at(LORES_PIXELS);
+        bytes.extend(lores.iter().map(|on| *on as u8));
+        bytes.extend_from_slice(&self.rng_state.to_le_bytes());
+        bytes.push(self.hires as u8);
+        bytes.extend(rest.iter().map(|on| *on as u8));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, Box<dyn Error>> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err("not a save state".into());
+        }
+        let version = reader.u16()?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(format!("unsupported save state version {}", version).into());
+        }
+
+        let memory = reader.take(MEMORY_SIZE)?.try_into()?;
+        let program_size = u32::from_le_bytes(reader.take(4)?.try_into()?) as usize;
+        let program_counter = reader.u16()?;
+        let stack_pointer = reader.u8()?;
+        let mut stack = [0; 16];
+        for addr in &mut stack {
+            *addr = reader.u16()?;
+        }
+        let registers = reader.take(16)?.try_into()?;
+        let index_register = reader.u16()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let mut keys = [false; 16];
+        for (key, byte) in keys.iter_mut().zip(reader.take(16)?) {
+            *key = *byte != 0;
+        }
+        let key_down_ld_vx_k = match reader.u8()? {
+            NO_KEY => None,
+            key => Some(key),
+        };
+        let mut pixels = [false; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+        for (pixel, byte) in pixels.iter_mut().zip(reader.take(LORES_PIXELS)?) {
+            *pixel = *byte != 0;
+        }
+        // zero restarts the generator from the default seed
+        let rng_state = if version == 1 {
+            0
+        } else {
+            u64::from_le_bytes(reader.take(8)?.try_into()?)
+        };
+        let hires = version >= 3 && reader.u8()? != 0;
+        if version >= 3 {
+            let rest = reader.take(pixels.len() - LORES_PIXELS)?;
+            for (pixel, byte) in pixels[LORES_PIXELS..].iter_mut().zip(rest) {
+                *pixel = *byte != 0;
+            }
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err("unexpected data after save state".into());
+        }
+        if stack_pointer as usize >= stack.len() {
+            return Err(format!("invalid stack pointer {}", stack_pointer).into());
+        }
+        if let Some(key) = key_down_ld_vx_k.filter(|key| *key >= 16) {
+            return Err(format!("invalid waiting key {}", key).into());
+        }
+
+        Ok(Snapshot {
+            memory,
+            program_size,
+            program_counter,
+            stack_pointer,
+            stack,
+            registers,
+            index_register,
+            delay_timer,
+            sound_timer,
+            keys,
+            key_down_ld_vx_k,
+            pixels,
+            hires,
+            rng_state,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if self.bytes.len() < len {
+            return Err("save state is truncated".into());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+
+    #[test]
+    fn test_round_trip() -> Result<(), Box<dyn Error>> {
+        let mut interpreter = Interpreter::new();
+        // CALL 0x204; (unused); LD I, 0x50; DRW V0, V0, 5
+        interpreter.load_program(&[0x22, 0x04, 0x00, 0x00, 0xA0, 0x50, 0xD0, 0x05])?;
+        interpreter.set_key(3, true);
+        for _ in 0..3 {
+            interpreter.step()?;
+        }
+
+        let snapshot = interpreter.snapshot();
+        assert_eq!(Snapshot::from_bytes(&snapshot.to_bytes())?, snapshot);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_hires() -> Result<(), Box<dyn Error>> {
+        let mut interpreter = Interpreter::new();
+        let mut rom = vec![0; 0xC0];
+        rom[..2].copy_from_slice(&[0x12, 0x60]);
+        // at 0x2C0: LD V0, 40; DRW V0, V0, 5, in the bottom half
+        rom.extend([0x60, 0x28, 0xD0, 0x05]);
+        interpreter.load_program(&rom)?;
+        interpreter.step()?;
+        interpreter.step()?;
+
+        let snapshot = interpreter.snapshot();
+        assert!(snapshot.hires);
+        assert_eq!(Snapshot::from_bytes(&snapshot.to_bytes())?, snapshot);
+        Ok(())
+    }
+
+    #[test]
+    fn test_loads_version_2() -> Result<(), Box<dyn Error>> {
+        let snapshot = Interpreter::new().snapshot();
+        let mut bytes = snapshot.to_bytes();
+        bytes.truncate(bytes.len() - 1 - (snapshot.pixels.len() - LORES_PIXELS));
+        bytes[4] = 2;
+
+        let loaded = Snapshot::from_bytes(&bytes)?;
+        assert!(!loaded.hires);
+        assert_eq!(loaded, snapshot);
+        Ok(())
+    }
+
+    #[test]
+    fn test_loads_version_1() -> Result<(), Box<dyn Error>> {
+        let snapshot = Interpreter::new().snapshot();
+        let mut bytes = snapshot.to_bytes();
+        bytes.truncate(bytes.len() - 8 - 1 - (snapshot.pixels.len() - LORES_PIXELS));
+        bytes[4] = 1;
+
+        let loaded = Snapshot::from_bytes(&bytes)?;
+        assert_eq!(loaded.rng_state, 0);
+        assert_eq!(loaded.pixels, snapshot.pixels);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.restore(&loaded);
+        assert_eq!(interpreter.snapshot(), snapshot);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_invalid_data() {
+        let bytes = Interpreter::new().snapshot().to_bytes();
+
+        assert!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Snapshot::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 4;
+        assert!(Snapshot::from_bytes(&wrong_version).is_err());
+
+        // the waiting key, before both halves of the pixels, the generator
+        // state, and the hires flag
+        let waiting_key = bytes.len() - 8 - 1 - 2 * LORES_PIXELS - 1;
+        let mut wrong_key = bytes.clone();
+        wrong_key[waiting_key] = 0x20;
+        assert!(Snapshot::from_bytes(&wrong_key).is_err());
+        wrong_key[waiting_key] = 0x0F;
+        assert!(Snapshot::from_bytes(&wrong_key).is_ok());
+
+        assert!(Snapshot::from_bytes(b"PATCH").is_err());
+    }
+}
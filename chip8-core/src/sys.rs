@@ -0,0 +1,49 @@
+//! Host services behind SYS 0NNN. On the COSMAC VIP, SYS ran machine code
+//! at NNN; every interpreter since ignores it, which leaves the opcode free
+//! for an embedder to give a meaning to, e.g. printing to the console or
+//! ending a test run.
+//!
+//! ```
+//! use chip8_core::{Interpreter, RunState, SysOutcome};
+//!
+//! let mut interpreter = Interpreter::new();
+//! // SYS 0xA00 ends the program, leaving its result in V0
+//! interpreter.set_sys_handler(|addr: u16, _: &mut Interpreter| match addr {
+//!     0xA00 => SysOutcome::Exit,
+//!     _ => SysOutcome::Continue,
+//! });
+//! // LD V0, 0x2A; SYS 0xA00
+//! interpreter.load_program(&[0x60, 0x2A, 0x0A, 0x00]).unwrap();
+//! interpreter.step().unwrap();
+//! interpreter.step().unwrap();
+//! assert_eq!(interpreter.registers()[0], 0x2A);
+//! assert_eq!(interpreter.state(), &RunState::Exited);
+//! ```
+
+use crate::Interpreter;
+
+/// What the program does after a SYS its handler ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysOutcome {
+    /// go on with the next instruction, as when SYS is ignored
+    Continue,
+    /// stop, as 00FD does
+    Exit,
+}
+
+/// Runs SYS 0NNN for the program, given to [`Interpreter::set_sys_handler`].
+/// Closures taking the address and the interpreter are handlers too.
+pub trait SysHandler {
+    /// Handle SYS `addr`. The interpreter's pc is already past it, and
+    /// its state can be read, or changed through [`Interpreter::poke`].
+    fn sys(&mut self, addr: u16, interpreter: &mut Interpreter) -> SysOutcome;
+}
+
+impl<F> SysHandler for F
+where
+    F: FnMut(u16, &mut Interpreter) -> SysOutcome,
+{
+    fn sys(&mut self, addr: u16, interpreter: &mut Interpreter) -> SysOutcome {
+        self(addr, interpreter)
+    }
+}
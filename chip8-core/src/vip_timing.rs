@@ -0,0 +1,88 @@
+//! Roughly how long each instruction took on the COSMAC VIP, for running
+//! games at the speed they were tuned for rather than at a flat number of
+//! instructions per frame.
+//!
+//! Costs are in 1802 machine cycles of 8 clocks each, from the VIP
+//! interpreter's own code. They ignore the smaller variations of the
+//! original: a skip that's taken, FX33 of a larger number, or a sprite that
+//! straddles two bytes of display memory all cost a few cycles more.
+
+use crate::interpreter::Op;
+
+/// machine cycles in one 60 Hz frame at the VIP's 1.7609 MHz clock
+pub const CYCLES_PER_FRAME: u32 = 3668;
+
+/// cycles each frame that the display takes for itself, one for each of the
+/// 8 bytes it fetches on each of its 128 lines
+pub const DISPLAY_CYCLES_PER_FRAME: u32 = 8 * 128;
+
+/// cycles each frame that are left for running instructions
+pub const CYCLES_FOR_INSTRUCTIONS: u32 = CYCLES_PER_FRAME - DISPLAY_CYCLES_PER_FRAME;
+
+/// fetching an instruction and jumping to its handler, on top of the handler itself
+const FETCH_CYCLES: u32 = 40;
+
+/// machine cycles the VIP takes to fetch and execute `op`
+pub fn cycles(op: &Op) -> u32 {
+    let execute = match op {
+        // clearing all 256 bytes of display memory
+        Op::Cls => 3078,
+        Op::Ret => 10,
+        // machine code routines are the ROM's own; count them as a call
+        Op::Sys => 26,
+        // the VIP had no EXIT, and nothing runs after it anyway
+        Op::Exit => 0,
+        Op::Jp { .. } | Op::LdI { .. } => 12,
+        Op::Call { .. } => 26,
+        Op::Se { .. }
+        | Op::Sne { .. }
+        | Op::Add { .. }
+        | Op::LdVxDt { .. }
+        | Op::LdDtVx { .. }
+        | Op::LdStVx { .. } => 10,
+        Op::SeVxVy { .. } | Op::SneVxVy { .. } | Op::Skp { .. } | Op::Sknp { .. } => 14,
+        Op::Ld { .. } => 6,
+        Op::LdVxVy { .. }
+        | Op::OrVxVy { .. }
+        | Op::AndVxVy { .. }
+        | Op::XorVxVy { .. }
+        | Op::AddVxVy { .. }
+        | Op::SubVxVy { .. }
+        | Op::ShrVxVy { .. }
+        | Op::SubnVxVy { .. }
+        | Op::ShlVxVy { .. } => 20,
+        Op::JpV0 { .. } => 22,
+        Op::Rnd { .. } => 36,
+        // setting up, then shifting each row into place and XORing it onto the display
+        Op::Drw { n, .. } => 26 + 68 * *n as u32,
+        // one pass of polling the keypad
+        Op::LdVxK { .. } => 18,
+        Op::AddIVx { .. } | Op::LdFVx { .. } => 16,
+        // dividing down by 100 and 10
+        Op::LdBVx { .. } => 152,
+        // the RPL flag instructions are SUPER-CHIP's, so count them as FX55 and FX65
+        Op::LdIVx { x } | Op::LdVxI { x } | Op::LdRVx { x } | Op::LdVxR { x } => {
+            14 + 14 * (*x as u32 + 1)
+        }
+        // MegaChip never ran on a VIP
+        Op::Invalid | Op::Mega(_) => 0,
+    };
+    FETCH_CYCLES + execute
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::decode;
+
+    #[test]
+    fn test_cycles() {
+        // LD Vx, byte is among the quickest, and drawing a tall sprite among the slowest
+        assert_eq!(cycles(&decode(0x6012)), 46);
+        assert_eq!(cycles(&decode(0xD01F)), 40 + 26 + 68 * 15);
+        assert!(cycles(&decode(0xF055)) < cycles(&decode(0xFF55)));
+        // a frame fits about 57 loads, but only two 15-row sprites
+        assert_eq!(CYCLES_FOR_INSTRUCTIONS / cycles(&decode(0x6012)), 57);
+        assert_eq!(CYCLES_FOR_INSTRUCTIONS / cycles(&decode(0xD01F)), 2);
+    }
+}
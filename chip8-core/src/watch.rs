@@ -0,0 +1,171 @@
+//! Watchpoints: stop when an instruction reads or writes a register or a
+//! byte of memory.
+//!
+//! Rather than instrumenting every access inside `execute`, each op's
+//! accesses are worked out up front from its operands and the quirks, the
+//! same way a reader of the spec would.
+
+use std::fmt;
+
+use crate::{
+    interpreter::{Op, RPL_FLAG_COUNT},
+    quirks::Quirks,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WatchTarget {
+    /// one of V0-VF
+    Register(u8),
+    Memory(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Which kinds of access a watchpoint stops on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Write,
+    Read,
+    ReadWrite,
+}
+
+impl WatchKind {
+    pub(crate) fn matches(self, access: Access) -> bool {
+        match self {
+            WatchKind::Write => access == Access::Write,
+            WatchKind::Read => access == Access::Read,
+            WatchKind::ReadWrite => true,
+        }
+    }
+}
+
+/// A watchpoint that triggered, with the value before and after the
+/// instruction ran. For reads the two are the same unless the instruction
+/// also wrote the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    /// address of the instruction that made the access
+    pub pc: u16,
+    pub target: WatchTarget,
+    pub access: Access,
+    pub old: u8,
+    pub new: u8,
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchTarget::Register(x) => write!(f, "V{:X}", x),
+            WatchTarget::Memory(addr) => write!(f, "[{:#05x}]", addr),
+        }
+    }
+}
+
+impl fmt::Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = match self.access {
+            Access::Read => "read",
+            Access::Write => "written",
+        };
+        write!(
+            f,
+            "{} {} at {:#05x}: {:#04x} -> {:#04x}",
+            self.target, verb, self.pc, self.old, self.new
+        )
+    }
+}
+
+/// every register and memory access `op` makes, given the current I
+pub(crate) fn accesses(
+    op: &Op,
+    index_register: u16,
+    quirks: &Quirks,
+) -> Vec<(WatchTarget, Access)> {
+    use Access::*;
+    let reg = |x: u8, access| (WatchTarget::Register(x), access);
+    let mem = |offset: u16, access| {
+        (
+            WatchTarget::Memory(index_register.wrapping_add(offset)),
+            access,
+        )
+    };
+    let vf = 0xF;
+
+    match *op {
+        Op::Cls
+        | Op::Ret
+        | Op::Sys
+        | Op::Exit
+        | Op::Jp { .. }
+        | Op::Call { .. }
+        | Op::LdI { .. } => vec![],
+        // MegaChip's memory accesses aren't watched
+        Op::Invalid | Op::Mega(_) => vec![],
+        Op::Se { x, .. } | Op::Sne { x, .. } => vec![reg(x, Read)],
+        Op::SeVxVy { x, y } | Op::SneVxVy { x, y } => vec![reg(x, Read), reg(y, Read)],
+        Op::Ld { x, .. } | Op::Rnd { x, .. } | Op::LdVxDt { x } | Op::LdVxK { x } => {
+            vec![reg(x, Write)]
+        }
+        Op::Add { x, .. } => vec![reg(x, Read), reg(x, Write)],
+        Op::LdVxVy { x, y } => vec![reg(y, Read), reg(x, Write)],
+        Op::OrVxVy { x, y } | Op::AndVxVy { x, y } | Op::XorVxVy { x, y } => {
+            let mut accesses = vec![reg(x, Read), reg(y, Read), reg(x, Write)];
+            if quirks.logic_resets_vf {
+                accesses.push(reg(vf, Write));
+            }
+            accesses
+        }
+        Op::AddVxVy { x, y } | Op::SubVxVy { x, y } | Op::SubnVxVy { x, y } => {
+            vec![reg(x, Read), reg(y, Read), reg(x, Write), reg(vf, Write)]
+        }
+        Op::ShrVxVy { x, y } | Op::ShlVxVy { x, y } => {
+            let source = if quirks.shift_uses_vy { y } else { x };
+            vec![reg(source, Read), reg(x, Write), reg(vf, Write)]
+        }
+        Op::JpV0 { nnn } => {
+            let x = if quirks.jump_uses_vx {
+                (nnn >> 8) as u8
+            } else {
+                0
+            };
+            vec![reg(x, Read)]
+        }
+        Op::Drw { x, y, n } => {
+            let mut accesses = vec![reg(x, Read), reg(y, Read)];
+            accesses.extend((0..n as u16).map(|offset| mem(offset, Read)));
+            accesses.push(reg(vf, Write));
+            accesses
+        }
+        Op::Skp { x } | Op::Sknp { x } | Op::LdDtVx { x } | Op::LdStVx { x } | Op::LdFVx { x } => {
+            vec![reg(x, Read)]
+        }
+        Op::AddIVx { x } => {
+            let mut accesses = vec![reg(x, Read)];
+            if quirks.add_i_sets_vf {
+                accesses.push(reg(vf, Write));
+            }
+            accesses
+        }
+        Op::LdBVx { x } => {
+            let mut accesses = vec![reg(x, Read)];
+            accesses.extend((0..3).map(|offset| mem(offset, Write)));
+            accesses
+        }
+        Op::LdIVx { x } => (0..=x)
+            .flat_map(|idx| [reg(idx, Read), mem(idx as u16, Write)])
+            .collect(),
+        Op::LdVxI { x } => (0..=x)
+            .flat_map(|idx| [mem(idx as u16, Read), reg(idx, Write)])
+            .collect(),
+        Op::LdRVx { x } => (0..=x.min(RPL_FLAG_COUNT as u8 - 1))
+            .map(|idx| reg(idx, Read))
+            .collect(),
+        Op::LdVxR { x } => (0..=x.min(RPL_FLAG_COUNT as u8 - 1))
+            .map(|idx| reg(idx, Write))
+            .collect(),
+    }
+}
@@ -0,0 +1,99 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from chip8-ffi/src/lib.rs; don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// An interpreter, owned by the host between `chip8_new` and `chip8_free`.
+typedef struct Chip8 Chip8;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create an interpreter with the default quirks. Free it with `chip8_free`.
+struct Chip8 *chip8_new(void);
+
+// Free an interpreter from `chip8_new`. Does nothing when given NULL.
+//
+// # Safety
+//
+// `chip8` must be NULL or a pointer from `chip8_new` that hasn't been freed.
+void chip8_free(struct Chip8 *chip8);
+
+// Reset the interpreter and load the `len` bytes at `rom` at 0x200.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`, and `rom` must point to `len` readable bytes.
+int32_t chip8_load_rom(struct Chip8 *chip8, const uint8_t *rom, size_t len);
+
+// Execute `count` instructions, stopping at the first one that fails.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+int32_t chip8_step(struct Chip8 *chip8, uint32_t count);
+
+// Count the delay and sound timers down. Call this 60 times per second.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+void chip8_tick_60hz(struct Chip8 *chip8);
+
+// Press or release CHIP-8 key `key`, 0 through F. Other keys are ignored.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+void chip8_set_key(struct Chip8 *chip8, uint8_t key, bool is_down);
+
+// Set all 16 keys at once: bit `i` of `keys` is whether key `i` is held.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+void chip8_set_keys(struct Chip8 *chip8, uint16_t keys);
+
+// The display, 64 pixels wide and `chip8_screen_height` tall, row by row,
+// as one byte per pixel: 1 when lit and 0 otherwise. The pointer stays
+// valid until the next call with `chip8`.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+const uint8_t *chip8_framebuffer(struct Chip8 *chip8);
+
+// The display's height: 32, or 64 for hires ROMs.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+size_t chip8_screen_height(const struct Chip8 *chip8);
+
+// Whether the buzzer should be sounding.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+bool chip8_should_play_sound(const struct Chip8 *chip8);
+
+// Why the last call that returned -1 failed, or NULL if the last call
+// succeeded. The string stays valid until the next call with `chip8`.
+//
+// # Safety
+//
+// `chip8` must come from `chip8_new`.
+const char *chip8_last_error(const struct Chip8 *chip8);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
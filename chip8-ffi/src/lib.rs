@@ -0,0 +1,230 @@
+//! C bindings for [`chip8_core`], built as `libchip8` (shared and static)
+//! with the header in `include/chip8.h`, generated by cbindgen. A test
+//! fails when it's out of date; `just header` regenerates it.
+//!
+//! A host creates a handle with `chip8_new`, loads a ROM, then each 60 Hz
+//! frame sets the keys, calls `chip8_tick_60hz` and `chip8_step`, and draws
+//! `chip8_framebuffer`. Functions that can fail return 0 on success and -1
+//! on failure, with the reason in `chip8_last_error`.
+
+use std::{
+    ffi::{c_char, CString},
+    ptr, slice,
+};
+
+use chip8_core::{
+    interpreter::{is_lit, HIRES_SCREEN_HEIGHT},
+    Chip8Error, Interpreter, SCREEN_WIDTH,
+};
+
+/// An interpreter, owned by the host between `chip8_new` and `chip8_free`.
+pub struct Chip8 {
+    interpreter: Interpreter,
+    /// the display as of the last `chip8_framebuffer`, one byte per pixel
+    framebuffer: [u8; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+    last_error: Option<CString>,
+}
+
+impl Chip8 {
+    fn result(&mut self, result: Result<(), Chip8Error>) -> i32 {
+        match result {
+            Ok(()) => {
+                self.last_error = None;
+                0
+            }
+            Err(e) => {
+                // messages never contain NUL bytes, but don't panic across the boundary if one does
+                self.last_error = CString::new(e.to_string()).ok();
+                -1
+            }
+        }
+    }
+}
+
+/// Create an interpreter with the default quirks. Free it with `chip8_free`.
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    Box::into_raw(Box::new(Chip8 {
+        interpreter: Interpreter::new(),
+        framebuffer: [0; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+        last_error: None,
+    }))
+}
+
+/// Free an interpreter from `chip8_new`. Does nothing when given NULL.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or a pointer from `chip8_new` that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Reset the interpreter and load the `len` bytes at `rom` at 0x200.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`, and `rom` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> i32 {
+    let chip8 = &mut *chip8;
+    let rom = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(rom, len)
+    };
+    let mut interpreter = Interpreter::with_quirks(chip8.interpreter.quirks());
+    let result = interpreter.load_program(rom);
+    if result.is_ok() {
+        chip8.interpreter = interpreter;
+    }
+    chip8.result(result)
+}
+
+/// Execute `count` instructions, stopping at the first one that fails.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8, count: u32) -> i32 {
+    let chip8 = &mut *chip8;
+    let result = (0..count).try_for_each(|_| chip8.interpreter.step().map(|_| ()));
+    chip8.result(result)
+}
+
+/// Count the delay and sound timers down. Call this 60 times per second.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_60hz(chip8: *mut Chip8) {
+    (*chip8).interpreter.tick_60hz();
+}
+
+/// Press or release CHIP-8 key `key`, 0 through F. Other keys are ignored.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, is_down: bool) {
+    if key < 16 {
+        (*chip8).interpreter.set_key(key as usize, is_down);
+    }
+}
+
+/// Set all 16 keys at once: bit `i` of `keys` is whether key `i` is held.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_keys(chip8: *mut Chip8, keys: u16) {
+    for key in 0..16 {
+        (*chip8).interpreter.set_key(key, keys & (1 << key) != 0);
+    }
+}
+
+/// The display, 64 pixels wide and `chip8_screen_height` tall, row by row,
+/// as one byte per pixel: 1 when lit and 0 otherwise. The pointer stays
+/// valid until the next call with `chip8`.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *mut Chip8) -> *const u8 {
+    let chip8 = &mut *chip8;
+    let rows = chip8.interpreter.rows();
+    for (idx, byte) in chip8.framebuffer.iter_mut().enumerate() {
+        *byte = is_lit(rows, idx % SCREEN_WIDTH, idx / SCREEN_WIDTH) as u8;
+    }
+    chip8.framebuffer.as_ptr()
+}
+
+/// The display's height: 32, or 64 for hires ROMs.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_screen_height(chip8: *const Chip8) -> usize {
+    (*chip8).interpreter.screen_height()
+}
+
+/// Whether the buzzer should be sounding.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_should_play_sound(chip8: *const Chip8) -> bool {
+    (*chip8).interpreter.should_play_sound()
+}
+
+/// Why the last call that returned -1 failed, or NULL if the last call
+/// succeeded. The string stays valid until the next call with `chip8`.
+///
+/// # Safety
+///
+/// `chip8` must come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_last_error(chip8: *const Chip8) -> *const c_char {
+    (*chip8)
+        .last_error
+        .as_ref()
+        .map_or(ptr::null(), |message| message.as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    #[test]
+    fn test_run_through_c_api() {
+        unsafe {
+            let chip8 = chip8_new();
+            // LD V0, 1; LD F, V0; DRW V0, V0, 5; then an invalid opcode
+            let rom = [0x60, 0x01, 0xF0, 0x29, 0xD0, 0x05, 0x5A, 0xB1];
+            assert_eq!(chip8_load_rom(chip8, rom.as_ptr(), rom.len()), 0);
+            assert_eq!(chip8_step(chip8, 3), 0);
+            assert!(chip8_last_error(chip8).is_null());
+
+            assert_eq!(chip8_screen_height(chip8), 32);
+            let pixels = slice::from_raw_parts(chip8_framebuffer(chip8), SCREEN_WIDTH * 32);
+            // the top of the font's "1" at (1, 1)
+            assert_eq!(pixels[SCREEN_WIDTH + 1..SCREEN_WIDTH + 5], [0, 0, 1, 0]);
+
+            assert_eq!(chip8_step(chip8, 1), -1);
+            let error = CStr::from_ptr(chip8_last_error(chip8));
+            assert_eq!(error.to_str().unwrap(), "invalid opcode 5AB1 at 0x206");
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn test_header_is_current() {
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).unwrap();
+        let mut header = vec![];
+        cbindgen::generate_with_config(crate_dir, config)
+            .unwrap()
+            .write(&mut header);
+        let path = format!("{}/include/chip8.h", crate_dir);
+        if std::env::var_os("CHIP8_UPDATE_HEADER").is_some() {
+            std::fs::write(&path, &header).unwrap();
+        }
+        assert!(
+            std::fs::read(&path).unwrap() == header,
+            "{} is out of date; run `just header` to regenerate it",
+            path
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! A `wasm-bindgen` interface to [`chip8_core`], for web pages that want to
+//! draw the display into their own canvas or WebGL context instead of using
+//! the macroquad frontend.
+//!
+//! Build it with `wasm-pack build chip8-wasm --target web`, then:
+//!
+//! ```js
+//! import init, { Chip8 } from "./pkg/chip8_wasm.js";
+//!
+//! await init();
+//! const chip8 = new Chip8();
+//! chip8.load_rom(new Uint8Array(await (await fetch("pong.ch8")).arrayBuffer()));
+//! // once per 60 Hz frame
+//! chip8.tick_60hz();
+//! chip8.step(5);
+//! const pixels = chip8.framebuffer(); // 64 x chip8.screen_height(), one byte per pixel, row by row
+//! ```
+
+use chip8_core::{interpreter::is_lit, Interpreter, Quirks, SCREEN_WIDTH};
+use wasm_bindgen::prelude::*;
+
+/// A CHIP-8 interpreter. As with the Rust API, the page runs instructions
+/// with `step`, ticks the timers with `tick_60hz` at 60 Hz, and reads the
+/// display and buzzer after each frame.
+#[wasm_bindgen]
+pub struct Chip8 {
+    interpreter: Interpreter,
+    quirks: Quirks,
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Chip8 {
+        Chip8 {
+            interpreter: Interpreter::new(),
+            quirks: Quirks::default(),
+        }
+    }
+
+    /// Emulate another interpreter variant, "vip", "schip", or "amiga",
+    /// from the next `load_rom` on.
+    pub fn set_quirks(&mut self, name: &str) -> Result<(), JsError> {
+        self.quirks = Quirks::from_name(name)
+            .ok_or_else(|| JsError::new(&format!("unknown quirks preset {}", name)))?;
+        Ok(())
+    }
+
+    /// Reset the interpreter and load `rom` at 0x200. Throws if the ROM
+    /// doesn't fit in memory.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        let mut interpreter = Interpreter::with_quirks(self.quirks);
+        interpreter.load_program(rom)?;
+        self.interpreter = interpreter;
+        Ok(())
+    }
+
+    /// Execute `count` instructions. Throws on an invalid opcode or a bad
+    /// call or return, leaving the program counter on that instruction.
+    pub fn step(&mut self, count: u32) -> Result<(), JsError> {
+        for _ in 0..count {
+            self.interpreter.step()?;
+        }
+        Ok(())
+    }
+
+    /// count the delay and sound timers down; call this 60 times per second
+    pub fn tick_60hz(&mut self) {
+        self.interpreter.tick_60hz();
+    }
+
+    /// the display, 64 pixels wide and `screen_height` tall, row by row, as
+    /// 1 for a lit pixel and 0 otherwise
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let rows = self.interpreter.rows();
+        (0..SCREEN_WIDTH * self.interpreter.screen_height())
+            .map(|idx| is_lit(rows, idx % SCREEN_WIDTH, idx / SCREEN_WIDTH) as u8)
+            .collect()
+    }
+
+    /// 32 rows, or 64 for hires ROMs
+    pub fn screen_height(&self) -> usize {
+        self.interpreter.screen_height()
+    }
+
+    /// the indices into `framebuffer` of the pixels that changed since the
+    /// last call, for pages that redraw only those
+    pub fn dirty_pixels(&mut self) -> Vec<u16> {
+        self.interpreter
+            .take_dirty_pixels()
+            .into_iter()
+            .map(|idx| idx as u16)
+            .collect()
+    }
+
+    /// press or release CHIP-8 key `key`, 0 through F; other keys are ignored
+    pub fn set_key(&mut self, key: u8, is_down: bool) {
+        if key < 16 {
+            self.interpreter.set_key(key as usize, is_down);
+        }
+    }
+
+    /// a press and release of key `key` too quick for `set_key`, e.g. a tap
+    /// between two frames, which the program sees until the next tick
+    pub fn press_and_release(&mut self, key: u8) {
+        if key < 16 {
+            self.interpreter.press_and_release(key as usize);
+        }
+    }
+
+    /// whether the buzzer should be sounding
+    pub fn should_play_sound(&self) -> bool {
+        self.interpreter.should_play_sound()
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
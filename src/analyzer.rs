@@ -0,0 +1,542 @@
+//! Static analysis of a ROM, as the `analyze` subcommand prints it. The
+//! control flow is followed from the entry point to tell code from data,
+//! then the reachable code is checked for what could trip up an
+//! interpreter: how deep the calls go, instructions only some variants
+//! have, stores into code, and words that aren't instructions at all.
+//!
+//! Jumps through `JP V0` depend on V0 and aren't followed, so code only
+//! reached through them shows up as data.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use chip8_core::{
+    interpreter::{decode, Op},
+    megachip::MegaOp,
+};
+
+use crate::{
+    disassembler::format_op,
+    rom_info::{self, Variant},
+};
+
+/// calls the interpreter's stack has room for
+const STACK_SIZE: usize = 16;
+/// states tracked while following I, beyond which stores aren't checked
+const MAX_STATES: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    Code,
+    Data,
+}
+
+/// How control gets from one instruction to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// on to the next instruction, including when a skip doesn't skip
+    Next,
+    Jump,
+    Call,
+    /// on past a call, once it returns
+    Return,
+    Skip,
+}
+
+/// Where a store through I writes into reachable code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    /// the storing instruction
+    pub from: u16,
+    /// the first address written
+    pub to: u16,
+}
+
+/// Fails if `rom` loaded at `start` runs past 0xFFFF, the last address an
+/// instruction can reach.
+pub fn check_fits(rom: &[u8], start: u16) -> Result<(), String> {
+    if start as usize + rom.len() > 1 << 16 {
+        return Err(format!(
+            "a {}-byte ROM at {:#05x} runs past 0xFFFF, the end of the address space",
+            rom.len(),
+            start
+        ));
+    }
+    Ok(())
+}
+
+pub struct Analysis {
+    pub start: u16,
+    rom: Vec<u8>,
+    /// whether the ROM turns on MegaChip mode, making LDHI I long
+    megachip: bool,
+    /// each reachable instruction's address and length in bytes
+    pub code: BTreeMap<u16, u16>,
+    /// the most calls deep the program gets, or None if they recurse or
+    /// nest past what the stack holds
+    pub max_stack_depth: Option<usize>,
+    /// reachable instructions only some variants have
+    pub extensions: Vec<(u16, Variant)>,
+    pub code_writes: Vec<CodeWrite>,
+    /// reachable words that aren't instructions
+    pub invalid: Vec<u16>,
+    /// `JP V0` instructions, whose targets aren't followed
+    pub computed_jumps: Vec<u16>,
+    /// jumps and calls out of the ROM, as (from, to)
+    pub leaves_rom: Vec<(u16, u16)>,
+}
+
+impl Analysis {
+    /// Analyze `rom` loaded at `start`, which is also where it begins
+    /// running, unless it runs past 0xFFFF.
+    pub fn new(rom: &[u8], start: u16) -> Result<Self, String> {
+        check_fits(rom, start)?;
+        let mut analysis = Analysis {
+            start,
+            rom: rom.to_vec(),
+            megachip: rom.chunks(2).any(|pair| pair == [0x00, 0x11]),
+            code: BTreeMap::new(),
+            max_stack_depth: Some(0),
+            extensions: vec![],
+            code_writes: vec![],
+            invalid: vec![],
+            computed_jumps: vec![],
+            leaves_rom: vec![],
+        };
+        analysis.follow_calls();
+        analysis.follow_stores();
+        Ok(analysis)
+    }
+
+    fn word(&self, addr: u16) -> Option<u16> {
+        let offset = addr.checked_sub(self.start)? as usize;
+        let high = *self.rom.get(offset)?;
+        let low = self.rom.get(offset + 1).copied().unwrap_or(0);
+        Some(((high as u16) << 8) | low as u16)
+    }
+
+    fn in_rom(&self, addr: u16) -> bool {
+        self.word(addr).is_some()
+    }
+
+    /// bytes the instruction `word` takes up
+    fn len(&self, word: u16) -> u16 {
+        match decode(word) {
+            _ if word == 0xF000 => 4,
+            Op::Mega(MegaOp::LdIHi { .. }) if self.megachip => 4,
+            _ => 2,
+        }
+    }
+
+    /// where execution can go after the instruction at `addr`, with how
+    /// many calls deep it is then
+    fn successors(&self, addr: u16, depth: usize) -> Vec<(u16, usize)> {
+        let word = self.word(addr).unwrap_or(0);
+        let next = addr.wrapping_add(self.len(word));
+        match decode(word) {
+            Op::Jp { nnn } => vec![(nnn, depth)],
+            Op::Call { nnn } => vec![(nnn, depth + 1), (next, depth)],
+            Op::Ret | Op::Exit | Op::JpV0 { .. } => vec![],
+            Op::Invalid if rom_info::extension(word).is_none() => vec![],
+            Op::Se { .. }
+            | Op::Sne { .. }
+            | Op::SeVxVy { .. }
+            | Op::SneVxVy { .. }
+            | Op::Skp { .. }
+            | Op::Sknp { .. } => {
+                // a skip jumps over the whole of a long instruction
+                let skipped = self.word(next).map_or(2, |word| self.len(word));
+                vec![(next, depth), (next.wrapping_add(skipped), depth)]
+            }
+            _ => vec![(next, depth)],
+        }
+    }
+
+    /// find the reachable code and how deep the calls in it go
+    fn follow_calls(&mut self) {
+        let mut seen = BTreeSet::new();
+        let mut queue = vec![(self.start, 0)];
+        while let Some((addr, depth)) = queue.pop() {
+            if !seen.insert((addr, depth)) {
+                continue;
+            }
+            let word = self.word(addr).unwrap_or(0);
+            if !self.code.contains_key(&addr) {
+                self.code.insert(addr, self.len(word));
+                if let Some(variant) = rom_info::extension(word) {
+                    self.extensions.push((addr, variant));
+                } else {
+                    match decode(word) {
+                        Op::Invalid => self.invalid.push(addr),
+                        Op::JpV0 { .. } => self.computed_jumps.push(addr),
+                        _ => (),
+                    }
+                }
+            }
+            for (to, depth) in self.successors(addr, depth) {
+                if !self.in_rom(to) {
+                    self.leaves_rom.push((addr, to));
+                } else if depth > STACK_SIZE {
+                    self.max_stack_depth = None;
+                } else {
+                    self.max_stack_depth = self.max_stack_depth.map(|max| max.max(depth));
+                    queue.push((to, depth));
+                }
+            }
+        }
+        for list in [&mut self.invalid, &mut self.computed_jumps] {
+            list.sort();
+        }
+        self.extensions.sort_by_key(|(addr, _)| *addr);
+        self.leaves_rom.sort();
+        self.leaves_rom.dedup();
+    }
+
+    /// whether `addr` is part of a reachable instruction
+    pub fn is_code(&self, addr: u16) -> bool {
+        self.code
+            .range(..=addr)
+            .next_back()
+            .is_some_and(|(start, len)| (addr as u32) < *start as u32 + *len as u32)
+    }
+
+    /// Follow the code again with what I holds where it's known, to find
+    /// stores through it into code.
+    fn follow_stores(&mut self) {
+        let mut seen = BTreeSet::new();
+        let mut queue = vec![(self.start, None)];
+        let mut writes = BTreeSet::new();
+        while let Some((addr, i)) = queue.pop() {
+            if seen.len() >= MAX_STATES || !seen.insert((addr, i)) {
+                continue;
+            }
+            let op = decode(self.word(addr).unwrap_or(0));
+            let written = match op {
+                Op::LdIVx { x } => Some(x as u16 + 1),
+                Op::LdBVx { .. } => Some(3),
+                _ => None,
+            };
+            if let (Some(i), Some(len)) = (i, written) {
+                if let Some(to) = (i..i + len).find(|to| self.is_code(*to)) {
+                    writes.insert((addr, to));
+                }
+            }
+            let i = match op {
+                Op::LdI { nnn } => Some(nnn),
+                // these move I or leave it somewhere that depends on the quirks
+                Op::AddIVx { .. }
+                | Op::LdFVx { .. }
+                | Op::LdIVx { .. }
+                | Op::LdVxI { .. }
+                | Op::Mega(MegaOp::LdIHi { .. }) => None,
+                _ if self.word(addr) == Some(0xF000) => None,
+                _ => i,
+            };
+            for (to, _) in self.successors(addr, 0) {
+                if self.in_rom(to) {
+                    queue.push((to, i));
+                }
+            }
+        }
+        self.code_writes = writes
+            .into_iter()
+            .map(|(from, to)| CodeWrite { from, to })
+            .collect();
+    }
+
+    /// where control goes after the instruction at `addr`, and how
+    fn edges(&self, addr: u16) -> Vec<(u16, EdgeKind)> {
+        let to: Vec<u16> = self
+            .successors(addr, 0)
+            .into_iter()
+            .map(|(to, _)| to)
+            .collect();
+        let next = addr.wrapping_add(self.code.get(&addr).copied().unwrap_or(2));
+        match (decode(self.word(addr).unwrap_or(0)), to.as_slice()) {
+            (Op::Jp { .. }, [to]) => vec![(*to, EdgeKind::Jump)],
+            (Op::Call { .. }, [call, after]) => {
+                vec![(*call, EdgeKind::Call), (*after, EdgeKind::Return)]
+            }
+            (_, [not_skipped, skipped]) => {
+                vec![(*not_skipped, EdgeKind::Next), (*skipped, EdgeKind::Skip)]
+            }
+            (_, [to]) if *to == next => vec![(*to, EdgeKind::Next)],
+            _ => vec![],
+        }
+    }
+
+    /// The reachable code split into basic blocks, by the address each
+    /// starts at, with their instructions' addresses. Blocks start at the
+    /// entry point and where control goes other than straight on, and end at
+    /// a branch or the start of another block.
+    pub fn basic_blocks(&self) -> BTreeMap<u16, Vec<u16>> {
+        let straight_on = |addr: u16| matches!(self.edges(addr).as_slice(), [(_, EdgeKind::Next)]);
+        let mut leaders: BTreeSet<u16> = self
+            .code
+            .keys()
+            .filter(|addr| !straight_on(**addr))
+            .flat_map(|addr| self.edges(*addr))
+            .map(|(to, _)| to)
+            .filter(|to| self.code.contains_key(to))
+            .collect();
+        leaders.insert(self.start);
+        leaders
+            .iter()
+            .map(|leader| {
+                let mut block = vec![*leader];
+                let mut addr = *leader;
+                while let [(next, EdgeKind::Next)] = self.edges(addr).as_slice() {
+                    if leaders.contains(next) || !self.code.contains_key(next) {
+                        break;
+                    }
+                    block.push(*next);
+                    addr = *next;
+                }
+                (*leader, block)
+            })
+            .collect()
+    }
+
+    /// The basic blocks and the edges between them, as a Graphviz graph.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cfg {\n  node [shape=box, fontname=monospace];\n");
+        let mut outside = BTreeSet::new();
+        for (leader, block) in self.basic_blocks() {
+            let lines: String = block
+                .iter()
+                .map(|addr| format!("{}\\l", self.describe(*addr)))
+                .collect();
+            out.push_str(&format!("  b{:03x} [label=\"{}\"];\n", leader, lines));
+            let last = *block.last().expect("blocks aren't empty");
+            for (to, kind) in self.edges(last) {
+                let node = if self.code.contains_key(&to) {
+                    format!("b{:03x}", to)
+                } else {
+                    outside.insert(to);
+                    format!("out{:03x}", to)
+                };
+                let style = match kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Call => " [label=\"call\", style=bold]",
+                    EdgeKind::Return => " [label=\"return\", style=dashed]",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                };
+                out.push_str(&format!("  b{:03x} -> {}{};\n", leader, node, style));
+            }
+        }
+        for addr in outside {
+            out.push_str(&format!(
+                "  out{:03x} [label=\"{:#05x}\\noutside the ROM\", shape=plaintext];\n",
+                addr, addr
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// the ROM split into runs of code and data, as (kind, first, last address)
+    pub fn regions(&self) -> Vec<(RegionKind, u16, u16)> {
+        let mut regions: Vec<(RegionKind, u16, u16)> = vec![];
+        for offset in 0..self.rom.len() {
+            let addr = (self.start as usize + offset) as u16;
+            let kind = if self.is_code(addr) {
+                RegionKind::Code
+            } else {
+                RegionKind::Data
+            };
+            match regions.last_mut() {
+                Some((last, _, end)) if *last == kind => *end = addr,
+                _ => regions.push((kind, addr, addr)),
+            }
+        }
+        regions
+    }
+
+    fn describe(&self, addr: u16) -> String {
+        let word = self.word(addr).unwrap_or(0);
+        format!("{:#05x} {:04X} {}", addr, word, format_op(&decode(word)))
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "entry:          {:#05x}", self.start)?;
+        let regions = self.regions();
+        for (kind, name) in [(RegionKind::Code, "code:"), (RegionKind::Data, "data:")] {
+            let runs: Vec<_> = regions.iter().filter(|(k, _, _)| *k == kind).collect();
+            let bytes: usize = runs
+                .iter()
+                .map(|(_, first, last)| (last - first) as usize + 1)
+                .sum();
+            let ranges: Vec<String> = runs
+                .iter()
+                .map(|(_, first, last)| format!("{:#05x}-{:#05x}", first, last))
+                .collect();
+            writeln!(f, "{:<15} {} bytes", name, bytes)?;
+            for row in ranges.chunks(4) {
+                writeln!(f, "  {}", row.join(", "))?;
+            }
+        }
+        match self.max_stack_depth {
+            Some(depth) => writeln!(f, "stack depth:    {} at most", depth)?,
+            None => writeln!(
+                f,
+                "stack depth:    unbounded, calls recurse or nest past {}",
+                STACK_SIZE
+            )?,
+        }
+
+        let sections: [(&str, Vec<String>); 5] = [
+            (
+                "variant-specific instructions",
+                self.extensions
+                    .iter()
+                    .map(|(addr, variant)| format!("{}  ({})", self.describe(*addr), variant))
+                    .collect(),
+            ),
+            (
+                "stores into code",
+                self.code_writes
+                    .iter()
+                    .map(|write| format!("{}  writes {:#05x}", self.describe(write.from), write.to))
+                    .collect(),
+            ),
+            (
+                "invalid instructions",
+                self.invalid
+                    .iter()
+                    .map(|addr| self.describe(*addr))
+                    .collect(),
+            ),
+            (
+                "computed jumps, not followed",
+                self.computed_jumps
+                    .iter()
+                    .map(|addr| self.describe(*addr))
+                    .collect(),
+            ),
+            (
+                "jumps out of the ROM",
+                self.leaves_rom
+                    .iter()
+                    .map(|(from, to)| format!("{}  to {:#05x}", self.describe(*from), to))
+                    .collect(),
+            ),
+        ];
+        for (title, lines) in sections {
+            if lines.is_empty() {
+                continue;
+            }
+            writeln!(f, "{} ({}):", title, lines.len())?;
+            for line in lines {
+                writeln!(f, "  {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_and_data() {
+        // 0x200: CALL 0x208
+        // 0x202: JP 0x206
+        // 0x204: sprite data
+        // 0x206: JP 0x206
+        // 0x208: HIGH
+        // 0x20A: RET
+        let rom = [
+            0x22, 0x08, 0x12, 0x06, 0xFF, 0x81, 0x12, 0x06, 0x00, 0xFF, 0x00, 0xEE,
+        ];
+        let analysis = Analysis::new(&rom, 0x200).unwrap();
+        assert_eq!(
+            analysis.regions(),
+            [
+                (RegionKind::Code, 0x200, 0x203),
+                (RegionKind::Data, 0x204, 0x205),
+                (RegionKind::Code, 0x206, 0x20B),
+            ]
+        );
+        assert_eq!(analysis.max_stack_depth, Some(1));
+        assert_eq!(analysis.extensions, [(0x208, Variant::SuperChip)]);
+        assert!(analysis.invalid.is_empty());
+        assert!(analysis
+            .to_string()
+            .contains("0x208 00FF SYS  (SUPER-CHIP)"));
+    }
+
+    #[test]
+    fn test_problems() {
+        // 0x200: LD I, 0x20A
+        // 0x202: LD [I], V1, over the word at 0x20A
+        // 0x204: SE V0, 1
+        // 0x206: JP V0, 0x300
+        // 0x208: CALL 0x200, recursing
+        // 0x20A: invalid
+        let rom = [
+            0xA2, 0x0A, 0xF1, 0x55, 0x30, 0x01, 0xB3, 0x00, 0x22, 0x00, 0xFF, 0xFF,
+        ];
+        let analysis = Analysis::new(&rom, 0x200).unwrap();
+        assert_eq!(analysis.max_stack_depth, None);
+        assert_eq!(
+            analysis.code_writes,
+            [CodeWrite {
+                from: 0x202,
+                to: 0x20A
+            }]
+        );
+        assert_eq!(analysis.invalid, [0x20A]);
+        assert_eq!(analysis.computed_jumps, [0x206]);
+        assert!(analysis.leaves_rom.is_empty());
+
+        let analysis = Analysis::new(&[0x13, 0x00], 0x200).unwrap();
+        assert_eq!(analysis.leaves_rom, [(0x200, 0x300)]);
+    }
+
+    #[test]
+    fn test_roms_up_to_0xffff() {
+        assert!(Analysis::new(&vec![0; 70_000], 0x200).is_err());
+
+        // CLS all the way to 0xFFFF, in a MegaChip-sized ROM
+        let rom = [0x00, 0xE0].repeat((0x10000 - 0x200) / 2);
+        let analysis = Analysis::new(&rom, 0x200).unwrap();
+        assert_eq!(analysis.regions(), [(RegionKind::Code, 0x200, 0xFFFF)]);
+        assert_eq!(analysis.leaves_rom, [(0xFFFE, 0x0000)]);
+    }
+
+    #[test]
+    fn test_basic_blocks() {
+        // 0x200: LD V0, 1
+        // 0x202: SE V0, 1
+        // 0x204: CALL 0x20A
+        // 0x206: CLS
+        // 0x208: JP 0x300
+        // 0x20A: RET
+        let rom = [
+            0x60, 0x01, 0x30, 0x01, 0x22, 0x0A, 0x00, 0xE0, 0x13, 0x00, 0x00, 0xEE,
+        ];
+        let analysis = Analysis::new(&rom, 0x200).unwrap();
+        let blocks = analysis.basic_blocks();
+        assert_eq!(
+            blocks.into_iter().collect::<Vec<_>>(),
+            [
+                (0x200, vec![0x200, 0x202]),
+                (0x204, vec![0x204]),
+                (0x206, vec![0x206, 0x208]),
+                (0x20A, vec![0x20A]),
+            ]
+        );
+        let dot = analysis.to_dot();
+        assert!(dot.starts_with("digraph cfg {"));
+        assert!(dot.contains("  b200 -> b204;\n  b200 -> b206 [label=\"skip\"];"));
+        assert!(dot.contains("  b204 -> b20a [label=\"call\", style=bold];"));
+        assert!(dot.contains("  b206 -> out300 [label=\"jump\"];"));
+        assert!(dot.contains("label=\"0x200 6001 LD V0, 0x01\\l0x202 3001 SE V0, 0x01\\l\""));
+    }
+}
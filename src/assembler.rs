@@ -0,0 +1,572 @@
+//! Assemble a subset of Octo's syntax into a CHIP-8 ROM.
+//!
+//! Supported:
+//!
+//! - labels `: name` and constants `:const name 42`, register names via `:alias name v3`
+//! - `clear`, `return` (or `;`), `exit`, `jump`, `jump0`, `native`, `:call`, and calling a
+//!   label by writing its name
+//! - `vx := ...` with a number, `vy`, `random n`, `delay`, or `key`
+//! - `+=`, `-=`, `=-`, `|=`, `&=`, `^=`, `>>=`, `<<=`
+//! - `i := addr`, `i := hex vx`, `i += vx`, `delay := vx`, `buzzer := vx`
+//! - `sprite vx vy n`, `bcd vx`, `save vx`, `load vx`, `saveflags vx`, `loadflags vx`
+//! - `if <cond> then <statement>`, where `<cond>` is `vx == n`, `vx != n`,
+//!   `vx == vy`, `vx != vy`, `vx key`, or `vx -key`
+//! - `loop ... again`, with `while <cond>` inside to break out
+//! - bare numbers and `:byte n` for data
+//!
+//! Programs start at `main` if there is one, as in Octo.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+};
+
+use chip8_core::interpreter::PROGRAM_START;
+
+use crate::symbols::Symbols;
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+enum Cond {
+    EqByte(u8, u8),
+    NeByte(u8, u8),
+    EqReg(u8, u8),
+    NeReg(u8, u8),
+    Key(u8),
+    NotKey(u8),
+}
+
+impl Cond {
+    /// a skip instruction that skips when the condition is false
+    fn skip_unless(&self) -> u16 {
+        self.negate().skip_if()
+    }
+
+    /// a skip instruction that skips when the condition is true
+    fn skip_if(&self) -> u16 {
+        let xy = |x: u8, y: u8| ((x as u16) << 8) | ((y as u16) << 4);
+        let xnn = |x: u8, nn: u8| ((x as u16) << 8) | nn as u16;
+        match *self {
+            Cond::EqByte(x, nn) => 0x3000 | xnn(x, nn),
+            Cond::NeByte(x, nn) => 0x4000 | xnn(x, nn),
+            Cond::EqReg(x, y) => 0x5000 | xy(x, y),
+            Cond::NeReg(x, y) => 0x9000 | xy(x, y),
+            Cond::Key(x) => 0xE09E | xy(x, 0),
+            Cond::NotKey(x) => 0xE0A1 | xy(x, 0),
+        }
+    }
+
+    fn negate(&self) -> Cond {
+        match *self {
+            Cond::EqByte(x, nn) => Cond::NeByte(x, nn),
+            Cond::NeByte(x, nn) => Cond::EqByte(x, nn),
+            Cond::EqReg(x, y) => Cond::NeReg(x, y),
+            Cond::NeReg(x, y) => Cond::EqReg(x, y),
+            Cond::Key(x) => Cond::NotKey(x),
+            Cond::NotKey(x) => Cond::Key(x),
+        }
+    }
+}
+
+struct Loop {
+    start: u16,
+    /// offsets of `while` jumps to patch with the address after `again`
+    breaks: Vec<usize>,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, u16>,
+    aliases: HashMap<&'a str, u8>,
+    /// (offset of an instruction, label whose address goes in its low 12 bits, line)
+    fixups: Vec<(usize, &'a str, usize)>,
+    loops: Vec<Loop>,
+}
+
+/// assemble Octo-style `source` into a ROM to be loaded at 0x200
+pub fn assemble(source: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(assemble_with_symbols(source)?.0)
+}
+
+/// assemble `source`, also naming the address of each label
+pub fn assemble_with_symbols(source: &str) -> Result<(Vec<u8>, Symbols), Box<dyn Error>> {
+    let tokens: Vec<Token> = source
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text,
+                line: idx + 1,
+            })
+        })
+        .collect();
+
+    let mut assembler = Assembler {
+        tokens,
+        pos: 0,
+        rom: vec![],
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: vec![],
+        loops: vec![],
+    };
+    assembler.run()?;
+    // where labels share an address, the first alphabetically names it
+    let mut names = BTreeMap::new();
+    let mut labels: Vec<_> = assembler.labels.into_iter().collect();
+    labels.sort();
+    for (name, addr) in labels {
+        names.entry(addr).or_insert_with(|| name.to_string());
+    }
+    Ok((assembler.rom, Symbols::new(names)))
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+impl<'a> Assembler<'a> {
+    fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        // as in Octo, jump to main unless the program already starts there
+        let starts_at_main = matches!(self.tokens.as_slice(), [colon, name, ..] if colon.text == ":" && name.text == "main");
+        let has_main = self
+            .tokens
+            .windows(2)
+            .any(|w| w[0].text == ":" && w[1].text == "main");
+        if has_main && !starts_at_main {
+            self.emit_addr(0x1000, "main", 1);
+        }
+
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+
+        if let Some(open) = self.loops.last() {
+            return Err(format!("loop at {:#05x} is missing its again", open.start).into());
+        }
+        for (offset, label, line) in std::mem::take(&mut self.fixups) {
+            let addr = *self
+                .labels
+                .get(label)
+                .ok_or_else(|| format!("line {}: undefined label {}", line, label))?;
+            self.rom[offset] |= (addr >> 8) as u8 & 0x0F;
+            self.rom[offset + 1] = addr as u8;
+        }
+        if self.rom.len() > chip8_core::interpreter::MEMORY_SIZE - PROGRAM_START {
+            return Err(format!("program is too large ({} bytes)", self.rom.len()).into());
+        }
+        Ok(())
+    }
+
+    fn error(&self, msg: &str) -> Box<dyn Error> {
+        let line = self
+            .tokens
+            .get(self.pos.saturating_sub(1))
+            .map_or(0, |t| t.line);
+        format!("line {}: {}", line, msg).into()
+    }
+
+    fn next(&mut self) -> Result<&'a str, Box<dyn Error>> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Box<dyn Error>> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(self.error(&format!("expected {}, found {}", expected, token)));
+        }
+        Ok(())
+    }
+
+    fn current_addr(&self) -> u16 {
+        (PROGRAM_START + self.rom.len()) as u16
+    }
+
+    fn emit(&mut self, word: u16) {
+        self.rom.extend_from_slice(&word.to_be_bytes());
+    }
+
+    /// an instruction whose only operand is the register that comes next
+    fn emit_x(&mut self, opcode: u16) -> Result<(), Box<dyn Error>> {
+        let x = self.register()? as u16;
+        self.emit(opcode | x << 8);
+        Ok(())
+    }
+
+    fn emit_addr(&mut self, opcode: u16, label: &'a str, line: usize) {
+        self.fixups.push((self.rom.len(), label, line));
+        self.emit(opcode);
+    }
+
+    fn register(&mut self) -> Result<u8, Box<dyn Error>> {
+        let token = self.next()?;
+        self.parse_register(token)
+            .ok_or_else(|| self.error(&format!("expected a register, found {}", token)))
+    }
+
+    fn parse_register(&self, token: &str) -> Option<u8> {
+        if let Some(x) = self.aliases.get(token) {
+            return Some(*x);
+        }
+        let digit = token.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    /// a number or constant
+    fn value(&mut self) -> Result<i64, Box<dyn Error>> {
+        let token = self.next()?;
+        if let Some(value) = self.constants.get(token) {
+            return Ok(*value as i64);
+        }
+        parse_number(token)
+            .ok_or_else(|| self.error(&format!("expected a number, found {}", token)))
+    }
+
+    fn byte(&mut self) -> Result<u8, Box<dyn Error>> {
+        let value = self.value()?;
+        if !(-128..=255).contains(&value) {
+            return Err(self.error(&format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as u8)
+    }
+
+    /// an instruction taking an address, which may be a label defined later
+    fn addr_instruction(&mut self, opcode: u16) -> Result<(), Box<dyn Error>> {
+        let token = self.next()?;
+        let line = self.tokens[self.pos - 1].line;
+        if let Some(value) = self.constants.get(token) {
+            self.emit(opcode | (value & 0x0FFF));
+        } else if let Some(value) = parse_number(token) {
+            if !(0..=0xFFF).contains(&value) {
+                return Err(self.error(&format!("address {} is out of range", value)));
+            }
+            self.emit(opcode | value as u16);
+        } else {
+            self.emit_addr(opcode, token, line);
+        }
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<Cond, Box<dyn Error>> {
+        let x = self.register()?;
+        match self.next()? {
+            "key" => Ok(Cond::Key(x)),
+            "-key" => Ok(Cond::NotKey(x)),
+            op @ ("==" | "!=") => {
+                let rhs = self
+                    .tokens
+                    .get(self.pos)
+                    .and_then(|t| self.parse_register(t.text));
+                let cond = match (op, rhs) {
+                    ("==", Some(y)) => Cond::EqReg(x, y),
+                    ("!=", Some(y)) => Cond::NeReg(x, y),
+                    ("==", None) => return Ok(Cond::EqByte(x, self.byte()?)),
+                    _ => return Ok(Cond::NeByte(x, self.byte()?)),
+                };
+                self.pos += 1;
+                Ok(cond)
+            }
+            other => Err(self.error(&format!("unsupported comparison {}", other))),
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), Box<dyn Error>> {
+        let token = self.next()?;
+        match token {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name, self.current_addr()).is_some() {
+                    return Err(self.error(&format!("label {} is defined twice", name)));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.constants.insert(name, value as u16);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let x = self.register()?;
+                self.aliases.insert(name, x);
+            }
+            ":call" => self.addr_instruction(0x2000)?,
+            ":byte" => {
+                let b = self.byte()?;
+                self.rom.push(b);
+            }
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "exit" => self.emit(0x00FD),
+            "jump" => self.addr_instruction(0x1000)?,
+            "jump0" => self.addr_instruction(0xB000)?,
+            "native" => self.addr_instruction(0x0000)?,
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.value()?;
+                if !(0..16).contains(&n) {
+                    return Err(self.error("sprite height must be 0-15"));
+                }
+                self.emit(0xD000 | x << 8 | y << 4 | n as u16);
+            }
+            "bcd" => self.emit_x(0xF033)?,
+            "save" => self.emit_x(0xF055)?,
+            "load" => self.emit_x(0xF065)?,
+            "saveflags" => self.emit_x(0xF075)?,
+            "loadflags" => self.emit_x(0xF085)?,
+            "delay" => {
+                self.expect(":=")?;
+                self.emit_x(0xF015)?;
+            }
+            "buzzer" => {
+                self.expect(":=")?;
+                self.emit_x(0xF018)?;
+            }
+            "i" => match self.next()? {
+                ":=" => {
+                    if self.tokens.get(self.pos).map(|t| t.text) == Some("hex") {
+                        self.pos += 1;
+                        self.emit_x(0xF029)?;
+                    } else {
+                        self.addr_instruction(0xA000)?;
+                    }
+                }
+                "+=" => self.emit_x(0xF01E)?,
+                other => return Err(self.error(&format!("unsupported operator i {}", other))),
+            },
+            "if" => {
+                let cond = self.condition()?;
+                self.expect("then")?;
+                self.emit(cond.skip_unless());
+                self.statement()?;
+            }
+            "loop" => self.loops.push(Loop {
+                start: self.current_addr(),
+                breaks: vec![],
+            }),
+            "while" => {
+                let cond = self.condition()?;
+                if self.loops.is_empty() {
+                    return Err(self.error("while outside of a loop"));
+                }
+                self.emit(cond.skip_if());
+                let offset = self.rom.len();
+                self.emit(0x1000);
+                self.loops
+                    .last_mut()
+                    .expect("checked above")
+                    .breaks
+                    .push(offset);
+            }
+            "again" => {
+                let open = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| self.error("again without loop"))?;
+                self.emit(0x1000 | open.start);
+                let end = self.current_addr();
+                for offset in open.breaks {
+                    self.rom[offset] = 0x10 | (end >> 8) as u8;
+                    self.rom[offset + 1] = end as u8;
+                }
+            }
+            _ => {
+                if let Some(x) = self.parse_register(token) {
+                    self.register_statement(x)?;
+                } else if let Some(value) = parse_number(token) {
+                    if !(-128..=255).contains(&value) {
+                        return Err(self.error(&format!("{} does not fit in a byte", value)));
+                    }
+                    self.rom.push(value as u8);
+                } else if token.starts_with(':') {
+                    return Err(self.error(&format!("unsupported directive {}", token)));
+                } else {
+                    // a bare name calls that subroutine
+                    let line = self.tokens[self.pos - 1].line;
+                    self.emit_addr(0x2000, token, line);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// a statement starting with register `x`, e.g. `v3 += 1`
+    fn register_statement(&mut self, x: u8) -> Result<(), Box<dyn Error>> {
+        let x16 = (x as u16) << 8;
+        let op = self.next()?;
+        let rhs = self.tokens.get(self.pos).map_or("", |t| t.text);
+        let y = self.parse_register(rhs).map(|y| (y as u16) << 4);
+
+        let alu = |n: u16| y.map(|y| 0x8000 | x16 | y | n);
+        let word = match (op, rhs) {
+            (":=", "random") => {
+                self.pos += 1;
+                0xC000 | x16 | self.byte()? as u16
+            }
+            (":=", "delay") => {
+                self.pos += 1;
+                0xF007 | x16
+            }
+            (":=", "key") => {
+                self.pos += 1;
+                0xF00A | x16
+            }
+            (":=", _) => match alu(0x0) {
+                Some(word) => {
+                    self.pos += 1;
+                    word
+                }
+                None => 0x6000 | x16 | self.byte()? as u16,
+            },
+            ("+=", _) => match alu(0x4) {
+                Some(word) => {
+                    self.pos += 1;
+                    word
+                }
+                None => 0x7000 | x16 | self.byte()? as u16,
+            },
+            ("-=", _) => match alu(0x5) {
+                Some(word) => {
+                    self.pos += 1;
+                    word
+                }
+                // Octo subtracts constants by adding their negation
+                None => 0x7000 | x16 | (self.byte()?.wrapping_neg()) as u16,
+            },
+            (op @ ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<="), _) => {
+                let n = match op {
+                    "=-" => 0x7,
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    ">>=" => 0x6,
+                    _ => 0xE,
+                };
+                match alu(n) {
+                    Some(word) => {
+                        self.pos += 1;
+                        word
+                    }
+                    None => return Err(self.error(&format!("{} needs a register", op))),
+                }
+            }
+            (other, _) => return Err(self.error(&format!("unsupported operator {}", other))),
+        };
+        self.emit(word);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::{disassemble, RenderOptions};
+
+    fn words(rom: &[u8]) -> Vec<u16> {
+        rom.chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_instructions() -> Result<(), Box<dyn Error>> {
+        let rom = assemble(
+            "
+            clear
+            v3 := 0x2A  v3 := v4  v3 += 1  v3 += v4  v3 -= 1  v3 -= v4  v3 =- v4
+            v3 |= v4  v3 &= v4  v3 ^= v4  v3 >>= v4  v3 <<= v4
+            v3 := random 0xF  v3 := delay  v3 := key  delay := v3  buzzer := v3
+            i := 0x300  i := hex v3  i += v3
+            sprite v3 v4 5  bcd v3  save v3  load v3  saveflags v3  loadflags v3
+            jump 0x200  jump0 0x300  native 0x123  :call 0x400  return  exit
+            ",
+        )?;
+        assert_eq!(
+            words(&rom),
+            [
+                0x00E0, 0x632A, 0x8340, 0x7301, 0x8344, 0x73FF, 0x8345, 0x8347, 0x8341, 0x8342,
+                0x8343, 0x8346, 0x834E, 0xC30F, 0xF307, 0xF30A, 0xF315, 0xF318, 0xA300, 0xF329,
+                0xF31E, 0xD345, 0xF333, 0xF355, 0xF365, 0xF375, 0xF385, 0x1200, 0xB300, 0x0123,
+                0x2400, 0x00EE, 0x00FD,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_labels_constants_and_control_flow() -> Result<(), Box<dyn Error>> {
+        let rom = assemble(
+            "
+            :const SPEED 3
+            :alias ball-x v1
+            : draw  # a subroutine before main, so main needs a jump
+              sprite ball-x v2 1 ;
+            : main
+              i := dot
+              loop
+                ball-x += SPEED
+                while ball-x != 60
+                if v0 key then draw
+              again
+              jump main
+            : dot 0x80
+            ",
+        )?;
+        let listing = disassemble(&rom, 0x200)?.render(RenderOptions::default());
+        assert!(listing.contains("0x200:  1206  JP    L_206"), "{}", listing);
+        assert_eq!(
+            words(&rom[..rom.len() - 1]),
+            [
+                0x1206, 0xD121, 0x00EE, // jump main; draw
+                0xA216, // i := dot
+                0x7103, 0x413C, 0x1214, // loop: ball-x += 3; while
+                0xE0A1, 0x2202, // if v0 key then draw
+                0x1208, // again
+                0x1206, // jump main
+            ]
+        );
+        assert_eq!(rom[rom.len() - 1], 0x80);
+
+        let (_, symbols) = assemble_with_symbols(": main\n: start\n  clear\n: sprite 0x80")?;
+        assert_eq!(symbols.to_text(), "0x200 main\n0x202 sprite\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(assemble("jump nowhere").is_err());
+        assert!(assemble("v3 := 256").is_err());
+        assert!(assemble("vG := 1").is_err());
+        assert!(assemble("loop v0 += 1").is_err());
+        assert!(assemble("v0 |= 1").is_err());
+        let error = assemble("clear\nv0 ?= 1").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: unsupported operator ?=");
+    }
+}
@@ -0,0 +1,186 @@
+//! `run --record-audio`: what the speakers play, written to a WAV file as
+//! it happens.
+//!
+//! The buzzer and MegaChip's digitized sound are rendered again here
+//! rather than captured from the audio device, following the same on/off
+//! and play/stop changes the frontend makes. Audio is written once per
+//! rendered frame for however long the frame took, so the file runs in
+//! real time, pauses included. The header is brought up to date after
+//! every write, so the file stays playable if the app is killed.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+use chip8_core::megachip::SoundEvent;
+
+use crate::{
+    buzzer::{tone_samples, wav_header, Tone, SAMPLE_RATE},
+    digitized_sound::to_pcm,
+};
+
+/// A digitized sound partway through playing.
+struct Playback {
+    samples: Vec<i16>,
+    /// where in `samples` the next output sample comes from
+    position: f64,
+    /// how far `position` moves per output sample
+    step: f64,
+    looping: bool,
+}
+
+pub struct AudioCapture<W: Write + Seek> {
+    writer: W,
+    /// bytes of samples written so far
+    data_len: u32,
+    /// one second of the buzzer's tone, looped
+    tone: Vec<i16>,
+    tone_position: usize,
+    playback: Option<Playback>,
+    /// fractions of a sample owed to or from earlier frames
+    remainder: f64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AudioCapture<std::fs::File> {
+    pub fn create(path: &str, tone: Tone) -> io::Result<Self> {
+        Self::new(std::fs::File::create(path)?, tone)
+    }
+}
+
+impl<W: Write + Seek> AudioCapture<W> {
+    pub fn new(mut writer: W, tone: Tone) -> io::Result<Self> {
+        writer.write_all(&wav_header(SAMPLE_RATE, 0))?;
+        Ok(AudioCapture {
+            writer,
+            data_len: 0,
+            tone: tone_samples(tone),
+            tone_position: 0,
+            playback: None,
+            remainder: 0.,
+        })
+    }
+
+    /// switch to the buzzer's new tone, as `Buzzer::set_tone` does
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.tone = tone_samples(tone);
+        self.tone_position = 0;
+    }
+
+    /// start or stop a digitized sound, as `DigitizedPlayer::handle` does
+    pub fn handle(&mut self, event: &SoundEvent) {
+        self.playback = match event {
+            SoundEvent::Play(sound) => Some(Playback {
+                samples: to_pcm(&sound.samples),
+                position: 0.,
+                step: f64::from(sound.sample_rate) / f64::from(SAMPLE_RATE),
+                looping: sound.looping,
+            }),
+            SoundEvent::Stop => None,
+        };
+    }
+
+    /// Write `seconds` of audio, with the buzzer sounding if `buzzer` is
+    /// set. The buzzer keeps its place in the tone while muted, as the
+    /// real one does.
+    pub fn push(&mut self, seconds: f32, buzzer: bool) -> io::Result<()> {
+        let due = f64::from(seconds) * f64::from(SAMPLE_RATE) + self.remainder;
+        // rounded, so frame times that are nearly whole samples don't lose one
+        let count = due.round().max(0.) as usize;
+        self.remainder = due - count as f64;
+
+        let mut bytes = Vec::with_capacity(count * 2);
+        for _ in 0..count {
+            let tone = if buzzer {
+                self.tone[self.tone_position]
+            } else {
+                0
+            };
+            self.tone_position = (self.tone_position + 1) % self.tone.len();
+            let sample = tone.saturating_add(self.next_digitized());
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.writer.write_all(&bytes)?;
+        self.data_len += bytes.len() as u32;
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer
+            .write_all(&wav_header(SAMPLE_RATE, self.data_len))?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()
+    }
+
+    /// the digitized sound's next sample, or silence once it's over
+    fn next_digitized(&mut self) -> i16 {
+        let Some(playback) = &mut self.playback else {
+            return 0;
+        };
+        if playback.position as usize >= playback.samples.len() {
+            if !playback.looping || playback.samples.is_empty() {
+                self.playback = None;
+                return 0;
+            }
+            playback.position %= playback.samples.len() as f64;
+        }
+        let sample = playback.samples[playback.position as usize];
+        playback.position += playback.step;
+        sample
+    }
+
+    #[cfg(test)]
+    fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chip8_core::megachip::DigitizedSound;
+
+    use super::*;
+    use crate::buzzer::Waveform;
+
+    fn samples(wav: &[u8]) -> Vec<i16> {
+        wav[44..]
+            .chunks(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_buzzer_is_written_in_real_time() -> io::Result<()> {
+        let tone = Tone {
+            waveform: Waveform::Square,
+            frequency: 441,
+        };
+        let mut capture = AudioCapture::new(Cursor::new(vec![]), tone)?;
+        // a hundredth of a second silent, then two sounding
+        capture.push(0.01, false)?;
+        capture.push(0.02, true)?;
+        let wav = capture.into_inner().into_inner();
+
+        assert_eq!(wav[..44], wav_header(SAMPLE_RATE, 2 * 1323));
+        let samples = samples(&wav);
+        assert_eq!(samples.len(), 1323);
+        assert!(samples[..441].iter().all(|sample| *sample == 0));
+        // the tone carried on from where it was while muted
+        assert_eq!(samples[441..], tone_samples(tone)[441..1323]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_digitized_sound_is_mixed_in() -> io::Result<()> {
+        let mut capture = AudioCapture::new(Cursor::new(vec![]), Tone::default())?;
+        // at half the output rate, each sample plays twice
+        capture.handle(&SoundEvent::Play(DigitizedSound {
+            sample_rate: (SAMPLE_RATE / 2) as u16,
+            samples: vec![0xFF, 0x00],
+            looping: false,
+        }));
+        capture.push(6. / SAMPLE_RATE as f32, false)?;
+        let wav = capture.into_inner().into_inner();
+        assert_eq!(samples(&wav), [0x7F00, 0x7F00, -0x8000, -0x8000, 0, 0]);
+        Ok(())
+    }
+}
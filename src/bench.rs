@@ -0,0 +1,80 @@
+//! `bench`: run a ROM flat out with nothing drawn, to measure how fast the
+//! core interprets it. The timers still tick once for every frame's worth
+//! of instructions, so ROMs take the same paths they would in a real run.
+
+use std::time::{Duration, Instant};
+
+use chip8_core::{Chip8Error, Interpreter, StepOutcome};
+
+use crate::profiler::Profiler;
+
+/// How long a run took.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchResult {
+    pub instructions: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn instructions_per_sec(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Execute `instructions` instructions, ticking the timers after every
+/// `per_tick` of them or as soon as a DRW waits for one, and time the lot.
+/// With a `profiler`, each instruction is also timed on its own, which
+/// slows the run down.
+pub fn run(
+    interpreter: &mut Interpreter,
+    instructions: u64,
+    per_tick: u64,
+    mut profiler: Option<&mut Profiler>,
+) -> Result<BenchResult, Chip8Error> {
+    let started = Instant::now();
+    for executed in 0..instructions {
+        if executed % per_tick.max(1) == 0 || interpreter.waiting_for_vblank() {
+            interpreter.tick_60hz();
+        }
+        let Some(profiler) = profiler.as_deref_mut() else {
+            interpreter.step()?;
+            continue;
+        };
+        let pc = interpreter.program_counter();
+        let word = interpreter.next_instruction();
+        let step_started = Instant::now();
+        let outcome = interpreter.step()?;
+        let elapsed = step_started.elapsed();
+        if let Some(word) = word.filter(|_| !matches!(outcome, StepOutcome::Breakpoint(_))) {
+            profiler.record(pc, word, elapsed);
+        }
+    }
+    Ok(BenchResult {
+        instructions,
+        elapsed: started.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::Quirks;
+
+    use super::*;
+
+    #[test]
+    fn test_run_counts_only_executed_instructions() -> Result<(), Chip8Error> {
+        // DRW V0, V0, 5; JP 0x200, with DRW waiting for the next tick
+        let mut interpreter = Interpreter::with_quirks(Quirks::COSMAC_VIP);
+        interpreter.load_program(&[0xD0, 0x05, 0x12, 0x00])?;
+        let mut profiler = Profiler::new();
+        let result = run(&mut interpreter, 1000, 15, Some(&mut profiler))?;
+        assert_eq!(result.instructions, 1000);
+        assert!(profiler.report().starts_with("1000 instructions executed"));
+
+        // an invalid opcode
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x5A, 0xB1])?;
+        assert!(run(&mut interpreter, 10, 15, None).is_err());
+        Ok(())
+    }
+}
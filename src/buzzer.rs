@@ -0,0 +1,238 @@
+//! The CHIP-8 buzzer: a tone that sounds while the sound timer is
+//! non-zero. It's a square wave at 440 Hz unless the config or the command
+//! line picks another waveform or pitch, for everyone or for one game.
+//!
+//! The tone is generated at startup rather than loaded from an asset, so it
+//! works the same natively and on the web. It plays on a loop the whole time
+//! and is muted and unmuted, which avoids the latency of starting a new sound
+//! every time the timer is set.
+
+use macroquad::audio::{
+    load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound,
+};
+use serde::{Deserialize, Serialize};
+
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// a little quieter than full scale, since square waves are harsh
+const AMPLITUDE: f32 = 0.25;
+
+pub const DEFAULT_FREQUENCY: u32 = 440;
+
+/// The shape of the buzzer's tone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    /// the classic harsh beep
+    #[default]
+    Square,
+    Triangle,
+    /// the softest
+    Sine,
+    /// white noise, with a new level every half period
+    Noise,
+}
+
+/// What the buzzer sounds like. Frequencies are clamped to what 44.1 kHz
+/// audio can play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tone {
+    pub waveform: Waveform,
+    /// in Hz
+    pub frequency: u32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            waveform: Waveform::default(),
+            frequency: DEFAULT_FREQUENCY,
+        }
+    }
+}
+
+pub struct Buzzer {
+    sound: Sound,
+    tone: Tone,
+    on: bool,
+}
+
+impl Buzzer {
+    pub async fn new(tone: Tone) -> Result<Self, macroquad::file::FileError> {
+        let sound = start_tone(tone).await?;
+        Ok(Buzzer {
+            sound,
+            tone,
+            on: false,
+        })
+    }
+
+    pub fn set_on(&mut self, on: bool) {
+        self.on = on;
+        set_sound_volume(self.sound, if on { 1. } else { 0. });
+    }
+
+    /// switch to another tone, e.g. for a newly opened ROM's, keeping the buzzer on or off
+    pub async fn set_tone(&mut self, tone: Tone) -> Result<(), macroquad::file::FileError> {
+        if tone == self.tone {
+            return Ok(());
+        }
+        let sound = start_tone(tone).await?;
+        stop_sound(self.sound);
+        self.sound = sound;
+        self.tone = tone;
+        self.set_on(self.on);
+        Ok(())
+    }
+}
+
+/// `tone` playing on a loop, muted
+async fn start_tone(tone: Tone) -> Result<Sound, macroquad::file::FileError> {
+    let sound = load_sound_from_bytes(&tone_wav(tone)).await?;
+    play_sound(
+        sound,
+        PlaySoundParams {
+            looped: true,
+            volume: 0.,
+        },
+    );
+    Ok(sound)
+}
+
+/// one second of `tone` as 16-bit mono PCM in a WAV container
+fn tone_wav(tone: Tone) -> Vec<u8> {
+    pcm_wav(SAMPLE_RATE, &tone_samples(tone))
+}
+
+/// One second of `tone` at `SAMPLE_RATE` as 16-bit samples. A whole
+/// second holds a whole number of periods, so it loops without a click.
+pub fn tone_samples(tone: Tone) -> Vec<i16> {
+    let frequency = u64::from(tone.frequency.clamp(1, SAMPLE_RATE / 2));
+    let peak = AMPLITUDE * i16::MAX as f32;
+    // a fixed xorshift sequence, so noise sounds the same every run
+    let mut noise_state: u32 = 0x2545_F491;
+    let mut noise_level = 0.;
+    let mut noise_half_period = None;
+    (0..u64::from(SAMPLE_RATE))
+        .map(|i| {
+            // how far sample i is into its period, from 0 to 1
+            let phase = (i * frequency % u64::from(SAMPLE_RATE)) as f32 / SAMPLE_RATE as f32;
+            let level = match tone.waveform {
+                Waveform::Square if phase < 0.5 => 1.,
+                Waveform::Square => -1.,
+                Waveform::Triangle => 1. - 4. * (phase - 0.5).abs(),
+                Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+                Waveform::Noise => {
+                    let half_period = 2 * i * frequency / u64::from(SAMPLE_RATE);
+                    if noise_half_period != Some(half_period) {
+                        noise_half_period = Some(half_period);
+                        noise_state ^= noise_state << 13;
+                        noise_state ^= noise_state >> 17;
+                        noise_state ^= noise_state << 5;
+                        noise_level = noise_state as f32 / u32::MAX as f32 * 2. - 1.;
+                    }
+                    noise_level
+                }
+            };
+            (level * peak) as i16
+        })
+        .collect()
+}
+
+/// `samples` as 16-bit mono PCM at `sample_rate` in a WAV container
+pub fn pcm_wav(sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+    let mut wav = wav_header(sample_rate, samples.len() as u32 * 2);
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// the 44 bytes before `data_len` bytes of 16-bit mono PCM at `sample_rate` in a WAV file
+pub fn wav_header(sample_rate: u32, data_len: u32) -> Vec<u8> {
+    let mut wav = Vec::with_capacity(44);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16_u32.to_le_bytes());
+    wav.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1_u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // bytes per second
+    wav.extend_from_slice(&2_u16.to_le_bytes()); // bytes per sample
+    wav.extend_from_slice(&16_u16.to_le_bytes()); // bits per sample
+
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(wav: &[u8]) -> Vec<i16> {
+        wav[44..]
+            .chunks(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_square_wave_wav() {
+        let wav = tone_wav(Tone {
+            frequency: 441,
+            ..Tone::default()
+        });
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(wav.len(), 44 + 2 * SAMPLE_RATE as usize);
+
+        let sample = samples(&wav);
+        // 441 Hz is exactly 100 samples per period
+        assert!(sample[0] > 0 && sample[49] > 0);
+        assert!(sample[50] < 0 && sample[99] < 0);
+        assert_eq!(sample[100], sample[0]);
+    }
+
+    #[test]
+    fn test_other_waveforms() {
+        let tone = |waveform| {
+            samples(&tone_wav(Tone {
+                waveform,
+                frequency: 441,
+            }))
+        };
+        let peak = (AMPLITUDE * i16::MAX as f32) as i16;
+
+        let triangle = tone(Waveform::Triangle);
+        assert_eq!((triangle[0], triangle[50]), (-peak, peak));
+        assert!(triangle[25].abs() < 100);
+        let sine = tone(Waveform::Sine);
+        assert!(sine[0].abs() < 100 && sine[50].abs() < 100);
+        assert!(sine[25] > peak - 100 && sine[75] < -peak + 100);
+
+        // noise holds each level for half a period
+        let noise = tone(Waveform::Noise);
+        assert!(noise[..50].iter().all(|sample| *sample == noise[0]));
+        assert_ne!(noise[50], noise[0]);
+        assert!(noise.iter().all(|sample| sample.abs() <= peak));
+    }
+
+    #[test]
+    fn test_frequency_is_clamped() {
+        assert_eq!(
+            tone_wav(Tone {
+                frequency: 100_000,
+                ..Tone::default()
+            }),
+            tone_wav(Tone {
+                frequency: SAMPLE_RATE / 2,
+                ..Tone::default()
+            })
+        );
+    }
+}
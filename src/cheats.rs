@@ -0,0 +1,225 @@
+//! Cheats: bytes of memory written back after every instruction, so the
+//! program can't change them, e.g. to freeze a lives counter. They come from
+//! `--cheat`, the config file, and the list `cheats` brings up while playing.
+
+use std::fmt;
+
+use macroquad::prelude::*;
+
+use chip8_core::Interpreter;
+
+use crate::{
+    poke_prompt::Edit,
+    ui_style::{FONT_SIZE, MARGIN, PANEL_BACKGROUND, ROW_HEIGHT},
+};
+
+const WIDTH: f32 = 320.;
+const HINT: &str = "Enter toggles, Delete removes, Escape closes";
+
+/// Bytes kept at an address while the cheat is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    /// Parse an address and the bytes to keep there, in hex as the state
+    /// editor takes them, e.g. `3F0=09` or `0x3F0=09 00`.
+    pub fn parse(text: &str) -> Result<Cheat, String> {
+        match Edit::parse(text)? {
+            Edit::Memory(addr, bytes) => Ok(Cheat {
+                addr,
+                bytes,
+                enabled: true,
+            }),
+            _ => Err(String::from("a cheat keeps memory as it is, e.g. 3F0=09")),
+        }
+    }
+
+    pub fn apply(&self, interpreter: &mut Interpreter) {
+        if self.enabled {
+            Edit::Memory(self.addr, self.bytes.clone()).apply(interpreter);
+        }
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05x}=", self.addr)?;
+        for (idx, byte) in self.bytes.iter().enumerate() {
+            let space = if idx == 0 { "" } else { " " };
+            write!(f, "{}{:02X}", space, byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// The running ROM's cheats as a list to turn on and off, with a last row
+/// for typing a new one. The game is paused while it's open.
+pub struct CheatMenu {
+    /// a cheat's index, or one past the last for the new cheat row
+    cursor: usize,
+    /// the new cheat so far, once Enter is pressed on its row
+    typing: Option<String>,
+    error: Option<String>,
+}
+
+impl CheatMenu {
+    pub fn new() -> Self {
+        CheatMenu {
+            cursor: 0,
+            typing: None,
+            error: None,
+        }
+    }
+
+    /// Handle this frame's input. Returns true when the menu is closed.
+    pub fn update(&mut self, cheats: &mut Vec<Cheat>) -> bool {
+        if let Some(text) = &mut self.typing {
+            while let Some(c) = get_char_pressed() {
+                if !c.is_control() {
+                    text.push(c);
+                }
+            }
+            if is_key_pressed(KeyCode::Backspace) {
+                text.pop();
+            }
+        }
+        let keys = [
+            KeyCode::Up,
+            KeyCode::Down,
+            KeyCode::Enter,
+            KeyCode::Space,
+            KeyCode::Delete,
+            KeyCode::Escape,
+        ];
+        keys.into_iter()
+            .filter(|key| is_key_pressed(*key))
+            .any(|key| self.handle(key, cheats))
+    }
+
+    fn handle(&mut self, key: KeyCode, cheats: &mut Vec<Cheat>) -> bool {
+        if let Some(text) = &self.typing {
+            match key {
+                KeyCode::Escape => {
+                    self.typing = None;
+                    self.error = None;
+                }
+                KeyCode::Enter => match Cheat::parse(text) {
+                    Ok(cheat) => {
+                        cheats.push(cheat);
+                        self.cursor = cheats.len();
+                        self.typing = None;
+                        self.error = None;
+                    }
+                    Err(e) => self.error = Some(e),
+                },
+                _ => (),
+            }
+            return false;
+        }
+        let rows = cheats.len() + 1;
+        match key {
+            KeyCode::Up => self.cursor = (self.cursor + rows - 1) % rows,
+            KeyCode::Down => self.cursor = (self.cursor + 1) % rows,
+            KeyCode::Escape => return true,
+            KeyCode::Enter | KeyCode::Space => match cheats.get_mut(self.cursor) {
+                Some(cheat) => cheat.enabled = !cheat.enabled,
+                None if key == KeyCode::Enter => self.typing = Some(String::new()),
+                None => (),
+            },
+            KeyCode::Delete if self.cursor < cheats.len() => {
+                cheats.remove(self.cursor);
+            }
+            _ => (),
+        }
+        false
+    }
+
+    pub fn draw(&self, cheats: &[Cheat]) {
+        let left = MARGIN * 2.;
+        let top = MARGIN * 2. + FONT_SIZE;
+        let rows = cheats.len() + 2;
+        draw_rectangle(
+            left - MARGIN,
+            top - MARGIN - FONT_SIZE,
+            WIDTH + 2. * MARGIN,
+            rows as f32 * ROW_HEIGHT + 2. * MARGIN + FONT_SIZE,
+            PANEL_BACKGROUND,
+        );
+        draw_text("Cheats", left, top - MARGIN / 2., FONT_SIZE, WHITE);
+
+        let new_cheat = match &self.typing {
+            Some(text) => format!("New: {}_", text),
+            None => String::from("Add a cheat..."),
+        };
+        let labels = cheats
+            .iter()
+            .map(|cheat| format!("[{}] {}", if cheat.enabled { "x" } else { " " }, cheat))
+            .chain([new_cheat]);
+        for (idx, label) in labels.enumerate() {
+            let y = top + idx as f32 * ROW_HEIGHT;
+            let selected = idx == self.cursor;
+            if selected {
+                draw_rectangle(left - 4., y, WIDTH + 8., ROW_HEIGHT, DARKBLUE);
+            }
+            let color = if selected { WHITE } else { LIGHTGRAY };
+            draw_text(&label, left, y + ROW_HEIGHT - 7., FONT_SIZE, color);
+        }
+        let (note, color) = match &self.error {
+            Some(e) => (e.as_str(), ORANGE),
+            None if self.typing.is_some() => ("e.g. 3F0=09; Enter adds, Escape cancels", GRAY),
+            None => (HINT, GRAY),
+        };
+        let y = top + (rows - 1) as f32 * ROW_HEIGHT;
+        draw_text(note, left, y + ROW_HEIGHT - 7., FONT_SIZE * 0.8, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Cheat::parse("0x3F0=09 01"),
+            Ok(Cheat {
+                addr: 0x3F0,
+                bytes: vec![9, 1],
+                enabled: true
+            })
+        );
+        assert_eq!(Cheat::parse("3f0=9").unwrap().to_string(), "0x3f0=09");
+        assert!(Cheat::parse("V3=2A").is_err());
+        assert!(Cheat::parse("3F0").is_err());
+    }
+
+    #[test]
+    fn test_handle() {
+        let mut cheats = vec![Cheat::parse("300=01").unwrap()];
+        let mut menu = CheatMenu::new();
+        assert!(!menu.handle(KeyCode::Enter, &mut cheats));
+        assert!(!cheats[0].enabled);
+
+        // the new cheat row takes typing until Enter adds it
+        menu.handle(KeyCode::Down, &mut cheats);
+        menu.handle(KeyCode::Enter, &mut cheats);
+        menu.typing = Some(String::from("V0=1"));
+        menu.handle(KeyCode::Enter, &mut cheats);
+        assert!(menu.error.is_some());
+        menu.typing = Some(String::from("301=FF"));
+        menu.handle(KeyCode::Enter, &mut cheats);
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(menu.typing, None);
+
+        // Up goes from the new cheat row to the one just added
+        menu.handle(KeyCode::Up, &mut cheats);
+        menu.handle(KeyCode::Delete, &mut cheats);
+        assert_eq!(cheats.len(), 1);
+        assert_eq!(cheats[0].addr, 0x300);
+        assert!(menu.handle(KeyCode::Escape, &mut cheats));
+    }
+}
@@ -0,0 +1,730 @@
+use chip8_core::{interpreter::MEMORY_SIZE, Quirks, WatchKind, WatchTarget};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{buzzer::Waveform, cheats::Cheat, palette::Palette};
+
+/// A CHIP-8 interpreter and toolkit.
+#[derive(Debug, Parser)]
+#[command(name = "chip8-rust", version)]
+pub struct Cli {
+    /// what to do; without one, opens the ROM menu
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a ROM, or an Octo `.8o` source file, in the GUI
+    Run(RunOptions),
+    /// Run a ROM with the debugger open, paused before the first instruction
+    Debug(RunOptions),
+    /// Run a ROM without a window for a number of frames, then print the display or save a PNG of
+    /// it. Without a ROM, run the built-in test ROMs and report which checks pass
+    Test(TestOptions),
+    /// Run a ROM as fast as possible without a window, then report instructions per second and
+    /// the time taken by each kind of instruction
+    Bench(BenchOptions),
+    /// Print statistics about a ROM
+    Info { rom: String },
+    /// Print the memory map with the ROM loaded, as the interpreter sees it at boot
+    Hexdump { rom: String },
+    /// Print an annotated listing of a ROM
+    Disasm {
+        rom: String,
+        /// highlight mnemonics, operands, and labels
+        #[arg(long)]
+        color: bool,
+        /// describe what each instruction does
+        #[arg(long)]
+        explain: bool,
+        /// print Octo source, with labels and data as bytes, that `asm` builds back into the ROM
+        #[arg(long, conflicts_with_all = ["color", "explain", "start_addr"])]
+        octo: bool,
+        /// symbol file naming addresses; by default `<rom>.sym` if there is one
+        #[arg(long, value_name = "FILE", conflicts_with = "octo")]
+        symbols: Option<String>,
+        /// mark the instructions this coverage file says ran, dimming the rest with `--color`
+        #[arg(long, value_name = "FILE", conflicts_with = "octo")]
+        coverage: Option<String>,
+        /// where the ROM is loaded, in hex; 0x600 for ETI-660 programs
+        #[arg(long, value_name = "ADDR", value_parser = parse_start_addr)]
+        start_addr: Option<u16>,
+    },
+    /// Follow a ROM's control flow to map its code and data and flag what could go wrong
+    Analyze {
+        rom: String,
+        /// also write the basic blocks and the jumps, calls, and skips between them as a
+        /// Graphviz DOT file
+        #[arg(long, value_name = "FILE")]
+        cfg: Option<String>,
+        /// where the ROM is loaded and starts running, in hex
+        #[arg(long, value_name = "ADDR", value_parser = parse_start_addr)]
+        start_addr: Option<u16>,
+    },
+    /// Report how much of a ROM ran, from a file written by `run --coverage` or `test --coverage`
+    Coverage {
+        rom: String,
+        /// the coverage file
+        coverage: String,
+        /// where the ROM is loaded, in hex
+        #[arg(long, value_name = "ADDR", value_parser = parse_start_addr)]
+        start_addr: Option<u16>,
+    },
+    /// Apply IPS, BPS, or text patches to a ROM and write out the patched ROM
+    Patch {
+        rom: String,
+        /// patch files to apply, in order
+        #[arg(required = true)]
+        patches: Vec<String>,
+        /// where to write the ROM, by default next to it with `.patched` before the extension
+        #[arg(short, long)]
+        out: Option<String>,
+        /// where the ROM is loaded, in hex, which text patch addresses count from
+        #[arg(long, value_name = "ADDR", value_parser = parse_start_addr)]
+        start_addr: Option<u16>,
+    },
+    /// Assemble Octo-style source into a ROM, and a `.sym` file naming its labels
+    Asm {
+        source: String,
+        /// where to write the ROM, by default next to the source with a `.ch8` extension
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+}
+
+/// Options for running a ROM in the GUI.
+#[derive(Debug, Default, PartialEq, Eq, Args)]
+pub struct RunOptions {
+    /// ROM or `.8o` file to run; without one, opens the ROM menu
+    pub rom: Option<String>,
+    /// config file to use instead of the default location
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<String>,
+    /// named pipe, unix socket, or file to stream presented frames to
+    #[arg(long, value_name = "PATH")]
+    pub stream_frames: Option<String>,
+    /// write the buzzer and MegaChip sound to this WAV file as they play
+    #[arg(long, value_name = "FILE", conflicts_with = "tui")]
+    pub record_audio: Option<String>,
+    /// encode the session to this video file with ffmpeg, at 60 fps with sound
+    #[arg(long, value_name = "FILE", conflicts_with = "tui")]
+    pub record_video: Option<String>,
+    /// write the keys pressed in each frame to this movie file on exit, to play back with `test --movie`
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["tui", "octo_state"])]
+    pub record_movie: Option<String>,
+    /// accept WebSocket connections on this localhost port to inspect and control the interpreter
+    #[arg(long, value_name = "PORT")]
+    pub debug_server: Option<u16>,
+    /// run the ROM side by side with these quirks as well, stopping where the two first differ
+    #[arg(
+        long,
+        value_name = "QUIRKS",
+        value_parser = parse_quirks,
+        requires = "rom",
+        conflicts_with_all = ["tui", "host", "connect", "megachip"]
+    )]
+    pub compare: Option<Quirks>,
+    /// wait on this port for a second player to join with `--connect`
+    #[arg(long, value_name = "PORT", requires = "rom", conflicts_with_all = ["connect", "tui"])]
+    pub host: Option<u16>,
+    /// join a game hosted with `--host`, at `HOST:PORT`; both players must run the same ROM and speed
+    #[arg(long, value_name = "ADDR", requires = "rom", conflicts_with = "tui")]
+    pub connect: Option<String>,
+    /// emulation speed in instructions per second
+    #[arg(long, value_parser = parse_ips)]
+    pub ips: Option<u32>,
+    /// give each instruction roughly the time it took on the COSMAC VIP, instead of running at a flat rate
+    #[arg(long, conflicts_with = "ips")]
+    pub vip_timing: bool,
+    /// interpreter variant to emulate: vip, schip, or amiga
+    #[arg(long, value_parser = parse_quirks)]
+    pub quirks: Option<Quirks>,
+    /// wrap sprites around the screen edges instead of clipping them, for ROMs that rely on it
+    #[arg(long)]
+    pub wrap_sprites: bool,
+    /// stop on FX33, FX55, FX65, or DXYN reaching past the end of memory, instead of wrapping around
+    #[arg(long)]
+    pub strict_memory: bool,
+    /// where to load the ROM and start running it, in hex; 0x600 for ETI-660 programs
+    #[arg(long, value_name = "ADDR", value_parser = parse_start_addr)]
+    pub start_addr: Option<u16>,
+    /// understand MegaChip instructions and show its 256x192 color display; always on for `.mc8` files
+    #[arg(long)]
+    pub megachip: bool,
+    /// ignore the built-in database of known ROMs' quirks, speed, and controls
+    #[arg(long)]
+    pub no_rom_db: bool,
+    /// seed for the random numbers RND gives, which otherwise follow the same sequence every run
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// display colors: default, green, amber, bw, or octo
+    #[arg(long, value_parser = parse_palette)]
+    pub palette: Option<Palette>,
+    /// shape of the buzzer's tone, instead of the config's
+    #[arg(long, value_enum)]
+    pub waveform: Option<Waveform>,
+    /// pitch of the buzzer in Hz, instead of the config's
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(20..=20_000))]
+    pub frequency: Option<u32>,
+    /// run in the terminal instead of opening a window
+    #[arg(long)]
+    pub tui: bool,
+    /// how the terminal frontend draws pixels
+    #[arg(long, value_enum, default_value_t, requires = "tui")]
+    pub tui_renderer: TuiRenderer,
+    /// initial window pixels per CHIP-8 pixel; the display scales to fit when resized
+    #[arg(long, value_parser = parse_scale)]
+    pub scale: Option<u32>,
+    /// present the display at each 60 Hz tick to reduce flicker
+    #[arg(long)]
+    pub double_buffer: bool,
+    /// skip invalid opcodes instead of pausing on them
+    #[arg(long)]
+    pub lenient: bool,
+    /// stop with a diagnostic on SYS, writes below 0x200, or undocumented opcodes, which other interpreters may not run the same
+    #[arg(long)]
+    pub strict: bool,
+    /// count and time every instruction, and print the busiest ones on exit
+    #[arg(long)]
+    pub profile: bool,
+    /// how many recently executed instructions the debugger shows when it stops, 0 for none
+    #[arg(long, value_name = "N")]
+    pub history: Option<usize>,
+    /// address to pause at before executing, in hex
+    #[arg(long = "break", value_name = "ADDR", value_parser = parse_addr)]
+    pub breakpoints: Vec<u16>,
+    /// register or address to pause at when accessed: `V3`, `0x300`, optionally with `:r`, `:w`, or `:rw`
+    #[arg(long = "watch", value_name = "TARGET", value_parser = parse_watchpoint)]
+    pub watchpoints: Vec<(WatchTarget, WatchKind)>,
+    /// memory to keep as it is after every instruction, as `ADDR=BYTES` in hex, e.g. `3F0=09` to freeze a counter
+    #[arg(long = "cheat", value_name = "ADDR=BYTES", value_parser = Cheat::parse, requires = "rom")]
+    pub cheats: Vec<Cheat>,
+    /// patch file to apply to the ROM before it runs, in order
+    #[arg(long = "patch", value_name = "FILE")]
+    pub patches: Vec<String>,
+    /// write which bytes ran as instructions to this file on quit, for `coverage` and `disasm --coverage`
+    #[arg(long, value_name = "FILE", requires = "rom")]
+    pub coverage: Option<String>,
+    /// symbol file naming addresses in the debugger; by default `<rom>.sym` if there is one
+    #[arg(long, value_name = "FILE", requires = "rom")]
+    pub symbols: Option<String>,
+    /// Octo-compatible JSON state to resume from
+    #[arg(long, value_name = "FILE")]
+    pub octo_state: Option<String>,
+}
+
+/// How `run --tui` draws the display.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TuiRenderer {
+    /// half blocks, two pixels per character in a 64x16 area
+    #[default]
+    HalfBlock,
+    /// braille dots, 2x4 pixels per character in a 32x8 area
+    Braille,
+}
+
+/// Options for running a ROM headlessly.
+#[derive(Debug, PartialEq, Eq, Args)]
+pub struct TestOptions {
+    pub rom: Option<String>,
+    /// how many 60 Hz frames to run
+    #[arg(long, default_value_t = 600)]
+    pub frames: u32,
+    /// emulation speed in instructions per second
+    #[arg(long, value_parser = parse_ips)]
+    pub ips: Option<u32>,
+    /// give each instruction roughly the time it took on the COSMAC VIP, instead of running at a flat rate
+    #[arg(long, conflicts_with = "ips")]
+    pub vip_timing: bool,
+    /// interpreter variant to emulate: vip, schip, or amiga
+    #[arg(long, value_parser = parse_quirks)]
+    pub quirks: Option<Quirks>,
+    /// wrap sprites around the screen edges instead of clipping them, for ROMs that rely on it
+    #[arg(long)]
+    pub wrap_sprites: bool,
+    /// stop on FX33, FX55, FX65, or DXYN reaching past the end of memory, instead of wrapping around
+    #[arg(long)]
+    pub strict_memory: bool,
+    /// stop with a diagnostic on SYS, writes below 0x200, or undocumented opcodes, which other interpreters may not run the same
+    #[arg(long)]
+    pub strict: bool,
+    /// where to load the ROM and start running it, in hex; 0x600 for ETI-660 programs
+    #[arg(long, value_name = "ADDR", value_parser = parse_start_addr)]
+    pub start_addr: Option<u16>,
+    /// ignore the built-in database of known ROMs' quirks and speed
+    #[arg(long)]
+    pub no_rom_db: bool,
+    /// seed for the random numbers RND gives, which otherwise follow the same sequence every run
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// run this many frames instead, then write a timestamped PNG of the display
+    #[arg(long, value_name = "N", conflicts_with = "frames", requires = "rom")]
+    pub screenshot_after: Option<u32>,
+    /// play the keys in this movie instead of running `--frames`, stopping where the state stops matching it
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["frames", "screenshot_after"], requires = "rom")]
+    pub movie: Option<String>,
+    /// colors for the screenshot: default, green, amber, bw, or octo
+    #[arg(long, value_parser = parse_palette, requires = "screenshot_after")]
+    pub palette: Option<Palette>,
+    /// count and time every instruction, and print the busiest ones afterwards
+    #[arg(long, requires = "rom")]
+    pub profile: bool,
+    /// write which bytes ran as instructions to this file, and print how much of the ROM ran
+    #[arg(long, value_name = "FILE", requires = "rom")]
+    pub coverage: Option<String>,
+}
+
+/// Options for benchmarking the interpreter on a ROM.
+#[derive(Debug, PartialEq, Eq, Args)]
+pub struct BenchOptions {
+    pub rom: String,
+    /// how many million instructions to run
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub millions: u64,
+    /// interpreter variant to emulate: vip, schip, or amiga
+    #[arg(long, value_parser = parse_quirks)]
+    pub quirks: Option<Quirks>,
+    /// ignore the built-in database of known ROMs' quirks and speed
+    #[arg(long)]
+    pub no_rom_db: bool,
+    /// skip the second run that times each instruction
+    #[arg(long)]
+    pub no_profile: bool,
+}
+
+/// a hex address, with or without a `0x` prefix
+fn parse_addr(s: &str) -> Result<u16, String> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid address {}", s))
+}
+
+/// an address for a program to start at, which has to be within memory
+fn parse_start_addr(s: &str) -> Result<u16, String> {
+    let addr = parse_addr(s)?;
+    if addr as usize >= MEMORY_SIZE {
+        return Err(format!("{} is past the end of memory", s));
+    }
+    Ok(addr)
+}
+
+/// `V3`, `0x300`, or either followed by `:r`, `:w` (the default), or `:rw`
+fn parse_watchpoint(s: &str) -> Result<(WatchTarget, WatchKind), String> {
+    let (target, kind) = s.split_once(':').unwrap_or((s, "w"));
+    let kind = match kind {
+        "r" => WatchKind::Read,
+        "w" => WatchKind::Write,
+        "rw" => WatchKind::ReadWrite,
+        _ => {
+            return Err(format!(
+                "invalid watchpoint access {} (expected r, w, or rw)",
+                kind
+            ))
+        }
+    };
+    let target = match target.strip_prefix(['V', 'v']) {
+        Some(x) => match u8::from_str_radix(x, 16) {
+            Ok(x) if x < 16 => WatchTarget::Register(x),
+            _ => return Err(format!("invalid register {}", target)),
+        },
+        None => WatchTarget::Memory(parse_addr(target)?),
+    };
+    Ok((target, kind))
+}
+
+fn parse_ips(s: &str) -> Result<u32, String> {
+    match s.parse() {
+        Ok(ips) if ips > 0 => Ok(ips),
+        _ => Err(format!("invalid instructions per second {}", s)),
+    }
+}
+
+fn parse_scale(s: &str) -> Result<u32, String> {
+    match s.parse() {
+        Ok(scale) if scale > 0 => Ok(scale),
+        _ => Err(format!("invalid scale {}", s)),
+    }
+}
+
+fn parse_palette(name: &str) -> Result<Palette, String> {
+    Palette::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = Palette::PRESETS.iter().map(|p| p.name).collect();
+        format!(
+            "unknown palette {} (expected one of: {})",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+fn parse_quirks(name: &str) -> Result<Quirks, String> {
+    Quirks::from_name(name).ok_or_else(|| {
+        let presets: Vec<&str> = Quirks::PRESETS.iter().map(|(name, _)| *name).collect();
+        format!(
+            "unknown quirks preset {} (expected one of: {})",
+            name,
+            presets.join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// parse `chip8-rust run <args>`
+    fn parse_run_options(args: &str) -> Option<RunOptions> {
+        let args = ["chip8-rust", "run"]
+            .into_iter()
+            .chain(args.split_whitespace());
+        match Cli::try_parse_from(args).ok()?.command {
+            Some(Command::Run(options)) => Some(options),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_parse_run_options() {
+        assert_eq!(parse_run_options(""), Some(RunOptions::default()));
+        assert_eq!(
+            parse_run_options("--patch a.ips PONG --patch b.txt"),
+            Some(RunOptions {
+                rom: Some(String::from("PONG")),
+                patches: vec![String::from("a.ips"), String::from("b.txt")],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("PONG --symbols pong.sym"),
+            Some(RunOptions {
+                rom: Some(String::from("PONG")),
+                symbols: Some(String::from("pong.sym")),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_run_options("--symbols pong.sym"), None);
+        assert_eq!(
+            parse_run_options("BLITZ --cheat 3F0=09 --cheat 0x31A=00"),
+            Some(RunOptions {
+                rom: Some(String::from("BLITZ")),
+                cheats: vec![
+                    Cheat::parse("3F0=09").unwrap(),
+                    Cheat::parse("31A=00").unwrap()
+                ],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--quirks schip"),
+            Some(RunOptions {
+                quirks: Some(Quirks::SUPER_CHIP),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--break 0x2a0 --break 300"),
+            Some(RunOptions {
+                breakpoints: vec![0x2A0, 0x300],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--watch VF --watch 0x300:rw"),
+            Some(RunOptions {
+                watchpoints: vec![
+                    (WatchTarget::Register(0xF), WatchKind::Write),
+                    (WatchTarget::Memory(0x300), WatchKind::ReadWrite),
+                ],
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--palette green"),
+            Some(RunOptions {
+                palette: Palette::from_name("green"),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--ips 1000"),
+            Some(RunOptions {
+                ips: Some(1000),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--seed 42"),
+            Some(RunOptions {
+                seed: Some(42),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--double-buffer"),
+            Some(RunOptions {
+                double_buffer: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--quirks schip --wrap-sprites"),
+            Some(RunOptions {
+                quirks: Quirks::from_name("schip"),
+                wrap_sprites: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--strict-memory"),
+            Some(RunOptions {
+                strict_memory: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--tui PONG"),
+            Some(RunOptions {
+                rom: Some(String::from("PONG")),
+                tui: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--tui --tui-renderer braille"),
+            Some(RunOptions {
+                tui: true,
+                tui_renderer: TuiRenderer::Braille,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--lenient"),
+            Some(RunOptions {
+                lenient: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--strict --lenient"),
+            Some(RunOptions {
+                strict: true,
+                lenient: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--vip-timing"),
+            Some(RunOptions {
+                vip_timing: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--start-addr 0x600"),
+            Some(RunOptions {
+                start_addr: Some(0x600),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--megachip"),
+            Some(RunOptions {
+                megachip: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--waveform triangle --frequency 220"),
+            Some(RunOptions {
+                waveform: Some(Waveform::Triangle),
+                frequency: Some(220),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--record-audio run.wav"),
+            Some(RunOptions {
+                record_audio: Some(String::from("run.wav")),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--record-video run.mp4"),
+            Some(RunOptions {
+                record_video: Some(String::from("run.mp4")),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--history 100"),
+            Some(RunOptions {
+                history: Some(100),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--profile"),
+            Some(RunOptions {
+                profile: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("--stream-frames /tmp/frames PONG"),
+            Some(RunOptions {
+                rom: Some(String::from("PONG")),
+                stream_frames: Some(String::from("/tmp/frames")),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("PONG --compare schip"),
+            Some(RunOptions {
+                rom: Some(String::from("PONG")),
+                compare: Some(Quirks::SUPER_CHIP),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_run_options("PONG --connect 192.168.1.2:7000"),
+            Some(RunOptions {
+                rom: Some(String::from("PONG")),
+                connect: Some(String::from("192.168.1.2:7000")),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_run_options_errors() {
+        assert!(parse_run_options("--stream-frames").is_none());
+        assert!(parse_run_options("--host 7000").is_none());
+        assert!(parse_run_options("PONG --compare eti660").is_none());
+        assert!(parse_run_options("PONG --compare vip --tui").is_none());
+        assert!(parse_run_options("PONG --host 7000 --connect localhost:7000").is_none());
+        assert!(parse_run_options("--bogus PONG").is_none());
+        assert!(parse_run_options("--tui-renderer braille").is_none());
+        assert!(parse_run_options("--tui --tui-renderer ascii").is_none());
+        assert!(parse_run_options("PONG TETRIS").is_none());
+        assert!(parse_run_options("--quirks eti660").is_none());
+        assert!(parse_run_options("--break here").is_none());
+        assert!(parse_run_options("--watch V10").is_none());
+        assert!(parse_run_options("--watch V1:x").is_none());
+        assert!(parse_run_options("PONG --cheat V3=2A").is_none());
+        assert!(parse_run_options("--ips 0").is_none());
+        assert!(parse_run_options("--ips fast").is_none());
+        assert!(parse_run_options("--ips 600 --vip-timing").is_none());
+        assert!(parse_run_options("--start-addr 0x1000").is_none());
+        assert!(parse_run_options("--scale 0").is_none());
+        assert!(parse_run_options("--palette plaid").is_none());
+    }
+
+    #[test]
+    fn test_subcommands() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+
+        assert!(Cli::try_parse_from(["chip8-rust"])
+            .unwrap()
+            .command
+            .is_none());
+        // a bare ROM is not a subcommand
+        assert!(Cli::try_parse_from(["chip8-rust", "PONG"]).is_err());
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "disasm", "PONG", "--explain"])
+                .unwrap()
+                .command,
+            Some(Command::Disasm {
+                color: false,
+                explain: true,
+                octo: false,
+                ..
+            })
+        ));
+        assert!(
+            Cli::try_parse_from(["chip8-rust", "disasm", "PONG", "--octo", "--color"]).is_err()
+        );
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "coverage", "PONG", "pong.cov"])
+                .unwrap()
+                .command,
+            Some(Command::Coverage { ref coverage, start_addr: None, .. }) if coverage == "pong.cov"
+        ));
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "test", "PONG", "--coverage", "pong.cov"])
+                .unwrap()
+                .command,
+            Some(Command::Test(TestOptions {
+                coverage: Some(_),
+                ..
+            }))
+        ));
+        assert!(matches!(
+            Cli::try_parse_from([
+                "chip8-rust",
+                "analyze",
+                "PONG",
+                "--start-addr",
+                "600",
+                "--cfg",
+                "pong.dot"
+            ])
+            .unwrap()
+            .command,
+            Some(Command::Analyze {
+                start_addr: Some(0x600),
+                cfg: Some(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "debug", "PONG", "--break", "0x200"]).unwrap().command,
+            Some(Command::Debug(RunOptions { ref breakpoints, .. })) if breakpoints == &[0x200]
+        ));
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "test", "PONG", "--screenshot-after", "60"])
+                .unwrap()
+                .command,
+            Some(Command::Test(TestOptions {
+                screenshot_after: Some(60),
+                palette: None,
+                ..
+            }))
+        ));
+        assert!(Cli::try_parse_from([
+            "chip8-rust",
+            "test",
+            "PONG",
+            "--frames",
+            "9",
+            "--screenshot-after",
+            "9"
+        ])
+        .is_err());
+        assert!(Cli::try_parse_from(["chip8-rust", "test", "PONG", "--palette", "bw"]).is_err());
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "test", "PONG", "--movie", "pong.movie"])
+                .unwrap()
+                .command,
+            Some(Command::Test(TestOptions { movie: Some(ref movie), .. })) if movie == "pong.movie"
+        ));
+        assert!(Cli::try_parse_from(["chip8-rust", "test", "--movie", "pong.movie"]).is_err());
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "bench", "PONG"])
+                .unwrap()
+                .command,
+            Some(Command::Bench(BenchOptions { millions: 10, .. }))
+        ));
+        assert!(Cli::try_parse_from(["chip8-rust", "bench", "PONG", "--millions", "0"]).is_err());
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "patch", "game.ch8", "fix.ips", "tr.bps", "-o", "out.ch8"])
+                .unwrap()
+                .command,
+            Some(Command::Patch { ref patches, out: Some(_), .. }) if patches.len() == 2
+        ));
+        assert!(Cli::try_parse_from(["chip8-rust", "patch", "game.ch8"]).is_err());
+        assert!(matches!(
+            Cli::try_parse_from(["chip8-rust", "test"]).unwrap().command,
+            Some(Command::Test(TestOptions { rom: None, .. }))
+        ));
+    }
+}
@@ -0,0 +1,305 @@
+use std::error::Error;
+
+use chip8_core::{
+    interpreter::{Interpreter, SCREEN_HEIGHT},
+    Quirks,
+};
+
+use crate::{
+    analyzer::Analysis,
+    assembler, bench,
+    cli::{BenchOptions, TestOptions},
+    coverage::Coverage,
+    disassembler, hexdump,
+    machine::{Chip8Machine, Inputs, DEFAULT_INSTRUCTIONS_PER_SECOND},
+    movie::Movie,
+    patch::Patch,
+    profiler::Profiler,
+    rom_db,
+    rom_info::{self, RomInfo},
+    screenshot, suite,
+    symbols::Symbols,
+    timing::TIMER_HZ,
+};
+
+/// read a ROM, assembling it first if it is `.8o` source
+pub fn read_rom_file(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if path.ends_with(".8o") {
+        Ok(assembler::assemble(&std::fs::read_to_string(path)?)
+            .map_err(|e| format!("{}: {}", path, e))?)
+    } else {
+        Ok(std::fs::read(path)?)
+    }
+}
+
+/// `info <rom>`: print statistics about a ROM without launching the GUI
+pub fn info(path: &str) -> Result<(), Box<dyn Error>> {
+    let rom = std::fs::read(path)?;
+    print!("{}", RomInfo::analyze(&rom));
+    Ok(())
+}
+
+/// `hexdump <rom>`: print the memory map with the ROM loaded, as the interpreter sees it at boot
+pub fn hexdump(path: &str) -> Result<(), Box<dyn Error>> {
+    let mut interpreter = Interpreter::new();
+    interpreter.read_program_from_file(path)?;
+    print!("{}", hexdump::dump_interpreter(&interpreter));
+    Ok(())
+}
+
+/// `disasm <rom> [--color] [--explain] [--symbols FILE] [--coverage FILE] [--start-addr ADDR]`:
+/// print an
+/// annotated listing of a ROM loaded at `start`
+pub fn disasm(
+    path: &str,
+    start: u16,
+    options: disassembler::RenderOptions,
+    symbols: Option<&String>,
+    coverage: Option<&String>,
+) -> Result<(), Box<dyn Error>> {
+    let rom = std::fs::read(path)?;
+    let symbols = match symbols.cloned().or_else(|| Symbols::default_path(path)) {
+        Some(symbols) => Symbols::read(&symbols)?,
+        None => Symbols::default(),
+    };
+    let mut listing = disassembler::disassemble(&rom, start)?.with_symbols(&symbols);
+    if let Some(coverage) = coverage {
+        listing = listing.with_coverage(read_coverage(coverage)?);
+    }
+    print!("{}", listing.render(options));
+    Ok(())
+}
+
+/// `disasm <rom> --octo`: print Octo source that `asm` assembles back into the ROM
+pub fn disasm_octo(path: &str) -> Result<(), Box<dyn Error>> {
+    let rom = std::fs::read(path)?;
+    print!("{}", disassembler::octo_source(&rom)?);
+    Ok(())
+}
+
+/// `analyze <rom> [--cfg out.dot] [--start-addr ADDR]`: map a ROM's code and data, following
+/// its control flow from `start`, and list what could trip up an interpreter
+pub fn analyze(path: &str, start: u16, cfg: Option<&String>) -> Result<(), Box<dyn Error>> {
+    let rom = std::fs::read(path)?;
+    let analysis = Analysis::new(&rom, start)?;
+    print!("{}", analysis);
+    if let Some(cfg) = cfg {
+        std::fs::write(cfg, analysis.to_dot())?;
+        println!("wrote the control-flow graph to {}", cfg);
+    }
+    Ok(())
+}
+
+fn read_coverage(path: &str) -> Result<Coverage, Box<dyn Error>> {
+    Ok(Coverage::parse(&std::fs::read_to_string(path)?).map_err(|e| format!("{}: {}", path, e))?)
+}
+
+/// `coverage <rom> <file> [--start-addr ADDR]`: report how much of a ROM a coverage file says ran
+pub fn coverage(path: &str, coverage: &str, start: u16) -> Result<(), Box<dyn Error>> {
+    let rom = std::fs::read(path)?;
+    print!("{}", read_coverage(coverage)?.report(&rom, start)?);
+    Ok(())
+}
+
+/// `asm <source.8o> [-o <rom.ch8>]`: assemble Octo-style source, by default next to the
+/// source, with its labels in a symbol file next to the ROM
+pub fn asm(source: &str, out: Option<&String>) -> Result<(), Box<dyn Error>> {
+    let (rom, symbols) = assembler::assemble_with_symbols(&std::fs::read_to_string(source)?)
+        .map_err(|e| format!("{}: {}", source, e))?;
+    let out = match out {
+        Some(out) => std::path::PathBuf::from(out),
+        None => std::path::Path::new(source).with_extension("ch8"),
+    };
+    std::fs::write(&out, &rom)?;
+    println!("wrote {} bytes to {}", rom.len(), out.display());
+    if !symbols.is_empty() {
+        let sym = out.with_extension("sym");
+        std::fs::write(&sym, symbols.to_text())?;
+        println!(
+            "wrote {} symbols to {}",
+            symbols.iter().count(),
+            sym.display()
+        );
+    }
+    Ok(())
+}
+
+/// `patch <rom> <patches>... [-o out] [--start-addr ADDR]`: apply patches to a ROM loaded at
+/// `start` and write the result
+pub fn patch(
+    rom: &str,
+    patches: &[String],
+    out: Option<&String>,
+    start: u16,
+) -> Result<(), Box<dyn Error>> {
+    let mut bytes = std::fs::read(rom)?;
+    for path in patches {
+        Patch::read(path, start)?
+            .apply(&mut bytes)
+            .map_err(|e| format!("could not apply {}: {}", path, e))?;
+    }
+    let out = match out {
+        Some(out) => std::path::PathBuf::from(out),
+        None => {
+            let path = std::path::Path::new(rom);
+            let extension = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("ch8");
+            path.with_extension(format!("patched.{}", extension))
+        }
+    };
+    std::fs::write(&out, &bytes)?;
+    println!("wrote {} bytes to {}", bytes.len(), out.display());
+    Ok(())
+}
+
+/// `test <rom>`: run a ROM without a window, then print the display it ends up showing, or
+/// with `--screenshot-after` save it as a PNG. Plain `test` runs the test suite instead.
+pub fn test(options: &TestOptions) -> Result<(), Box<dyn Error>> {
+    let Some(rom) = &options.rom else {
+        return test_suite();
+    };
+    let rom_bytes = read_rom_file(rom)?;
+    let profile = if options.no_rom_db {
+        None
+    } else {
+        rom_db::lookup(&rom_bytes)
+    };
+    let mut machine = Chip8Machine::new();
+    let quirks = options
+        .quirks
+        .or(profile.as_ref().map(|profile| profile.quirks))
+        .unwrap_or_else(|| rom_info::detect_variant(&rom_bytes).quirks());
+    machine.set_quirks(Quirks {
+        wrap_sprites: quirks.wrap_sprites || options.wrap_sprites,
+        memory_wraps: quirks.memory_wraps && !options.strict_memory,
+        ..quirks
+    });
+    if let Some(ips) = options.ips.or(profile
+        .as_ref()
+        .and_then(|profile| profile.instructions_per_second))
+    {
+        machine.set_instructions_per_second(ips);
+    }
+    if let Some(seed) = options.seed {
+        machine.set_seed(seed);
+    }
+    machine.set_profiling(options.profile);
+    if let Some(addr) = options.start_addr {
+        machine.set_program_start(addr);
+    }
+    machine.set_vip_timing(options.vip_timing);
+    machine.set_strict(options.strict);
+    machine.load(&rom_bytes)?;
+    let output = if let Some(path) = &options.movie {
+        let movie = Movie::parse(&std::fs::read_to_string(path)?)?;
+        if movie.rom_sha1 != rom_db::sha1(&rom_bytes) {
+            eprintln!("Warning: {} was recorded with a different ROM", path);
+        }
+        let output = movie.play(&mut machine)?;
+        println!("Played {} frames of {}", movie.frames.len(), path);
+        output
+    } else {
+        let frames = options.screenshot_after.unwrap_or(options.frames);
+        machine.frames(&Inputs::default(), frames)?
+    };
+    if options.screenshot_after.is_some() {
+        let png = screenshot::encode_png(
+            &output.rows,
+            output.height,
+            &options.palette.unwrap_or_default(),
+        )?;
+        let path = screenshot::file_name(std::time::SystemTime::now(), "png");
+        std::fs::write(&path, png)?;
+        println!("Wrote {}", path);
+    } else {
+        print!("{}", suite::format_screen(&output.rows, output.height));
+    }
+    if let Some(profiler) = machine.profiler() {
+        print!("\n{}", profiler.report());
+    }
+    if let Some(path) = &options.coverage {
+        std::fs::write(path, machine.coverage().to_text())?;
+        let start = machine.interpreter().program_start();
+        match machine.coverage().report(&rom_bytes, start) {
+            Ok(report) => print!("\n{}", report),
+            Err(e) => eprintln!("\nNo coverage report: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// `bench <rom> [--millions N]`: run a ROM flat out without a window and print how many
+/// instructions per second the interpreter managed, then run it again timing each instruction
+pub fn bench(options: &BenchOptions) -> Result<(), Box<dyn Error>> {
+    let rom = read_rom_file(&options.rom)?;
+    let profile = if options.no_rom_db {
+        None
+    } else {
+        rom_db::lookup(&rom)
+    };
+    let quirks = options
+        .quirks
+        .or(profile.as_ref().map(|profile| profile.quirks))
+        .unwrap_or_else(|| rom_info::detect_variant(&rom).quirks());
+    let ips = profile
+        .and_then(|profile| profile.instructions_per_second)
+        .unwrap_or(DEFAULT_INSTRUCTIONS_PER_SECOND);
+    let per_tick = (ips as f64 / TIMER_HZ).round() as u64;
+    let instructions = options.millions * 1_000_000;
+    let load = || -> Result<Interpreter, Box<dyn Error>> {
+        let mut interpreter = Interpreter::with_quirks(quirks);
+        interpreter.load_program(&rom)?;
+        Ok(interpreter)
+    };
+
+    let result = bench::run(&mut load()?, instructions, per_tick, None)?;
+    println!(
+        "{} instructions in {:.2?}: {:.2}M per second, {:.0}x the {} per second it usually runs at",
+        result.instructions,
+        result.elapsed,
+        result.instructions_per_sec() / 1e6,
+        result.instructions_per_sec() / ips as f64,
+        ips,
+    );
+    if !options.no_profile {
+        let mut profiler = Profiler::new();
+        let profiled = bench::run(&mut load()?, instructions, per_tick, Some(&mut profiler))?;
+        println!(
+            "\ntiming each instruction, which slowed the run to {:.2?}:",
+            profiled.elapsed
+        );
+        print!("{}", profiler.report());
+    }
+    Ok(())
+}
+
+/// run each ROM in the test suite, printing which checks pass, and the screen of any ROM
+/// with a failing check so its expected screen can be updated if the failure is intended
+fn test_suite() -> Result<(), Box<dyn Error>> {
+    let (mut passed, mut total) = (0, 0);
+    for rom in &suite::SUITE {
+        println!("{}", rom.name);
+        let screen = rom.run()?;
+        let results = rom.check(&screen)?;
+        for result in &results {
+            println!(
+                "  {}  {}",
+                if result.passed { "pass" } else { "FAIL" },
+                result.name
+            );
+        }
+        if results.iter().any(|result| !result.passed) {
+            print!("{}", suite::format_screen(&screen, SCREEN_HEIGHT));
+        }
+        total += results.len();
+        passed += results.iter().filter(|result| result.passed).count();
+    }
+
+    println!("{} of {} checks passed", passed, total);
+    if passed < total {
+        return Err(format!("{} checks failed", total - passed).into());
+    }
+    Ok(())
+}
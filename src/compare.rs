@@ -0,0 +1,325 @@
+//! `run --compare <quirks>`: run a ROM twice side by side, on the left
+//! with the quirks it would normally get and on the right with another
+//! preset, feeding both the same keys. After every frame the two states
+//! are hashed, and the first frame where they differ is reported and the
+//! run paused on it, with the pixels that differ outlined. This is how to
+//! find out which quirk a ROM that "works on emulator X" depends on.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use chip8_core::{
+    interpreter::{is_lit, Snapshot, SCREEN_WIDTH},
+    Quirks,
+};
+use macroquad::prelude::*;
+
+use crate::{
+    display::Display,
+    layout,
+    machine::{Chip8Machine, FrameOutput, Inputs},
+    palette::Palette,
+    ui_style::{FONT_SIZE, MARGIN},
+};
+
+/// room above the displays for their labels
+const HEADER: f32 = 36.;
+/// room below them for the status line
+const FOOTER: f32 = 36.;
+const DIVERGED: Color = Color::new(1., 0.2, 0.2, 1.);
+
+/// The first frame where the two runs' states stopped matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// counting from 1 for the first frame run
+    pub frame: u64,
+    /// the parts of the state that differ, e.g. "registers"
+    pub parts: Vec<&'static str>,
+}
+
+/// One side of the comparison.
+struct Side {
+    machine: Chip8Machine,
+    name: String,
+    /// why this side stopped running, if it has
+    error: Option<String>,
+}
+
+/// Two machines running the same ROM with different quirks, in step.
+pub struct Comparison {
+    sides: [Side; 2],
+    frame: u64,
+    divergence: Option<Divergence>,
+}
+
+impl Comparison {
+    /// Compare `left` and `right`, which should have the same ROM loaded.
+    pub fn new(left: Chip8Machine, right: Chip8Machine) -> Self {
+        let side = |machine: Chip8Machine| Side {
+            name: quirks_name(machine.interpreter().quirks()),
+            machine,
+            error: None,
+        };
+        Comparison {
+            sides: [side(left), side(right)],
+            frame: 0,
+            divergence: None,
+        }
+    }
+
+    /// Run up to `count` frames on both sides with `inputs`, stopping
+    /// early on the frame where they first diverge. A side whose ROM fails
+    /// stops there, which usually means a divergence too.
+    pub fn frames(&mut self, inputs: &Inputs, count: u32) -> [FrameOutput; 2] {
+        let mut outputs = self.idle_frame();
+        for _ in 0..count {
+            outputs = self.sides.each_mut().map(|side| {
+                if side.error.is_some() {
+                    return side.machine.idle_frame();
+                }
+                side.machine.frame(inputs).unwrap_or_else(|e| {
+                    side.error = Some(e.to_string());
+                    side.machine.idle_frame()
+                })
+            });
+            self.frame += 1;
+            if self.divergence.is_none() {
+                let [left, right] = &self.sides;
+                let (left, right) = (left.machine.snapshot(), right.machine.snapshot());
+                if state_hash(&left) != state_hash(&right) {
+                    self.divergence = Some(Divergence {
+                        frame: self.frame,
+                        parts: differences(&left, &right),
+                    });
+                    break;
+                }
+            }
+        }
+        outputs
+    }
+
+    /// both sides' current screens, without running anything
+    pub fn idle_frame(&mut self) -> [FrameOutput; 2] {
+        self.sides.each_mut().map(|side| side.machine.idle_frame())
+    }
+
+    pub fn divergence(&self) -> Option<&Divergence> {
+        self.divergence.as_ref()
+    }
+}
+
+/// the state as one number, equal for equal states
+fn state_hash(snapshot: &Snapshot) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    snapshot.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// which parts of the state differ between `a` and `b`
+fn differences(a: &Snapshot, b: &Snapshot) -> Vec<&'static str> {
+    let parts = [
+        ("screen", a.pixels != b.pixels || a.hires != b.hires),
+        ("registers", a.registers != b.registers),
+        ("I", a.index_register != b.index_register),
+        ("pc", a.program_counter != b.program_counter),
+        (
+            "stack",
+            a.stack_pointer != b.stack_pointer || a.stack != b.stack,
+        ),
+        ("memory", a.memory != b.memory),
+        (
+            "timers",
+            a.delay_timer != b.delay_timer || a.sound_timer != b.sound_timer,
+        ),
+        ("key wait", a.key_down_ld_vx_k != b.key_down_ld_vx_k),
+        ("RND", a.rng_state != b.rng_state),
+    ];
+    parts
+        .into_iter()
+        .filter(|(_, differs)| *differs)
+        .map(|(part, _)| part)
+        .collect()
+}
+
+/// the preset `quirks` match, ignoring sprite and memory wrapping, or "custom"
+fn quirks_name(quirks: Quirks) -> String {
+    let name = std::iter::once(("default", Quirks::default()))
+        .chain(Quirks::PRESETS)
+        .find(|(_, preset)| {
+            Quirks {
+                wrap_sprites: quirks.wrap_sprites,
+                memory_wraps: quirks.memory_wraps,
+                ..*preset
+            } == quirks
+        })
+        .map_or("custom", |(name, _)| name);
+    let mut name = format!("{} quirks", name);
+    if quirks.wrap_sprites {
+        name.push_str(", wrapping sprites");
+    }
+    if !quirks.memory_wraps {
+        name.push_str(", checking memory");
+    }
+    name
+}
+
+/// indices of the pixels shown differently in `a` and `b`
+fn differing_pixels(a: &FrameOutput, b: &FrameOutput) -> Vec<usize> {
+    let height = a.height.max(b.height);
+    (0..SCREEN_WIDTH * height)
+        .filter(|&idx| {
+            let (x, y) = (idx % SCREEN_WIDTH, idx / SCREEN_WIDTH);
+            is_lit(&a.rows, x, y) != is_lit(&b.rows, x, y)
+        })
+        .collect()
+}
+
+/// Draw both sides' `outputs` next to each other with their labels, the
+/// pixels that differ outlined, and a status line underneath.
+pub fn draw(
+    comparison: &Comparison,
+    displays: &mut [Display; 2],
+    outputs: &[FrameOutput; 2],
+    palette: &Palette,
+    paused: bool,
+) {
+    let half = screen_width() / 2.;
+    let differing = differing_pixels(&outputs[0], &outputs[1]);
+    for (idx, ((display, output), side)) in displays
+        .iter_mut()
+        .zip(outputs)
+        .zip(&comparison.sides)
+        .enumerate()
+    {
+        display.update(&output.rows, output.height, palette);
+        let area = layout::fit_display(
+            half - MARGIN * 2.,
+            screen_height() - HEADER - FOOTER,
+            display.size(),
+        );
+        let dest = Rect::new(
+            area.x + half * idx as f32 + MARGIN,
+            area.y + HEADER,
+            area.w,
+            area.h,
+        );
+        display.draw(dest);
+        let pixel_size = dest.w / SCREEN_WIDTH as f32;
+        for pixel in &differing {
+            draw_rectangle_lines(
+                dest.x + (pixel % SCREEN_WIDTH) as f32 * pixel_size,
+                dest.y + (pixel / SCREEN_WIDTH) as f32 * pixel_size,
+                pixel_size,
+                pixel_size,
+                2.,
+                DIVERGED,
+            );
+        }
+        let label = match &side.error {
+            Some(e) => format!("{}: stopped, {}", side.name, e),
+            None => side.name.clone(),
+        };
+        draw_text(&label, dest.x, dest.y - FONT_SIZE / 2., FONT_SIZE, WHITE);
+    }
+
+    let (status, color) = match &comparison.divergence {
+        Some(divergence) => (
+            format!(
+                "Diverged at frame {}: {} differ{}",
+                divergence.frame,
+                divergence.parts.join(", "),
+                if comparison.frame > divergence.frame {
+                    format!(" (now at frame {})", comparison.frame)
+                } else {
+                    String::new()
+                }
+            ),
+            DIVERGED,
+        ),
+        None => (format!("Frame {}, identical", comparison.frame), WHITE),
+    };
+    let status = if paused {
+        format!("{}; paused", status)
+    } else {
+        status
+    };
+    draw_text(
+        &status,
+        MARGIN,
+        screen_height() - FOOTER / 2.,
+        FONT_SIZE,
+        color,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a machine running `rom` with `quirks`
+    fn machine(rom: &[u8], quirks: Quirks) -> Chip8Machine {
+        let mut machine = Chip8Machine::new();
+        machine.set_quirks(quirks);
+        machine.load(rom).unwrap();
+        machine
+    }
+
+    #[test]
+    fn test_same_quirks_never_diverge() {
+        let pong = crate::library::embedded("PONG").unwrap();
+        let mut comparison = Comparison::new(
+            machine(pong, Quirks::COSMAC_VIP),
+            machine(pong, Quirks::COSMAC_VIP),
+        );
+        comparison.frames(&Inputs::default(), 120);
+        assert_eq!(comparison.frame, 120);
+        assert_eq!(comparison.divergence(), None);
+    }
+
+    #[test]
+    fn test_first_divergence_is_found() {
+        // LD V1, 3; LD V2, 0xFF, then wait two frames, then SHR V1, V2 and loop
+        let rom = [
+            0x61, 0x03, 0x62, 0xFF, 0x63, 0x02, 0xF3, 0x15, 0xF4, 0x07, 0x34, 0x00, 0x12, 0x08,
+            0x81, 0x26, 0x12, 0x10,
+        ];
+        let mut comparison = Comparison::new(
+            machine(&rom, Quirks::COSMAC_VIP),
+            machine(&rom, Quirks::SUPER_CHIP),
+        );
+        comparison.frames(&Inputs::default(), 10);
+        let divergence = comparison.divergence().unwrap();
+        assert_eq!(divergence.frame, 3);
+        assert_eq!(divergence.parts, ["registers"]);
+        // the run stops on the frame that diverged
+        assert_eq!(comparison.frame, 3);
+        comparison.frames(&Inputs::default(), 10);
+        assert_eq!(comparison.divergence().unwrap().frame, 3);
+        assert_eq!(comparison.frame, 13);
+    }
+
+    #[test]
+    fn test_quirks_name() {
+        assert_eq!(quirks_name(Quirks::SUPER_CHIP), "schip quirks");
+        assert_eq!(
+            quirks_name(Quirks {
+                wrap_sprites: true,
+                ..Quirks::COSMAC_VIP
+            }),
+            "vip quirks, wrapping sprites"
+        );
+        assert_eq!(
+            quirks_name(Quirks {
+                memory_wraps: false,
+                ..Quirks::SUPER_CHIP
+            }),
+            "schip quirks, checking memory"
+        );
+        assert_eq!(
+            quirks_name(Quirks {
+                jump_uses_vx: true,
+                ..Quirks::COSMAC_VIP
+            }),
+            "custom quirks"
+        );
+    }
+}
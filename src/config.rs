@@ -0,0 +1,578 @@
+use std::{collections::BTreeMap, error::Error};
+
+use macroquad::input::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    buzzer::Tone,
+    gamepad::GamepadMapping,
+    keys::{key_name, Hotkey, Key},
+    palette::Palette,
+    phosphor::Phosphor,
+    rom_db,
+};
+
+/// the original layout of the CHIP-8 keypad, mapped onto the left side of a QWERTY keyboard
+const DEFAULT_KEYPAD: [KeyCode; 16] = {
+    use KeyCode::*;
+    [
+        X,    // 0
+        Key1, // 1
+        Key2, // 2
+        Key3, // 3
+        Q,    // 4
+        W,    // 5
+        E,    // 6
+        A,    // 7
+        S,    // 8
+        D,    // 9
+        Z,    // A
+        C,    // B
+        Key4, // C
+        R,    // D
+        F,    // E
+        V,    // F
+    ]
+};
+
+/// Emulator controls, as opposed to the game's keypad.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hotkeys {
+    pub quit: Hotkey,
+    /// open the pause menu, which Escape always closes
+    pub pause_menu: Hotkey,
+    pub toggle_hud: Hotkey,
+    /// show the hex keypad, with the keys bound to it and which are held
+    pub toggle_keypad: Hotkey,
+    pub toggle_debugger: Hotkey,
+    pub cycle_palette: Hotkey,
+    /// switch the running ROM to the next quirks preset
+    pub cycle_quirks: Hotkey,
+    pub cycle_phosphor: Hotkey,
+    pub toggle_crt: Hotkey,
+    /// Alt+Enter also toggles fullscreen
+    pub toggle_fullscreen: Hotkey,
+    pub open_rom: Hotkey,
+    pub rom_menu: Hotkey,
+    pub screenshot: Hotkey,
+    pub record_gif: Hotkey,
+    /// open the screen for rebinding the keypad
+    pub remap_keys: Hotkey,
+    /// continue after stopping at a breakpoint
+    pub resume: Hotkey,
+    pub pause: Hotkey,
+    /// while paused, run one instruction
+    pub step: Hotkey,
+    /// while paused, run one frame
+    pub step_frame: Hotkey,
+    /// while paused, type a new value for a register, timer, or memory byte
+    pub edit_state: Hotkey,
+    /// list the running ROM's cheats, to turn them on and off or add one
+    pub cheats: Hotkey,
+    /// search memory for the address of a game variable, narrowing it down between plays
+    pub memory_search: Hotkey,
+    pub export_octo_state: Hotkey,
+    pub save_state: Hotkey,
+    pub next_save_slot: Hotkey,
+    pub load_state: Hotkey,
+    /// hold to run backwards
+    pub rewind: Hotkey,
+    /// start the ROM over, keeping it in memory as it is
+    pub reset: Hotkey,
+    /// read the ROM file again and start it over, e.g. after rebuilding it
+    pub reload_rom: Hotkey,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Hotkeys {
+            quit: Hotkey {
+                shift: true,
+                ..Hotkey::new(KeyCode::Escape)
+            },
+            pause_menu: Hotkey::new(KeyCode::Escape),
+            toggle_hud: Hotkey::new(KeyCode::F3),
+            toggle_keypad: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::Y)
+            },
+            toggle_debugger: Hotkey::new(KeyCode::F2),
+            cycle_palette: Hotkey::new(KeyCode::F1),
+            cycle_quirks: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::K)
+            },
+            cycle_phosphor: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::H)
+            },
+            toggle_crt: Hotkey::new(KeyCode::F10),
+            toggle_fullscreen: Hotkey::new(KeyCode::F11),
+            open_rom: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::O)
+            },
+            rom_menu: Hotkey::new(KeyCode::F12),
+            screenshot: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::I)
+            },
+            record_gif: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::G)
+            },
+            remap_keys: Hotkey::new(KeyCode::F4),
+            resume: Hotkey::new(KeyCode::F9),
+            pause: Hotkey::new(KeyCode::P),
+            step: Hotkey::new(KeyCode::Space),
+            step_frame: Hotkey::new(KeyCode::N),
+            edit_state: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::U)
+            },
+            cheats: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::J)
+            },
+            memory_search: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::B)
+            },
+            export_octo_state: Hotkey::new(KeyCode::F8),
+            save_state: Hotkey::new(KeyCode::F5),
+            next_save_slot: Hotkey::new(KeyCode::F6),
+            load_state: Hotkey::new(KeyCode::F7),
+            rewind: Hotkey::new(KeyCode::Backspace),
+            reset: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::T)
+            },
+            reload_rom: Hotkey {
+                ctrl: true,
+                ..Hotkey::new(KeyCode::L)
+            },
+        }
+    }
+}
+
+impl Hotkeys {
+    /// every hotkey with its name in the config file
+    pub fn all(&self) -> Vec<(&'static str, Hotkey)> {
+        vec![
+            ("quit", self.quit),
+            ("pause_menu", self.pause_menu),
+            ("toggle_hud", self.toggle_hud),
+            ("toggle_keypad", self.toggle_keypad),
+            ("toggle_debugger", self.toggle_debugger),
+            ("cycle_palette", self.cycle_palette),
+            ("cycle_quirks", self.cycle_quirks),
+            ("cycle_phosphor", self.cycle_phosphor),
+            ("toggle_crt", self.toggle_crt),
+            ("toggle_fullscreen", self.toggle_fullscreen),
+            ("open_rom", self.open_rom),
+            ("rom_menu", self.rom_menu),
+            ("screenshot", self.screenshot),
+            ("record_gif", self.record_gif),
+            ("remap_keys", self.remap_keys),
+            ("resume", self.resume),
+            ("pause", self.pause),
+            ("step", self.step),
+            ("step_frame", self.step_frame),
+            ("edit_state", self.edit_state),
+            ("cheats", self.cheats),
+            ("memory_search", self.memory_search),
+            ("export_octo_state", self.export_octo_state),
+            ("save_state", self.save_state),
+            ("next_save_slot", self.next_save_slot),
+            ("load_state", self.load_state),
+            ("rewind", self.rewind),
+            ("reset", self.reset),
+            ("reload_rom", self.reload_rom),
+        ]
+    }
+}
+
+/// Settings read from `config.toml`. Missing entries fall back to defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// physical key for each CHIP-8 key 0-F
+    pub keypad: [Key; 16],
+    /// patch files to apply to a ROM, keyed by the ROM's file name
+    pub patches: BTreeMap<String, Vec<String>>,
+    /// cheats to turn on for a ROM, keyed by its file name, each as `address=bytes` in hex
+    pub cheats: BTreeMap<String, Vec<String>>,
+    pub hotkeys: Hotkeys,
+    /// display colors, unless `--palette` says otherwise
+    pub palette: Palette,
+    /// how quickly pixels light up and fade out
+    pub phosphor: Phosphor,
+    /// start with the CRT effect on
+    pub crt: bool,
+    /// the buzzer's waveform and pitch
+    pub buzzer: Tone,
+    /// tones that replace `buzzer` for particular ROMs, keyed by file name
+    pub buzzer_profiles: BTreeMap<String, Tone>,
+    /// CHIP-8 key for each gamepad button
+    pub gamepad: GamepadMapping,
+    /// gamepad mappings that replace `gamepad` for particular ROMs, keyed by file name
+    pub gamepad_profiles: BTreeMap<String, GamepadMapping>,
+    /// keyboard controls for particular ROMs, by name, each found by the ROM's SHA-1
+    pub control_profiles: BTreeMap<String, ControlProfile>,
+}
+
+/// Keys that press CHIP-8 keys for one ROM, on top of the keypad, e.g. the
+/// arrow keys for its directions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlProfile {
+    /// SHA-1 of the ROM, in hex as `sha1sum` prints it
+    pub sha1: String,
+    /// CHIP-8 key for each physical key
+    pub keys: BTreeMap<Key, u8>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keypad: DEFAULT_KEYPAD.map(Key),
+            patches: BTreeMap::new(),
+            cheats: BTreeMap::new(),
+            hotkeys: Hotkeys::default(),
+            palette: Palette::default(),
+            phosphor: Phosphor::default(),
+            crt: false,
+            buzzer: Tone::default(),
+            buzzer_profiles: BTreeMap::new(),
+            gamepad: GamepadMapping::default(),
+            gamepad_profiles: BTreeMap::new(),
+            control_profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(toml: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Load the config file, or the defaults if there is none.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &std::path::Path) -> Result<Self, Box<dyn Error>> {
+        match std::fs::read_to_string(path) {
+            Ok(toml) => Self::parse(&toml),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config is always representable as TOML")
+    }
+
+    /// Write the config file, creating its directory if needed. Comments
+    /// in an existing file are not preserved.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    /// Bind CHIP-8 key `slot` to `key`. If another CHIP-8 key was bound to
+    /// `key`, it takes over `slot`'s old key so no two share one.
+    pub fn rebind_key(&mut self, slot: usize, key: Key) {
+        if let Some(other) = self.keypad.iter().position(|k| *k == key) {
+            self.keypad[other] = self.keypad[slot];
+        }
+        self.keypad[slot] = key;
+    }
+
+    /// patches configured for the ROM at `rom_path`
+    pub fn patches_for(&self, rom_path: &str) -> &[String] {
+        self.patches
+            .get(rom_file_name(rom_path))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// cheats configured for the ROM at `rom_path`
+    pub fn cheats_for(&self, rom_path: &str) -> &[String] {
+        self.cheats
+            .get(rom_file_name(rom_path))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    /// the gamepad mapping for the ROM at `rom_path`
+    pub fn gamepad_for(&self, rom_path: &str) -> &GamepadMapping {
+        self.gamepad_profiles
+            .get(rom_file_name(rom_path))
+            .unwrap_or(&self.gamepad)
+    }
+
+    /// the buzzer's tone for the ROM at `rom_path`
+    pub fn buzzer_for(&self, rom_path: &str) -> Tone {
+        self.buzzer_profiles
+            .get(rom_file_name(rom_path))
+            .copied()
+            .unwrap_or(self.buzzer)
+    }
+
+    /// the name and controls of the profile for `rom`, going by its SHA-1
+    pub fn controls_for(&self, rom: &[u8]) -> Option<(&str, &ControlProfile)> {
+        let sha1 = rom_db::sha1(rom);
+        self.control_profiles
+            .iter()
+            .find(|(_, profile)| profile.sha1.eq_ignore_ascii_case(&sha1))
+            .map(|(name, profile)| (name.as_str(), profile))
+    }
+
+    /// Bindings that would make one key do two things, as human readable warnings.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = vec![];
+
+        for (a, key_a) in self.keypad.iter().enumerate() {
+            for (b, key_b) in self.keypad.iter().enumerate().skip(a + 1) {
+                if key_a == key_b {
+                    conflicts.push(format!(
+                        "CHIP-8 keys {:X} and {:X} are both bound to {}",
+                        a,
+                        b,
+                        key_name(key_a.0)
+                    ));
+                }
+            }
+        }
+
+        let hotkeys = self.hotkeys.all();
+        for (idx, (name, hotkey)) in hotkeys.iter().enumerate() {
+            if let Some(slot) = self.keypad.iter().position(|k| k.0 == hotkey.key) {
+                conflicts.push(format!(
+                    "Hotkey {} ({}) collides with CHIP-8 key {:X}",
+                    name, hotkey, slot
+                ));
+            }
+            for (other_name, other) in hotkeys.iter().skip(idx + 1) {
+                if hotkey == other {
+                    conflicts.push(format!(
+                        "Hotkeys {} and {} are both bound to {}",
+                        name, other_name, hotkey
+                    ));
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+fn rom_file_name(rom_path: &str) -> &str {
+    std::path::Path::new(rom_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(rom_path)
+}
+
+/// `$XDG_CONFIG_HOME/chip8-rust/config.toml`, falling back to `~/.config`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_path() -> Option<std::path::PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+        })?;
+    Some(config_home.join("chip8-rust").join("config.toml"))
+}
+
+/// where files the app keeps for itself live, `$XDG_DATA_HOME/chip8-rust`
+/// falling back to `~/.local/share/chip8-rust`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| std::path::Path::new(&home).join(".local").join("share"))
+        })?;
+    Some(data_home.join("chip8-rust"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        buzzer::{Waveform, DEFAULT_FREQUENCY},
+        gamepad::PadButton,
+        library,
+    };
+
+    #[test]
+    fn test_defaults_have_no_conflicts() {
+        assert_eq!(Config::default().conflicts(), Vec::<String>::new());
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_overrides() {
+        let config = Config::parse(
+            r#"
+            [hotkeys]
+            toggle_hud = "Ctrl+H"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.hotkeys.toggle_hud.key, KeyCode::H);
+        assert!(config.hotkeys.toggle_hud.ctrl);
+        assert_eq!(config.hotkeys.quit, Hotkeys::default().quit);
+
+        assert!(Config::parse("[hotkeys]\ntoggle_hud = \"Nope\"").is_err());
+
+        assert_eq!(
+            Config::parse("palette = \"amber\"").unwrap().palette.name,
+            "amber"
+        );
+        assert!(Config::parse("palette = \"plaid\"").is_err());
+
+        let phosphor = Config::parse("phosphor = { fade_in = 0.5, fade_out = 0.1 }")
+            .unwrap()
+            .phosphor;
+        assert_eq!(phosphor.fade_out, 0.1);
+        assert_eq!(
+            Config::parse("phosphor = \"Instant\"")
+                .unwrap()
+                .phosphor
+                .name(),
+            Some("instant")
+        );
+        assert!(Config::parse("phosphor = \"flicker\"").is_err());
+        assert!(Config::parse("phosphor = { fade_in = 0, fade_out = 1 }").is_err());
+    }
+
+    #[test]
+    fn test_patches_for_rom() {
+        let config = Config::parse("[patches]\nPONG = [\"pong.ips\"]").unwrap();
+        assert_eq!(config.patches_for("assets/roms/PONG"), ["pong.ips"]);
+        assert!(config.patches_for("assets/roms/TETRIS").is_empty());
+    }
+
+    #[test]
+    fn test_cheats_for_rom() {
+        let config = Config::parse(r#"cheats = { BLITZ = ["0x3F0=09", "31A=00 00"] }"#).unwrap();
+        assert_eq!(config.cheats_for("roms/BLITZ"), ["0x3F0=09", "31A=00 00"]);
+        assert!(config.cheats_for("roms/PONG").is_empty());
+    }
+
+    #[test]
+    fn test_gamepad_profiles() {
+        let config = Config::parse(
+            r#"
+            [gamepad_profiles.PONG]
+            DPadUp = 0x1
+            DPadDown = 0x4
+            "#,
+        )
+        .unwrap();
+        let pong = config.gamepad_for("assets/roms/PONG");
+        assert_eq!(pong.0.len(), 2);
+        assert_eq!(pong.0[&PadButton::DPadDown], 0x4);
+        assert_eq!(config.gamepad_for("TETRIS"), &GamepadMapping::default());
+    }
+
+    #[test]
+    fn test_control_profiles() {
+        let pong = library::embedded("PONG").unwrap();
+        let config = Config::parse(&format!(
+            r#"
+            [control_profiles.pong]
+            sha1 = "{}"
+            keys = {{ Up = 0x1, Down = 0x4 }}
+            "#,
+            rom_db::sha1(pong).to_uppercase()
+        ))
+        .unwrap();
+        let (name, profile) = config.controls_for(pong).unwrap();
+        assert_eq!(name, "pong");
+        assert_eq!(profile.keys[&Key(KeyCode::Down)], 0x4);
+        assert_eq!(config.controls_for(&[0x12, 0x00]), None);
+        assert!(Config::parse("[control_profiles.x]\nsha1 = \"\"\nkeys = { Nope = 1 }").is_err());
+    }
+
+    #[test]
+    fn test_buzzer_profiles() {
+        let config = Config::parse(
+            r#"
+            buzzer = { waveform = "sine" }
+
+            [buzzer_profiles.PONG]
+            waveform = "noise"
+            frequency = 220
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.buzzer_for("TETRIS"),
+            Tone {
+                waveform: Waveform::Sine,
+                frequency: DEFAULT_FREQUENCY
+            }
+        );
+        assert_eq!(
+            config.buzzer_for("assets/roms/PONG"),
+            Tone {
+                waveform: Waveform::Noise,
+                frequency: 220
+            }
+        );
+        assert!(Config::parse("buzzer = { waveform = \"sawtooth\" }").is_err());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut config = Config::default();
+        config.hotkeys.toggle_hud = Hotkey::new(KeyCode::Q);
+        config.hotkeys.export_octo_state = Hotkey::new(KeyCode::Q);
+        config.keypad[0] = Key(KeyCode::Key1);
+
+        assert_eq!(
+            config.conflicts(),
+            vec![
+                "CHIP-8 keys 0 and 1 are both bound to 1",
+                "Hotkey toggle_hud (Q) collides with CHIP-8 key 4",
+                "Hotkeys toggle_hud and export_octo_state are both bound to Q",
+                "Hotkey export_octo_state (Q) collides with CHIP-8 key 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rebind_key_swaps_duplicates() {
+        let mut config = Config::default();
+        // 0 is on X, 1 is on 1
+        config.rebind_key(0, Key(KeyCode::Key1));
+        assert_eq!(config.keypad[0], Key(KeyCode::Key1));
+        assert_eq!(config.keypad[1], Key(KeyCode::X));
+
+        config.rebind_key(0, Key(KeyCode::M));
+        assert_eq!(config.keypad[0], Key(KeyCode::M));
+        assert_eq!(config.conflicts(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_to_toml_round_trips() {
+        let mut config = Config::parse("[patches]\nPONG = [\"pong.ips\"]").unwrap();
+        config.rebind_key(0xA, Key(KeyCode::Y));
+        config.palette = Palette::DEFAULT.next();
+        config.phosphor = Phosphor {
+            fade_in: 0.5,
+            fade_out: 0.125,
+        };
+        config.hotkeys.quit = Hotkey::parse("Ctrl+Q").unwrap();
+        config
+            .gamepad_profiles
+            .insert(String::from("PONG"), GamepadMapping::default());
+        assert_eq!(Config::parse(&config.to_toml()).unwrap(), config);
+    }
+}
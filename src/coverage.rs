@@ -0,0 +1,186 @@
+//! Coverage: which bytes of memory have run as instructions since the ROM
+//! was loaded, to find dead code or see how much of a game a play session
+//! reached. Resets and restored states keep what's been covered.
+//!
+//! `--coverage` saves it as text, a header and then the executed ranges:
+//!
+//! ```text
+//! chip8-coverage 1
+//! 0x200-0x2a3
+//! 0x2b0-0x2b1
+//! ```
+//!
+//! which `chip8-rust coverage` reports on and `disasm --coverage` marks.
+
+use std::fmt::Write;
+
+use crate::analyzer::{Analysis, RegionKind};
+
+const HEADER: &str = "chip8-coverage 1";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// by address, whether the byte has been part of an executed instruction
+    executed: Vec<bool>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// record that the instruction `word` ran at `addr`
+    pub fn record(&mut self, addr: u16, word: u16) {
+        let len = if word == 0xF000 { 4 } else { 2 };
+        let end = addr as usize + len;
+        if self.executed.len() < end {
+            self.executed.resize(end, false);
+        }
+        self.executed[addr as usize..end].fill(true);
+    }
+
+    pub fn is_executed(&self, addr: u16) -> bool {
+        self.executed.get(addr as usize).copied().unwrap_or(false)
+    }
+
+    /// the executed bytes as (first, last) address ranges
+    pub fn ranges(&self) -> Vec<(u16, u16)> {
+        let addrs = (0..=u16::MAX).take(self.executed.len());
+        ranges(addrs.filter(|addr| self.is_executed(*addr)))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(HEADER) {
+            return Err(String::from("not a chip8-rust coverage file"));
+        }
+        let mut coverage = Coverage::new();
+        for (idx, line) in lines.enumerate() {
+            let bad = || format!("bad range on line {}", idx + 2);
+            let (first, last) = line.trim().split_once('-').ok_or_else(bad)?;
+            let parse = |addr: &str| {
+                let addr = addr.strip_prefix("0x").unwrap_or(addr);
+                u16::from_str_radix(addr, 16).map_err(|_| bad())
+            };
+            let (first, last) = (parse(first)?, parse(last)?);
+            if last < first {
+                return Err(bad());
+            }
+            coverage
+                .executed
+                .resize(coverage.executed.len().max(last as usize + 1), false);
+            coverage.executed[first as usize..=last as usize].fill(true);
+        }
+        Ok(coverage)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for (first, last) in self.ranges() {
+            let _ = writeln!(text, "{:#05x}-{:#05x}", first, last);
+        }
+        text
+    }
+
+    /// How much of `rom`, loaded at `start`, ran, and which code the
+    /// analyzer finds reachable never did. ROMs running past 0xFFFF can't
+    /// be analyzed, so get no report.
+    pub fn report(&self, rom: &[u8], start: u16) -> Result<String, String> {
+        let analysis = Analysis::new(rom, start)?;
+        let addrs = (0..rom.len()).map(|offset| (start as usize + offset) as u16);
+        let executed = addrs.clone().filter(|addr| self.is_executed(*addr)).count();
+        let code: usize = analysis
+            .regions()
+            .iter()
+            .filter(|(kind, _, _)| *kind == RegionKind::Code)
+            .map(|(_, first, last)| (last - first) as usize + 1)
+            .sum();
+        let missed =
+            ranges(addrs.filter(|addr| analysis.is_code(*addr) && !self.is_executed(*addr)));
+
+        let mut out = String::new();
+        let share = |count: usize, of: usize| 100. * count as f64 / of.max(1) as f64;
+        let _ = writeln!(
+            out,
+            "executed:       {} of {} bytes ({:.1}%)",
+            executed,
+            rom.len(),
+            share(executed, rom.len())
+        );
+        write_ranges(&mut out, &self.ranges());
+        let missed_bytes: usize = missed
+            .iter()
+            .map(|(first, last)| (last - first) as usize + 1)
+            .sum();
+        let _ = writeln!(
+            out,
+            "never run:      {} of {} bytes of reachable code ({:.1}%)",
+            missed_bytes,
+            code,
+            share(missed_bytes, code)
+        );
+        write_ranges(&mut out, &missed);
+        Ok(out)
+    }
+}
+
+/// sorted addresses as runs of consecutive ones
+fn ranges(addrs: impl Iterator<Item = u16>) -> Vec<(u16, u16)> {
+    let mut ranges: Vec<(u16, u16)> = vec![];
+    for addr in addrs {
+        match ranges.last_mut() {
+            Some((_, last)) if last.checked_add(1) == Some(addr) => *last = addr,
+            _ => ranges.push((addr, addr)),
+        }
+    }
+    ranges
+}
+
+fn write_ranges(out: &mut String, ranges: &[(u16, u16)]) {
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|(first, last)| format!("{:#05x}-{:#05x}", first, last))
+        .collect();
+    for row in ranges.chunks(4) {
+        let _ = writeln!(out, "  {}", row.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut coverage = Coverage::new();
+        coverage.record(0x200, 0x6001);
+        coverage.record(0x202, 0x1200);
+        coverage.record(0x300, 0xF000);
+        assert!(coverage.is_executed(0x303));
+        assert!(!coverage.is_executed(0x204));
+        assert_eq!(coverage.ranges(), [(0x200, 0x203), (0x300, 0x303)]);
+        let text = coverage.to_text();
+        assert_eq!(text, "chip8-coverage 1\n0x200-0x203\n0x300-0x303\n");
+        assert_eq!(Coverage::parse(&text), Ok(coverage));
+        assert!(Coverage::parse("0x200-0x203").is_err());
+        assert!(Coverage::parse("chip8-coverage 1\n0x203-0x200").is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        // 0x200: SE V0, 1; 0x202: CLS; 0x204: JP 0x204
+        let rom = [0x30, 0x01, 0x00, 0xE0, 0x12, 0x04];
+        let mut coverage = Coverage::new();
+        coverage.record(0x200, 0x3001);
+        coverage.record(0x204, 0x1204);
+        let report = coverage.report(&rom, 0x200).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "executed:       4 of 6 bytes (66.7%)");
+        assert_eq!(lines[1], "  0x200-0x201, 0x204-0x205");
+        assert_eq!(
+            lines[2],
+            "never run:      2 of 6 bytes of reachable code (33.3%)"
+        );
+        assert_eq!(lines[3], "  0x202-0x203");
+    }
+}
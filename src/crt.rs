@@ -0,0 +1,118 @@
+//! An optional CRT look: the display is drawn at its native 64x32 into a
+//! texture, then stretched onto the window through a shader that bends it
+//! slightly, darkens the gaps between rows, and lets lit pixels glow.
+
+use macroquad::prelude::*;
+
+use chip8_core::interpreter::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    uv = texcoord;
+    color = color0 / 255.0;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 100
+precision mediump float;
+
+varying vec2 uv;
+varying lowp vec4 color;
+
+uniform sampler2D Texture;
+
+const vec2 SIZE = vec2(64.0, 32.0);
+const float CURVATURE = 0.06;
+const float GLOW = 0.35;
+
+// barrel distortion, pushing the corners outwards
+vec2 curve(vec2 coord) {
+    vec2 centered = coord * 2.0 - 1.0;
+    centered += centered * (centered.yx * centered.yx) * CURVATURE;
+    return centered * 0.5 + 0.5;
+}
+
+void main() {
+    vec2 coord = curve(uv);
+    if (coord.x < 0.0 || coord.x > 1.0 || coord.y < 0.0 || coord.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec3 pixel = texture2D(Texture, coord).rgb;
+
+    vec3 glow = vec3(0.0);
+    vec2 texel = 1.0 / SIZE;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            glow += texture2D(Texture, coord + vec2(float(x), float(y)) * texel).rgb;
+        }
+    }
+    pixel += glow / 9.0 * GLOW;
+
+    // brightest in the middle of each CHIP-8 row, dark between rows
+    float scanline = 0.65 + 0.35 * sin(fract(coord.y * SIZE.y) * 3.14159);
+    pixel *= scanline;
+
+    // fade towards the edges like the glass of a tube
+    vec2 edge = coord * (1.0 - coord);
+    pixel *= clamp(pow(edge.x * edge.y * 16.0, 0.15), 0.0, 1.0);
+
+    gl_FragColor = vec4(pixel, 1.0) * color;
+}
+"#;
+
+pub struct Crt {
+    material: Material,
+    target: RenderTarget,
+}
+
+impl Crt {
+    pub fn new() -> Result<Self, String> {
+        let material = load_material(VERTEX_SHADER, FRAGMENT_SHADER, MaterialParams::default())
+            .map_err(|e| format!("{:?}", e))?;
+        let target = render_target(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+        target.texture.set_filter(FilterMode::Nearest);
+        Ok(Crt { material, target })
+    }
+
+    /// Run `draw_display`, which should draw one unit per CHIP-8 pixel, into
+    /// the 64x32 texture, then draw that onto `dest` with the CRT effect.
+    pub fn draw(&self, draw_display: impl FnOnce(), dest: Rect) {
+        let (width, height) = (SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32);
+        // y points up here, unlike on screen, because render targets are
+        // stored bottom row first
+        set_camera(&Camera2D {
+            target: vec2(width / 2., height / 2.),
+            zoom: vec2(2. / width, 2. / height),
+            render_target: Some(self.target),
+            ..Default::default()
+        });
+        draw_display();
+        set_default_camera();
+
+        gl_use_material(self.material);
+        draw_texture_ex(
+            self.target.texture,
+            dest.x,
+            dest.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(dest.w, dest.h)),
+                ..Default::default()
+            },
+        );
+        gl_use_default_material();
+    }
+}
@@ -0,0 +1,290 @@
+use macroquad::prelude::*;
+
+use chip8_core::interpreter::{decode, Interpreter, Op};
+
+use crate::{
+    disassembler::format_op_named,
+    symbols::Symbols,
+    ui_style::{FONT_SIZE, LINE_HEIGHT, MARGIN, OVERLAY_BACKGROUND},
+};
+
+/// the deepest calls shown, with the rest summed up above them
+const SHOWN_CALLS: usize = 5;
+/// warn once this few levels are left before the stack overflows
+const DEPTH_WARNING: usize = 3;
+/// how many executed instructions the interpreter keeps unless `--history` says otherwise
+pub const DEFAULT_HISTORY: usize = 32;
+/// the most recent instructions shown in the history panel
+const SHOWN_HISTORY: usize = 16;
+
+/// One active call, as the call tree shows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Call {
+    /// where the called subroutine starts, if the CALL is still in memory to say
+    entry: Option<u16>,
+    /// the address of the CALL
+    site: u16,
+    /// 1 for a call from the main program
+    depth: usize,
+}
+
+/// The active calls, outermost first. The stack only holds return
+/// addresses, so each subroutine's entry is read back from its CALL.
+fn call_tree(interpreter: &Interpreter) -> Vec<Call> {
+    let memory = interpreter.memory();
+    interpreter
+        .call_stack()
+        .iter()
+        .enumerate()
+        .map(|(idx, ret)| {
+            let site = ret.wrapping_sub(2);
+            let word = memory
+                .get(site as usize..site as usize + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+            let entry = match word.map(decode) {
+                Some(Op::Call { nnn }) => Some(nnn),
+                _ => None,
+            };
+            Call {
+                entry,
+                site,
+                depth: idx + 1,
+            }
+        })
+        .collect()
+}
+
+/// the call tree as indented lines, deepest last, and whether it's near overflowing
+fn call_lines(interpreter: &Interpreter, symbols: &Symbols) -> (Vec<String>, bool) {
+    let calls = call_tree(interpreter);
+    let max = interpreter.max_call_depth();
+    let near_overflow = calls.len() + DEPTH_WARNING >= max;
+    let mut lines = vec![format!(
+        "calls {}/{}{}",
+        calls.len(),
+        max,
+        if near_overflow { ", near overflow" } else { "" }
+    )];
+    let hidden = calls.len().saturating_sub(SHOWN_CALLS);
+    if hidden > 0 {
+        lines.push(format!("  ({} outer calls)", hidden));
+    }
+    for call in &calls[hidden..] {
+        let entry = call
+            .entry
+            .map_or_else(|| String::from("?"), |entry| symbols.format(entry));
+        lines.push(format!(
+            "{}{}  from {:#05x}",
+            "  ".repeat(call.depth - hidden),
+            entry,
+            call.site
+        ));
+    }
+    (lines, near_overflow)
+}
+
+/// the most recently executed instructions, oldest first, each with the registers it changed
+fn history_lines(interpreter: &Interpreter, symbols: &Symbols) -> Vec<String> {
+    let history = interpreter.history();
+    let skipped = history.len().saturating_sub(SHOWN_HISTORY);
+    history
+        .skip(skipped)
+        .map(|entry| {
+            let deltas: Vec<String> = entry.deltas().map(|delta| delta.to_string()).collect();
+            format!(
+                "{:#05x}  {:04X}  {:<18}{}",
+                entry.pc,
+                entry.word,
+                format_op_named(&entry.op, symbols),
+                deltas.join("  ")
+            )
+            .trim_end()
+            .to_string()
+        })
+        .collect()
+}
+
+/// Draws the instructions that led up to a stop in the top-right corner
+/// of the window, the last one being the instruction just executed.
+pub fn draw_history(interpreter: &Interpreter, symbols: &Symbols) {
+    let mut lines = vec![String::from("history")];
+    lines.extend(history_lines(interpreter, symbols));
+    if lines.len() == 1 {
+        lines.push(String::from("(nothing recorded, see --history)"));
+    }
+
+    let width = lines
+        .iter()
+        .map(|line| measure_text(line, None, FONT_SIZE as u16, 1.).width)
+        .fold(0., f32::max);
+    let x = screen_width() - MARGIN - width;
+    draw_rectangle(
+        x - 6.,
+        MARGIN - 6.,
+        width + 12.,
+        lines.len() as f32 * LINE_HEIGHT + 12.,
+        OVERLAY_BACKGROUND,
+    );
+    for (idx, line) in lines.iter().enumerate() {
+        draw_text(
+            line,
+            x,
+            MARGIN + (idx + 1) as f32 * LINE_HEIGHT - 6.,
+            FONT_SIZE,
+            if idx == 0 { GRAY } else { WHITE },
+        );
+    }
+}
+
+/// Draws the interpreter's registers, timers, call tree, and next
+/// instruction in the top-left corner of the window, with addresses
+/// `symbols` names by name.
+pub fn draw_debug_overlay(interpreter: &Interpreter, symbols: &Symbols) {
+    let next = match interpreter.next_instruction() {
+        Some(word) => format!("{:04X}  {}", word, format_op_named(&decode(word), symbols)),
+        None => String::from("(outside memory)"),
+    };
+    let pc = interpreter.program_counter();
+    let location = symbols
+        .locate(pc)
+        .map_or_else(String::new, |name| format!(" <{}>", name));
+
+    let mut lines = vec![
+        format!("PC {:#05x}{}  {}", pc, location, next),
+        format!(
+            "I  {:#05x}  DT {:3}  ST {:3}",
+            interpreter.index_register(),
+            interpreter.delay_timer(),
+            interpreter.sound_timer()
+        ),
+    ];
+    for (row_idx, values) in interpreter.registers().chunks(4).enumerate() {
+        let cells: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(col, value)| format!("V{:X} {:02X}", row_idx * 4 + col, value))
+            .collect();
+        lines.push(cells.join("  "));
+    }
+    let calls_start = lines.len();
+    let (calls, near_overflow) = call_lines(interpreter, symbols);
+    lines.extend(calls);
+
+    let width = lines
+        .iter()
+        .map(|line| measure_text(line, None, FONT_SIZE as u16, 1.).width)
+        .fold(0., f32::max);
+    draw_rectangle(
+        MARGIN - 6.,
+        MARGIN - 6.,
+        width + 12.,
+        lines.len() as f32 * LINE_HEIGHT + 12.,
+        OVERLAY_BACKGROUND,
+    );
+    for (idx, line) in lines.iter().enumerate() {
+        let color = if near_overflow && idx == calls_start {
+            ORANGE
+        } else {
+            WHITE
+        };
+        draw_text(
+            line,
+            MARGIN,
+            MARGIN + (idx + 1) as f32 * LINE_HEIGHT - 6.,
+            FONT_SIZE,
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::Chip8Error;
+
+    use super::*;
+
+    #[test]
+    fn test_call_lines() -> Result<(), Chip8Error> {
+        // CALL 0x204; CALL 0x208 from there, then CALL 0x208 forever
+        let mut interpreter = Interpreter::new();
+        interpreter.load_program(&[0x22, 0x04, 0x00, 0x00, 0x22, 0x08, 0x00, 0x00, 0x22, 0x08])?;
+        assert_eq!(
+            call_lines(&interpreter, &Symbols::default()),
+            (vec![String::from("calls 0/15")], false)
+        );
+
+        interpreter.step()?;
+        interpreter.step()?;
+        assert_eq!(
+            call_tree(&interpreter),
+            [
+                Call {
+                    entry: Some(0x204),
+                    site: 0x200,
+                    depth: 1
+                },
+                Call {
+                    entry: Some(0x208),
+                    site: 0x204,
+                    depth: 2
+                },
+            ]
+        );
+        assert_eq!(
+            call_lines(&interpreter, &Symbols::default()).0[1..],
+            ["  0x204  from 0x200", "    0x208  from 0x204"]
+        );
+        let symbols = Symbols::parse("0x208 spin").unwrap();
+        assert_eq!(
+            call_lines(&interpreter, &symbols).0[2],
+            "    spin  from 0x204"
+        );
+
+        for _ in 0..10 {
+            interpreter.step()?;
+        }
+        let (lines, near_overflow) = call_lines(&interpreter, &Symbols::default());
+        assert!(near_overflow);
+        assert_eq!(lines[0], "calls 12/15, near overflow");
+        assert_eq!(lines[1], "  (7 outer calls)");
+        assert_eq!(lines[2], "  0x208  from 0x208");
+        assert_eq!(lines.len(), 2 + SHOWN_CALLS);
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_lines() -> Result<(), Chip8Error> {
+        // LD V0, 5; LD I, 0x300; JP 0x200
+        let mut interpreter = Interpreter::new();
+        interpreter.set_history_len(20);
+        interpreter.load_program(&[0x60, 0x05, 0xA3, 0x00, 0x12, 0x00])?;
+        for _ in 0..3 {
+            interpreter.step()?;
+        }
+        assert_eq!(
+            history_lines(&interpreter, &Symbols::default()),
+            [
+                "0x200  6005  LD V0, 0x05       V0 00->05",
+                "0x202  A300  LD I, 0x300       I 000->300",
+                "0x204  1200  JP 0x200",
+            ]
+        );
+
+        let symbols = Symbols::parse("0x200 main\n0x300 sprite").unwrap();
+        assert_eq!(
+            history_lines(&interpreter, &symbols)[1..],
+            [
+                "0x202  A300  LD I, sprite      I 000->300",
+                "0x204  1200  JP main"
+            ]
+        );
+
+        for _ in 0..17 {
+            interpreter.step()?;
+        }
+        let lines = history_lines(&interpreter, &Symbols::default());
+        assert_eq!(lines.len(), SHOWN_HISTORY);
+        assert!(lines[SHOWN_HISTORY - 1].starts_with("0x202"));
+        Ok(())
+    }
+}
@@ -0,0 +1,486 @@
+//! `--debug-server <port>`: inspect and control the running interpreter
+//! from another program, over WebSocket on localhost.
+//!
+//! Each text message is a JSON request with a `cmd`, answered by one JSON
+//! reply that has `"ok": true` and the command's results, or `"ok": false`
+//! and an `error`:
+//!
+//! | request                                       | reply                                     |
+//! |-----------------------------------------------|-------------------------------------------|
+//! | `{"cmd": "state"}`                            | `pc`, `i`, `v`, `stack`, `dt`, `st`, `paused` |
+//! | `{"cmd": "pause"}`, `{"cmd": "resume"}`       | `paused`                                  |
+//! | `{"cmd": "step", "count": 1}`                 | `pc` after stepping, only while paused; at most 10000 |
+//! | `{"cmd": "read_memory", "addr": 512, "len": 16}` | `bytes`                                |
+//! | `{"cmd": "add_breakpoint", "addr": 512}`      | `breakpoints`                             |
+//! | `{"cmd": "remove_breakpoint", "addr": 512}`   | `breakpoints`                             |
+//! | `{"cmd": "key", "key": 5, "down": true}`      | `keys`, held until released this way     |
+//!
+//! Connections are served on threads of their own, but requests are
+//! answered by the main loop between frames, so they never race emulation.
+//! Handshakes from web pages that aren't served from localhost are turned
+//! away, so a site open in a browser can't take control.
+//! Browsers can't accept connections, so there's no server on the web.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::machine::{Chip8Machine, Inputs};
+
+/// from RFC 6455, appended to the client's key to prove the server speaks WebSocket
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// largest message accepted, far more than any request needs
+const MAX_MESSAGE_LEN: u64 = 64 * 1024;
+/// most instructions one `step` runs, since the frame waits for them all
+const MAX_STEP_COUNT: u32 = 10_000;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    State,
+    Pause,
+    Resume,
+    Step {
+        #[serde(default = "one")]
+        count: u32,
+    },
+    ReadMemory {
+        addr: usize,
+        len: usize,
+    },
+    AddBreakpoint {
+        addr: u16,
+    },
+    RemoveBreakpoint {
+        addr: u16,
+    },
+    Key {
+        key: usize,
+        down: bool,
+    },
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// a request from a connection, with where to send its reply
+type Pending = (String, Sender<String>);
+
+pub struct DebugServer {
+    requests: Receiver<Pending>,
+    /// keys held down by `key` requests
+    keys: [bool; 16],
+}
+
+impl DebugServer {
+    /// Listen on `port` on localhost, accepting connections on a background thread.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let (sender, requests) = channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = serve_connection(stream, sender) {
+                        log::info!("debug server connection closed: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(DebugServer {
+            requests,
+            keys: [false; 16],
+        })
+    }
+
+    /// Answer the requests that came in since the last call. Stepping runs
+    /// instructions straight away, so call this before the frame runs.
+    pub fn serve(&mut self, machine: &mut Chip8Machine, paused: &mut bool) {
+        while let Ok((message, reply)) = self.requests.try_recv() {
+            let response = match serde_json::from_str(&message) {
+                Ok(request) => self.respond(request, machine, paused),
+                Err(e) => Err(format!("bad request: {}", e)),
+            };
+            let response = match response {
+                Ok(Value::Object(mut fields)) => {
+                    fields.insert(String::from("ok"), Value::Bool(true));
+                    Value::Object(fields)
+                }
+                Ok(other) => other,
+                Err(e) => json!({ "ok": false, "error": e }),
+            };
+            // the connection may have gone away while waiting
+            let _ = reply.send(response.to_string());
+        }
+    }
+
+    /// which keys requests are holding down, to combine with the real ones
+    pub fn keys(&self) -> [bool; 16] {
+        self.keys
+    }
+
+    fn respond(
+        &mut self,
+        request: Request,
+        machine: &mut Chip8Machine,
+        paused: &mut bool,
+    ) -> Result<Value, String> {
+        let interpreter = machine.interpreter_mut();
+        Ok(match request {
+            Request::State => json!({
+                "pc": interpreter.program_counter(),
+                "i": interpreter.index_register(),
+                "v": interpreter.registers(),
+                "stack": interpreter.call_stack(),
+                "dt": interpreter.delay_timer(),
+                "st": interpreter.sound_timer(),
+                "paused": *paused,
+            }),
+            Request::Pause | Request::Resume => {
+                *paused = request == Request::Pause;
+                json!({ "paused": *paused })
+            }
+            Request::Step { .. } if !*paused => return Err(String::from("pause before stepping")),
+            Request::Step { count } if count > MAX_STEP_COUNT => {
+                return Err(format!(
+                    "step at most {} instructions at once",
+                    MAX_STEP_COUNT
+                ))
+            }
+            Request::Step { count } => {
+                let inputs = Inputs {
+                    keys: self.keys,
+                    ..Default::default()
+                };
+                for _ in 0..count {
+                    machine
+                        .step_instruction(&inputs)
+                        .map_err(|e| e.to_string())?;
+                }
+                json!({ "pc": machine.interpreter().program_counter() })
+            }
+            Request::ReadMemory { addr, len } => {
+                let memory = interpreter.memory();
+                let start = addr.min(memory.len());
+                let end = addr.saturating_add(len).min(memory.len());
+                json!({ "bytes": &memory[start..end] })
+            }
+            Request::AddBreakpoint { addr } | Request::RemoveBreakpoint { addr } => {
+                if matches!(request, Request::AddBreakpoint { .. }) {
+                    interpreter.add_breakpoint(addr);
+                } else {
+                    interpreter.remove_breakpoint(addr);
+                }
+                json!({ "breakpoints": interpreter.breakpoints().collect::<Vec<_>>() })
+            }
+            Request::Key { key, down } => {
+                *self
+                    .keys
+                    .get_mut(key)
+                    .ok_or_else(|| format!("no key {}; keys are 0 through 15", key))? = down;
+                json!({ "keys": self.keys })
+            }
+        })
+    }
+}
+
+/// Upgrade `stream` to WebSocket, then pass each message to the main loop
+/// and send back its reply, until the client closes the connection.
+fn serve_connection(stream: TcpStream, requests: Sender<Pending>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    handshake(&mut reader, &mut writer)?;
+    loop {
+        let (opcode, payload) = read_frame(&mut reader)?;
+        match opcode {
+            OPCODE_TEXT => {
+                let message = String::from_utf8_lossy(&payload).into_owned();
+                let (reply, response) = channel();
+                if requests.send((message, reply)).is_err() {
+                    return Ok(());
+                }
+                let Ok(response) = response.recv() else {
+                    return Ok(());
+                };
+                write_frame(&mut writer, OPCODE_TEXT, response.as_bytes())?;
+            }
+            OPCODE_PING => write_frame(&mut writer, OPCODE_PONG, &payload)?,
+            OPCODE_CLOSE => {
+                write_frame(&mut writer, OPCODE_CLOSE, &[])?;
+                return Ok(());
+            }
+            _ => (),
+        }
+    }
+}
+
+/// read the client's HTTP upgrade request and accept it
+fn handshake(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+    let mut key = None;
+    let mut origin = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            } else if name.trim().eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_string());
+            }
+        }
+    }
+    // browsers always send the page's origin; other clients needn't
+    if let Some(origin) = origin.filter(|origin| !is_local_origin(origin)) {
+        writer
+            .write_all(b"HTTP/1.1 403 Forbidden\r\n\r\nonly pages on localhost may connect\r\n")?;
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("refused a connection from {}", origin),
+        ));
+    }
+    let Some(key) = key else {
+        writer.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\nexpected a WebSocket upgrade\r\n")?;
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a WebSocket request",
+        ));
+    };
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )
+}
+
+/// whether `origin`, as a browser sends it, is a page served from this machine
+fn is_local_origin(origin: &str) -> bool {
+    let Some(host) = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"))
+    else {
+        return false;
+    };
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => host,
+    };
+    ["localhost", "127.0.0.1", "[::1]"]
+        .iter()
+        .any(|local| host.eq_ignore_ascii_case(local))
+}
+
+/// the `Sec-WebSocket-Accept` answer to a client's `Sec-WebSocket-Key`
+fn accept_key(key: &str) -> String {
+    let digest = sha1_smol::Sha1::from(format!("{}{}", key, ACCEPT_GUID)).digest();
+    base64(&digest.bytes())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (idx, byte)| {
+            group | (*byte as u32) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * idx) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// One frame's opcode and unmasked payload. Fragmented messages aren't
+/// supported, since requests are tiny.
+fn read_frame(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too long",
+        ));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    for (idx, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[idx % 4];
+    }
+    Ok((opcode, payload))
+}
+
+/// send `payload` as a single unmasked frame, as servers do
+fn write_frame(writer: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // the example from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64(b"ab"), "YWI=");
+
+        let request = "GET / HTTP/1.1\r\nsec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+        let mut response = vec![];
+        handshake(&mut request.as_bytes(), &mut response).unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101"));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert!(handshake(&mut "GET / HTTP/1.1\r\n\r\n".as_bytes(), &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_origins() {
+        let handshake_from = |origin: &str| {
+            let request = format!(
+                "GET / HTTP/1.1\r\nOrigin: {}\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+                origin
+            );
+            let mut response = vec![];
+            let result = handshake(&mut request.as_bytes(), &mut response);
+            assert_eq!(result.is_ok(), response.starts_with(b"HTTP/1.1 101"));
+            result.is_ok()
+        };
+        for origin in [
+            "http://localhost:8000",
+            "https://127.0.0.1",
+            "http://[::1]:3000",
+        ] {
+            assert!(handshake_from(origin), "{}", origin);
+        }
+        for origin in [
+            "https://example.com",
+            "http://localhost.example.com",
+            "http://127.0.0.1.example.com:80",
+            "null",
+            "file://",
+        ] {
+            assert!(!handshake_from(origin), "{}", origin);
+        }
+    }
+
+    #[test]
+    fn test_frames() {
+        // a masked "Hello" from the client, as in RFC 6455
+        let frame = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        assert_eq!(
+            read_frame(&mut &frame[..]).unwrap(),
+            (OPCODE_TEXT, b"Hello".to_vec())
+        );
+
+        let mut sent = vec![];
+        write_frame(&mut sent, OPCODE_TEXT, &[7; 200]).unwrap();
+        assert_eq!(sent[..4], [0x81, 126, 0, 200]);
+        assert_eq!(
+            read_frame(&mut &sent[..]).unwrap(),
+            (OPCODE_TEXT, vec![7; 200])
+        );
+    }
+
+    #[test]
+    fn test_requests() {
+        let (_sender, requests) = channel();
+        let mut server = DebugServer {
+            requests,
+            keys: [false; 16],
+        };
+        let mut machine = Chip8Machine::new();
+        // LD V0, 7; JP 0x200
+        machine.load(&[0x60, 0x07, 0x12, 0x00]).unwrap();
+        let mut paused = false;
+        let mut request = |json: &str| {
+            let request = serde_json::from_str(json).unwrap();
+            server.respond(request, &mut machine, &mut paused)
+        };
+
+        assert!(request(r#"{"cmd": "step"}"#).is_err());
+        assert_eq!(request(r#"{"cmd": "pause"}"#).unwrap()["paused"], true);
+        assert_eq!(request(r#"{"cmd": "step"}"#).unwrap()["pc"], 0x202);
+        assert!(request(r#"{"cmd": "step", "count": 4294967295}"#).is_err());
+        assert_eq!(
+            request(r#"{"cmd": "step", "count": 10000}"#).unwrap()["pc"],
+            0x202
+        );
+        let state = request(r#"{"cmd": "state"}"#).unwrap();
+        assert_eq!(state["v"][0], 7);
+        assert_eq!(
+            request(r#"{"cmd": "read_memory", "addr": 512, "len": 2}"#).unwrap()["bytes"],
+            json!([0x60, 0x07])
+        );
+        assert_eq!(
+            request(r#"{"cmd": "read_memory", "addr": 4095, "len": 9}"#).unwrap()["bytes"],
+            json!([0])
+        );
+        assert_eq!(
+            request(r#"{"cmd": "add_breakpoint", "addr": 514}"#).unwrap()["breakpoints"],
+            json!([514])
+        );
+        assert_eq!(
+            request(r#"{"cmd": "key", "key": 5, "down": true}"#).unwrap()["keys"][5],
+            true
+        );
+        assert!(request(r#"{"cmd": "key", "key": 16, "down": true}"#).is_err());
+        assert!(serde_json::from_str::<Request>(r#"{"cmd": "explode"}"#).is_err());
+    }
+}
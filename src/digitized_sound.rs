@@ -0,0 +1,78 @@
+//! MegaChip's digitized sound: the ROM's 8-bit samples, converted to a WAV
+//! and played through macroquad like the buzzer.
+
+use std::collections::HashMap;
+
+use macroquad::audio::{load_sound_from_bytes, play_sound, stop_sound, PlaySoundParams, Sound};
+
+use chip8_core::megachip::SoundEvent;
+
+use crate::buzzer::pcm_wav;
+
+#[derive(Default)]
+pub struct DigitizedPlayer {
+    /// Every sound played so far, by rate and samples. macroquad can't
+    /// free sounds, so ROMs that play the same one over and over reuse it.
+    loaded: HashMap<(u16, Vec<u8>), Sound>,
+    playing: Option<Sound>,
+}
+
+impl DigitizedPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// stop whatever's playing, then start the sound `event` asks for, if any
+    pub async fn handle(&mut self, event: SoundEvent) -> Result<(), macroquad::file::FileError> {
+        if let Some(sound) = self.playing.take() {
+            stop_sound(sound);
+        }
+        let SoundEvent::Play(digitized) = event else {
+            return Ok(());
+        };
+        let key = (digitized.sample_rate, digitized.samples);
+        let sound = match self.loaded.get(&key) {
+            Some(sound) => *sound,
+            None => {
+                let sound = load_sound_from_bytes(&wav(key.0, &key.1)).await?;
+                self.loaded.insert(key, sound);
+                sound
+            }
+        };
+        play_sound(
+            sound,
+            PlaySoundParams {
+                looped: digitized.looping,
+                volume: 1.,
+            },
+        );
+        self.playing = Some(sound);
+        Ok(())
+    }
+}
+
+/// 8-bit unsigned samples as a 16-bit WAV, which every backend can decode
+fn wav(sample_rate: u16, samples: &[u8]) -> Vec<u8> {
+    pcm_wav(u32::from(sample_rate), &to_pcm(samples))
+}
+
+/// 8-bit unsigned samples as signed 16-bit ones
+pub fn to_pcm(samples: &[u8]) -> Vec<i16> {
+    samples
+        .iter()
+        .map(|sample| (i16::from(*sample) - 128) << 8)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav() {
+        let wav = wav(8000, &[0x80, 0xFF, 0x00]);
+        assert_eq!(&wav[24..28], &8000_u32.to_le_bytes());
+        let sample = |i: usize| i16::from_le_bytes([wav[44 + 2 * i], wav[45 + 2 * i]]);
+        assert_eq!([sample(0), sample(1), sample(2)], [0, 0x7F00, -0x8000]);
+    }
+}
@@ -0,0 +1,723 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+
+use chip8_core::{
+    interpreter::{decode, Op, PROGRAM_START},
+    megachip::MegaOp,
+};
+
+use crate::{
+    analyzer::{check_fits, Analysis},
+    coverage::Coverage,
+    symbols::Symbols,
+};
+
+/// An operand of a decoded instruction, kept structured so it can be colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(u8),
+    Address(u16),
+    Byte(u8),
+    Nibble(u8),
+    /// fixed operands like `I`, `DT`, or `[I]`
+    Keyword(&'static str),
+}
+
+/// How an address is referenced by another instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XrefKind {
+    Jump,
+    Call,
+    /// `LD I, addr`, usually pointing at sprite data
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Xref {
+    pub from: u16,
+    pub kind: XrefKind,
+}
+
+/// mnemonic and operands of an op, in Cowgod's notation
+pub fn parts(op: &Op) -> (&'static str, Vec<Operand>) {
+    use Operand::*;
+    match *op {
+        Op::Cls => ("CLS", vec![]),
+        Op::Ret => ("RET", vec![]),
+        Op::Sys => ("SYS", vec![]),
+        Op::Exit => ("EXIT", vec![]),
+        Op::Jp { nnn } => ("JP", vec![Address(nnn)]),
+        Op::Call { nnn } => ("CALL", vec![Address(nnn)]),
+        Op::Se { x, nn } => ("SE", vec![Register(x), Byte(nn)]),
+        Op::Sne { x, nn } => ("SNE", vec![Register(x), Byte(nn)]),
+        Op::SeVxVy { x, y } => ("SE", vec![Register(x), Register(y)]),
+        Op::Ld { x, nn } => ("LD", vec![Register(x), Byte(nn)]),
+        Op::Add { x, nn } => ("ADD", vec![Register(x), Byte(nn)]),
+        Op::LdVxVy { x, y } => ("LD", vec![Register(x), Register(y)]),
+        Op::OrVxVy { x, y } => ("OR", vec![Register(x), Register(y)]),
+        Op::AndVxVy { x, y } => ("AND", vec![Register(x), Register(y)]),
+        Op::XorVxVy { x, y } => ("XOR", vec![Register(x), Register(y)]),
+        Op::AddVxVy { x, y } => ("ADD", vec![Register(x), Register(y)]),
+        Op::SubVxVy { x, y } => ("SUB", vec![Register(x), Register(y)]),
+        Op::ShrVxVy { x, y } => ("SHR", vec![Register(x), Register(y)]),
+        Op::SubnVxVy { x, y } => ("SUBN", vec![Register(x), Register(y)]),
+        Op::ShlVxVy { x, y } => ("SHL", vec![Register(x), Register(y)]),
+        Op::SneVxVy { x, y } => ("SNE", vec![Register(x), Register(y)]),
+        Op::LdI { nnn } => ("LD", vec![Keyword("I"), Address(nnn)]),
+        Op::JpV0 { nnn } => ("JP", vec![Register(0), Address(nnn)]),
+        Op::Rnd { x, nn } => ("RND", vec![Register(x), Byte(nn)]),
+        Op::Drw { x, y, n } => ("DRW", vec![Register(x), Register(y), Nibble(n)]),
+        Op::Skp { x } => ("SKP", vec![Register(x)]),
+        Op::Sknp { x } => ("SKNP", vec![Register(x)]),
+        Op::LdVxDt { x } => ("LD", vec![Register(x), Keyword("DT")]),
+        Op::LdVxK { x } => ("LD", vec![Register(x), Keyword("K")]),
+        Op::LdDtVx { x } => ("LD", vec![Keyword("DT"), Register(x)]),
+        Op::LdStVx { x } => ("LD", vec![Keyword("ST"), Register(x)]),
+        Op::AddIVx { x } => ("ADD", vec![Keyword("I"), Register(x)]),
+        Op::LdFVx { x } => ("LD", vec![Keyword("F"), Register(x)]),
+        Op::LdBVx { x } => ("LD", vec![Keyword("B"), Register(x)]),
+        Op::LdIVx { x } => ("LD", vec![Keyword("[I]"), Register(x)]),
+        Op::LdVxI { x } => ("LD", vec![Register(x), Keyword("[I]")]),
+        Op::LdRVx { x } => ("LD", vec![Keyword("R"), Register(x)]),
+        Op::LdVxR { x } => ("LD", vec![Register(x), Keyword("R")]),
+        Op::Mega(op) => match op {
+            MegaOp::Off => ("MEGAOFF", vec![]),
+            MegaOp::On => ("MEGAON", vec![]),
+            MegaOp::LdIHi { nn } => ("LDHI", vec![Keyword("I"), Byte(nn)]),
+            MegaOp::LdPal { nn } => ("LDPAL", vec![Byte(nn)]),
+            MegaOp::SprW { nn } => ("SPRW", vec![Byte(nn)]),
+            MegaOp::SprH { nn } => ("SPRH", vec![Byte(nn)]),
+            MegaOp::Alpha { nn } => ("ALPHA", vec![Byte(nn)]),
+            MegaOp::DigiSnd { n } => ("DIGISND", vec![Nibble(n)]),
+            MegaOp::StopSnd => ("STOPSND", vec![]),
+            MegaOp::BlendMode { n } => ("BMODE", vec![Nibble(n)]),
+            MegaOp::CollisionColor { nn } => ("CCOL", vec![Byte(nn)]),
+            MegaOp::ScrollUp { n } => ("SCRU", vec![Nibble(n)]),
+        },
+        Op::Invalid => ("???", vec![]),
+    }
+}
+
+/// what an op does, in words, with its actual registers
+pub fn explain(op: &Op) -> String {
+    match *op {
+        Op::Cls => String::from("clear the screen"),
+        Op::Ret => String::from("return from subroutine"),
+        Op::Sys => String::from("machine code routine, ignored"),
+        Op::Exit => String::from("stop the program"),
+        Op::Jp { .. } => String::from("jump"),
+        Op::Call { .. } => String::from("call subroutine"),
+        Op::Se { x, nn } => format!("skip next if V{:X} == {:#04x}", x, nn),
+        Op::Sne { x, nn } => format!("skip next if V{:X} != {:#04x}", x, nn),
+        Op::SeVxVy { x, y } => format!("skip next if V{:X} == V{:X}", x, y),
+        Op::Ld { x, nn } => format!("V{:X} = {:#04x}", x, nn),
+        Op::Add { x, nn } => format!("V{:X} += {:#04x}", x, nn),
+        Op::LdVxVy { x, y } => format!("V{:X} = V{:X}", x, y),
+        Op::OrVxVy { x, y } => format!("V{:X} |= V{:X}", x, y),
+        Op::AndVxVy { x, y } => format!("V{:X} &= V{:X}", x, y),
+        Op::XorVxVy { x, y } => format!("V{:X} ^= V{:X}", x, y),
+        Op::AddVxVy { x, y } => format!("V{:X} += V{:X}, VF = carry", x, y),
+        Op::SubVxVy { x, y } => format!("V{:X} -= V{:X}, VF = no borrow", x, y),
+        Op::ShrVxVy { x, .. } => format!("V{:X} >>= 1, VF = bit shifted out", x),
+        Op::SubnVxVy { x, y } => format!("V{0:X} = V{1:X} - V{0:X}, VF = no borrow", x, y),
+        Op::ShlVxVy { x, .. } => format!("V{:X} <<= 1, VF = bit shifted out", x),
+        Op::SneVxVy { x, y } => format!("skip next if V{:X} != V{:X}", x, y),
+        Op::LdI { .. } => String::from("set I"),
+        Op::JpV0 { .. } => String::from("jump, offset by V0"),
+        Op::Rnd { x, nn } => format!("V{:X} = random & {:#04x}", x, nn),
+        Op::Drw { x, y, n } => format!(
+            "draw {} row sprite from I at (V{:X}, V{:X}), VF = collision",
+            n, x, y
+        ),
+        Op::Skp { x } => format!("skip next if key V{:X} is down", x),
+        Op::Sknp { x } => format!("skip next if key V{:X} is up", x),
+        Op::LdVxDt { x } => format!("V{:X} = delay timer", x),
+        Op::LdVxK { x } => format!("wait for a key press, V{:X} = key", x),
+        Op::LdDtVx { x } => format!("delay timer = V{:X}", x),
+        Op::LdStVx { x } => format!("sound timer = V{:X}", x),
+        Op::AddIVx { x } => format!("I += V{:X}", x),
+        Op::LdFVx { x } => format!("I = font sprite for digit V{:X}", x),
+        Op::LdBVx { x } => format!("store V{:X} as 3 decimal digits from I", x),
+        Op::LdIVx { x } => format!("store V0-V{:X} from I", x),
+        Op::LdVxI { x } => format!("load V0-V{:X} from I", x),
+        Op::LdRVx { x } => format!("save V0-V{:X} to the RPL flags", x),
+        Op::LdVxR { x } => format!("load V0-V{:X} from the RPL flags", x),
+        Op::Mega(op) => match op {
+            MegaOp::Off => String::from("MegaChip: leave Mega mode"),
+            MegaOp::On => String::from("MegaChip: enter Mega mode"),
+            MegaOp::LdIHi { nn } => format!("MegaChip: I = {:#04x} << 16 | next word", nn),
+            MegaOp::LdPal { nn } => format!("MegaChip: load {} palette colors from I", nn),
+            MegaOp::SprW { nn } => format!("MegaChip: sprite width = {}", nn),
+            MegaOp::SprH { nn } => format!("MegaChip: sprite height = {}", nn),
+            MegaOp::Alpha { nn } => format!("MegaChip: screen alpha = {:#04x}", nn),
+            MegaOp::DigiSnd { n: 1 } => String::from("MegaChip: play the sound at I once"),
+            MegaOp::DigiSnd { .. } => String::from("MegaChip: play the sound at I on a loop"),
+            MegaOp::StopSnd => String::from("MegaChip: stop the sound"),
+            MegaOp::BlendMode { n } => format!("MegaChip: blend mode {}", n),
+            MegaOp::CollisionColor { nn } => format!("MegaChip: collide with color {}", nn),
+            MegaOp::ScrollUp { n } => format!("MegaChip: scroll up {} lines", n),
+        },
+        Op::Invalid => String::from("not an instruction, likely data"),
+    }
+}
+
+/// How to render a `Disassembly`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// ANSI colors for terminals
+    pub color: bool,
+    /// a comment after each instruction saying what it does
+    pub explain: bool,
+}
+
+/// instructions are padded to this width before an explanation
+const EXPLAIN_COLUMN: usize = 32;
+
+struct Line {
+    addr: u16,
+    word: u16,
+    op: Op,
+}
+
+/// A decoded ROM listing, with labels for every jump/call target.
+pub struct Disassembly {
+    lines: Vec<Line>,
+    labels: BTreeMap<u16, String>,
+    xrefs: BTreeMap<u16, Vec<Xref>>,
+    /// what ran in a session, to mark instructions by
+    coverage: Option<Coverage>,
+}
+
+/// ANSI escape codes used when rendering with color
+mod style {
+    pub const MNEMONIC: &str = "\x1b[1;36m";
+    pub const REGISTER: &str = "\x1b[33m";
+    pub const ADDRESS: &str = "\x1b[32m";
+    pub const IMMEDIATE: &str = "\x1b[35m";
+    pub const COMMENT: &str = "\x1b[2m";
+    pub const RESET: &str = "\x1b[0m";
+}
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("{}{}{}", style, text, style::RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+fn format_operand(operand: Operand, label: Option<&str>, color: bool) -> String {
+    match operand {
+        Operand::Register(x) => paint(&format!("V{:X}", x), style::REGISTER, color),
+        Operand::Address(addr) => match label {
+            Some(label) => paint(label, style::ADDRESS, color),
+            None => paint(&format!("{:#05x}", addr), style::ADDRESS, color),
+        },
+        Operand::Byte(nn) => paint(&format!("{:#04x}", nn), style::IMMEDIATE, color),
+        Operand::Nibble(n) => paint(&format!("{}", n), style::IMMEDIATE, color),
+        Operand::Keyword(k) => paint(k, style::REGISTER, color),
+    }
+}
+
+/// a single op as plain text, e.g. "LD V0, 0x2a"
+pub fn format_op(op: &Op) -> String {
+    format_op_named(op, &Symbols::default())
+}
+
+/// a single op as plain text, with the addresses `symbols` names by name
+pub fn format_op_named(op: &Op, symbols: &Symbols) -> String {
+    let (mnemonic, operands) = parts(op);
+    let operands: Vec<String> = operands
+        .into_iter()
+        .map(|o| {
+            let name = match o {
+                Operand::Address(addr) => symbols.name(addr),
+                _ => None,
+            };
+            format_operand(o, name, false)
+        })
+        .collect();
+    format!("{} {}", mnemonic, operands.join(", "))
+        .trim_end()
+        .to_string()
+}
+
+/// where each address is referenced from, by the ops at the given addresses
+fn find_xrefs(ops: impl Iterator<Item = (u16, Op)>) -> BTreeMap<u16, Vec<Xref>> {
+    let mut xrefs: BTreeMap<u16, Vec<Xref>> = BTreeMap::new();
+    for (from, op) in ops {
+        let target = match op {
+            Op::Jp { nnn } | Op::JpV0 { nnn } => Some((nnn, XrefKind::Jump)),
+            Op::Call { nnn } => Some((nnn, XrefKind::Call)),
+            Op::LdI { nnn } => Some((nnn, XrefKind::Index)),
+            _ => None,
+        };
+        if let Some((addr, kind)) = target {
+            xrefs.entry(addr).or_default().push(Xref { from, kind });
+        }
+    }
+    xrefs
+}
+
+/// e.g. `sub_206` for a subroutine, `data_208` for sprites, `L_202` otherwise
+fn label_name(addr: u16, refs: &[Xref]) -> String {
+    let prefix = if refs.iter().any(|r| r.kind == XrefKind::Call) {
+        "sub"
+    } else if refs.iter().all(|r| r.kind == XrefKind::Index) {
+        "data"
+    } else {
+        "L"
+    };
+    format!("{}_{:03X}", prefix, addr)
+}
+
+/// e.g. "called from 0x200; loaded into I from 0x20a"
+fn describe_xrefs(refs: &[Xref]) -> Option<String> {
+    let mut groups: Vec<String> = vec![];
+    for (kind, verb) in [
+        (XrefKind::Call, "called from"),
+        (XrefKind::Jump, "jumped to from"),
+        (XrefKind::Index, "loaded into I from"),
+    ] {
+        let sources: Vec<String> = refs
+            .iter()
+            .filter(|x| x.kind == kind)
+            .map(|x| format!("{:#05x}", x.from))
+            .collect();
+        if !sources.is_empty() {
+            groups.push(format!("{} {}", verb, sources.join(", ")));
+        }
+    }
+    if groups.is_empty() {
+        None
+    } else {
+        Some(groups.join("; "))
+    }
+}
+
+/// `op` as a statement `asm` assembles back into `word`, with addresses
+/// written by `addr`, or None for words it has no statement for
+fn octo_statement(op: &Op, word: u16, addr: impl Fn(u16) -> String) -> Option<String> {
+    let v = |x: u8| format!("v{:x}", x);
+    Some(match *op {
+        Op::Cls => String::from("clear"),
+        Op::Ret => String::from("return"),
+        Op::Exit => String::from("exit"),
+        // SUPER-CHIP and MegaChip's 0NNN instructions assemble the same way
+        Op::Sys | Op::Mega(_) => format!("native {:#05x}", word & 0x0FFF),
+        Op::Jp { nnn } => format!("jump {}", addr(nnn)),
+        Op::Call { nnn } => format!(":call {}", addr(nnn)),
+        // a skip is an `if` whose statement is the next line
+        Op::Se { x, nn } => format!("if {} != {:#04x} then", v(x), nn),
+        Op::Sne { x, nn } => format!("if {} == {:#04x} then", v(x), nn),
+        Op::SeVxVy { x, y } => format!("if {} != {} then", v(x), v(y)),
+        Op::SneVxVy { x, y } => format!("if {} == {} then", v(x), v(y)),
+        Op::Skp { x } => format!("if {} -key then", v(x)),
+        Op::Sknp { x } => format!("if {} key then", v(x)),
+        Op::Ld { x, nn } => format!("{} := {:#04x}", v(x), nn),
+        Op::Add { x, nn } => format!("{} += {:#04x}", v(x), nn),
+        Op::LdVxVy { x, y } => format!("{} := {}", v(x), v(y)),
+        Op::OrVxVy { x, y } => format!("{} |= {}", v(x), v(y)),
+        Op::AndVxVy { x, y } => format!("{} &= {}", v(x), v(y)),
+        Op::XorVxVy { x, y } => format!("{} ^= {}", v(x), v(y)),
+        Op::AddVxVy { x, y } => format!("{} += {}", v(x), v(y)),
+        Op::SubVxVy { x, y } => format!("{} -= {}", v(x), v(y)),
+        Op::ShrVxVy { x, y } => format!("{} >>= {}", v(x), v(y)),
+        Op::SubnVxVy { x, y } => format!("{} =- {}", v(x), v(y)),
+        Op::ShlVxVy { x, y } => format!("{} <<= {}", v(x), v(y)),
+        Op::LdI { nnn } => format!("i := {}", addr(nnn)),
+        Op::JpV0 { nnn } => format!("jump0 {}", addr(nnn)),
+        Op::Rnd { x, nn } => format!("{} := random {:#04x}", v(x), nn),
+        Op::Drw { x, y, n } => format!("sprite {} {} {}", v(x), v(y), n),
+        Op::LdVxDt { x } => format!("{} := delay", v(x)),
+        Op::LdVxK { x } => format!("{} := key", v(x)),
+        Op::LdDtVx { x } => format!("delay := {}", v(x)),
+        Op::LdStVx { x } => format!("buzzer := {}", v(x)),
+        Op::AddIVx { x } => format!("i += {}", v(x)),
+        Op::LdFVx { x } => format!("i := hex {}", v(x)),
+        Op::LdBVx { x } => format!("bcd {}", v(x)),
+        Op::LdIVx { x } => format!("save {}", v(x)),
+        Op::LdVxI { x } => format!("load {}", v(x)),
+        Op::LdRVx { x } => format!("saveflags {}", v(x)),
+        Op::LdVxR { x } => format!("loadflags {}", v(x)),
+        Op::Invalid => return None,
+    })
+}
+
+fn octo_bytes(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:#04x}", b)).collect();
+    bytes.join(" ")
+}
+
+/// Disassemble `rom` to Octo source that `asm` assembles back into the same
+/// bytes, for a ROM loaded at 0x200 as `asm` expects.
+///
+/// This takes two passes: the analyzer follows the control flow to tell
+/// code from data, then the code's jumps, calls, and `LD I`s give the
+/// labels. Data is written as bytes, in rows that start at each label, and
+/// each label has a comment saying where it's referenced from. Targets in
+/// the middle of an instruction or outside the ROM are left as addresses.
+/// ROMs too big to fit below 0x10000 are refused.
+pub fn octo_source(rom: &[u8]) -> Result<String, String> {
+    let start = PROGRAM_START as u16;
+    let analysis = Analysis::new(rom, start)?;
+    let at = |offset: usize| (start as usize + offset) as u16;
+    let word = |addr: u16| {
+        let offset = (addr - start) as usize;
+        u16::from_be_bytes([rom[offset], *rom.get(offset + 1).unwrap_or(&0)])
+    };
+    let xrefs = find_xrefs(
+        analysis
+            .code
+            .keys()
+            .map(|addr| (*addr, decode(word(*addr)))),
+    );
+    let labels: BTreeMap<u16, String> = xrefs
+        .iter()
+        .filter(|(addr, _)| {
+            **addr >= start
+                && ((**addr - start) as usize) < rom.len()
+                && (analysis.code.contains_key(addr) || !analysis.is_code(**addr))
+        })
+        .map(|(addr, refs)| (*addr, label_name(*addr, refs)))
+        .collect();
+    let addr = |addr: u16| {
+        labels
+            .get(&addr)
+            .cloned()
+            .unwrap_or_else(|| format!("{:#05x}", addr))
+    };
+
+    let mut out = String::from(
+        "# disassembled by chip8-rust; `chip8-rust asm` builds it back into the ROM\n",
+    );
+    let mut offset = 0;
+    while offset < rom.len() {
+        let pc = at(offset);
+        if let Some(label) = labels.get(&pc) {
+            out.push('\n');
+            let _ = match describe_xrefs(&xrefs[&pc]) {
+                Some(xrefs) => writeln!(out, ": {}  # {}", label, xrefs),
+                None => writeln!(out, ": {}", label),
+            };
+        }
+        let (text, len) = match analysis.code.get(&pc).map(|len| *len as usize) {
+            Some(len) if offset + len <= rom.len() => {
+                let op = decode(word(pc));
+                // an `if` at the end would have no statement to skip
+                let statement = octo_statement(&op, word(pc), addr)
+                    .filter(|s| !s.ends_with(" then") || offset + len < rom.len());
+                match statement {
+                    Some(statement) if len == 2 => (statement, 2),
+                    _ => (octo_bytes(&rom[offset..offset + len]), len),
+                }
+            }
+            _ => {
+                // data runs until the next instruction or label, 8 bytes a row
+                let len = (offset + 1..rom.len())
+                    .take(7)
+                    .map(at)
+                    .position(|addr| {
+                        analysis.code.contains_key(&addr) || labels.contains_key(&addr)
+                    })
+                    .map_or((rom.len() - offset).min(8), |idx| idx + 1);
+                (octo_bytes(&rom[offset..offset + len]), len)
+            }
+        };
+        let _ = writeln!(out, "  {:<28}# {:#05x}", text, pc);
+        offset += len;
+    }
+    Ok(out)
+}
+
+/// Decode `rom` as if it were loaded at `start` (usually 0x200).
+///
+/// Every word is treated as an instruction, so sprite data shows up as
+/// (often invalid) opcodes. ROMs running past 0xFFFF are refused, as their
+/// addresses wouldn't fit in an instruction.
+pub fn disassemble(rom: &[u8], start: u16) -> Result<Disassembly, String> {
+    check_fits(rom, start)?;
+    let lines: Vec<Line> = rom
+        .chunks(2)
+        .enumerate()
+        .map(|(idx, pair)| {
+            let word = ((pair[0] as u16) << 8) | *pair.get(1).unwrap_or(&0) as u16;
+            Line {
+                addr: (start as usize + idx * 2) as u16,
+                word,
+                op: decode(word),
+            }
+        })
+        .collect();
+
+    let xrefs = find_xrefs(lines.iter().map(|line| (line.addr, line.op)));
+    // only label targets that land on the start of a listed word
+    let labels = xrefs
+        .iter()
+        .filter(|(addr, _)| lines.iter().any(|l| l.addr == **addr))
+        .map(|(addr, refs)| (*addr, label_name(*addr, refs)))
+        .collect();
+
+    Ok(Disassembly {
+        lines,
+        labels,
+        xrefs,
+        coverage: None,
+    })
+}
+
+impl Disassembly {
+    /// use the names in `symbols` for labels, labeling their addresses even
+    /// if nothing jumps there
+    pub fn with_symbols(mut self, symbols: &Symbols) -> Self {
+        for (addr, name) in symbols.iter() {
+            if self.lines.iter().any(|l| l.addr == addr) {
+                self.labels.insert(addr, name.to_string());
+            }
+        }
+        self
+    }
+
+    /// Mark each instruction by whether it ran, with `+` before those that
+    /// did, and with color, dimming those that didn't.
+    pub fn with_coverage(mut self, coverage: Coverage) -> Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    pub fn xrefs(&self, addr: u16) -> &[Xref] {
+        self.xrefs.get(&addr).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    fn format_operand(&self, operand: Operand, color: bool) -> String {
+        let label = match operand {
+            Operand::Address(addr) => self.label(addr),
+            _ => None,
+        };
+        format_operand(operand, label, color)
+    }
+
+    fn format_xrefs(&self, addr: u16) -> Option<String> {
+        describe_xrefs(self.xrefs(addr)).map(|xrefs| format!("; {}", xrefs))
+    }
+
+    /// Render the listing, optionally with ANSI colors and explanations.
+    pub fn render(&self, options: RenderOptions) -> String {
+        let color = options.color;
+        let mut out = String::new();
+        for line in &self.lines {
+            let ran = self.coverage.as_ref().map(|c| c.is_executed(line.addr));
+            if let Some(label) = self.label(line.addr) {
+                let _ = write!(out, "{}:", paint(label, style::ADDRESS, color));
+                if let Some(comment) = self.format_xrefs(line.addr) {
+                    let _ = write!(out, "  {}", paint(&comment, style::COMMENT, color));
+                }
+                out.push('\n');
+            }
+
+            // lines that never ran are dimmed as a whole
+            let dimmed = color && ran == Some(false);
+            let color = color && !dimmed;
+            let (mnemonic, operands) = parts(&line.op);
+            let plain_operands: Vec<String> = operands
+                .iter()
+                .map(|o| self.format_operand(*o, false))
+                .collect();
+            let operands: Vec<String> = operands
+                .into_iter()
+                .map(|o| self.format_operand(o, color))
+                .collect();
+            // pad before painting so escape codes don't throw off alignment
+            let mut text = format!(
+                "{:#05x}:  {:04X}  {} {}",
+                line.addr,
+                line.word,
+                paint(&format!("{:<5}", mnemonic), style::MNEMONIC, color),
+                operands.join(", ")
+            );
+            if options.explain {
+                let plain_len = format!(
+                    "{:#05x}:  {:04X}  {:<5} {}",
+                    line.addr,
+                    line.word,
+                    mnemonic,
+                    plain_operands.join(", ")
+                )
+                .len();
+                let padding = EXPLAIN_COLUMN.saturating_sub(plain_len);
+                let comment = format!("; {}", explain(&line.op));
+                let _ = write!(
+                    text,
+                    "{}{}",
+                    " ".repeat(padding),
+                    paint(&comment, style::COMMENT, color)
+                );
+            }
+            let text = match ran {
+                Some(ran) => format!("{} {}", if ran { '+' } else { ' ' }, text.trim_end()),
+                None => text.trim_end().to_string(),
+            };
+            let _ = writeln!(out, "{}", paint(&text, style::COMMENT, dimmed));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(RenderOptions::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: CALL 0x206
+    // 0x202: JP 0x202
+    // 0x204: LD I, 0x208
+    // 0x206: RET
+    // 0x208: sprite data
+    const ROM: [u8; 10] = [0x22, 0x06, 0x12, 0x02, 0xA2, 0x08, 0x00, 0xEE, 0xFF, 0x81];
+
+    #[test]
+    fn test_labels_and_xrefs() {
+        let disasm = disassemble(&ROM, 0x200).unwrap();
+        assert_eq!(disasm.label(0x206), Some("sub_206"));
+        assert_eq!(disasm.label(0x202), Some("L_202"));
+        assert_eq!(disasm.label(0x208), Some("data_208"));
+        assert_eq!(disasm.label(0x200), None);
+        assert_eq!(
+            disasm.xrefs(0x206),
+            &[Xref {
+                from: 0x200,
+                kind: XrefKind::Call
+            }]
+        );
+    }
+
+    #[test]
+    fn test_render_plain() {
+        let listing = disassemble(&ROM, 0x200)
+            .unwrap()
+            .render(RenderOptions::default());
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines[0], "0x200:  2206  CALL  sub_206");
+        assert_eq!(lines[1], "L_202:  ; jumped to from 0x202");
+        assert_eq!(lines[2], "0x202:  1202  JP    L_202");
+        assert!(listing.contains("sub_206:  ; called from 0x200\n0x206:  00EE  RET"));
+        assert!(!listing.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_octo_source() {
+        let source = octo_source(&ROM).unwrap();
+        assert!(source.contains(": sub_206  # called from 0x200\n  return"));
+        assert!(source.contains(": L_202  # jumped to from 0x202\n  jump L_202"));
+        // LD I at 0x204 is never reached, so it and the sprite it points at are data
+        assert!(source.contains("\n  0xa2 0x08 "));
+        assert!(!source.contains("data_208"));
+        assert_eq!(crate::assembler::assemble(&source).unwrap(), ROM);
+    }
+
+    #[test]
+    fn test_octo_source_round_trips() {
+        for (name, rom) in crate::library::ROMS {
+            let source = octo_source(rom).unwrap();
+            let rebuilt = crate::assembler::assemble(&source)
+                .unwrap_or_else(|e| panic!("{}: {}\n{}", name, e, source));
+            assert_eq!(rebuilt, *rom, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_refuses_roms_past_0xffff() {
+        let rom = vec![0; 70_000];
+        assert!(disassemble(&rom, 0x200).is_err());
+        assert!(octo_source(&rom).is_err());
+
+        // JP 0x200, then data up to 0xFFFF
+        let mut rom = vec![0xFF; 0x10000 - 0x200];
+        rom[..2].copy_from_slice(&[0x12, 0x00]);
+        let disasm = disassemble(&rom, 0x200).unwrap();
+        assert_eq!(disasm.lines.last().map(|line| line.addr), Some(0xFFFE));
+        let source = octo_source(&rom).unwrap();
+        assert!(source.ends_with("# 0xfffa\n"));
+        assert!(disassemble(&rom[..0x10000 - 0x300], 0x300).is_ok());
+        assert!(disassemble(&rom, 0x300).is_err());
+    }
+
+    #[test]
+    fn test_with_symbols() {
+        let symbols = Symbols::parse("0x200 start\n0x206 reset_ball\n0x300 elsewhere").unwrap();
+        let listing = disassemble(&ROM, 0x200)
+            .unwrap()
+            .with_symbols(&symbols)
+            .to_string();
+        assert!(listing.starts_with("start:\n0x200:  2206  CALL  reset_ball\n"));
+        assert!(listing.contains("reset_ball:  ; called from 0x200\n"));
+        assert!(!listing.contains("elsewhere"));
+        assert_eq!(format_op_named(&decode(0x1300), &symbols), "JP elsewhere");
+    }
+
+    #[test]
+    fn test_render_coverage() {
+        let mut coverage = Coverage::new();
+        coverage.record(0x200, 0x2206);
+        coverage.record(0x206, 0x00EE);
+        let disasm = disassemble(&ROM, 0x200).unwrap().with_coverage(coverage);
+        let listing = disasm.to_string();
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines[0], "+ 0x200:  2206  CALL  sub_206");
+        assert_eq!(lines[2], "  0x202:  1202  JP    L_202");
+        assert!(listing.contains("sub_206:  ; called from 0x200\n+ 0x206:  00EE  RET"));
+
+        let listing = disasm.render(RenderOptions {
+            color: true,
+            ..Default::default()
+        });
+        assert!(listing.contains("\x1b[2m  0x202:  1202  JP    L_202\x1b[0m"));
+        assert!(listing.contains("+ 0x200:  2206  \x1b[1;36mCALL"));
+    }
+
+    #[test]
+    fn test_format_op() {
+        assert_eq!(format_op(&decode(0x6A42)), "LD VA, 0x42");
+        assert_eq!(format_op(&decode(0x2206)), "CALL 0x206");
+        assert_eq!(format_op(&decode(0x00EE)), "RET");
+        assert_eq!(format_op(&decode(0x00FD)), "EXIT");
+    }
+
+    #[test]
+    fn test_render_explained() {
+        let listing = disassemble(&ROM, 0x200).unwrap().render(RenderOptions {
+            explain: true,
+            ..Default::default()
+        });
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(
+            lines[0],
+            "0x200:  2206  CALL  sub_206     ; call subroutine"
+        );
+        let ret = lines.iter().find(|l| l.starts_with("0x206")).unwrap();
+        assert_eq!(ret.find(';'), Some(EXPLAIN_COLUMN));
+        assert!(ret.ends_with("; return from subroutine"));
+        assert_eq!(explain(&decode(0x8AB5)), "VA -= VB, VF = no borrow");
+    }
+
+    #[test]
+    fn test_render_color() {
+        let listing = disassemble(&ROM, 0x200).unwrap().render(RenderOptions {
+            color: true,
+            ..Default::default()
+        });
+        assert!(listing.contains("\x1b[1;36mCALL \x1b[0m"));
+        assert!(listing.contains("\x1b[32msub_206\x1b[0m"));
+    }
+}
@@ -0,0 +1,152 @@
+//! The CHIP-8 display as a single 64x64 texture, updated each frame and
+//! drawn scaled up with nearest filtering, rather than as a rectangle per
+//! pixel. Only the top 32 rows are shown unless the ROM is hires.
+//! MegaChip's 256x192 display gets a texture of its own.
+
+use macroquad::prelude::*;
+
+use chip8_core::{
+    interpreter::{dirty_pixels, is_lit, Rows, HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT, SCREEN_WIDTH},
+    megachip::{MEGA_HEIGHT, MEGA_WIDTH},
+};
+
+use crate::{palette::Palette, phosphor::Phosphor};
+
+pub struct Display {
+    /// how lit each pixel currently looks, from 0 to 1, so pixels that
+    /// switch off fade out over a few frames
+    brightness: [f32; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+    phosphor: Phosphor,
+    /// the rows the brightness last faded towards
+    drawn: Rows,
+    /// the pixels still fading in or out, which need a step every frame
+    /// even when the rows don't change
+    fading: Vec<usize>,
+    /// the palette `image` is in, or none before the first `update`
+    palette: Option<Palette>,
+    image: Image,
+    texture: Texture2D,
+    /// how many rows of the texture are in use
+    height: usize,
+    /// MegaChip's display, made the first time a ROM enters Mega mode
+    mega: Option<(Image, Texture2D)>,
+    /// how opaque MegaChip's display is drawn
+    mega_alpha: f32,
+    /// whether `draw` shows MegaChip's display rather than the CHIP-8 one
+    showing_mega: bool,
+}
+
+impl Display {
+    pub fn new() -> Self {
+        let image = Image::gen_image_color(SCREEN_WIDTH as u16, HIRES_SCREEN_HEIGHT as u16, BLACK);
+        let texture = Texture2D::from_image(&image);
+        texture.set_filter(FilterMode::Nearest);
+        Display {
+            brightness: [0.; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+            phosphor: Phosphor::default(),
+            drawn: [0; HIRES_SCREEN_HEIGHT],
+            fading: vec![],
+            palette: None,
+            image,
+            texture,
+            height: SCREEN_HEIGHT,
+            mega: None,
+            mega_alpha: 1.,
+            showing_mega: false,
+        }
+    }
+
+    pub fn phosphor(&self) -> Phosphor {
+        self.phosphor
+    }
+
+    pub fn set_phosphor(&mut self, phosphor: Phosphor) {
+        self.phosphor = phosphor;
+    }
+
+    /// fade each pixel towards `rows`, of which `height` are shown, and
+    /// upload the result in `palette`'s colors. Only the pixels that
+    /// changed or are still fading are redrawn, and nothing is uploaded
+    /// when there are none, unless the palette changed.
+    pub fn update(&mut self, rows: &Rows, height: usize, palette: &Palette) {
+        self.height = height;
+        self.showing_mega = false;
+        let redraw = if self.palette == Some(*palette) {
+            let mut redraw = dirty_pixels(&self.drawn, rows);
+            redraw.append(&mut self.fading);
+            redraw.sort_unstable();
+            redraw.dedup();
+            redraw
+        } else {
+            self.palette = Some(*palette);
+            self.fading.clear();
+            (0..self.brightness.len()).collect()
+        };
+        if redraw.is_empty() {
+            return;
+        }
+        for idx in redraw {
+            let on = is_lit(rows, idx % SCREEN_WIDTH, idx / SCREEN_WIDTH);
+            let brightness = self.phosphor.step(self.brightness[idx], on);
+            self.brightness[idx] = brightness;
+            if brightness != if on { 1. } else { 0. } {
+                self.fading.push(idx);
+            }
+            let color: [u8; 4] = palette.blend(brightness).into();
+            self.image.bytes[idx * 4..idx * 4 + 4].copy_from_slice(&color);
+        }
+        self.drawn = *rows;
+        self.texture.update(&self.image);
+    }
+
+    /// upload MegaChip's display, one ARGB color per pixel, for `draw` to show until the next `update`
+    pub fn update_mega(&mut self, screen: &[u32], alpha: u8) {
+        let (image, texture) = self.mega.get_or_insert_with(|| {
+            let image = Image::gen_image_color(MEGA_WIDTH as u16, MEGA_HEIGHT as u16, BLACK);
+            let texture = Texture2D::from_image(&image);
+            texture.set_filter(FilterMode::Nearest);
+            (image, texture)
+        });
+        for (rgba, argb) in image.bytes.chunks_exact_mut(4).zip(screen) {
+            let [_, r, g, b] = argb.to_be_bytes();
+            rgba.copy_from_slice(&[r, g, b, 0xFF]);
+        }
+        texture.update(image);
+        self.mega_alpha = alpha as f32 / 255.;
+        self.showing_mega = true;
+    }
+
+    /// the size of the display `draw` shows, in its own pixels
+    pub fn size(&self) -> (f32, f32) {
+        if self.showing_mega {
+            (MEGA_WIDTH as f32, MEGA_HEIGHT as f32)
+        } else {
+            (SCREEN_WIDTH as f32, self.height as f32)
+        }
+    }
+
+    /// draw the display stretched over `dest`
+    pub fn draw(&self, dest: Rect) {
+        let (texture, tint, source) = match &self.mega {
+            Some((_, texture)) if self.showing_mega => {
+                (*texture, Color::new(1., 1., 1., self.mega_alpha), None)
+            }
+            _ => (
+                self.texture,
+                WHITE,
+                Some(Rect::new(0., 0., SCREEN_WIDTH as f32, self.height as f32)),
+            ),
+        };
+        draw_texture_ex(
+            texture,
+            dest.x,
+            dest.y,
+            tint,
+            DrawTextureParams {
+                dest_size: Some(vec2(dest.w, dest.h)),
+                source,
+                ..Default::default()
+            },
+        );
+    }
+}
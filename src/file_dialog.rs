@@ -0,0 +1,22 @@
+//! Choosing a ROM with the system's file dialog, through rfd behind the
+//! default `file-dialog` feature. There's no dialog in the browser, since
+//! there is nowhere to read the file from.
+
+/// whether `pick_rom` can show a dialog in this build
+pub const AVAILABLE: bool = cfg!(all(feature = "file-dialog", not(target_arch = "wasm32")));
+
+/// ask for a ROM or `.8o` source file, returning None if the user cancels
+#[cfg(all(feature = "file-dialog", not(target_arch = "wasm32")))]
+pub fn pick_rom() -> Option<String> {
+    let path = rfd::FileDialog::new()
+        .set_title("Open ROM")
+        .add_filter("CHIP-8 ROMs", &["ch8", "c8", "rom", "8o"])
+        .add_filter("All files", &["*"])
+        .pick_file()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+#[cfg(not(all(feature = "file-dialog", not(target_arch = "wasm32"))))]
+pub fn pick_rom() -> Option<String> {
+    None
+}
@@ -0,0 +1,66 @@
+use chip8_core::{HaltReason, RunState};
+use macroquad::prelude::*;
+
+use crate::ui_style::{FONT_SIZE, MARGIN, OVERLAY_BACKGROUND};
+
+/// What to tell the player when the program has finished, e.g. by ending
+/// on a jump to itself, with `reset` the hotkey that starts it over.
+/// Errors aren't finishing, they're reported when they happen.
+pub fn notice(state: &RunState, reset: &str) -> Option<String> {
+    let why = match state {
+        RunState::Halted {
+            reason: reason @ (HaltReason::JumpToSelf { .. } | HaltReason::IdleLoop { .. }),
+        } => reason.to_string(),
+        RunState::Halted {
+            reason: HaltReason::EndOfProgram,
+        } => String::from("ran past the end of the program"),
+        RunState::Exited => String::from("the program exited"),
+        _ => return None,
+    };
+    Some(format!("Finished: {}. {} to reset", why, reset))
+}
+
+/// Draws `notice` centered at the top of the window.
+pub fn draw_notice(notice: &str) {
+    let dims = measure_text(notice, None, FONT_SIZE as u16, 1.);
+    let padding = 6.;
+    let x = (screen_width() - dims.width) / 2.;
+    draw_rectangle(
+        x - padding,
+        MARGIN,
+        dims.width + padding * 2.,
+        dims.height + padding * 2.,
+        OVERLAY_BACKGROUND,
+    );
+    draw_text(
+        notice,
+        x,
+        MARGIN + padding + dims.offset_y,
+        FONT_SIZE,
+        WHITE,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notice() {
+        let halted = |reason| RunState::Halted { reason };
+        assert_eq!(
+            notice(&halted(HaltReason::JumpToSelf { addr: 0x2A0 }), "Ctrl+T").as_deref(),
+            Some("Finished: jumping to itself at 0x2a0. Ctrl+T to reset")
+        );
+        assert_eq!(
+            notice(&RunState::Exited, "Ctrl+T").as_deref(),
+            Some("Finished: the program exited. Ctrl+T to reset")
+        );
+        assert!(notice(&halted(HaltReason::IdleLoop { addr: 0x200 }), "Ctrl+T").is_some());
+        assert_eq!(notice(&RunState::WaitingForKey, "Ctrl+T"), None);
+        assert_eq!(
+            notice(&halted(HaltReason::Error(String::from("stack"))), "Ctrl+T"),
+            None
+        );
+    }
+}
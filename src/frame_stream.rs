@@ -0,0 +1,164 @@
+//! Push every presented frame to an external consumer.
+//!
+//! Frames go through a bounded mpsc channel, so a slow consumer drops frames
+//! instead of stalling emulation. `FrameStream::to_path` drains the channel
+//! on a background thread into a named pipe, unix socket, or regular file,
+//! using this little-endian wire format per frame:
+//!
+//! | bytes | field                                  |
+//! |-------|----------------------------------------|
+//! | 4     | magic `C8FR`                           |
+//! | 8     | frame index (u64)                      |
+//! | 2     | width (u16)                            |
+//! | 2     | height (u16)                           |
+//! | w * h | one byte per pixel, row-major, 0 or 1  |
+
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+
+use chip8_core::interpreter::{is_lit, Rows, SCREEN_WIDTH};
+
+const MAGIC: &[u8; 4] = b"C8FR";
+
+/// frames allowed to queue up before new ones are dropped
+const DEFAULT_CAPACITY: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// counts presented frames, including dropped ones
+    pub index: u64,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<bool>,
+}
+
+impl Frame {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + self.pixels.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.index.to_le_bytes());
+        bytes.extend_from_slice(&(self.width as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u16).to_le_bytes());
+        bytes.extend(self.pixels.iter().map(|on| *on as u8));
+        bytes
+    }
+}
+
+pub struct FrameStream {
+    sender: SyncSender<Frame>,
+    index: u64,
+}
+
+impl FrameStream {
+    /// A stream whose frames can be read from the returned receiver.
+    pub fn channel() -> (Self, Receiver<Frame>) {
+        let (sender, receiver) = sync_channel(DEFAULT_CAPACITY);
+        (FrameStream { sender, index: 0 }, receiver)
+    }
+
+    /// A stream written to `path` on a background thread. Unix sockets are
+    /// connected to; anything else (e.g. a fifo made with `mkfifo`) is
+    /// opened for writing. Opening a fifo blocks until a reader shows up,
+    /// so frames are dropped until then.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_path(path: &str) -> Self {
+        use std::io::Write;
+
+        let path = path.to_string();
+        let (stream, receiver) = Self::channel();
+        std::thread::spawn(move || {
+            let mut writer = match open_writer(&path) {
+                Ok(writer) => writer,
+                Err(e) => {
+                    log::warn!("could not open frame stream {}: {}", path, e);
+                    return;
+                }
+            };
+            for frame in receiver {
+                if let Err(e) = writer.write_all(&frame.encode()) {
+                    log::warn!("frame stream closed: {}", e);
+                    break;
+                }
+            }
+        });
+
+        stream
+    }
+
+    /// Queue the top `height` of the current display's `rows`. Returns false
+    /// once the consumer has gone away.
+    pub fn push(&mut self, rows: &Rows, height: usize) -> bool {
+        let frame = Frame {
+            index: self.index,
+            width: SCREEN_WIDTH,
+            height,
+            pixels: (0..SCREEN_WIDTH * height)
+                .map(|idx| is_lit(rows, idx % SCREEN_WIDTH, idx / SCREEN_WIDTH))
+                .collect(),
+        };
+        self.index += 1;
+
+        match self.sender.try_send(frame) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn open_writer(path: &str) -> std::io::Result<Box<dyn std::io::Write + Send>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            return Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?));
+        }
+    }
+
+    Ok(Box::new(
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::interpreter::{set_lit, HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT};
+
+    #[test]
+    fn test_channel_receives_frames_in_order() {
+        let (mut stream, receiver) = FrameStream::channel();
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        set_lit(&mut rows, 3, 0, true);
+
+        assert!(stream.push(&rows, SCREEN_HEIGHT));
+        assert!(stream.push(&rows, SCREEN_HEIGHT));
+
+        let first = receiver.recv().unwrap();
+        assert_eq!(first.index, 0);
+        assert_eq!((first.width, first.height), (64, 32));
+        assert!(first.pixels[3]);
+        assert_eq!(first.pixels.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(receiver.recv().unwrap().index, 1);
+
+        drop(receiver);
+        assert!(!stream.push(&rows, SCREEN_HEIGHT));
+    }
+
+    #[test]
+    fn test_encode_header() {
+        let frame = Frame {
+            index: 2,
+            width: 2,
+            height: 1,
+            pixels: vec![true, false],
+        };
+        assert_eq!(
+            frame.encode(),
+            vec![b'C', b'8', b'F', b'R', 2, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0, 1, 0]
+        );
+    }
+}
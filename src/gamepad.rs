@@ -0,0 +1,151 @@
+//! Gamepad input, mapped onto the CHIP-8 keypad.
+//!
+//! Natively gamepads are read through gilrs, behind the default `gamepad`
+//! feature. macroquad's web loader has no way to reach the browser's
+//! Gamepad API, so in the browser no gamepads are ever connected.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A gamepad button, named as in the config file. Face buttons are named
+/// by position since labels differ between controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PadButton {
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    RightTrigger,
+    LeftTrigger2,
+    RightTrigger2,
+    Select,
+    Start,
+}
+
+/// CHIP-8 key for each gamepad button that does something.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GamepadMapping(pub BTreeMap<PadButton, u8>);
+
+impl Default for GamepadMapping {
+    /// the D-pad on 2/4/6/8, which most games use for movement, and 5 to
+    /// act, with the other face buttons on keys that are often "start"
+    fn default() -> Self {
+        use PadButton::*;
+        GamepadMapping(BTreeMap::from([
+            (DPadUp, 0x2),
+            (DPadDown, 0x8),
+            (DPadLeft, 0x4),
+            (DPadRight, 0x6),
+            (South, 0x5),
+            (East, 0x0),
+            (North, 0xA),
+            (West, 0xB),
+            (Start, 0xF),
+        ]))
+    }
+}
+
+impl GamepadMapping {
+    /// which CHIP-8 keys are held, given which buttons are
+    pub fn keys(&self, is_pressed: impl Fn(PadButton) -> bool) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for (button, key) in &self.0 {
+            if let Some(down) = keys.get_mut(*key as usize) {
+                *down |= is_pressed(*button);
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+pub struct Gamepads {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+impl Gamepads {
+    pub fn new() -> Result<Self, String> {
+        let gilrs = gilrs::Gilrs::new().map_err(|e| e.to_string())?;
+        Ok(Gamepads { gilrs })
+    }
+
+    /// CHIP-8 keys held on any connected gamepad. Also returns the names
+    /// of gamepads connected since the last call.
+    pub fn keys(&mut self, mapping: &GamepadMapping) -> ([bool; 16], Vec<String>) {
+        let mut connected = vec![];
+        while let Some(event) = self.gilrs.next_event() {
+            if event.event == gilrs::EventType::Connected {
+                connected.push(self.gilrs.gamepad(event.id).name().to_string());
+            }
+        }
+
+        let mut keys = [false; 16];
+        for (_, gamepad) in self.gilrs.gamepads() {
+            let pad_keys = mapping.keys(|button| gamepad.is_pressed(gilrs_button(button)));
+            for (key, down) in keys.iter_mut().zip(pad_keys) {
+                *key |= down;
+            }
+        }
+        (keys, connected)
+    }
+}
+
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+fn gilrs_button(button: PadButton) -> gilrs::Button {
+    use gilrs::Button;
+    match button {
+        PadButton::DPadUp => Button::DPadUp,
+        PadButton::DPadDown => Button::DPadDown,
+        PadButton::DPadLeft => Button::DPadLeft,
+        PadButton::DPadRight => Button::DPadRight,
+        PadButton::South => Button::South,
+        PadButton::East => Button::East,
+        PadButton::North => Button::North,
+        PadButton::West => Button::West,
+        PadButton::LeftTrigger => Button::LeftTrigger,
+        PadButton::RightTrigger => Button::RightTrigger,
+        PadButton::LeftTrigger2 => Button::LeftTrigger2,
+        PadButton::RightTrigger2 => Button::RightTrigger2,
+        PadButton::Select => Button::Select,
+        PadButton::Start => Button::Start,
+    }
+}
+
+/// Stands in for gilrs where it isn't available; never has a gamepad.
+#[cfg(not(all(feature = "gamepad", not(target_arch = "wasm32"))))]
+pub struct Gamepads;
+
+#[cfg(not(all(feature = "gamepad", not(target_arch = "wasm32"))))]
+impl Gamepads {
+    pub fn new() -> Result<Self, String> {
+        Ok(Gamepads)
+    }
+
+    pub fn keys(&mut self, mapping: &GamepadMapping) -> ([bool; 16], Vec<String>) {
+        (mapping.keys(|_| false), vec![])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapping_keys() {
+        let mapping = GamepadMapping::default();
+        let keys = mapping.keys(|button| matches!(button, PadButton::DPadUp | PadButton::South));
+        let held: Vec<usize> = (0..16).filter(|key| keys[*key]).collect();
+        assert_eq!(held, [0x2, 0x5]);
+
+        // out of range keys are ignored rather than panicking
+        let mapping = GamepadMapping(BTreeMap::from([(PadButton::South, 0x10)]));
+        assert_eq!(mapping.keys(|_| true), [false; 16]);
+    }
+}
@@ -0,0 +1,994 @@
+//! The window `run` opens: the running ROM with the menus, overlays, and
+//! hotkeys around it. Each rendered frame handles the menus and hotkeys,
+//! runs the 60 Hz ticks due since the last, then plays, records, and
+//! draws the result.
+
+use std::{collections::BTreeMap, error::Error, ops::ControlFlow};
+
+use chip8_core::{
+    interpreter::{Rows, SCREEN_HEIGHT, SCREEN_WIDTH},
+    WatchKind, WatchTarget,
+};
+use macroquad::prelude::*;
+
+use crate::{
+    assembler,
+    buzzer::{Buzzer, Tone},
+    capture_input,
+    cheats::{Cheat, CheatMenu},
+    cheats_for,
+    cli::RunOptions,
+    config::Config,
+    configure_for_rom, controls_for,
+    crt::Crt,
+    debug_overlay,
+    digitized_sound::DigitizedPlayer,
+    display::Display,
+    file_dialog, file_name, finished,
+    gamepad::{GamepadMapping, Gamepads},
+    hud, keypad_overlay,
+    keys::{self, key_name},
+    layout,
+    library::{self, RecentFiles},
+    load_config,
+    machine::{Chip8Machine, FrameOutput, Inputs},
+    memory_search::{SearchAction, SearchPanel},
+    memory_view::MemoryView,
+    menu::{MenuItem, StartMenu},
+    new_machine, octo,
+    palette::Palette,
+    pause_menu::{self, PauseAction, PauseMenu},
+    poke_prompt::PokePrompt,
+    print_profile, program_start, read_rom,
+    recorders::Recorders,
+    remap::RemapScreen,
+    remote::Remote,
+    restore_rpl_flags,
+    rom_db::Profile,
+    rpl_flags::RplFlags,
+    save_coverage,
+    save_slots::SaveSlots,
+    symbols::Symbols,
+    timing::{TickClock, TimingStats},
+    toast::Toasts,
+    touch_keypad::TouchKeypad,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{config_path, screenshot};
+
+/// toggles fullscreen along with `toggle_fullscreen`, as in most emulators
+const ALT_ENTER: keys::Hotkey = keys::Hotkey {
+    alt: true,
+    ..keys::Hotkey::new(KeyCode::Enter)
+};
+
+/// where F8 writes the Octo-compatible state
+#[cfg(not(target_arch = "wasm32"))]
+const OCTO_STATE_FILE: &str = "chip8-state.octo.json";
+
+/// a ROM chosen this frame, with the path or name it goes by and, if it was
+/// read from a file, the patches applied to it
+type Opened = (String, Vec<u8>, Option<Vec<String>>);
+
+/// Open `options.rom` in the window, paused with the debugger open if
+/// `debug` is set, and run it until the player quits.
+pub async fn run(options: RunOptions, debug: bool) -> Result<(), Box<dyn Error>> {
+    let mut gui = Gui::new(options, debug).await?;
+    while gui.update().await {
+        next_frame().await;
+    }
+    gui.finish();
+    Ok(())
+}
+
+/// Everything the window keeps from one frame to the next.
+struct Gui {
+    options: RunOptions,
+    config: Config,
+    machine: Chip8Machine,
+    toasts: Toasts,
+    recent: RecentFiles,
+    /// keys for the ROM beyond the keypad
+    controls: BTreeMap<keys::Key, u8>,
+    /// the file the running ROM was read from and the patches put on it, for reloading
+    rom_file: Option<(String, Vec<String>)>,
+    rom_name: String,
+    /// names for addresses in the running ROM, for the debugger
+    symbols: Symbols,
+    /// the speed the ROM was set up to run at, before any changes from the pause menu
+    usual_speed: u32,
+    save_slots: SaveSlots,
+    rpl_flags: RplFlags,
+    gamepad_mapping: GamepadMapping,
+    gamepads: Option<Gamepads>,
+    buzzer: Option<Buzzer>,
+    digitized: DigitizedPlayer,
+    display: Display,
+    crt: Option<Crt>,
+    crt_enabled: bool,
+    palette: Palette,
+    fullscreen: bool,
+    timing: TimingStats,
+    clock: TickClock,
+    touch_keypad: TouchKeypad,
+    /// taps wait for a frame that runs, since renders can come faster than frames
+    queued_taps: [bool; 16],
+    show_hud: bool,
+    show_keypad: bool,
+    show_debugger: bool,
+    memory_view: MemoryView,
+    menu: Option<StartMenu>,
+    remap_screen: Option<RemapScreen>,
+    pause_menu: Option<PauseMenu>,
+    poke_prompt: Option<PokePrompt>,
+    cheat_menu: Option<CheatMenu>,
+    /// the search goes on while the panel is closed, to play between narrowing it down
+    memory_search: Option<SearchPanel>,
+    searching: bool,
+    /// paused with the pause hotkey, or stopped at a breakpoint or watchpoint
+    paused: bool,
+    /// stopped by an error, breakpoint, or watchpoint since last running,
+    /// which shows the instructions that led there
+    stopped: bool,
+    recorders: Recorders,
+    remote: Remote,
+}
+
+impl Gui {
+    async fn new(options: RunOptions, debug: bool) -> Result<Self, Box<dyn Error>> {
+        let mut machine = new_machine(&options);
+        let mut toasts = Toasts::new();
+        let config = load_config(&options, &mut toasts);
+        let mut recent = RecentFiles::load();
+        let mut remote = Remote::new(&options, &mut toasts);
+        // if a rom is given, load that. Else show the menu, with PONG behind it
+        let mut menu = None;
+        let profile;
+        let controls;
+        let mut rom_file = None;
+        let symbols;
+        if let Some(rom) = &options.rom {
+            let start = program_start(&options);
+            let bytes = read_rom(rom, &options.patches, start, &config, &mut toasts)?;
+            profile = configure_for_rom(&mut machine, &options, rom, &bytes, &mut toasts);
+            controls = controls_for(&config, &bytes, profile.as_ref(), &mut toasts);
+            *machine.cheats_mut() = cheats_for(&config, rom, &options.cheats, &mut toasts);
+            machine.load(&bytes)?;
+            let speed = machine.instructions_per_second();
+            remote.start_netplay(&options, &bytes, speed, &mut toasts);
+            rom_file = Some((rom.clone(), options.patches.clone()));
+            symbols = symbols_for(rom, options.symbols.as_ref(), &mut toasts);
+            toasts.info(format!("Loaded {}", rom));
+            recent.push(rom);
+            if let Err(e) = recent.save() {
+                toasts.warn(format!("Could not save recent files: {}", e));
+            }
+        } else {
+            let pong = library::embedded("PONG").ok_or("PONG isn't built in")?;
+            profile = configure_for_rom(&mut machine, &options, "PONG", pong, &mut toasts);
+            controls = controls_for(&config, pong, profile.as_ref(), &mut toasts);
+            *machine.cheats_mut() = cheats_for(&config, "PONG", &[], &mut toasts);
+            machine.load(pong)?;
+            symbols = Symbols::default();
+            menu = Some(StartMenu::new(&recent));
+        }
+        for addr in &options.breakpoints {
+            machine.interpreter_mut().add_breakpoint(*addr);
+        }
+        for (target, kind) in &options.watchpoints {
+            machine.interpreter_mut().add_watchpoint(*target, *kind);
+        }
+        if let Some(path) = &options.octo_state {
+            let snapshot = octo::import_state(&std::fs::read_to_string(path)?)?;
+            machine.restore(&snapshot);
+            toasts.info(format!("Imported Octo state from {}", path));
+        }
+
+        let mut display = Display::new();
+        display.set_phosphor(config.phosphor);
+        let crt = match Crt::new() {
+            Ok(crt) => Some(crt),
+            Err(e) => {
+                toasts.warn(format!("CRT effect unavailable: {}", e));
+                None
+            }
+        };
+        let rom_name = options
+            .rom
+            .as_deref()
+            .map_or_else(|| String::from("PONG"), file_name);
+        let rpl_flags = restore_rpl_flags(&rom_name, &mut machine, &mut toasts);
+        let tone = tone_for(&config, &options, &rom_name);
+        let buzzer = match Buzzer::new(tone).await {
+            Ok(buzzer) => Some(buzzer),
+            Err(e) => {
+                toasts.warn(format!("Sound disabled: {}", e));
+                None
+            }
+        };
+        let gamepads = match Gamepads::new() {
+            Ok(gamepads) => Some(gamepads),
+            Err(e) => {
+                toasts.warn(format!("Gamepads disabled: {}", e));
+                None
+            }
+        };
+        let height = machine.interpreter().screen_height();
+        let recorders = Recorders::start(&options, tone, height, &mut toasts);
+
+        Ok(Gui {
+            usual_speed: machine.instructions_per_second(),
+            timing: TimingStats::new(machine.instructions_per_second() as f64, get_time()),
+            clock: TickClock::new(),
+            save_slots: SaveSlots::new(&rom_name),
+            gamepad_mapping: gamepad_for(&config, &rom_name, profile.as_ref()),
+            palette: options.palette.unwrap_or(config.palette),
+            crt_enabled: config.crt,
+            fullscreen: false,
+            touch_keypad: TouchKeypad::new(),
+            queued_taps: [false; 16],
+            show_hud: false,
+            show_keypad: false,
+            show_debugger: debug,
+            memory_view: MemoryView::new(),
+            remap_screen: None,
+            pause_menu: None,
+            poke_prompt: None,
+            cheat_menu: None,
+            memory_search: None,
+            searching: false,
+            paused: debug,
+            stopped: false,
+            digitized: DigitizedPlayer::new(),
+            options,
+            config,
+            machine,
+            toasts,
+            recent,
+            controls,
+            rom_file,
+            rom_name,
+            symbols,
+            rpl_flags,
+            gamepads,
+            buzzer,
+            display,
+            crt,
+            menu,
+            recorders,
+            remote,
+        })
+    }
+
+    /// Handle this frame's menus and hotkeys, run the ticks due, and draw
+    /// the result. Returns false once the player quits.
+    async fn update(&mut self) -> bool {
+        self.update_remap_screen();
+        if self.config.hotkeys.quit.is_pressed() {
+            return false;
+        }
+        let ControlFlow::Continue(mut opened) = self.update_menus() else {
+            return false;
+        };
+        // while remapping or in a menu, keys are for the menu
+        if !self.in_menu() {
+            self.view_hotkeys();
+            opened = self.machine_hotkeys().or(opened);
+        }
+        if let Some(opened) = opened {
+            self.open(opened).await;
+        }
+
+        // run the 60 Hz ticks due since the last render, then render current state (visuals, audio)
+        let inputs = self.inputs();
+        let ticks = self.clock.ticks_due(get_frame_time() as f64);
+        let output = self.run_frames(&inputs, ticks);
+        self.capture(&output);
+        self.play_sound(&output).await;
+        let sounding = output.sound && self.buzzer.is_some();
+        self.recorders
+            .push(&output, ticks, &self.palette, sounding, &mut self.toasts);
+        self.timing
+            .set_target_instructions_per_sec(self.machine.instructions_per_second() as f64);
+        self.timing.record_frame(get_frame_time() as f64);
+        self.timing.update(get_time());
+        self.draw(&output, &inputs);
+        true
+    }
+
+    /// whether a menu, prompt, or panel is open, which takes the keys and
+    /// holds the game still
+    fn in_menu(&self) -> bool {
+        self.remap_screen.is_some()
+            || self.menu.is_some()
+            || self.pause_menu.is_some()
+            || self.poke_prompt.is_some()
+            || self.cheat_menu.is_some()
+            || self.searching
+    }
+
+    /// open or close the key remapping screen, and save the bindings it makes
+    fn update_remap_screen(&mut self) {
+        let listening = self
+            .remap_screen
+            .as_ref()
+            .is_some_and(RemapScreen::is_listening);
+        if !listening && self.menu.is_none() && self.config.hotkeys.remap_keys.is_pressed() {
+            self.remap_screen = match self.remap_screen {
+                Some(_) => None,
+                None => Some(RemapScreen::new()),
+            };
+        }
+        if let Some(screen) = &mut self.remap_screen {
+            if let Some((slot, key)) = screen.update() {
+                self.config.rebind_key(slot, key);
+                self.toasts.info(format!(
+                    "Bound CHIP-8 key {:X} to {}",
+                    slot,
+                    key_name(key.0)
+                ));
+                save_config(&self.config, &self.options, &mut self.toasts);
+                for conflict in self.config.conflicts() {
+                    self.toasts.warn(conflict);
+                }
+            }
+        }
+    }
+
+    /// Open, close, and act on the menus, prompts, and panels. Breaks when
+    /// the pause menu quits, and otherwise continues with the ROM chosen
+    /// from the ROM menu, if any.
+    fn update_menus(&mut self) -> ControlFlow<(), Option<Opened>> {
+        if let Some(action) = self.pause_menu.as_mut().and_then(PauseMenu::update) {
+            self.pause_action(action)?;
+        } else if !self.in_menu() && self.config.hotkeys.pause_menu.is_pressed() {
+            self.pause_menu = Some(PauseMenu::new());
+        }
+        if self.remap_screen.is_none() && self.config.hotkeys.rom_menu.is_pressed() {
+            self.menu = match self.menu {
+                Some(_) => None,
+                None => Some(StartMenu::new(&self.recent)),
+            };
+        }
+        let mut opened = None;
+        if let Some(item) = self.menu.as_mut().and_then(StartMenu::update) {
+            let (start, config) = (program_start(&self.options), &self.config);
+            opened = match item {
+                MenuItem::OpenFile => pick_rom(start, config, &mut self.recent, &mut self.toasts),
+                MenuItem::Recent(path) => {
+                    open_rom_file(&path, start, config, &mut self.recent, &mut self.toasts)
+                        .map(|rom| (path, rom, Some(vec![])))
+                }
+                MenuItem::Embedded(name) => {
+                    library::embedded(name).map(|rom| (name.to_string(), rom.to_vec(), None))
+                }
+            };
+        }
+        if let Some(result) = self.poke_prompt.as_mut().and_then(PokePrompt::update) {
+            if let Some(edit) = result {
+                edit.apply(self.machine.interpreter_mut());
+                self.toasts.info(format!("Set {}", edit));
+            }
+            self.poke_prompt = None;
+        }
+        if let Some(list) = &mut self.cheat_menu {
+            if list.update(self.machine.cheats_mut()) {
+                self.cheat_menu = None;
+            }
+        }
+        self.update_memory_search();
+        ControlFlow::Continue(opened)
+    }
+
+    fn pause_action(&mut self, action: PauseAction) -> ControlFlow<()> {
+        let machine = &mut self.machine;
+        match action {
+            PauseAction::Resume => self.pause_menu = None,
+            PauseAction::Reset if self.remote.refuses("Resetting", &mut self.toasts) => {}
+            PauseAction::Reset => {
+                machine.reset();
+                self.paused = false;
+                self.pause_menu = None;
+            }
+            PauseAction::OpenRom => {
+                self.menu = Some(StartMenu::new(&self.recent));
+                self.pause_menu = None;
+            }
+            PauseAction::KeyBindings => {
+                self.remap_screen = Some(RemapScreen::new());
+                self.pause_menu = None;
+            }
+            PauseAction::NextPalette => self.palette = self.palette.next(),
+            PauseAction::PreviousPalette => self.palette = self.palette.previous(),
+            PauseAction::Faster | PauseAction::Slower
+                if self.remote.refuses("Changing the speed", &mut self.toasts) => {}
+            PauseAction::Faster => machine.set_instructions_per_second(
+                machine.instructions_per_second() + pause_menu::SPEED_STEP,
+            ),
+            PauseAction::Slower => machine.set_instructions_per_second(
+                machine
+                    .instructions_per_second()
+                    .saturating_sub(pause_menu::SPEED_STEP)
+                    .max(pause_menu::SPEED_STEP),
+            ),
+            PauseAction::Quit => return ControlFlow::Break(()),
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// act on what's picked in the memory search panel, while it's open
+    fn update_memory_search(&mut self) {
+        let action = self
+            .memory_search
+            .as_mut()
+            .filter(|_| self.searching)
+            .and_then(|panel| panel.update(self.machine.interpreter().memory()));
+        let Some(action) = action else {
+            return;
+        };
+        match action {
+            SearchAction::Close => (),
+            SearchAction::Watch(addr) => {
+                self.machine
+                    .interpreter_mut()
+                    .add_watchpoint(WatchTarget::Memory(addr), WatchKind::Write);
+                self.toasts
+                    .info(format!("Watching writes to {:#05x}", addr));
+            }
+            SearchAction::Cheat(_) if self.remote.refuses("Cheating", &mut self.toasts) => {}
+            SearchAction::Cheat(addr) => {
+                let value = self.machine.interpreter().memory()[addr as usize];
+                let cheat = Cheat {
+                    addr,
+                    bytes: vec![value],
+                    enabled: true,
+                };
+                self.toasts.info(format!("Cheat {} on", cheat));
+                self.machine.cheats_mut().push(cheat);
+            }
+        }
+        self.searching = false;
+    }
+
+    /// the hotkeys that change how the game is shown, and not the game itself
+    fn view_hotkeys(&mut self) {
+        let hotkeys = &self.config.hotkeys;
+        if hotkeys.toggle_hud.is_pressed() {
+            self.show_hud = !self.show_hud;
+        }
+        if hotkeys.toggle_keypad.is_pressed() {
+            self.show_keypad = !self.show_keypad;
+        }
+        if hotkeys.toggle_debugger.is_pressed() {
+            self.show_debugger = !self.show_debugger;
+        }
+        if self.show_debugger {
+            self.memory_view.update(self.machine.interpreter());
+        }
+        if hotkeys.toggle_fullscreen.is_pressed() || ALT_ENTER.is_pressed() {
+            self.fullscreen = !self.fullscreen;
+            set_fullscreen(self.fullscreen);
+        }
+        if hotkeys.toggle_crt.is_pressed() {
+            self.crt_enabled = !self.crt_enabled;
+        }
+        if hotkeys.cycle_phosphor.is_pressed() {
+            self.display.set_phosphor(self.display.phosphor().next());
+            self.toasts
+                .info(format!("Phosphor: {}", self.display.phosphor()));
+        }
+        if hotkeys.cycle_palette.is_pressed() {
+            self.palette = self.palette.next();
+            self.toasts.info(format!("Palette: {}", self.palette));
+        }
+    }
+
+    /// The hotkeys that pause, change, or replace the running game. Returns
+    /// the ROM to open instead, if one was chosen.
+    fn machine_hotkeys(&mut self) -> Option<Opened> {
+        let hotkeys = &self.config.hotkeys;
+        let (machine, toasts) = (&mut self.machine, &mut self.toasts);
+        if hotkeys.cycle_quirks.is_pressed() && !self.remote.refuses("Changing the quirks", toasts)
+        {
+            toasts.info(format!("Quirks: {}", machine.cycle_quirks()));
+        }
+        if self.paused && hotkeys.resume.is_pressed() {
+            self.paused = false;
+        }
+        if hotkeys.edit_state.is_pressed() && !self.remote.refuses("Editing the state", toasts) {
+            if self.paused {
+                self.poke_prompt = Some(PokePrompt::new());
+            } else {
+                toasts.info(format!("Pause with {} to edit the state", hotkeys.pause));
+            }
+        }
+        if hotkeys.cheats.is_pressed() && !self.remote.refuses("Cheating", toasts) {
+            self.cheat_menu = Some(CheatMenu::new());
+        }
+        if hotkeys.memory_search.is_pressed() {
+            let memory = machine.interpreter().memory();
+            self.memory_search
+                .get_or_insert_with(|| SearchPanel::new(memory));
+            self.searching = true;
+        }
+        if hotkeys.pause.is_pressed() {
+            self.paused = !self.paused;
+            if self.paused {
+                toasts.info(format!(
+                    "Paused: {} steps an instruction, {} a frame",
+                    hotkeys.step, hotkeys.step_frame
+                ));
+            }
+        }
+        let mut opened = None;
+        if hotkeys.open_rom.is_pressed() && !file_dialog::AVAILABLE {
+            toasts.warn("Opening ROMs isn't supported in this build");
+        } else if hotkeys.open_rom.is_pressed() {
+            let start = program_start(&self.options);
+            opened = pick_rom(start, &self.config, &mut self.recent, toasts);
+        }
+        if hotkeys.reset.is_pressed() && !self.remote.refuses("Resetting", toasts) {
+            machine.reset();
+            self.paused = false;
+            toasts.info("Reset");
+        }
+        if hotkeys.reload_rom.is_pressed() {
+            match &self.rom_file {
+                Some((path, patches)) => match read_rom(
+                    path,
+                    patches,
+                    program_start(&self.options),
+                    &self.config,
+                    toasts,
+                ) {
+                    Ok(rom) => opened = Some((path.clone(), rom, Some(patches.clone()))),
+                    Err(e) => toasts.warn(format!("Could not reload {}: {}", path, e)),
+                },
+                None => toasts.warn(format!(
+                    "{} is built in, so there's no file to reload",
+                    self.rom_name
+                )),
+            }
+        }
+        if hotkeys.export_octo_state.is_pressed() {
+            export_octo_state(machine, toasts);
+        }
+        self.save_slot_hotkeys();
+        opened
+    }
+
+    /// save to, load from, and switch between the save state slots
+    fn save_slot_hotkeys(&mut self) {
+        let hotkeys = &self.config.hotkeys;
+        let (save_slots, toasts) = (&mut self.save_slots, &mut self.toasts);
+        let megachip = self.machine.interpreter().megachip().is_some();
+        if megachip && (hotkeys.save_state.is_pressed() || hotkeys.load_state.is_pressed()) {
+            toasts.warn("Save states don't cover MegaChip ROMs");
+        } else if hotkeys.save_state.is_pressed() {
+            match save_slots.save(&self.machine.snapshot()) {
+                Ok(()) => toasts.info(format!("Saved state to slot {}", save_slots.slot())),
+                Err(e) => toasts.warn(format!("Could not save state: {}", e)),
+            }
+        }
+        if hotkeys.next_save_slot.is_pressed() {
+            save_slots.next_slot();
+            toasts.info(format!("Save slot {}", save_slots.slot()));
+        }
+        if hotkeys.load_state.is_pressed()
+            && !megachip
+            && !self.remote.refuses("Loading a state", toasts)
+        {
+            match save_slots.load() {
+                Ok(snapshot) => {
+                    self.machine.restore(&snapshot);
+                    toasts.info(format!("Loaded state from slot {}", save_slots.slot()));
+                }
+                Err(e) => toasts.warn(format!("Could not load state: {}", e)),
+            }
+        }
+    }
+
+    /// switch to running the ROM `opened`, with its settings, leaving any
+    /// netplay game
+    async fn open(&mut self, (path, rom, patches): Opened) {
+        let (config, toasts) = (&self.config, &mut self.toasts);
+        let machine = &mut self.machine;
+        // the running game's settings, kept in case the new ROM won't load
+        let (quirks, megachip, speed) = (
+            machine.quirks(),
+            machine.megachip(),
+            machine.instructions_per_second(),
+        );
+        let profile = configure_for_rom(machine, &self.options, &path, &rom, toasts);
+        if let Err(e) = machine.load(&rom) {
+            machine.set_quirks(quirks);
+            machine.set_megachip(megachip);
+            machine.set_instructions_per_second(speed);
+            toasts.warn(format!("Could not open {}: {}", path, e));
+            return;
+        }
+        self.usual_speed = machine.instructions_per_second();
+        self.rom_name = file_name(&path);
+        self.save_slots = SaveSlots::new(&self.rom_name);
+        self.rpl_flags = restore_rpl_flags(&self.rom_name, machine, toasts);
+        self.gamepad_mapping = gamepad_for(config, &self.rom_name, profile.as_ref());
+        self.controls = controls_for(config, &rom, profile.as_ref(), toasts);
+        *machine.cheats_mut() = cheats_for(config, &path, &[], toasts);
+        self.memory_search = None;
+        self.symbols = symbols_for(&path, None, toasts);
+        let tone = tone_for(config, &self.options, &self.rom_name);
+        if let Some(buzzer) = &mut self.buzzer {
+            if let Err(e) = buzzer.set_tone(tone).await {
+                toasts.warn(format!("Could not change the buzzer: {}", e));
+            }
+        }
+        self.recorders.set_tone(tone);
+        self.paused = false;
+        self.menu = None;
+        self.remote.leave_netplay(toasts);
+        toasts.info(format!("Loaded {}", path));
+        self.rom_file = patches.map(|patches| (path, patches));
+    }
+
+    /// the keys held and tapped this frame, on the keyboard, gamepads, touch
+    /// keypad, and through the debug server
+    fn inputs(&mut self) -> Inputs {
+        let mut pad_keys = [false; 16];
+        if let Some(gamepads) = &mut self.gamepads {
+            let connected;
+            (pad_keys, connected) = gamepads.keys(&self.gamepad_mapping);
+            for name in connected {
+                self.toasts.info(format!("Gamepad connected: {}", name));
+            }
+        }
+        self.remote
+            .serve(&mut self.machine, &mut self.paused, &mut pad_keys);
+        for (key, held) in pad_keys.iter_mut().zip(self.touch_keypad.update()) {
+            *key |= held;
+        }
+        let mut inputs = capture_input(&self.config.keypad, &self.controls, pad_keys);
+        for (queued, tapped) in self.queued_taps.iter_mut().zip(inputs.taps) {
+            *queued |= tapped;
+        }
+        inputs.taps = self.queued_taps;
+        inputs
+    }
+
+    /// Run the `ticks` due with `inputs`, or step, rewind, or hold still as
+    /// the menus, netplay, and hotkeys say, and pause where the program
+    /// stopped or failed.
+    fn run_frames(&mut self, inputs: &Inputs, ticks: u32) -> FrameOutput {
+        let in_menu = self.in_menu();
+        let netplay_result = if in_menu {
+            None
+        } else {
+            self.remote.netplay_frame(
+                &mut self.machine,
+                inputs,
+                ticks,
+                self.paused,
+                &mut self.toasts,
+            )
+        };
+        let hotkeys = &self.config.hotkeys;
+        let machine = &mut self.machine;
+        let result = if in_menu {
+            Ok(machine.idle_frame())
+        } else if let Some(result) = netplay_result {
+            result
+        } else if hotkeys.rewind.is_down() {
+            for _ in 0..ticks {
+                machine.rewind_frame();
+            }
+            Ok(machine.idle_frame())
+        } else if self.paused && hotkeys.step.is_pressed() {
+            machine.step_instruction(inputs)
+        } else if self.paused && hotkeys.step_frame.is_pressed() {
+            machine.frame(inputs)
+        } else if self.paused {
+            Ok(machine.idle_frame())
+        } else {
+            let result = machine.frames(inputs, ticks);
+            if let Ok(output) = &result {
+                for _ in 0..ticks {
+                    self.timing.record_timer_tick();
+                }
+                self.timing.record_instructions(output.instructions);
+            }
+            result
+        };
+        if ticks > 0 || self.paused || in_menu {
+            self.queued_taps = [false; 16];
+        }
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                // the pc stays on the failing instruction, so the debugger shows it
+                self.paused = true;
+                self.stopped = true;
+                self.show_debugger = true;
+                self.toasts.warn(format!("Stopped: {}", e));
+                machine.idle_frame()
+            }
+        };
+        if let Err(e) = self
+            .rpl_flags
+            .save_if_changed(machine.interpreter().rpl_flags())
+        {
+            self.toasts.warn(format!("Could not save RPL flags: {}", e));
+        }
+        if let Some(stop) = output.stop {
+            self.paused = true;
+            self.stopped = true;
+            self.show_debugger = true;
+            self.toasts.info(format!(
+                "Stopped at {}, {} to continue",
+                stop, hotkeys.resume
+            ));
+        }
+        output
+    }
+
+    /// take a screenshot of `output`, or start or stop recording a GIF, as the hotkeys say
+    fn capture(&mut self, output: &FrameOutput) {
+        let hotkeys = &self.config.hotkeys;
+        let in_menu = self.remap_screen.is_some() || self.menu.is_some();
+        if !in_menu && hotkeys.screenshot.is_pressed() {
+            save_screenshot(&output.rows, output.height, &self.palette, &mut self.toasts);
+        }
+        let toggle_recording = !in_menu && hotkeys.record_gif.is_pressed();
+        self.recorders.record_gif(
+            output,
+            toggle_recording,
+            &hotkeys.record_gif,
+            self.palette,
+            &mut self.toasts,
+        );
+    }
+
+    /// sound the buzzer as `output` says, and play MegaChip's digitized sound
+    async fn play_sound(&mut self, output: &FrameOutput) {
+        if let Some(buzzer) = &mut self.buzzer {
+            if output.sound_changed {
+                buzzer.set_on(output.sound);
+            }
+        }
+        let sound_event = self
+            .machine
+            .interpreter_mut()
+            .megachip_mut()
+            .and_then(|mega| mega.take_sound_event());
+        // no buzzer means no audio at all
+        if let Some(event) = sound_event.filter(|_| self.buzzer.is_some()) {
+            self.recorders.handle(&event);
+            if let Err(e) = self.digitized.handle(event).await {
+                self.toasts.warn(format!("Could not play sound: {}", e));
+            }
+        }
+    }
+
+    /// draw the display from `output`, and the overlays and menus over it
+    fn draw(&mut self, output: &FrameOutput, inputs: &Inputs) {
+        clear_background(BLACK);
+        let interpreter = self.machine.interpreter();
+        let mega = interpreter.megachip().filter(|mega| mega.enabled());
+        match mega {
+            Some(mega) => self.display.update_mega(mega.screen(), mega.alpha()),
+            None => self
+                .display
+                .update(&output.rows, output.height, &self.palette),
+        }
+        let display = &self.display;
+        let dest = layout::fit_display(
+            screen_width(),
+            self.touch_keypad.display_height(),
+            display.size(),
+        );
+        match &self.crt {
+            // the CRT effect is sized for the 64x32 CHIP-8 display
+            Some(crt) if self.crt_enabled && mega.is_none() && output.height == SCREEN_HEIGHT => {
+                crt.draw(
+                    || display.draw(Rect::new(0., 0., SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32)),
+                    dest,
+                )
+            }
+            _ => display.draw(dest),
+        }
+
+        if self.show_debugger {
+            debug_overlay::draw_debug_overlay(interpreter, &self.symbols);
+            self.memory_view.draw(interpreter, self.machine.coverage());
+        }
+        self.stopped &= self.paused;
+        if self.show_debugger && self.stopped {
+            debug_overlay::draw_history(interpreter, &self.symbols);
+        }
+        if self.show_hud {
+            let speed = self.machine.instructions_per_second() as f64 / self.usual_speed as f64;
+            hud::draw_hud(
+                self.timing.last_sample(),
+                (!self.options.vip_timing).then_some(speed),
+            );
+        }
+        self.touch_keypad.draw(&inputs.keys);
+        let config = &self.config;
+        if self.show_keypad {
+            keypad_overlay::draw_keypad_overlay(&config.keypad, &inputs.keys);
+        }
+        if let Some(notice) =
+            finished::notice(interpreter.state(), &config.hotkeys.reset.to_string())
+        {
+            finished::draw_notice(&notice);
+        }
+        if let Some(screen) = &self.remap_screen {
+            screen.draw(&config.keypad);
+        }
+        if let Some(menu) = &self.menu {
+            menu.draw(&config.hotkeys.rom_menu.to_string());
+        }
+        if let Some(pause_menu) = &self.pause_menu {
+            let speed = if self.options.vip_timing {
+                String::from("VIP timing")
+            } else {
+                format!("{} ips", self.machine.instructions_per_second())
+            };
+            pause_menu.draw(self.palette.name, &speed);
+        }
+        if let Some(prompt) = &self.poke_prompt {
+            prompt.draw();
+        }
+        if let Some(list) = &self.cheat_menu {
+            list.draw(self.machine.cheats());
+        }
+        if let Some(panel) = self.memory_search.as_ref().filter(|_| self.searching) {
+            panel.draw(interpreter.memory());
+        }
+        self.toasts.update(get_frame_time());
+        self.toasts.draw();
+    }
+
+    /// finish the recordings, and write what `--record-movie`, `--profile`,
+    /// and `--coverage` ask for
+    fn finish(self) {
+        self.recorders.finish();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (Some(path), Some(movie)) = (&self.options.record_movie, self.machine.movie()) {
+            match std::fs::write(path, movie.to_text()) {
+                Ok(()) => println!("Saved movie {}, {} frames", path, movie.frames.len()),
+                Err(e) => eprintln!("Could not save the movie: {}", e),
+            }
+        }
+        print_profile(&self.machine);
+        save_coverage(&self.options, &self.machine);
+    }
+}
+
+/// the names for addresses in the ROM at `path`: from `file`, else its `.sym` file, or its
+/// labels if it's a `.8o` source
+fn symbols_for(path: &str, file: Option<&String>, toasts: &mut Toasts) -> Symbols {
+    let symbols = match file.cloned().or_else(|| Symbols::default_path(path)) {
+        Some(file) => Symbols::read(&file),
+        None if path.ends_with(".8o") => std::fs::read_to_string(path)
+            .map_err(Into::into)
+            .and_then(|source| assembler::assemble_with_symbols(&source))
+            .map(|(_, symbols)| symbols),
+        None => Ok(Symbols::default()),
+    };
+    symbols.unwrap_or_else(|e| {
+        toasts.warn(format!("Skipping symbols: {}", e));
+        Symbols::default()
+    })
+}
+
+/// the gamepad mapping for `rom_name`: the config's profile for it if there
+/// is one, otherwise the config's mapping with the ROM's known controls on it
+fn gamepad_for(config: &Config, rom_name: &str, profile: Option<&Profile>) -> GamepadMapping {
+    match profile {
+        Some(profile) if !config.gamepad_profiles.contains_key(rom_name) => {
+            profile.gamepad(&config.gamepad)
+        }
+        _ => config.gamepad_for(rom_name).clone(),
+    }
+}
+
+/// the buzzer's tone for `rom_name`, from the config unless `--waveform` or `--frequency` say otherwise
+fn tone_for(config: &Config, options: &RunOptions, rom_name: &str) -> Tone {
+    let tone = config.buzzer_for(rom_name);
+    Tone {
+        waveform: options.waveform.unwrap_or(tone.waveform),
+        frequency: options.frequency.unwrap_or(tone.frequency),
+    }
+}
+
+/// a ROM picked from the file dialog, if one was, remembered as recently opened
+fn pick_rom(
+    start: u16,
+    config: &Config,
+    recent: &mut RecentFiles,
+    toasts: &mut Toasts,
+) -> Option<Opened> {
+    file_dialog::pick_rom().and_then(|path| {
+        open_rom_file(&path, start, config, recent, toasts).map(|rom| (path, rom, Some(vec![])))
+    })
+}
+
+/// write back a config changed at runtime
+fn save_config(config: &Config, options: &RunOptions, toasts: &mut Toasts) {
+    #[cfg(not(target_arch = "wasm32"))]
+    match config_path(options).map(|path| config.save(&path)) {
+        Some(Ok(())) => {}
+        Some(Err(e)) => toasts.warn(format!("Could not save config: {}", e)),
+        None => toasts.warn("Nowhere to save the config, changes last until exit"),
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = options;
+        write_to_console(&config.to_toml(), "the config", toasts);
+    }
+}
+
+/// write the display to a timestamped PNG in the working directory
+fn save_screenshot(rows: &Rows, height: usize, palette: &Palette, toasts: &mut Toasts) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let path = screenshot::file_name(std::time::SystemTime::now(), "png");
+        match screenshot::encode_png(rows, height, palette)
+            .and_then(|png| Ok(std::fs::write(&path, png)?))
+        {
+            Ok(()) => toasts.info(format!("Saved screenshot {}", path)),
+            Err(e) => toasts.warn(format!("Could not save screenshot: {}", e)),
+        }
+    }
+
+    // there's nowhere to write files in the browser
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (rows, height, palette);
+        toasts.warn("Screenshots aren't supported in the browser");
+    }
+}
+
+fn export_octo_state(machine: &Chip8Machine, toasts: &mut Toasts) {
+    let json = octo::export_state(&machine.snapshot());
+
+    #[cfg(not(target_arch = "wasm32"))]
+    match std::fs::write(OCTO_STATE_FILE, json) {
+        Ok(()) => toasts.info(format!("Exported Octo state to {}", OCTO_STATE_FILE)),
+        Err(e) => toasts.warn(format!("Could not export Octo state: {}", e)),
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    write_to_console(&json, "the Octo state", toasts);
+}
+
+/// hand `text`, which is `what`, over via the console, there being no
+/// filesystem in the browser to write it to
+#[cfg(target_arch = "wasm32")]
+fn write_to_console(text: &str, what: &str, toasts: &mut Toasts) {
+    log::info!("{}", text);
+    toasts.info(format!("Wrote {} to the browser console", what));
+}
+
+/// read a ROM picked at runtime and remember it as recently opened
+fn open_rom_file(
+    path: &str,
+    start: u16,
+    config: &Config,
+    recent: &mut RecentFiles,
+    toasts: &mut Toasts,
+) -> Option<Vec<u8>> {
+    match read_rom(path, &[], start, config, toasts) {
+        Ok(rom) => {
+            recent.push(path);
+            if let Err(e) = recent.save() {
+                toasts.warn(format!("Could not save recent files: {}", e));
+            }
+            Some(rom)
+        }
+        Err(e) => {
+            toasts.warn(format!("Could not open {}: {}", path, e));
+            None
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fmt::Write, ops::Range};
+
+use chip8_core::interpreter::Interpreter;
+
+const BYTES_PER_ROW: usize = 16;
+
+/// A named span of memory, announced with a comment line in the dump.
+pub struct Region {
+    pub name: &'static str,
+    pub range: Range<usize>,
+}
+
+/// Addresses to highlight in the dump.
+#[derive(Default)]
+pub struct Markers {
+    pub pc: Option<usize>,
+    pub i: Option<usize>,
+}
+
+/// Format `memory` as a classic hexdump: 16 bytes per row with an ASCII
+/// column. Rows of zeroes outside any region are collapsed into a single
+/// `*` line. The byte at PC is prefixed with `>` and the byte at I with `*`.
+pub fn hexdump(memory: &[u8], regions: &[Region], markers: &Markers) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "; > PC, * I");
+
+    let is_marked = |addr: usize| markers.pc == Some(addr) || markers.i == Some(addr);
+    let mut collapsing = false;
+    for (row_idx, row) in memory.chunks(BYTES_PER_ROW).enumerate() {
+        let start = row_idx * BYTES_PER_ROW;
+        let row_range = start..start + row.len();
+
+        let starting_regions: Vec<&Region> = regions
+            .iter()
+            .filter(|r| row_range.contains(&r.range.start))
+            .collect();
+        let in_region = regions
+            .iter()
+            .any(|r| r.range.start < row_range.end && row_range.start < r.range.end);
+        let is_blank = row.iter().all(|b| *b == 0) && !row_range.clone().any(is_marked);
+        if is_blank && !in_region {
+            if !collapsing {
+                let _ = writeln!(out, "*");
+                collapsing = true;
+            }
+            continue;
+        }
+        collapsing = false;
+
+        for region in starting_regions {
+            let _ = writeln!(
+                out,
+                "; {} ({:#05x}-{:#05x})",
+                region.name,
+                region.range.start,
+                region.range.end.saturating_sub(1)
+            );
+        }
+
+        let _ = write!(out, "{:#05x}: ", start);
+        for (idx, b) in row.iter().enumerate() {
+            let addr = start + idx;
+            let prefix = if markers.pc == Some(addr) {
+                '>'
+            } else if markers.i == Some(addr) {
+                '*'
+            } else {
+                ' '
+            };
+            if idx == BYTES_PER_ROW / 2 {
+                out.push(' ');
+            }
+            let _ = write!(out, "{}{:02X}", prefix, b);
+        }
+        let ascii: String = row
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "  |{}|", ascii);
+    }
+
+    out
+}
+
+/// Dump the full memory map of an interpreter, with its font and program
+/// areas annotated and its PC and I positions highlighted.
+pub fn dump_interpreter(interpreter: &Interpreter) -> String {
+    let regions = [
+        Region {
+            name: "font",
+            range: interpreter.font_range(),
+        },
+        Region {
+            name: "program",
+            range: interpreter.program_range(),
+        },
+    ];
+    let markers = Markers {
+        pc: Some(interpreter.program_counter() as usize),
+        i: Some(interpreter.index_register() as usize),
+    };
+
+    hexdump(interpreter.memory(), &regions, &markers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hexdump_marks_and_collapses() {
+        let mut memory = [0_u8; 64];
+        memory[0x20] = b'A';
+        memory[0x21] = 0xFF;
+
+        let regions = [Region {
+            name: "program",
+            range: 0x20..0x22,
+        }];
+        let markers = Markers {
+            pc: Some(0x20),
+            i: Some(0x21),
+        };
+        let dump = hexdump(&memory, &regions, &markers);
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(lines[1], "*");
+        assert_eq!(lines[2], "; program (0x020-0x021)");
+        assert!(lines[3].starts_with("0x020: >41*FF 00"));
+        assert!(lines[3].ends_with("|A...............|"));
+        assert_eq!(lines[4], "*");
+        assert_eq!(lines.len(), 5);
+    }
+}
@@ -0,0 +1,70 @@
+use macroquad::prelude::*;
+
+use crate::{
+    timing::TimingSample,
+    ui_style::{FONT_SIZE, LINE_HEIGHT, MARGIN, OVERLAY_BACKGROUND},
+};
+
+/// drift beyond this fraction of the target is highlighted
+const DRIFT_WARNING: f64 = 0.1;
+
+/// Draws timing diagnostics in the top-right corner of the window, with
+/// `speed` the instruction rate as a multiple of the ROM's usual one, or
+/// None when VIP timing sets the pace.
+pub fn draw_hud(sample: Option<TimingSample>, speed: Option<f64>) {
+    let speed = match speed {
+        Some(speed) => format!("Speed {:.2}x", speed),
+        None => String::from("Speed: VIP timing"),
+    };
+    let mut lines: Vec<(String, bool)> = match sample {
+        Some(s) => vec![
+            (
+                format!(
+                    "IPS {:.0}/{:.0} ({:+.1}%)",
+                    s.instructions_per_sec,
+                    s.target_instructions_per_sec,
+                    s.instruction_drift() * 100.
+                ),
+                s.instruction_drift().abs() > DRIFT_WARNING,
+            ),
+            (
+                format!(
+                    "Timers {:.1} Hz ({:+.1}%)",
+                    s.timer_hz,
+                    s.timer_drift() * 100.
+                ),
+                s.timer_drift().abs() > DRIFT_WARNING,
+            ),
+            (
+                format!("FPS {:.1}, dropped {}", s.frames_per_sec, s.dropped_frames),
+                s.dropped_frames > 0,
+            ),
+        ],
+        None => vec![(String::from("measuring..."), false)],
+    };
+    lines.push((speed, false));
+
+    let width = lines
+        .iter()
+        .map(|(line, _)| measure_text(line, None, FONT_SIZE as u16, 1.).width)
+        .fold(0., f32::max);
+    let x = screen_width() - width - MARGIN;
+
+    draw_rectangle(
+        x - 6.,
+        MARGIN - 6.,
+        width + 12.,
+        lines.len() as f32 * LINE_HEIGHT + 12.,
+        OVERLAY_BACKGROUND,
+    );
+    for (idx, (line, is_off_target)) in lines.iter().enumerate() {
+        let color = if *is_off_target { ORANGE } else { WHITE };
+        draw_text(
+            line,
+            x,
+            MARGIN + (idx + 1) as f32 * LINE_HEIGHT - 6.,
+            FONT_SIZE,
+            color,
+        );
+    }
+}
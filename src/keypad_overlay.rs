@@ -0,0 +1,43 @@
+use macroquad::prelude::*;
+
+use crate::{
+    keys::{key_name, Key},
+    remap::LAYOUT,
+    ui_style::{MARGIN, OVERLAY_BACKGROUND},
+};
+
+const FONT_SIZE: f32 = 18.;
+const SMALL_FONT_SIZE: f32 = 14.;
+const CELL_SIZE: f32 = 40.;
+
+/// Draws the hex keypad in the bottom-right corner of the window, each key
+/// labelled with the physical key bound to it in `keypad` and lit while
+/// `pressed`.
+pub fn draw_keypad_overlay(keypad: &[Key; 16], pressed: &[bool; 16]) {
+    let left = screen_width() - 4. * CELL_SIZE - MARGIN;
+    let top = screen_height() - 4. * CELL_SIZE - MARGIN;
+    draw_rectangle(
+        left - 6.,
+        top - 6.,
+        4. * CELL_SIZE + 12.,
+        4. * CELL_SIZE + 12.,
+        OVERLAY_BACKGROUND,
+    );
+    for (idx, slot) in LAYOUT.iter().enumerate() {
+        let x = left + (idx % 4) as f32 * CELL_SIZE;
+        let y = top + (idx / 4) as f32 * CELL_SIZE;
+        if pressed[*slot] {
+            draw_rectangle(x + 2., y + 2., CELL_SIZE - 4., CELL_SIZE - 4., DARKBLUE);
+        }
+        let color = if pressed[*slot] { WHITE } else { GRAY };
+        draw_rectangle_lines(x + 2., y + 2., CELL_SIZE - 4., CELL_SIZE - 4., 2., color);
+        draw_text(&format!("{:X}", slot), x + 7., y + 19., FONT_SIZE, color);
+        draw_text(
+            &key_name(keypad[*slot].0),
+            x + 7.,
+            y + 33.,
+            SMALL_FONT_SIZE,
+            LIGHTGRAY,
+        );
+    }
+}
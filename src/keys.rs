@@ -0,0 +1,188 @@
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use macroquad::input::{is_key_down, is_key_pressed, KeyCode};
+
+/// every key that can be bound, in the order they are listed to users
+#[rustfmt::skip]
+const BINDABLE_KEYS: [KeyCode; 105] = {
+    use KeyCode::*;
+    [
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9,
+        KpDecimal, KpDivide, KpMultiply, KpSubtract, KpAdd, KpEnter, KpEqual,
+        Space, Apostrophe, Comma, Minus, Period, Slash, Semicolon, Equal,
+        LeftBracket, Backslash, RightBracket, GraveAccent,
+        Escape, Enter, Tab, Backspace, Insert, Delete,
+        Right, Left, Down, Up, PageUp, PageDown, Home, End,
+        CapsLock, ScrollLock, NumLock, PrintScreen, Pause, Menu,
+        LeftShift, LeftControl, LeftAlt, LeftSuper,
+        RightShift, RightControl, RightAlt, RightSuper,
+    ]
+};
+
+/// the name used for a key in the config file, e.g. "F3", "Q", or "1"
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name.strip_prefix("Key") {
+        Some(digit) => digit.to_string(),
+        None => name,
+    }
+}
+
+/// case-insensitive inverse of `key_name`
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .copied()
+        .find(|key| key_name(*key).eq_ignore_ascii_case(name))
+}
+
+/// An emulator hotkey: a key plus required modifiers, written "Shift+Escape".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub key: KeyCode,
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl Hotkey {
+    pub const fn new(key: KeyCode) -> Self {
+        Hotkey {
+            key,
+            shift: false,
+            ctrl: false,
+            alt: false,
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key_part = parts.pop().unwrap_or_default();
+        let key = parse_key(key_part).ok_or_else(|| format!("unknown key \"{}\"", key_part))?;
+
+        let mut hotkey = Hotkey::new(key);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "shift" => hotkey.shift = true,
+                "ctrl" => hotkey.ctrl = true,
+                "alt" => hotkey.alt = true,
+                _ => return Err(format!("unknown modifier \"{}\"", modifier)),
+            }
+        }
+        Ok(hotkey)
+    }
+
+    /// true on the frame the hotkey was pressed
+    pub fn is_pressed(&self) -> bool {
+        is_key_pressed(self.key) && self.modifiers_held()
+    }
+
+    /// true for as long as the hotkey is held
+    pub fn is_down(&self) -> bool {
+        is_key_down(self.key) && self.modifiers_held()
+    }
+
+    fn modifiers_held(&self) -> bool {
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let alt = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+        (!self.shift || shift) && (!self.ctrl || ctrl) && (!self.alt || alt)
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", key_name(self.key))
+    }
+}
+
+impl Serialize for Hotkey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hotkey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Hotkey::parse(&s).map_err(de::Error::custom)
+    }
+}
+
+/// A single physical key, stored by name in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key(pub KeyCode);
+
+/// ordered by key code, so keys can be looked up in a `BTreeMap`
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.0 as u32).cmp(&(other.0 as u32))
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&key_name(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_key(&s)
+            .map(Key)
+            .ok_or_else(|| de::Error::custom(format!("unknown key \"{}\"", s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names_round_trip() {
+        for key in BINDABLE_KEYS {
+            assert_eq!(parse_key(&key_name(key)), Some(key));
+        }
+        assert_eq!(key_name(KeyCode::Key1), "1");
+        assert_eq!(parse_key("escape"), Some(KeyCode::Escape));
+        assert_eq!(parse_key("Unknown"), None);
+    }
+
+    #[test]
+    fn test_parse_hotkey() {
+        assert_eq!(Hotkey::parse("F3"), Ok(Hotkey::new(KeyCode::F3)));
+
+        let quit = Hotkey::parse("Shift+Escape").unwrap();
+        assert!(quit.shift && !quit.ctrl);
+        assert_eq!(quit.key, KeyCode::Escape);
+        assert_eq!(quit.to_string(), "Shift+Escape");
+
+        let fullscreen = Hotkey::parse("alt+enter").unwrap();
+        assert!(fullscreen.alt && !fullscreen.shift);
+        assert_eq!(fullscreen.to_string(), "Alt+Enter");
+
+        assert!(Hotkey::parse("Hyper+F3").is_err());
+        assert!(Hotkey::parse("Ctrl+").is_err());
+    }
+}
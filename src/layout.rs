@@ -0,0 +1,58 @@
+use macroquad::math::Rect;
+
+/// Where to draw a display of `size` pixels in a window of the given size:
+/// scaled by the largest whole number that fits, so every display pixel is
+/// the same size, and centered with the rest left as black bars. Never
+/// smaller than one window pixel per display pixel.
+pub fn fit_display(window_width: f32, window_height: f32, size: (f32, f32)) -> Rect {
+    let (width, height) = size;
+    let scale = (window_width / width)
+        .min(window_height / height)
+        .floor()
+        .max(1.);
+    Rect::new(
+        ((window_width - width * scale) / 2.).floor(),
+        ((window_height - height * scale) / 2.).floor(),
+        width * scale,
+        height * scale,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::{
+        interpreter::{SCREEN_HEIGHT, SCREEN_WIDTH},
+        megachip::{MEGA_HEIGHT, MEGA_WIDTH},
+    };
+
+    use super::*;
+
+    const CHIP8: (f32, f32) = (SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32);
+
+    #[test]
+    fn test_fit_display() {
+        assert_eq!(
+            fit_display(1024., 512., CHIP8),
+            Rect::new(0., 0., 1024., 512.)
+        );
+        // limited by width, bars above and below
+        assert_eq!(
+            fit_display(1920., 1080., CHIP8),
+            Rect::new(0., 60., 1920., 960.)
+        );
+        assert_eq!(
+            fit_display(1000., 1000., CHIP8),
+            Rect::new(20., 260., 960., 480.)
+        );
+        assert_eq!(
+            fit_display(10., 10., CHIP8),
+            Rect::new(-27., -11., 64., 32.)
+        );
+        // MegaChip's display at 4x, in the same window
+        let mega = (MEGA_WIDTH as f32, MEGA_HEIGHT as f32);
+        assert_eq!(
+            fit_display(1024., 1000., mega),
+            Rect::new(0., 116., 1024., 768.)
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! ROMs to offer in the start menu: the ones built into the binary, and
+//! files opened recently.
+//!
+//! Natively the recent list is kept in `$XDG_DATA_HOME/chip8-rust/recent`,
+//! one path per line. The browser has no files to reopen, so there the
+//! list stays empty.
+
+include!(concat!(env!("OUT_DIR"), "/roms.rs"));
+
+/// how many recently opened files are remembered
+pub const RECENT_COUNT: usize = 8;
+
+/// a built-in ROM by name, ignoring case
+pub fn embedded(name: &str) -> Option<&'static [u8]> {
+    ROMS.iter()
+        .find(|(rom, _)| rom.eq_ignore_ascii_case(name))
+        .map(|(_, bytes)| *bytes)
+}
+
+/// Recently opened ROM files, most recent first.
+#[derive(Debug, Default)]
+pub struct RecentFiles {
+    paths: Vec<String>,
+}
+
+impl RecentFiles {
+    /// the saved list, or an empty one if there isn't one yet
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(contents) = file_path().and_then(|path| std::fs::read_to_string(path).ok()) {
+            return RecentFiles {
+                paths: contents
+                    .lines()
+                    .map(String::from)
+                    .take(RECENT_COUNT)
+                    .collect(),
+            };
+        }
+        RecentFiles::default()
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// move `path` to the front, dropping the oldest once the list is full
+    pub fn push(&mut self, path: &str) {
+        self.paths.retain(|recent| recent != path);
+        self.paths.insert(0, path.to_string());
+        self.paths.truncate(RECENT_COUNT);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = file_path().ok_or("no data directory")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut contents = self.paths.join("\n");
+        contents.push('\n');
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn file_path() -> Option<std::path::PathBuf> {
+    Some(crate::config::data_dir()?.join("recent"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded() {
+        assert_eq!(embedded("pong").map(<[u8]>::len), Some(246));
+        assert!(embedded("not a rom").is_none());
+        // duplicates and the README are left out
+        assert!(embedded("IBM_LOGO").is_none());
+        assert!(embedded("README").is_none());
+    }
+
+    #[test]
+    fn test_recent_files() {
+        let mut recent = RecentFiles::default();
+        for idx in 0..RECENT_COUNT + 2 {
+            recent.push(&format!("{}.ch8", idx));
+        }
+        recent.push("3.ch8");
+        assert_eq!(recent.paths().len(), RECENT_COUNT);
+        assert_eq!(recent.paths()[0], "3.ch8");
+        assert_eq!(recent.paths()[1], "9.ch8");
+        assert_eq!(recent.paths().iter().filter(|p| *p == "3.ch8").count(), 1);
+    }
+}
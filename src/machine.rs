@@ -0,0 +1,795 @@
+use std::time::Instant;
+
+use chip8_core::{
+    interpreter::{decode, Interpreter, Rows, Snapshot, HIRES_SCREEN_HEIGHT, PROGRAM_START},
+    vip_timing, Chip8Error, Quirks, StepOutcome,
+};
+
+use crate::{
+    cheats::Cheat,
+    coverage::Coverage,
+    movie::Movie,
+    profiler::Profiler,
+    rewind::{RewindBuffer, REWIND_SECONDS},
+    timing::TIMER_HZ,
+};
+
+/// default emulation speed, 5 instructions per 60 Hz frame
+pub const DEFAULT_INSTRUCTIONS_PER_SECOND: u32 = 300;
+
+/// Everything a frontend feeds into a frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Inputs {
+    /// whether CHIP-8 key i is held down
+    pub keys: [bool; 16],
+    /// whether CHIP-8 key i was pressed and released since the last
+    /// frame, too quickly to show up in `keys`
+    pub taps: [bool; 16],
+}
+
+/// Everything a frontend needs to present a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameOutput {
+    /// the display, packed as `Interpreter::rows` packs it
+    pub rows: Rows,
+    /// how many of `rows` are in use, more for hires ROMs
+    pub height: usize,
+    /// the buzzer should be sounding
+    pub sound: bool,
+    /// the buzzer switched on or off during this frame
+    pub sound_changed: bool,
+    /// why the frame ended early: a breakpoint or watchpoint
+    pub stop: Option<StepOutcome>,
+    /// how many instructions ran
+    pub instructions: u32,
+}
+
+/// The "batteries included" way to run a ROM: owns the interpreter along
+/// with frame timing and buzzer state, so a frontend only has to call
+/// `frame` 60 times per second. `Interpreter` remains the low-level core for
+/// anyone who needs finer control.
+pub struct Chip8Machine {
+    interpreter: Interpreter,
+    quirks: Quirks,
+    seed: Option<u64>,
+    lenient: bool,
+    strict: bool,
+    history_len: usize,
+    program_start: u16,
+    megachip: bool,
+    instructions_per_second: u32,
+    /// instructions owed from earlier frames, in 1/60ths, when the speed
+    /// isn't a multiple of 60
+    instruction_remainder: u32,
+    /// charge instructions their COSMAC VIP cycle costs instead of counting them
+    vip_timing: bool,
+    /// cycles left in this frame, or owed from the last one when negative
+    cycle_balance: i64,
+    sound: bool,
+    /// when set, frames present `front_buffer` instead of the live display
+    double_buffered: bool,
+    front_buffer: Rows,
+    /// state at the start of each recent frame
+    history: RewindBuffer,
+    profiler: Option<Profiler>,
+    /// what's run since the ROM was loaded
+    coverage: Coverage,
+    /// every frame since the ROM was loaded or reset, when recording
+    movie: Option<Movie>,
+    recording_movie: bool,
+    /// memory written back after every instruction
+    cheats: Vec<Cheat>,
+}
+
+impl Chip8Machine {
+    pub fn new() -> Self {
+        Chip8Machine {
+            interpreter: Interpreter::new(),
+            quirks: Quirks::default(),
+            seed: None,
+            lenient: false,
+            strict: false,
+            history_len: 0,
+            program_start: PROGRAM_START as u16,
+            megachip: false,
+            instructions_per_second: DEFAULT_INSTRUCTIONS_PER_SECOND,
+            instruction_remainder: 0,
+            vip_timing: false,
+            cycle_balance: 0,
+            sound: false,
+            double_buffered: false,
+            front_buffer: [0; HIRES_SCREEN_HEIGHT],
+            history: RewindBuffer::new(REWIND_SECONDS * TIMER_HZ as usize),
+            profiler: None,
+            coverage: Coverage::new(),
+            movie: None,
+            recording_movie: false,
+            cheats: vec![],
+        }
+    }
+
+    /// Present the display as it was at each 60 Hz tick rather than
+    /// wherever the frame's instruction budget happened to stop. Games that
+    /// wait on the delay timer and then erase and redraw their sprites are
+    /// always shown between redraws, at the cost of one frame of latency.
+    /// Off by default, since real hardware shows partial redraws.
+    pub fn set_double_buffered(&mut self, double_buffered: bool) {
+        self.double_buffered = double_buffered;
+    }
+
+    pub fn set_instructions_per_second(&mut self, instructions_per_second: u32) {
+        self.instructions_per_second = instructions_per_second;
+        self.instruction_remainder = 0;
+    }
+
+    /// Run as many instructions each frame as would have fit in a COSMAC
+    /// VIP frame, going by each one's approximate cost in machine cycles,
+    /// rather than `instructions_per_second` of them whatever they are.
+    /// Slow instructions like DRW then slow a game down as they did on
+    /// the VIP. Off by default.
+    pub fn set_vip_timing(&mut self, vip_timing: bool) {
+        self.vip_timing = vip_timing;
+        self.cycle_balance = 0;
+    }
+
+    /// quirks for the interpreter, taking effect on the next `load`
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// the quirks the next `load` uses
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Switch the running ROM to the next quirks preset, going from the
+    /// default through `Quirks::PRESETS` and keeping whether sprites and
+    /// memory wrap.
+    /// Returns the preset's name.
+    pub fn cycle_quirks(&mut self) -> &'static str {
+        let presets: Vec<(&str, Quirks)> = std::iter::once(("default", Quirks::default()))
+            .chain(Quirks::PRESETS)
+            .map(|(name, preset)| {
+                let quirks = Quirks {
+                    wrap_sprites: self.quirks.wrap_sprites,
+                    memory_wraps: self.quirks.memory_wraps,
+                    ..preset
+                };
+                (name, quirks)
+            })
+            .collect();
+        let next = presets
+            .iter()
+            .position(|(_, quirks)| *quirks == self.quirks)
+            .map_or(0, |current| (current + 1) % presets.len());
+        let (name, quirks) = presets[next];
+        self.quirks = quirks;
+        self.interpreter.set_quirks(quirks);
+        name
+    }
+
+    /// seed for RND, taking effect on the next `load`
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    /// skip invalid opcodes instead of stopping on them, taking effect on the next `load`
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// stop on SYS, writes below 0x200, and undocumented opcodes, taking effect on the next `load`
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// how many executed instructions the interpreter keeps for
+    /// `Interpreter::history`, taking effect on the next `load`
+    pub fn set_history_len(&mut self, len: usize) {
+        self.history_len = len;
+    }
+
+    /// where ROMs are loaded and start running, taking effect on the next `load`
+    pub fn set_program_start(&mut self, addr: u16) {
+        self.program_start = addr;
+    }
+
+    /// support MegaChip ROMs, taking effect on the next `load`
+    pub fn set_megachip(&mut self, megachip: bool) {
+        self.megachip = megachip;
+    }
+
+    /// whether the next `load` supports MegaChip ROMs
+    pub fn megachip(&self) -> bool {
+        self.megachip
+    }
+
+    /// Count and time every instruction executed, per instruction form and
+    /// per address, from now until the next `load`. Off by default, since
+    /// timing each instruction slows emulation down.
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profiler = profiling.then(Profiler::new);
+    }
+
+    /// what's been executed so far, if profiling
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// which bytes have run as instructions since the ROM was loaded
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    /// Record the inputs of every frame as a movie, starting from the next
+    /// `load`. The movie starts over when the ROM is reset or a state is
+    /// restored, and stepping single instructions isn't recorded, so a
+    /// movie made while debugging won't play back.
+    pub fn set_recording_movie(&mut self, recording: bool) {
+        self.recording_movie = recording;
+        if !recording {
+            self.movie = None;
+        }
+    }
+
+    /// the movie recorded so far
+    pub fn movie(&self) -> Option<&Movie> {
+        self.movie.as_ref()
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// the cheats, to add to or turn on and off; they stay across loads
+    pub fn cheats_mut(&mut self) -> &mut Vec<Cheat> {
+        &mut self.cheats
+    }
+
+    /// load a ROM into a freshly initialized interpreter, keeping the current one if it doesn't fit
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let mut interpreter = Interpreter::with_quirks(self.quirks);
+        if let Some(seed) = self.seed {
+            interpreter.set_seed(seed);
+        }
+        interpreter.set_lenient(self.lenient);
+        interpreter.set_strict(self.strict);
+        interpreter.set_history_len(self.history_len);
+        interpreter.set_program_start(self.program_start);
+        interpreter.set_megachip(self.megachip);
+        interpreter.load_program(rom)?;
+        self.interpreter = interpreter;
+        self.sound = false;
+        self.front_buffer = *self.interpreter.rows();
+        self.history.clear();
+        if let Some(profiler) = &mut self.profiler {
+            *profiler = Profiler::new();
+        }
+        self.coverage = Coverage::new();
+        if self.recording_movie {
+            self.movie = Some(Movie::new(rom));
+        }
+        Ok(())
+    }
+
+    /// start the loaded ROM over without reading it again; see `Interpreter::reset`
+    pub fn reset(&mut self) {
+        self.interpreter.reset();
+        self.instruction_remainder = 0;
+        self.cycle_balance = 0;
+        self.front_buffer = *self.interpreter.rows();
+        self.history.clear();
+        if let Some(movie) = &mut self.movie {
+            movie.frames.clear();
+        }
+    }
+
+    /// Run one 60 Hz frame: apply inputs, tick the timers, then execute the
+    /// frame's instruction budget.
+    pub fn frame(&mut self, inputs: &Inputs) -> Result<FrameOutput, Chip8Error> {
+        // snapshots leave out MegaChip's state, so there's nothing to rewind to
+        if self.interpreter.megachip().is_none() {
+            self.history.push(self.interpreter.snapshot());
+        }
+        self.set_keys(inputs);
+        self.interpreter.tick_60hz();
+        // after the tick, which would let go of them straight away
+        self.tap_keys(inputs);
+        if self.double_buffered {
+            self.front_buffer = *self.interpreter.rows();
+        }
+
+        let budget = if self.vip_timing {
+            // a frame can't save up cycles, but an instruction that ran past
+            // the end of the last one eats into this one
+            self.cycle_balance =
+                self.cycle_balance.min(0) + vip_timing::CYCLES_FOR_INSTRUCTIONS as i64;
+            u32::MAX
+        } else {
+            let hz = TIMER_HZ as u32;
+            self.instruction_remainder += self.instructions_per_second;
+            let budget = self.instruction_remainder / hz;
+            self.instruction_remainder %= hz;
+            budget
+        };
+
+        let mut stop = None;
+        let mut instructions = 0;
+        for _ in 0..budget {
+            if self.interpreter.waiting_for_vblank() || (self.vip_timing && self.cycle_balance <= 0)
+            {
+                break;
+            }
+            let cycles = self.next_instruction_cycles();
+            let outcome = self.step()?;
+            if self.vip_timing && !matches!(outcome, StepOutcome::Breakpoint(_)) {
+                self.cycle_balance -= cycles as i64;
+            }
+            if outcome != StepOutcome::Executed {
+                stop = Some(outcome);
+                break;
+            }
+            instructions += 1;
+        }
+
+        let sound = self.interpreter.should_play_sound();
+        let sound_changed = sound != self.sound;
+        self.sound = sound;
+        if let Some(movie) = &mut self.movie {
+            movie.push(inputs, &self.interpreter);
+        }
+
+        Ok(FrameOutput {
+            rows: *self.presented_rows(),
+            height: self.interpreter.screen_height(),
+            sound,
+            sound_changed,
+            stop,
+            instructions,
+        })
+    }
+
+    /// Run `count` frames back to back, e.g. to catch up after a slow
+    /// render, stopping early at a breakpoint or watchpoint. A count of 0
+    /// presents the current state with the buzzer left as it is.
+    pub fn frames(&mut self, inputs: &Inputs, count: u32) -> Result<FrameOutput, Chip8Error> {
+        let sound_before = self.sound;
+        let mut output = FrameOutput {
+            rows: *self.presented_rows(),
+            height: self.interpreter.screen_height(),
+            sound: self.sound,
+            sound_changed: false,
+            stop: None,
+            instructions: 0,
+        };
+        for frame in 0..count {
+            let instructions = output.instructions;
+            // a tap is one short press, in the first frame
+            let inputs = match frame {
+                0 => *inputs,
+                _ => Inputs {
+                    taps: [false; 16],
+                    ..*inputs
+                },
+            };
+            output = self.frame(&inputs)?;
+            output.instructions += instructions;
+            if output.stop.is_some() {
+                break;
+            }
+        }
+        output.sound_changed = output.sound != sound_before;
+        Ok(output)
+    }
+
+    /// Execute a single instruction without ticking the timers, for
+    /// stepping through a paused program. The buzzer stays quiet, and the
+    /// live display is shown even when double buffered so each draw is
+    /// visible as it happens.
+    pub fn step_instruction(&mut self, inputs: &Inputs) -> Result<FrameOutput, Chip8Error> {
+        self.set_keys(inputs);
+        self.tap_keys(inputs);
+        // there are no frames while stepping, so don't wait for one
+        self.interpreter.end_vblank_wait();
+        let outcome = self.step()?;
+        Ok(FrameOutput {
+            rows: *self.interpreter.rows(),
+            height: self.interpreter.screen_height(),
+            stop: (outcome != StepOutcome::Executed).then_some(outcome),
+            instructions: (outcome == StepOutcome::Executed) as u32,
+            ..self.idle_frame()
+        })
+    }
+
+    /// what the next instruction costs on a COSMAC VIP, and at least one
+    /// cycle so that a frame's budget always runs out, even with no
+    /// instruction to fetch
+    fn next_instruction_cycles(&self) -> u32 {
+        self.interpreter
+            .next_instruction()
+            .map_or(1, |word| vip_timing::cycles(&decode(word)).max(1))
+    }
+
+    /// step the interpreter, recording the instruction's coverage, and its timing when profiling
+    fn step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        let pc = self.interpreter.program_counter();
+        let word = self.interpreter.next_instruction();
+        let outcome = self.profiled_step()?;
+        if let Some(word) = word.filter(|_| !matches!(outcome, StepOutcome::Breakpoint(_))) {
+            self.coverage.record(pc, word);
+        }
+        for cheat in &self.cheats {
+            cheat.apply(&mut self.interpreter);
+        }
+        Ok(outcome)
+    }
+
+    fn profiled_step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        let Some(profiler) = &mut self.profiler else {
+            return self.interpreter.step();
+        };
+        let pc = self.interpreter.program_counter();
+        let word = self.interpreter.next_instruction();
+        let started = Instant::now();
+        let outcome = self.interpreter.step()?;
+        let elapsed = started.elapsed();
+        // stopping at a breakpoint doesn't execute anything
+        if let Some(word) = word.filter(|_| !matches!(outcome, StepOutcome::Breakpoint(_))) {
+            profiler.record(pc, word, elapsed);
+        }
+        Ok(outcome)
+    }
+
+    fn set_keys(&mut self, inputs: &Inputs) {
+        for (idx, is_down) in inputs.keys.iter().enumerate() {
+            self.interpreter.set_key(idx, *is_down);
+        }
+    }
+
+    fn tap_keys(&mut self, inputs: &Inputs) {
+        for (idx, tapped) in inputs.taps.iter().enumerate() {
+            if *tapped {
+                self.interpreter.press_and_release(idx);
+            }
+        }
+    }
+
+    /// Go back to the start of the previous frame instead of running a new
+    /// one, staying put once the history runs out. The buzzer stays quiet
+    /// while rewinding.
+    pub fn rewind_frame(&mut self) -> FrameOutput {
+        if let Some(snapshot) = self.history.pop() {
+            self.interpreter.restore(&snapshot);
+            self.front_buffer = *self.interpreter.rows();
+            if let Some(movie) = &mut self.movie {
+                movie.frames.pop();
+            }
+        }
+        self.idle_frame()
+    }
+
+    /// Present the current state without running anything, with the buzzer
+    /// quiet, e.g. while paused.
+    pub fn idle_frame(&mut self) -> FrameOutput {
+        let sound_changed = self.sound;
+        self.sound = false;
+        FrameOutput {
+            rows: *self.presented_rows(),
+            height: self.interpreter.screen_height(),
+            sound: false,
+            sound_changed,
+            stop: None,
+            instructions: 0,
+        }
+    }
+
+    fn presented_rows(&self) -> &Rows {
+        if self.double_buffered {
+            &self.front_buffer
+        } else {
+            self.interpreter.rows()
+        }
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.interpreter.snapshot()
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.interpreter.restore(snapshot);
+        self.sound = self.interpreter.should_play_sound();
+        self.front_buffer = *self.interpreter.rows();
+        if let Some(movie) = &mut self.movie {
+            movie.frames.clear();
+        }
+    }
+
+    pub fn instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::RunState;
+
+    #[test]
+    fn test_frame_runs_instruction_budget() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // 5x LD V0, 2 fills the first frame, so LD ST, V0 only runs in the second
+        let mut rom = [0x60, 0x02].repeat(5);
+        rom.extend([0xF0, 0x18]);
+        machine.load(&rom)?;
+
+        assert!(!machine.frame(&Inputs::default())?.sound);
+        assert!(machine.frame(&Inputs::default())?.sound);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_reports_sound_edges() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // LD V0, 2; LD ST, V0; JP 0x204
+        machine.load(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04])?;
+
+        let output = machine.frame(&Inputs::default())?;
+        assert!(output.sound && output.sound_changed);
+        let output = machine.frame(&Inputs::default())?;
+        assert!(output.sound && !output.sound_changed);
+        let output = machine.frame(&Inputs::default())?;
+        assert!(!output.sound && output.sound_changed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_taps_last_one_frame() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // SKNP V0; ADD V1, 1; JP 0x200
+        machine.load(&[0xE0, 0xA1, 0x71, 0x01, 0x12, 0x00])?;
+        let mut inputs = Inputs::default();
+        inputs.taps[0] = true;
+        machine.frames(&inputs, 3)?;
+        // key 0 read as down twice in the first frame's 5 instructions, then up
+        assert_eq!(machine.interpreter().registers()[1], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cheats_rewrite_memory_after_each_instruction() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // LD I, 0x300; LD V0, 5; LD [I], V0; LD I, 0x300; LD V0, [I]; JP 0x20A
+        machine.load(&[
+            0xA3, 0x00, 0x60, 0x05, 0xF0, 0x55, 0xA3, 0x00, 0xF0, 0x65, 0x12, 0x0A,
+        ])?;
+        machine.cheats_mut().push(Cheat::parse("300=09").unwrap());
+        machine.frames(&Inputs::default(), 2)?;
+        assert_eq!(machine.interpreter().registers()[0], 9);
+        assert_eq!(machine.interpreter().memory()[0x300], 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vip_timing_charges_cycles() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        machine.set_vip_timing(true);
+        // LD I, 0x50; DRW V0, V0, 15; JP 0x202
+        machine.load(&[0xA0, 0x50, 0xD0, 0x0F, 0x12, 0x02])?;
+        let frames: Vec<u32> = (0..3)
+            .map(|_| {
+                machine
+                    .frame(&Inputs::default())
+                    .map(|output| output.instructions)
+            })
+            .collect::<Result<_, _>>()?;
+        // a DRW and JP pair costs 1138 of the 2644 cycles in a frame, and
+        // the DRW that runs past the end of one frame is paid for in the next
+        assert_eq!(frames, [6, 4, 4]);
+
+        // whereas loads are cheap
+        machine.load(&[0x60, 0x01, 0x12, 0x00])?;
+        assert_eq!(machine.frame(&Inputs::default())?.instructions, 54);
+
+        // a halted program, with no instruction left to fetch, still ends the frame
+        machine.load(&[0x1F, 0xFF])?;
+        machine.frame(&Inputs::default())?;
+        assert!(matches!(
+            machine.interpreter().state(),
+            RunState::Halted { .. }
+        ));
+        assert_eq!(machine.next_instruction_cycles(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_double_buffering_presents_display_at_tick() -> Result<(), Chip8Error> {
+        // draw the "0" glyph, then erase it again in the next frame;
+        // LD I, 0x50 (font); DRW V0, V0, 5; 3x SYS; DRW V0, V0, 5; JP 0x20C
+        let rom = [
+            0xA0, 0x50, 0xD0, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xD0, 0x05, 0x12, 0x0C,
+        ];
+        let drawn = |output: &FrameOutput| output.rows.iter().any(|row| *row != 0);
+
+        let mut machine = Chip8Machine::new();
+        machine.load(&rom)?;
+        assert!(drawn(&machine.frame(&Inputs::default())?));
+        assert!(!drawn(&machine.frame(&Inputs::default())?));
+
+        machine.load(&rom)?;
+        machine.set_double_buffered(true);
+        // the sprite shows up one frame later, as it stood at the tick
+        assert!(!drawn(&machine.frame(&Inputs::default())?));
+        assert!(drawn(&machine.frame(&Inputs::default())?));
+        assert!(!drawn(&machine.frame(&Inputs::default())?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_profiling_counts_executed_instructions() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        machine.set_profiling(true);
+        // ADD V0, 1; JP 0x200
+        machine.load(&[0x70, 0x01, 0x12, 0x00])?;
+        machine.interpreter_mut().add_breakpoint(0x202);
+        // ADD, then a stop at the breakpoint; JP and ADD, then the breakpoint again
+        machine.frame(&Inputs::default())?;
+        machine.frame(&Inputs::default())?;
+        let report = machine.profiler().unwrap().report();
+        assert!(report.starts_with("3 instructions executed"));
+
+        machine.load(&[0x70, 0x01])?;
+        assert!(machine
+            .profiler()
+            .unwrap()
+            .report()
+            .starts_with("0 instructions"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_coverage_records_executed_instructions() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // JP 0x204; CLS, never run; ADD V0, 1; JP 0x204
+        machine.load(&[0x12, 0x04, 0x00, 0xE0, 0x70, 0x01, 0x12, 0x04])?;
+        machine.interpreter_mut().add_breakpoint(0x206);
+        machine.frame(&Inputs::default())?;
+        assert_eq!(
+            machine.coverage().ranges(),
+            [(0x200, 0x201), (0x204, 0x205)]
+        );
+
+        machine.reset();
+        machine.interpreter_mut().remove_breakpoint(0x206);
+        machine.frame(&Inputs::default())?;
+        assert_eq!(
+            machine.coverage().ranges(),
+            [(0x200, 0x201), (0x204, 0x207)]
+        );
+        machine.load(&[0x12, 0x00])?;
+        assert!(machine.coverage().ranges().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewind_restores_earlier_frames() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // ADD V0, 1; JP 0x200
+        machine.load(&[0x70, 0x01, 0x12, 0x00])?;
+        for _ in 0..3 {
+            machine.frame(&Inputs::default())?;
+        }
+        let after_one_frame = {
+            let mut machine = Chip8Machine::new();
+            machine.load(&[0x70, 0x01, 0x12, 0x00])?;
+            machine.frame(&Inputs::default())?;
+            machine.snapshot()
+        };
+
+        machine.rewind_frame();
+        machine.rewind_frame();
+        assert_eq!(machine.snapshot(), after_one_frame);
+
+        // the history ends at the state right after loading
+        machine.rewind_frame();
+        let start = machine.snapshot();
+        machine.rewind_frame();
+        assert_eq!(machine.snapshot(), start);
+        assert_eq!(start.registers[0], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frame_ends_at_breakpoint() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // LD V0, 1; LD V1, 1; LD V2, 1
+        machine.load(&[0x60, 0x01, 0x61, 0x01, 0x62, 0x01])?;
+        machine.interpreter_mut().add_breakpoint(0x202);
+
+        assert_eq!(
+            machine.frame(&Inputs::default())?.stop,
+            Some(StepOutcome::Breakpoint(0x202))
+        );
+        assert_eq!(machine.interpreter().registers()[..3], [1, 0, 0]);
+        assert_eq!(machine.frame(&Inputs::default())?.stop, None);
+        assert_eq!(machine.interpreter().registers()[..3], [1, 1, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_instruction_runs_one_instruction() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // LD V0, 2; LD DT, V0; LD V1, 1
+        machine.load(&[0x60, 0x02, 0xF0, 0x15, 0x61, 0x01])?;
+
+        machine.step_instruction(&Inputs::default())?;
+        assert_eq!(machine.interpreter().registers()[..2], [2, 0]);
+        machine.step_instruction(&Inputs::default())?;
+        machine.step_instruction(&Inputs::default())?;
+        assert_eq!(machine.interpreter().registers()[..2], [2, 1]);
+        // stepping leaves the timers alone
+        assert_eq!(machine.interpreter().delay_timer(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frames_spread_instructions_per_second() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        // ADD V0, 1; JP 0x200
+        machine.load(&[0x70, 0x01, 0x12, 0x00])?;
+        // 8.5 instructions per frame
+        machine.set_instructions_per_second(510);
+
+        assert_eq!(machine.frame(&Inputs::default())?.instructions, 8);
+        assert_eq!(machine.frame(&Inputs::default())?.instructions, 9);
+        assert_eq!(machine.frames(&Inputs::default(), 4)?.instructions, 34);
+        assert_eq!(machine.frames(&Inputs::default(), 0)?.instructions, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycle_quirks() -> Result<(), Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        machine.set_quirks(Quirks {
+            wrap_sprites: true,
+            ..Quirks::default()
+        });
+        machine.load(&[0x12, 0x00])?;
+
+        assert_eq!(machine.cycle_quirks(), "vip");
+        assert_eq!(
+            machine.interpreter().quirks(),
+            Quirks {
+                wrap_sprites: true,
+                ..Quirks::COSMAC_VIP
+            }
+        );
+        assert_eq!(machine.cycle_quirks(), "schip");
+        assert_eq!(machine.cycle_quirks(), "amiga");
+        assert_eq!(machine.cycle_quirks(), "default");
+        assert!(machine.interpreter().quirks().wrap_sprites);
+
+        // quirks that match no preset start over from the default
+        machine.set_quirks(Quirks {
+            shift_uses_vy: false,
+            ..Quirks::COSMAC_VIP
+        });
+        assert_eq!(machine.cycle_quirks(), "default");
+
+        Ok(())
+    }
+}
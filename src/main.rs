@@ -1,163 +1,554 @@
-use std::error::Error;
-use std::io::Read;
+use std::{collections::BTreeMap, error::Error};
 
-use interpreter::Interpreter;
+use cheats::Cheat;
+use chip8_core::{interpreter::PROGRAM_START, Quirks};
+use clap::Parser;
+use cli::{Cli, Command, RunOptions};
+use config::Config;
+use display::Display;
+use keys::key_name;
+use machine::{Chip8Machine, Inputs, DEFAULT_INSTRUCTIONS_PER_SECOND};
+use rom_db::Profile;
+use rpl_flags::RplFlags;
+use timing::TickClock;
+use toast::Toasts;
 
-mod font;
-mod interpreter;
+mod analyzer;
+mod assembler;
+#[cfg(not(target_arch = "wasm32"))]
+mod audio_capture;
+mod bench;
+mod buzzer;
+mod cheats;
+mod cli;
+mod commands;
+mod compare;
+mod config;
+mod coverage;
+mod crt;
+mod debug_overlay;
+#[cfg(not(target_arch = "wasm32"))]
+mod debug_server;
+mod digitized_sound;
+mod disassembler;
+mod display;
+mod file_dialog;
+mod finished;
+#[cfg(not(target_arch = "wasm32"))]
+mod frame_stream;
+mod gamepad;
+#[cfg(test)]
+mod golden;
+mod gui;
+mod hexdump;
+mod hud;
+mod keypad_overlay;
+mod keys;
+mod layout;
+mod library;
+mod machine;
+mod memory_search;
+mod memory_view;
+mod menu;
+mod movie;
+#[cfg(not(target_arch = "wasm32"))]
+mod netplay;
+mod octo;
+mod palette;
+mod patch;
+mod pause_menu;
+mod phosphor;
+mod poke_prompt;
+mod profiler;
+mod recorders;
+mod recording;
+mod remap;
+mod remote;
+mod rewind;
+mod rom_db;
+mod rom_info;
+mod rpl_flags;
+mod save_slots;
+mod screenshot;
+mod suite;
+mod symbols;
+mod timing;
+mod toast;
+mod touch_keypad;
+#[cfg(all(feature = "tui", not(target_arch = "wasm32")))]
+mod tui;
+mod ui_style;
+#[cfg(not(target_arch = "wasm32"))]
+mod video_export;
 
-use macroquad::audio::{load_sound, play_sound, set_sound_volume, PlaySoundParams};
 use macroquad::prelude::*;
 
-use macroquad::{
-    color::Color,
-    input::{is_key_released, KeyCode},
-    window::{next_frame, Conf},
-};
+use macroquad::window::{next_frame, Conf};
 
-const SCALE: f32 = 16.;
+/// initial window pixels per CHIP-8 pixel, unless `--scale` says otherwise
+const DEFAULT_SCALE: f32 = 16.;
 
-const WINDOW_WIDTH: f32 = 64. * SCALE;
-const WINDOW_HEIGHT: f32 = 32. * SCALE;
-
-fn conf() -> Conf {
+fn conf(scale: f32) -> Conf {
     #[allow(clippy::cast_possible_truncation)]
     Conf {
         window_title: String::from("Chip 8"),
-        window_width: WINDOW_WIDTH as i32,
-        window_height: WINDOW_HEIGHT as i32,
+        window_width: (64. * scale) as i32,
+        window_height: (32. * scale) as i32,
         high_dpi: true,
         ..Default::default()
     }
 }
 
-const INSTRUCTIONS_PER_LOOP: usize = 5;
-
-fn capture_keyboard_input(interpreter: &mut Interpreter) {
-    for (idx, k) in [
-        // this order relates to the original layout of the Chip-8 Keyboard
-        KeyCode::X,    // 0
-        KeyCode::Key1, // 1
-        KeyCode::Key2, // 2
-        KeyCode::Key3, // 3
-        KeyCode::Q,    // 4
-        KeyCode::W,    // 5
-        KeyCode::E,    // 6
-        KeyCode::A,    // 7
-        KeyCode::S,    // 8
-        KeyCode::D,    // 9
-        KeyCode::Z,    // A
-        KeyCode::C,    // B
-        KeyCode::Key4, // C
-        KeyCode::R,    // D
-        KeyCode::F,    // E
-        KeyCode::V,    // F
-    ]
-    .iter()
-    .enumerate()
-    {
-        if is_key_down(*k) {
-            interpreter.set_key(idx, true);
-        } else {
-            interpreter.set_key(idx, false);
-        }
+/// CHIP-8 keys held on the keyboard, through the keypad or the ROM's own
+/// `controls`, or on a gamepad as given by `pad_keys`
+fn capture_input(
+    keypad: &[keys::Key; 16],
+    controls: &BTreeMap<keys::Key, u8>,
+    pad_keys: [bool; 16],
+) -> Inputs {
+    let mut inputs = Inputs::default();
+    let bindings = keypad
+        .iter()
+        .enumerate()
+        .chain(controls.iter().map(|(k, idx)| (*idx as usize & 0xF, k)));
+    for (idx, k) in bindings {
+        inputs.keys[idx] |= is_key_down(k.0) || pad_keys[idx];
+        // pressed and let go since the last frame, which is_key_down misses
+        inputs.taps[idx] |= is_key_pressed(k.0) && !is_key_down(k.0);
     }
+    inputs
 }
 
-fn update_display(interpreter: &Interpreter, pixel_brightness: &mut [f32; 64 * 32]) {
-    for (idx, on) in interpreter.pixels().iter().enumerate() {
-        if *on {
-            pixel_brightness[idx] += 0.25;
-            pixel_brightness[idx] = clamp(pixel_brightness[idx], 0., 1.);
-        } else {
-            // fade out
-            pixel_brightness[idx] -= 0.05;
-            pixel_brightness[idx] = clamp(pixel_brightness[idx], 0., 1.);
+/// the keyboard controls for `rom` on top of the keypad: its profile in the
+/// config, or else the arrow keys for its directions in the ROM database
+fn controls_for(
+    config: &Config,
+    rom: &[u8],
+    profile: Option<&Profile>,
+    toasts: &mut Toasts,
+) -> BTreeMap<keys::Key, u8> {
+    if let Some((name, controls)) = config.controls_for(rom) {
+        toasts.info(format!("Using controls {}", name));
+        return controls.keys.clone();
+    }
+    profile.map(Profile::arrow_keys).unwrap_or_default()
+}
+
+/// the cheats the config has for the ROM at `path`, then `extra`, skipping
+/// any the config has wrong
+fn cheats_for(config: &Config, path: &str, extra: &[Cheat], toasts: &mut Toasts) -> Vec<Cheat> {
+    let mut cheats = vec![];
+    for text in config.cheats_for(path) {
+        match Cheat::parse(text) {
+            Ok(cheat) => cheats.push(cheat),
+            Err(e) => toasts.warn(format!("Skipping cheat {}: {}", text, e)),
         }
     }
-    for (idx, brightness) in pixel_brightness.iter().enumerate() {
-        let row = (idx / 64) as f32;
-        let col = (idx % 64) as f32;
-        let red = Color::from_hex(0xA4193D);
-        let tan = Color::from_hex(0xFFDFB9);
-        let color = Color::from_rgba(
-            ((red.r * brightness + tan.r * (1. - brightness)) / 2. * 255.) as u8,
-            ((red.g * brightness + tan.g * (1. - brightness)) / 2. * 255.) as u8,
-            ((red.b * brightness + tan.b * (1. - brightness)) / 2. * 255.) as u8,
-            255,
-        );
+    cheats.extend_from_slice(extra);
+    cheats
+}
 
-        draw_rectangle(col * SCALE, row * SCALE, 1.0 * SCALE, 1.0 * SCALE, color);
+/// the last component of `path`, which names a ROM in save slots and per-ROM settings
+fn file_name(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// whether to run `path` with MegaChip support, as `.mc8` files always are
+fn wants_megachip(options: &RunOptions, path: &str) -> bool {
+    options.megachip || path.to_ascii_lowercase().ends_with(".mc8")
+}
+
+/// Set `machine` up to run `rom`, read from `path`, with the quirks and
+/// speed the ROM database has for it unless `options` say otherwise. ROMs
+/// it doesn't know get the quirks of the variant their instructions
+/// suggest. Returns the ROM's entry, if it has one.
+fn configure_for_rom(
+    machine: &mut Chip8Machine,
+    options: &RunOptions,
+    path: &str,
+    rom: &[u8],
+    toasts: &mut Toasts,
+) -> Option<Profile> {
+    let profile = if options.no_rom_db {
+        None
+    } else {
+        rom_db::lookup(rom)
+    };
+    if let Some(profile) = &profile {
+        toasts.info(format!(
+            "Running {} as {} from the ROM database",
+            profile.title, profile.platform
+        ));
     }
+    let quirks = options
+        .quirks
+        .or(profile.as_ref().map(|profile| profile.quirks))
+        .unwrap_or_else(|| {
+            let variant = rom_info::detect_variant(rom);
+            log::info!(
+                "{} isn't in the ROM database, running it as {} going by its instructions",
+                path,
+                variant
+            );
+            if variant.quirks() != Quirks::default() {
+                toasts.info(format!("Using {} quirks", variant));
+            }
+            variant.quirks()
+        });
+    machine.set_quirks(Quirks {
+        wrap_sprites: quirks.wrap_sprites || options.wrap_sprites,
+        memory_wraps: quirks.memory_wraps && !options.strict_memory,
+        ..quirks
+    });
+    machine.set_instructions_per_second(
+        options
+            .ips
+            .or(profile
+                .as_ref()
+                .and_then(|profile| profile.instructions_per_second))
+            .unwrap_or(DEFAULT_INSTRUCTIONS_PER_SECOND),
+    );
+    machine.set_megachip(
+        wants_megachip(options, path) || profile.as_ref().is_some_and(|profile| profile.megachip),
+    );
+    profile
 }
 
-const PONG_ROM: &[u8; 246] = include_bytes!(".././assets/roms/PONG");
+/// read a ROM from disk and apply the patches given in the config, then
+/// `patches`, for the ROM loaded at `start`
+fn read_rom(
+    path: &str,
+    patches: &[String],
+    start: u16,
+    config: &Config,
+    toasts: &mut Toasts,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut rom = commands::read_rom_file(path)?;
+    for patch_path in config.patches_for(path).iter().chain(patches) {
+        patch::Patch::read(patch_path, start)?
+            .apply(&mut rom)
+            .map_err(|e| format!("could not apply {}: {}", patch_path, e))?;
+        toasts.info(format!("Applied patch {}", patch_path));
+    }
+    Ok(rom)
+}
 
-#[macroquad::main(conf)]
-async fn main() -> Result<(), Box<dyn Error>> {
+/// read the config file, reporting problems as warnings rather than refusing to start
+fn load_config(options: &RunOptions, toasts: &mut Toasts) -> Config {
+    #[cfg(not(target_arch = "wasm32"))]
+    let config = {
+        match config_path(options).map(|p| Config::load(&p)) {
+            Some(Ok(config)) => config,
+            Some(Err(e)) => {
+                toasts.warn(format!("Ignoring config file: {}", e));
+                Config::default()
+            }
+            None => Config::default(),
+        }
+    };
+    #[cfg(target_arch = "wasm32")]
+    let config = {
+        let _ = options;
+        Config::default()
+    };
+
+    for conflict in config.conflicts() {
+        toasts.warn(conflict);
+    }
+    config
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn config_path(options: &RunOptions) -> Option<std::path::PathBuf> {
+    options
+        .config
+        .as_ref()
+        .map(std::path::PathBuf::from)
+        .or_else(config::default_path)
+}
+
+fn main() {
     #[cfg(not(target_arch = "wasm32"))]
     env_logger::init();
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Some(Command::Info { rom }) => commands::info(&rom),
+        Some(Command::Hexdump { rom }) => commands::hexdump(&rom),
+        Some(Command::Disasm {
+            rom,
+            color,
+            explain,
+            octo,
+            symbols,
+            coverage,
+            start_addr,
+        }) if !octo => commands::disasm(
+            &rom,
+            start_addr.unwrap_or(PROGRAM_START as u16),
+            disassembler::RenderOptions { color, explain },
+            symbols.as_ref(),
+            coverage.as_ref(),
+        ),
+        Some(Command::Disasm { rom, .. }) => commands::disasm_octo(&rom),
+        Some(Command::Analyze {
+            rom,
+            cfg,
+            start_addr,
+        }) => commands::analyze(
+            &rom,
+            start_addr.unwrap_or(PROGRAM_START as u16),
+            cfg.as_ref(),
+        ),
+        Some(Command::Coverage {
+            rom,
+            coverage,
+            start_addr,
+        }) => commands::coverage(&rom, &coverage, start_addr.unwrap_or(PROGRAM_START as u16)),
+        Some(Command::Patch {
+            rom,
+            patches,
+            out,
+            start_addr,
+        }) => commands::patch(
+            &rom,
+            &patches,
+            out.as_ref(),
+            start_addr.unwrap_or(PROGRAM_START as u16),
+        ),
+        Some(Command::Asm { source, out }) => commands::asm(&source, out.as_ref()),
+        Some(Command::Test(options)) => commands::test(&options),
+        Some(Command::Bench(options)) => commands::bench(&options),
+        Some(Command::Run(options) | Command::Debug(options)) if options.compare.is_some() => {
+            start_compare(options);
+            Ok(())
+        }
+        Some(Command::Run(options)) if options.tui => run_tui(&options),
+        Some(Command::Run(options)) => {
+            start_gui(options, false);
+            Ok(())
+        }
+        Some(Command::Debug(options)) => {
+            start_gui(options, true);
+            Ok(())
+        }
+        // without a command, start at the menu
+        None => {
+            start_gui(RunOptions::default(), false);
+            Ok(())
+        }
+    };
 
-    let mut interpreter = Interpreter::new();
-    // if a rom is given, load that. Else load PONG
-    let rom = std::env::args().nth(1);
-    if let Some(rom) = rom {
-        // read file
-        let mut rom_file = std::fs::File::open(rom)?;
-        let mut rom_bytes = Vec::new();
-        rom_file.read_to_end(&mut rom_bytes)?;
-        interpreter.load_program(&rom_bytes);
-    } else {
-        interpreter.load_program(PONG_ROM);
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
     }
+}
 
-    // let rom = std::env::args().nth(1).expect(USAGE);
-    #[cfg(target_arch = "wasm32")]
-    wasm_logger::init(wasm_logger::Config::default());
-
-    // let mut should_step = false;
-
-    // TODO: sound?
-    let mut pixel_brightness: [f32; 64 * 32] = [0.; 64 * 32];
-    let sound = load_sound("assets/sounds/beep.ogg").await?;
-    play_sound(
-        sound,
-        PlaySoundParams {
-            looped: true,
-            volume: 0.,
-        },
-    );
+/// open the window and run `options.rom` in it, paused with the debugger open if `debug` is set
+fn start_gui(options: RunOptions, debug: bool) {
+    let scale = options.scale.map_or(DEFAULT_SCALE, |scale| scale as f32);
+    macroquad::Window::from_config(conf(scale), async move {
+        if let Err(err) = gui::run(options, debug).await {
+            log::error!("Error: {:?}", err);
+        }
+    });
+}
+
+/// open a window twice as wide as usual and run `run --compare` in it
+fn start_compare(options: RunOptions) {
+    let scale = options.scale.map_or(DEFAULT_SCALE, |scale| scale as f32) / 2.;
+    let mut conf = conf(scale);
+    conf.window_width *= 2;
+    conf.window_height += 72;
+    macroquad::Window::from_config(conf, async move {
+        if let Err(err) = run_compare(options).await {
+            log::error!("Error: {:?}", err);
+        }
+    });
+}
 
+/// `run --compare`: run `options.rom` with the quirks it would normally get
+/// and with the `--compare` preset side by side, pausing where they diverge
+async fn run_compare(options: RunOptions) -> Result<(), Box<dyn Error>> {
+    let mut toasts = Toasts::new();
+    let config = load_config(&options, &mut toasts);
+    let path = options.rom.as_deref().ok_or("--compare needs a ROM")?;
+    let quirks = options.compare.ok_or("--compare needs quirks")?;
+    let rom = read_rom(
+        path,
+        &options.patches,
+        program_start(&options),
+        &config,
+        &mut toasts,
+    )?;
+    let mut left = new_machine(&options);
+    let profile = configure_for_rom(&mut left, &options, path, &rom, &mut toasts);
+    let controls = controls_for(&config, &rom, profile.as_ref(), &mut toasts);
+    left.load(&rom)?;
+    let mut right = new_machine(&options);
+    // its toasts would only repeat the left side's
+    configure_for_rom(&mut right, &options, path, &rom, &mut Toasts::new());
+    right.set_quirks(Quirks {
+        wrap_sprites: quirks.wrap_sprites || options.wrap_sprites,
+        memory_wraps: quirks.memory_wraps && !options.strict_memory,
+        ..quirks
+    });
+    right.load(&rom)?;
+
+    let mut comparison = compare::Comparison::new(left, right);
+    let mut displays = [Display::new(), Display::new()];
+    for display in &mut displays {
+        display.set_phosphor(config.phosphor);
+    }
+    let palette = options.palette.unwrap_or(config.palette);
+    let mut clock = TickClock::new();
+    let mut paused = false;
     loop {
-        if is_key_down(KeyCode::LeftShift) && is_key_released(KeyCode::Escape) {
+        let hotkeys = &config.hotkeys;
+        if hotkeys.quit.is_pressed() {
             break;
         }
-
-        // // TODO: temporarily for debugging.. we require pressing Space to step forward
-        // if is_key_pressed(KeyCode::Space) {
-        //     should_step = true;
-        // }
-
-        // capture changes
-        capture_keyboard_input(&mut interpreter);
-        interpreter.decrement_timers(); // assumes game loop is running at approx 60fps
-
-        // step forward and rewndcurrent state (visuals, audio)
-        for _ in 0..INSTRUCTIONS_PER_LOOP {
-            // if should_step {
-            interpreter.step()?;
-            if interpreter.should_play_sound() {
-                set_sound_volume(sound, 1.);
-            } else {
-                set_sound_volume(sound, 0.);
-            }
-            update_display(&interpreter, &mut pixel_brightness);
-            // should_step = false;
-            // }
+        if paused && hotkeys.resume.is_pressed() {
+            paused = false;
+        } else if hotkeys.pause.is_pressed() {
+            paused = !paused;
+        }
+        let inputs = capture_input(&config.keypad, &controls, [false; 16]);
+        let ticks = clock.ticks_due(get_frame_time() as f64);
+        let diverged = comparison.divergence().is_some();
+        let outputs = if paused && hotkeys.step_frame.is_pressed() {
+            comparison.frames(&inputs, 1)
+        } else if paused {
+            comparison.idle_frame()
+        } else {
+            comparison.frames(&inputs, ticks)
+        };
+        if let Some(divergence) = comparison.divergence().filter(|_| !diverged) {
+            paused = true;
+            toasts.warn(format!(
+                "Diverged at frame {}, {} to continue",
+                divergence.frame, hotkeys.resume
+            ));
         }
 
+        clear_background(BLACK);
+        compare::draw(&comparison, &mut displays, &outputs, &palette, paused);
+        toasts.update(get_frame_time());
+        toasts.draw();
         next_frame().await;
     }
-
     Ok(())
 }
+
+/// give the machine the RPL flags `rom_name` saved in an earlier run
+fn restore_rpl_flags(rom_name: &str, machine: &mut Chip8Machine, toasts: &mut Toasts) -> RplFlags {
+    let mut rpl_flags = RplFlags::new(rom_name);
+    match rpl_flags.load() {
+        Ok(flags) => machine.interpreter_mut().set_rpl_flags(flags),
+        Err(e) => toasts.warn(format!("Could not load RPL flags: {}", e)),
+    }
+    rpl_flags
+}
+
+/// print the `--profile` report, if profiling
+fn print_profile(machine: &Chip8Machine) {
+    if let Some(profiler) = machine.profiler() {
+        print!("{}", profiler.report());
+    }
+}
+
+/// write the coverage to the file `--coverage` names, if any
+fn save_coverage(options: &RunOptions, machine: &Chip8Machine) {
+    if let Some(path) = &options.coverage {
+        match std::fs::write(path, machine.coverage().to_text()) {
+            Ok(()) => println!("Saved coverage {}", path),
+            Err(e) => eprintln!("Could not save the coverage: {}", e),
+        }
+    }
+}
+
+/// a machine set up as `options` asks, with nothing loaded yet
+/// where `options` load the ROM
+fn program_start(options: &RunOptions) -> u16 {
+    options.start_addr.unwrap_or(PROGRAM_START as u16)
+}
+
+fn new_machine(options: &RunOptions) -> Chip8Machine {
+    let mut machine = Chip8Machine::new();
+    machine.set_double_buffered(options.double_buffer);
+    machine.set_lenient(options.lenient);
+    machine.set_strict(options.strict);
+    machine.set_history_len(options.history.unwrap_or(debug_overlay::DEFAULT_HISTORY));
+    if let Some(addr) = options.start_addr {
+        machine.set_program_start(addr);
+    }
+    machine.set_vip_timing(options.vip_timing);
+    machine.set_profiling(options.profile);
+    if let Some(seed) = options.seed {
+        machine.set_seed(seed);
+    }
+    machine.set_recording_movie(options.record_movie.is_some());
+    machine
+}
+
+/// `run --tui`: run `options.rom`, or PONG, in the terminal instead of a window
+fn run_tui(options: &RunOptions) -> Result<(), Box<dyn Error>> {
+    // warnings are logged as well, before the terminal is taken over
+    let mut toasts = Toasts::new();
+    let config = load_config(options, &mut toasts);
+    let mut machine = new_machine(options);
+    let (path, rom) = match &options.rom {
+        Some(rom) => (
+            rom.as_str(),
+            read_rom(
+                rom,
+                &options.patches,
+                program_start(options),
+                &config,
+                &mut toasts,
+            )?,
+        ),
+        None => (
+            "PONG",
+            library::embedded("PONG")
+                .ok_or("PONG isn't built in")?
+                .to_vec(),
+        ),
+    };
+    configure_for_rom(&mut machine, options, path, &rom, &mut toasts);
+    *machine.cheats_mut() = cheats_for(&config, path, &options.cheats, &mut toasts);
+    machine.load(&rom)?;
+    for addr in &options.breakpoints {
+        machine.interpreter_mut().add_breakpoint(*addr);
+    }
+    let rom_name = options
+        .rom
+        .as_deref()
+        .map_or_else(|| String::from("PONG"), file_name);
+    let mut rpl_flags = restore_rpl_flags(&rom_name, &mut machine, &mut toasts);
+    let palette = options.palette.unwrap_or(config.palette);
+
+    #[cfg(all(feature = "tui", not(target_arch = "wasm32")))]
+    {
+        tui::run(
+            &mut machine,
+            &config.keypad.map(|k| key_name(k.0)),
+            &palette,
+            options.tui_renderer,
+        )?;
+        rpl_flags.save_if_changed(machine.interpreter().rpl_flags())?;
+        print_profile(&machine);
+        save_coverage(options, &machine);
+        Ok(())
+    }
+
+    #[cfg(not(all(feature = "tui", not(target_arch = "wasm32"))))]
+    {
+        let _ = (machine, palette, &mut rpl_flags);
+        Err("this build doesn't include the terminal frontend (the `tui` feature)".into())
+    }
+}
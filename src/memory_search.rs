@@ -0,0 +1,278 @@
+//! A search for the address a game keeps a variable at, like the score or
+//! the lives left: start with every address, play on, then keep only those
+//! that changed, stayed the same, went up or down, or hold a value, until
+//! few are left. The one found can be watched or frozen with a cheat.
+
+use macroquad::prelude::*;
+
+use chip8_core::interpreter::MEMORY_SIZE;
+
+use crate::ui_style::{FONT_SIZE, LINE_HEIGHT, MARGIN, PANEL_BACKGROUND};
+
+const WIDTH: f32 = 420.;
+/// candidates listed at once
+const ROWS: usize = 8;
+const HINT: &str = "changed, same, more, less, =NN, reset, watch, or cheat";
+
+/// What to keep the candidates to, comparing memory now with when last filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(u8),
+}
+
+impl Filter {
+    fn keeps(self, before: u8, now: u8) -> bool {
+        match self {
+            Filter::Changed => now != before,
+            Filter::Unchanged => now == before,
+            Filter::Increased => now > before,
+            Filter::Decreased => now < before,
+            Filter::Equals(value) => now == value,
+        }
+    }
+}
+
+/// Addresses that still could hold the variable, and memory as it was the
+/// last time they were narrowed down.
+pub struct MemorySearch {
+    previous: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl MemorySearch {
+    /// a search of every address, starting from `memory`
+    pub fn new(memory: &[u8]) -> Self {
+        MemorySearch {
+            previous: memory.to_vec(),
+            candidates: (0..MEMORY_SIZE as u16).collect(),
+        }
+    }
+
+    /// keep the candidates `filter` matches in `memory`, then compare against it next time
+    pub fn filter(&mut self, memory: &[u8], filter: Filter) {
+        let previous = &self.previous;
+        self.candidates
+            .retain(|addr| filter.keeps(previous[*addr as usize], memory[*addr as usize]));
+        self.previous = memory.to_vec();
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+}
+
+/// A line typed into the search panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Filter(Filter),
+    Reset,
+    Watch,
+    Cheat,
+}
+
+impl Command {
+    fn parse(text: &str) -> Result<Command, String> {
+        let text = text.trim().to_ascii_lowercase();
+        if let Some(value) = text.strip_prefix('=') {
+            let value = value.trim();
+            let value = value.strip_prefix("0x").unwrap_or(value);
+            return u8::from_str_radix(value, 16)
+                .map(|value| Command::Filter(Filter::Equals(value)))
+                .map_err(|_| format!("{} isn't a byte in hex", value));
+        }
+        Ok(match text.as_str() {
+            "changed" => Command::Filter(Filter::Changed),
+            "same" => Command::Filter(Filter::Unchanged),
+            "more" => Command::Filter(Filter::Increased),
+            "less" => Command::Filter(Filter::Decreased),
+            "reset" => Command::Reset,
+            "watch" => Command::Watch,
+            "cheat" => Command::Cheat,
+            _ => return Err(format!("no command {}; try {}", text, HINT)),
+        })
+    }
+}
+
+/// What to do with the candidate picked in the panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchAction {
+    Close,
+    /// stop when the program writes to this address
+    Watch(u16),
+    /// keep this address at the value it has now
+    Cheat(u16),
+}
+
+/// The panel for a [`MemorySearch`], which pauses the game while it's
+/// open. Commands are typed and run with Enter, and the arrow keys pick a
+/// candidate for `watch` and `cheat`. Closing it keeps the search going, to
+/// play on before narrowing it down again.
+pub struct SearchPanel {
+    search: MemorySearch,
+    cursor: usize,
+    text: String,
+    error: Option<String>,
+}
+
+impl SearchPanel {
+    pub fn new(memory: &[u8]) -> Self {
+        SearchPanel {
+            search: MemorySearch::new(memory),
+            cursor: 0,
+            text: String::new(),
+            error: None,
+        }
+    }
+
+    /// Handle this frame's typing, with `memory` as it is now.
+    pub fn update(&mut self, memory: &[u8]) -> Option<SearchAction> {
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() {
+                self.text.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.text.pop();
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.cursor = self.cursor.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.cursor += 1;
+        }
+        self.cursor = self
+            .cursor
+            .min(self.search.candidates().len().saturating_sub(1));
+        if is_key_pressed(KeyCode::Escape) {
+            self.text.clear();
+            return Some(SearchAction::Close);
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            let text = std::mem::take(&mut self.text);
+            return self.run(&text, memory);
+        }
+        None
+    }
+
+    fn run(&mut self, text: &str, memory: &[u8]) -> Option<SearchAction> {
+        self.error = None;
+        let selected = self.search.candidates().get(self.cursor).copied();
+        match (Command::parse(text), selected) {
+            (Ok(Command::Filter(filter)), _) => {
+                self.search.filter(memory, filter);
+                self.cursor = 0;
+            }
+            (Ok(Command::Reset), _) => *self = SearchPanel::new(memory),
+            (Ok(Command::Watch), Some(addr)) => return Some(SearchAction::Watch(addr)),
+            (Ok(Command::Cheat), Some(addr)) => return Some(SearchAction::Cheat(addr)),
+            (Ok(_), None) => self.error = Some(String::from("no addresses are left")),
+            (Err(e), _) => self.error = Some(e),
+        }
+        None
+    }
+
+    /// Draws the panel in the top-right corner, listing candidates with the
+    /// value they had when last filtered and the value they have in `memory`.
+    pub fn draw(&self, memory: &[u8]) {
+        let candidates = self.search.candidates();
+        let first = self.cursor.saturating_sub(ROWS - 1);
+        let shown = &candidates[first..candidates.len().min(first + ROWS)];
+        let lines = shown.len() + 3;
+        let x = screen_width() - MARGIN - WIDTH;
+        draw_rectangle(
+            x - 6.,
+            MARGIN - 6.,
+            WIDTH + 12.,
+            lines as f32 * LINE_HEIGHT + 12.,
+            PANEL_BACKGROUND,
+        );
+        let line_y = |idx: usize| MARGIN + (idx + 1) as f32 * LINE_HEIGHT - 6.;
+        let title = format!("Memory search: {} addresses", candidates.len());
+        draw_text(&title, x, line_y(0), FONT_SIZE, WHITE);
+        for (idx, addr) in shown.iter().enumerate() {
+            let addr = *addr as usize;
+            let row = format!(
+                "{:#05x}  {:02X}, was {:02X}",
+                addr, memory[addr], self.search.previous[addr]
+            );
+            let selected = first + idx == self.cursor;
+            if selected {
+                draw_rectangle(
+                    x - 4.,
+                    line_y(idx + 1) - LINE_HEIGHT + 7.,
+                    WIDTH + 8.,
+                    LINE_HEIGHT,
+                    DARKBLUE,
+                );
+            }
+            let color = if selected { WHITE } else { LIGHTGRAY };
+            draw_text(&row, x, line_y(idx + 1), FONT_SIZE, color);
+        }
+        let prompt = format!("> {}_", self.text);
+        draw_text(&prompt, x, line_y(shown.len() + 1), FONT_SIZE, WHITE);
+        let (note, color) = match &self.error {
+            Some(e) => (e.as_str(), ORANGE),
+            None => (HINT, GRAY),
+        };
+        draw_text(note, x, line_y(shown.len() + 2), FONT_SIZE * 0.8, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let mut memory = [0u8; MEMORY_SIZE];
+        memory[0x300] = 3;
+        memory[0x301] = 3;
+        let mut search = MemorySearch::new(&memory);
+        assert_eq!(search.candidates().len(), MEMORY_SIZE);
+
+        // the lives counter at 0x300 goes down, while 0x301 goes up
+        memory[0x300] = 2;
+        memory[0x301] = 4;
+        search.filter(&memory, Filter::Changed);
+        assert_eq!(search.candidates(), [0x300, 0x301]);
+        search.filter(&memory, Filter::Unchanged);
+        assert_eq!(search.candidates(), [0x300, 0x301]);
+        memory[0x300] = 1;
+        memory[0x301] = 5;
+        search.filter(&memory, Filter::Decreased);
+        assert_eq!(search.candidates(), [0x300]);
+        search.filter(&memory, Filter::Equals(2));
+        assert!(search.candidates().is_empty());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut memory = [0u8; MEMORY_SIZE];
+        let mut panel = SearchPanel::new(&memory);
+        memory[0x2FF] = 9;
+        memory[0x300] = 9;
+        assert_eq!(panel.run("=09", &memory), None);
+        assert_eq!(panel.search.candidates(), [0x2FF, 0x300]);
+        panel.cursor = 1;
+        assert_eq!(
+            panel.run("cheat", &memory),
+            Some(SearchAction::Cheat(0x300))
+        );
+        assert_eq!(
+            panel.run("Watch", &memory),
+            Some(SearchAction::Watch(0x300))
+        );
+
+        assert_eq!(panel.run("bigger", &memory), None);
+        assert!(panel.error.is_some());
+        panel.run("= 0x0A", &memory);
+        assert_eq!(panel.run("watch", &memory), None);
+        assert_eq!(panel.error.as_deref(), Some("no addresses are left"));
+        panel.run("reset", &memory);
+        assert_eq!(panel.search.candidates().len(), MEMORY_SIZE);
+    }
+}
@@ -0,0 +1,163 @@
+use macroquad::prelude::*;
+
+use chip8_core::interpreter::{Interpreter, MEMORY_SIZE};
+
+use crate::{
+    coverage::Coverage,
+    ui_style::{FONT_SIZE, LINE_HEIGHT, MARGIN, OVERLAY_BACKGROUND},
+};
+
+const BYTES_PER_ROW: usize = 16;
+/// rows shown at once
+const ROWS: usize = 8;
+const LAST_TOP: usize = MEMORY_SIZE / BYTES_PER_ROW - ROWS;
+/// rows kept above PC while following it
+const CONTEXT_ROWS: usize = 2;
+const PC_COLOR: Color = Color::new(0.8, 0.6, 0., 1.);
+const I_COLOR: Color = Color::new(0., 0.5, 0.8, 1.);
+/// behind bytes that have run as instructions
+const EXECUTED_COLOR: Color = Color::new(0., 0.3, 0.1, 1.);
+
+/// A hex dump of the 4K memory map in the debugger, scrolled with the
+/// arrow keys and Page Up/Down. It follows PC until scrolled; Home goes
+/// back to following it and End jumps to I.
+pub struct MemoryView {
+    /// the first row shown, or None to keep PC in view
+    top: Option<usize>,
+}
+
+impl MemoryView {
+    pub fn new() -> Self {
+        MemoryView { top: None }
+    }
+
+    /// handle this frame's scrolling keys
+    pub fn update(&mut self, interpreter: &Interpreter) {
+        let keys = [
+            KeyCode::Up,
+            KeyCode::Down,
+            KeyCode::PageUp,
+            KeyCode::PageDown,
+            KeyCode::Home,
+            KeyCode::End,
+        ];
+        let (pc, i) = (interpreter.program_counter(), interpreter.index_register());
+        for key in keys.into_iter().filter(|key| is_key_pressed(*key)) {
+            self.handle(key, pc as usize, i as usize);
+        }
+    }
+
+    fn handle(&mut self, key: KeyCode, pc: usize, i: usize) {
+        let top = self.top_row(pc);
+        self.top = match key {
+            KeyCode::Up => Some(top.saturating_sub(1)),
+            KeyCode::Down => Some(top + 1),
+            KeyCode::PageUp => Some(top.saturating_sub(ROWS)),
+            KeyCode::PageDown => Some(top + ROWS),
+            KeyCode::Home => None,
+            KeyCode::End => Some((i % MEMORY_SIZE) / BYTES_PER_ROW),
+            _ => self.top,
+        }
+        .map(|top| top.min(LAST_TOP));
+    }
+
+    /// the first row to show, with PC at `pc`
+    fn top_row(&self, pc: usize) -> usize {
+        self.top
+            .unwrap_or_else(|| ((pc % MEMORY_SIZE) / BYTES_PER_ROW).saturating_sub(CONTEXT_ROWS))
+            .min(LAST_TOP)
+    }
+
+    /// Draws the rows in view in the bottom-left corner of the window, with
+    /// the instruction at PC and the byte at I highlighted, and the bytes
+    /// `coverage` says have run shaded.
+    pub fn draw(&self, interpreter: &Interpreter, coverage: &Coverage) {
+        let memory = interpreter.memory();
+        let pc = interpreter.program_counter() as usize;
+        let i = interpreter.index_register() as usize;
+        let top = self.top_row(pc);
+        let title = if self.top.is_some() {
+            "memory  Home follows PC, End goes to I"
+        } else {
+            "memory  following PC; arrows and Page Up/Down scroll"
+        };
+        let rows: Vec<String> = (top..top + ROWS)
+            .map(|row| {
+                let start = row * BYTES_PER_ROW;
+                let bytes: Vec<String> = memory[start..start + BYTES_PER_ROW]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect();
+                format!("{:#05x}: {}", start, bytes.join(" "))
+            })
+            .collect();
+
+        let width = std::iter::once(title)
+            .chain(rows.iter().map(String::as_str))
+            .map(|line| measure_text(line, None, FONT_SIZE as u16, 1.).width)
+            .fold(0., f32::max);
+        let height = (ROWS + 1) as f32 * LINE_HEIGHT;
+        let y = screen_height() - MARGIN - height;
+        draw_rectangle(
+            MARGIN - 6.,
+            y - 6.,
+            width + 12.,
+            height + 12.,
+            OVERLAY_BACKGROUND,
+        );
+        draw_text(title, MARGIN, y + LINE_HEIGHT - 6., FONT_SIZE, GRAY);
+        for (idx, text) in rows.iter().enumerate() {
+            let line_y = y + (idx + 1) as f32 * LINE_HEIGHT;
+            let start = (top + idx) * BYTES_PER_ROW;
+            for col in 0..BYTES_PER_ROW {
+                let addr = start + col;
+                let color = if addr == pc || addr == pc + 1 {
+                    PC_COLOR
+                } else if addr == i {
+                    I_COLOR
+                } else if coverage.is_executed(addr as u16) {
+                    EXECUTED_COLOR
+                } else {
+                    continue;
+                };
+                // "0x200: " then three characters per byte
+                let offset = 7 + col * 3;
+                let x = MARGIN + measure_text(&text[..offset], None, FONT_SIZE as u16, 1.).width;
+                let cell = measure_text(&text[offset..offset + 2], None, FONT_SIZE as u16, 1.);
+                draw_rectangle(
+                    x - 1.,
+                    line_y + 2.,
+                    cell.width + 2.,
+                    LINE_HEIGHT - 1.,
+                    color,
+                );
+            }
+            draw_text(text, MARGIN, line_y + LINE_HEIGHT - 6., FONT_SIZE, WHITE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrolling() {
+        let mut view = MemoryView::new();
+        // following PC, with a couple of rows above it
+        assert_eq!(view.top_row(0x200), 0x1E);
+        assert_eq!(view.top_row(0x0), 0);
+        assert_eq!(view.top_row(0xFFE), LAST_TOP);
+
+        view.handle(KeyCode::Down, 0x200, 0x300);
+        assert_eq!(view.top_row(0x400), 0x1F);
+        view.handle(KeyCode::PageUp, 0x200, 0x300);
+        assert_eq!(view.top_row(0x400), 0x17);
+        view.handle(KeyCode::End, 0x200, 0xFFF);
+        assert_eq!(view.top_row(0x400), LAST_TOP);
+        view.handle(KeyCode::PageDown, 0x200, 0x300);
+        assert_eq!(view.top_row(0x400), LAST_TOP);
+        view.handle(KeyCode::Home, 0x200, 0x300);
+        assert_eq!(view.top_row(0x400), 0x3E);
+    }
+}
@@ -0,0 +1,141 @@
+use macroquad::prelude::*;
+
+use crate::{
+    file_dialog, library,
+    ui_style::{FONT_SIZE, MARGIN, PANEL_BACKGROUND, ROW_HEIGHT},
+};
+
+const WIDTH: f32 = 480.;
+
+/// Something the start menu can launch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuItem {
+    /// show the file dialog
+    OpenFile,
+    /// a recently opened file, by path
+    Recent(String),
+    /// a ROM built into the binary, by name
+    Embedded(&'static str),
+}
+
+impl MenuItem {
+    fn label(&self) -> String {
+        match self {
+            MenuItem::OpenFile => String::from("Open file..."),
+            MenuItem::Recent(path) => path.clone(),
+            MenuItem::Embedded(name) => name.to_string(),
+        }
+    }
+
+    fn section(&self) -> &'static str {
+        match self {
+            MenuItem::OpenFile => "",
+            MenuItem::Recent(_) => "recent",
+            MenuItem::Embedded(_) => "built in",
+        }
+    }
+}
+
+/// A list of ROMs to pick from with the arrow keys and Enter: recently
+/// opened files first, then the built-in ones.
+pub struct StartMenu {
+    items: Vec<MenuItem>,
+    cursor: usize,
+}
+
+impl StartMenu {
+    pub fn new(recent: &library::RecentFiles) -> Self {
+        let mut items = vec![];
+        if file_dialog::AVAILABLE {
+            items.push(MenuItem::OpenFile);
+        }
+        items.extend(recent.paths().iter().cloned().map(MenuItem::Recent));
+        items.extend(
+            library::ROMS
+                .iter()
+                .map(|(name, _)| MenuItem::Embedded(name)),
+        );
+        StartMenu { items, cursor: 0 }
+    }
+
+    /// Handle this frame's input, returning the item chosen if any.
+    pub fn update(&mut self) -> Option<MenuItem> {
+        let last = self.items.len().saturating_sub(1);
+        let page = visible_rows().max(1);
+        if is_key_pressed(KeyCode::Up) {
+            self.cursor = self.cursor.checked_sub(1).unwrap_or(last);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.cursor = if self.cursor == last {
+                0
+            } else {
+                self.cursor + 1
+            };
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            self.cursor = self.cursor.saturating_sub(page);
+        }
+        if is_key_pressed(KeyCode::PageDown) {
+            self.cursor = (self.cursor + page).min(last);
+        }
+        if is_key_pressed(KeyCode::Home) {
+            self.cursor = 0;
+        }
+        if is_key_pressed(KeyCode::End) {
+            self.cursor = last;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            return self.items.get(self.cursor).cloned();
+        }
+        None
+    }
+
+    /// `close_key` is shown as the way back to the game
+    pub fn draw(&self, close_key: &str) {
+        let rows = visible_rows();
+        // keep the cursor on screen, scrolling a page at a time
+        let first = self.cursor / rows.max(1) * rows;
+        let shown = &self.items[first..self.items.len().min(first + rows)];
+
+        let left = (screen_width() - WIDTH) / 2.;
+        let top = MARGIN * 2. + FONT_SIZE;
+        draw_rectangle(
+            left - MARGIN,
+            top - MARGIN - FONT_SIZE,
+            WIDTH + 2. * MARGIN,
+            shown.len() as f32 * ROW_HEIGHT + 2. * MARGIN + FONT_SIZE,
+            PANEL_BACKGROUND,
+        );
+        draw_text(
+            &format!("Choose a ROM, {} to close", close_key),
+            left,
+            top - MARGIN / 2.,
+            FONT_SIZE,
+            WHITE,
+        );
+
+        for (idx, item) in shown.iter().enumerate() {
+            let y = top + idx as f32 * ROW_HEIGHT;
+            let selected = first + idx == self.cursor;
+            if selected {
+                draw_rectangle(left - 4., y, WIDTH + 8., ROW_HEIGHT, DARKBLUE);
+            }
+            let color = if selected { WHITE } else { LIGHTGRAY };
+            draw_text(&item.label(), left, y + ROW_HEIGHT - 7., FONT_SIZE, color);
+            let section = item.section();
+            let width = measure_text(section, None, FONT_SIZE as u16, 1.).width;
+            draw_text(
+                section,
+                left + WIDTH - width,
+                y + ROW_HEIGHT - 7.,
+                FONT_SIZE,
+                GRAY,
+            );
+        }
+    }
+}
+
+/// how many items fit in the window under the title
+fn visible_rows() -> usize {
+    ((screen_height() - 4. * MARGIN - FONT_SIZE) / ROW_HEIGHT).max(1.) as usize
+}
@@ -0,0 +1,239 @@
+//! Input movies: the keys pressed in every frame of a session, which play
+//! back to the same game as long as the emulator behaves the same.
+//!
+//! To catch the emulator changing under an old movie, every
+//! `CHECKSUM_INTERVAL` frames the movie also keeps a checksum of the
+//! interpreter's state, and playback stops at the first frame where the
+//! state it reaches has a different one. Movies are text, a header line
+//! and then a line per frame:
+//!
+//! ```text
+//! chip8-movie 1 <SHA-1 of the ROM>
+//! <keys> <taps> [checksum]
+//! ```
+//!
+//! with the keys held and tapped as 4 hex digits, bit i for CHIP-8 key i,
+//! and the checksum the first 16 hex digits of the SHA-1 of the state's
+//! save file encoding. A movie doesn't keep the speed or quirks it was
+//! made with, so it plays back with the same options; checksums tell when
+//! it doesn't.
+
+use std::{error::Error, fmt};
+
+use chip8_core::interpreter::Interpreter;
+
+use crate::{
+    machine::{Chip8Machine, FrameOutput, Inputs},
+    rom_db,
+    timing::TIMER_HZ,
+};
+
+const HEADER: &str = "chip8-movie 1";
+/// frames between checksums, once a second
+pub const CHECKSUM_INTERVAL: usize = TIMER_HZ as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovieFrame {
+    pub inputs: Inputs,
+    /// the state's checksum after this frame, every `CHECKSUM_INTERVAL` frames
+    pub checksum: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_sha1: String,
+    pub frames: Vec<MovieFrame>,
+}
+
+/// Where playback stopped matching the movie.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Desync {
+    /// frame number, counting from 1
+    pub frame: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Desync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "desync at frame {}: the state's checksum is {}, the movie has {}",
+            self.frame, self.actual, self.expected
+        )
+    }
+}
+
+impl Error for Desync {}
+
+/// the checksum of `interpreter`'s state, as movies keep it
+pub fn checksum(interpreter: &Interpreter) -> String {
+    let mut sha1 = rom_db::sha1(&interpreter.snapshot().to_bytes());
+    sha1.truncate(16);
+    sha1
+}
+
+impl Movie {
+    /// an empty movie of `rom`
+    pub fn new(rom: &[u8]) -> Self {
+        Movie {
+            rom_sha1: rom_db::sha1(rom),
+            frames: vec![],
+        }
+    }
+
+    /// add a frame run with `inputs`, which left `interpreter` as it is
+    pub fn push(&mut self, inputs: &Inputs, interpreter: &Interpreter) {
+        let due = (self.frames.len() + 1).is_multiple_of(CHECKSUM_INTERVAL);
+        self.frames.push(MovieFrame {
+            inputs: *inputs,
+            checksum: due.then(|| checksum(interpreter)),
+        });
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let rom_sha1 = lines
+            .next()
+            .and_then(|header| header.strip_prefix(HEADER))
+            .map(str::trim)
+            .ok_or("not a chip8-rust movie")?;
+        let frames = lines
+            .enumerate()
+            .map(|(idx, line)| parse_frame(line).ok_or(format!("bad frame on line {}", idx + 2)))
+            .collect::<Result<_, _>>()?;
+        Ok(Movie {
+            rom_sha1: rom_sha1.to_string(),
+            frames,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", HEADER, self.rom_sha1);
+        for frame in &self.frames {
+            text.push_str(&format!(
+                "{:04X} {:04X}",
+                to_mask(&frame.inputs.keys),
+                to_mask(&frame.inputs.taps)
+            ));
+            if let Some(checksum) = &frame.checksum {
+                text.push(' ');
+                text.push_str(checksum);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Play the movie on `machine`, which has its ROM freshly loaded,
+    /// checking the state against each checksum. Returns the last frame,
+    /// or where the state first differed.
+    pub fn play(&self, machine: &mut Chip8Machine) -> Result<FrameOutput, Box<dyn Error>> {
+        let mut output = machine.frames(&Inputs::default(), 0)?;
+        for (idx, frame) in self.frames.iter().enumerate() {
+            output = machine.frame(&frame.inputs)?;
+            if let Some(expected) = &frame.checksum {
+                let actual = checksum(machine.interpreter());
+                if *expected != actual {
+                    return Err(Desync {
+                        frame: idx + 1,
+                        expected: expected.clone(),
+                        actual,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+fn to_mask(keys: &[bool; 16]) -> u16 {
+    (0..16).filter(|idx| keys[*idx]).map(|idx| 1 << idx).sum()
+}
+
+fn from_mask(mask: u16) -> [bool; 16] {
+    std::array::from_fn(|idx| mask & (1 << idx) != 0)
+}
+
+fn parse_frame(line: &str) -> Option<MovieFrame> {
+    let mut parts = line.split_whitespace();
+    let keys = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let taps = u16::from_str_radix(parts.next()?, 16).ok()?;
+    let checksum = parts.next().map(String::from);
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(MovieFrame {
+        inputs: Inputs {
+            keys: from_mask(keys),
+            taps: from_mask(taps),
+        },
+        checksum,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::Chip8Error;
+
+    use super::*;
+
+    /// rolls a random number into V3, and counts in V2 while key 0 is held
+    const ROM: [u8; 8] = [0xC3, 0xFF, 0xE0, 0xA1, 0x72, 0x01, 0x12, 0x00];
+
+    /// a movie holding key 1 for the first half second
+    fn record(frames: usize) -> Result<Movie, Chip8Error> {
+        let mut machine = Chip8Machine::new();
+        machine.set_recording_movie(true);
+        machine.load(&ROM)?;
+        for idx in 0..frames {
+            let mut inputs = Inputs::default();
+            inputs.keys[1] = idx < 30;
+            machine.frame(&inputs)?;
+        }
+        Ok(machine.movie().unwrap().clone())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), Chip8Error> {
+        let movie = record(CHECKSUM_INTERVAL * 2)?;
+        assert_eq!(movie.frames.iter().flat_map(|f| &f.checksum).count(), 2);
+        let text = movie.to_text();
+        assert!(text.starts_with("chip8-movie 1 "));
+        assert_eq!(text.lines().nth(1), Some("0002 0000"));
+        assert_eq!(Movie::parse(&text), Ok(movie));
+        assert!(Movie::parse("0000 0000").is_err());
+        assert!(Movie::parse("chip8-movie 1 abc\nzz").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_play_finds_desync() -> Result<(), Chip8Error> {
+        let mut movie = record(CHECKSUM_INTERVAL * 3)?;
+        let mut machine = Chip8Machine::new();
+        machine.load(&ROM)?;
+        assert!(movie.play(&mut machine).is_ok());
+
+        // a different seed plays out differently from the start
+        let mut machine = Chip8Machine::new();
+        machine.set_seed(99);
+        machine.load(&ROM)?;
+        let err = movie.play(&mut machine).unwrap_err();
+        let desync = err.downcast_ref::<Desync>().unwrap();
+        assert_eq!(desync.frame, CHECKSUM_INTERVAL);
+
+        // as does a changed input, caught at the next checksum
+        for frame in &mut movie.frames[CHECKSUM_INTERVAL + 5..CHECKSUM_INTERVAL + 15] {
+            frame.inputs.keys[0] = true;
+        }
+        let mut machine = Chip8Machine::new();
+        machine.load(&ROM)?;
+        let err = movie.play(&mut machine).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<Desync>().unwrap().frame,
+            CHECKSUM_INTERVAL * 2
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+//! Two players on different machines sharing one game over TCP. One
+//! instance hosts with `--host <port>` and the other joins with
+//! `--connect <host:port>`; both must run the same ROM at the same speed.
+//!
+//! The instances run in lockstep: each frame's keys are sent to the peer
+//! and a frame only runs once both players' keys for it are in, so the two
+//! emulations stay identical. Keys are applied `INPUT_DELAY` frames after
+//! they're pressed, which hides the round trip on a reasonable connection.
+//! The players' keys are combined, so each should stick to their own.
+//!
+//! After a hello of `C8NP`, the ROM's SHA-1 and the speed as a u32, each
+//! frame is two little-endian bytes of held keys, bit `i` for key `i`.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+};
+
+const MAGIC: &[u8; 4] = b"C8NP";
+/// frames between pressing a key and it reaching the game
+const INPUT_DELAY: usize = 3;
+
+/// what the background thread hears from the peer
+enum Message {
+    Connected(TcpStream),
+    Keys(u16),
+    Closed(String),
+}
+
+/// What the next frame should do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exchange {
+    /// no one has connected yet
+    Waiting,
+    /// the peer just connected; frames start running from the next one
+    Connected,
+    /// the peer's keys for this frame haven't arrived, so hold still
+    Stalled,
+    /// run one frame with these keys
+    Run([bool; 16]),
+}
+
+pub struct Netplay {
+    messages: Receiver<Message>,
+    writer: Option<TcpStream>,
+    /// our keys for the frames still to run, oldest first
+    local: VecDeque<u16>,
+    remote: VecDeque<u16>,
+}
+
+impl Netplay {
+    /// Wait for a player to connect on `port`, on a background thread.
+    /// `rom` and `speed` must match theirs.
+    pub fn host(port: u16, rom: &[u8], speed: u32) -> io::Result<Self> {
+        Ok(Self::accept(
+            TcpListener::bind(("0.0.0.0", port))?,
+            rom,
+            speed,
+        ))
+    }
+
+    fn accept(listener: TcpListener, rom: &[u8], speed: u32) -> Self {
+        Self::start(hello(rom, speed), move || Ok(listener.accept()?.0))
+    }
+
+    /// Join the game hosted at `addr`, connecting on a background thread.
+    pub fn connect(addr: &str, rom: &[u8], speed: u32) -> Self {
+        let addr = addr.to_string();
+        Self::start(hello(rom, speed), move || TcpStream::connect(addr))
+    }
+
+    fn start(
+        hello: Vec<u8>,
+        open: impl FnOnce() -> io::Result<TcpStream> + Send + 'static,
+    ) -> Self {
+        let (sender, messages) = channel();
+        std::thread::spawn(move || {
+            let reason = match open().and_then(|stream| run_peer(stream, &hello, &sender)) {
+                Ok(()) => String::from("the other player left"),
+                Err(e) => e.to_string(),
+            };
+            let _ = sender.send(Message::Closed(reason));
+        });
+        Netplay {
+            messages,
+            writer: None,
+            local: [0].repeat(INPUT_DELAY).into(),
+            remote: [0].repeat(INPUT_DELAY).into(),
+        }
+    }
+
+    /// Send this frame's `keys` and see whether the next frame can run.
+    /// Fails once the connection is gone.
+    pub fn exchange(&mut self, keys: [bool; 16]) -> Result<Exchange, String> {
+        loop {
+            match self.messages.try_recv() {
+                Ok(Message::Connected(stream)) => {
+                    self.writer = Some(stream);
+                    return Ok(Exchange::Connected);
+                }
+                Ok(Message::Keys(keys)) => self.remote.push_back(keys),
+                Ok(Message::Closed(reason)) => return Err(reason),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(String::from("connection lost")),
+            }
+        }
+        let Some(writer) = &mut self.writer else {
+            return Ok(Exchange::Waiting);
+        };
+        // one set of our keys for each frame run, sent as soon as it's known
+        if self.local.len() <= INPUT_DELAY {
+            let bits = to_bits(keys);
+            writer
+                .write_all(&bits.to_le_bytes())
+                .map_err(|e| e.to_string())?;
+            self.local.push_back(bits);
+        }
+        if self.remote.is_empty() {
+            return Ok(Exchange::Stalled);
+        }
+        let local = self.local.pop_front().unwrap_or_default();
+        let remote = self.remote.pop_front().unwrap_or_default();
+        Ok(Exchange::Run(from_bits(local | remote)))
+    }
+}
+
+impl Drop for Netplay {
+    /// hang up, so the other player hears about it rather than stalling
+    fn drop(&mut self) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn hello(rom: &[u8], speed: u32) -> Vec<u8> {
+    let mut hello = MAGIC.to_vec();
+    hello.extend_from_slice(&sha1_smol::Sha1::from(rom).digest().bytes());
+    hello.extend_from_slice(&speed.to_le_bytes());
+    hello
+}
+
+/// check the peer is running the same game, then pass on their keys until they hang up
+fn run_peer(stream: TcpStream, hello: &[u8], sender: &Sender<Message>) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = stream.try_clone()?;
+    let mut writer = stream.try_clone()?;
+    writer.write_all(hello)?;
+    let mut theirs = vec![0; hello.len()];
+    reader.read_exact(&mut theirs)?;
+    if theirs[..MAGIC.len()] != *MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the other end isn't netplay",
+        ));
+    }
+    if theirs != hello {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the other player is running a different ROM or speed",
+        ));
+    }
+    if sender.send(Message::Connected(stream)).is_err() {
+        return Ok(());
+    }
+    let mut keys = [0; 2];
+    loop {
+        match reader.read_exact(&mut keys) {
+            Ok(()) => {
+                if sender
+                    .send(Message::Keys(u16::from_le_bytes(keys)))
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn to_bits(keys: [bool; 16]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .fold(0, |bits, (key, down)| bits | (*down as u16) << key)
+}
+
+fn from_bits(bits: u16) -> [bool; 16] {
+    std::array::from_fn(|key| bits & (1 << key) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// exchange until something other than `Stalled` or `Waiting` comes back
+    fn next(netplay: &mut Netplay, keys: [bool; 16]) -> Result<Exchange, String> {
+        let started = Instant::now();
+        loop {
+            match netplay.exchange(keys)? {
+                Exchange::Stalled | Exchange::Waiting
+                    if started.elapsed() < Duration::from_secs(5) =>
+                {
+                    std::thread::sleep(Duration::from_millis(1))
+                }
+                exchange => return Ok(exchange),
+            }
+        }
+    }
+
+    fn pair(guest_rom: &[u8]) -> (Netplay, Netplay) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let host = Netplay::accept(listener, b"PONG", 900);
+        let guest = Netplay::connect(&addr, guest_rom, 900);
+        (host, guest)
+    }
+
+    #[test]
+    fn test_lockstep() {
+        let (mut host, mut guest) = pair(b"PONG");
+        assert_eq!(next(&mut host, [false; 16]), Ok(Exchange::Connected));
+        assert_eq!(next(&mut guest, [false; 16]), Ok(Exchange::Connected));
+
+        let mut host_keys = [false; 16];
+        host_keys[1] = true;
+        let mut guest_keys = [false; 16];
+        guest_keys[0xC] = true;
+        let mut both = host_keys;
+        both[0xC] = true;
+        // the first frames run with no keys, until the delayed ones arrive
+        for frame in 0..INPUT_DELAY + 2 {
+            let expected = if frame < INPUT_DELAY {
+                [false; 16]
+            } else {
+                both
+            };
+            assert_eq!(next(&mut host, host_keys), Ok(Exchange::Run(expected)));
+            assert_eq!(next(&mut guest, guest_keys), Ok(Exchange::Run(expected)));
+        }
+
+        // the frames the guest sent keys for before leaving still run
+        drop(guest);
+        let ran = (0..=INPUT_DELAY)
+            .take_while(|_| next(&mut host, host_keys).is_ok())
+            .count();
+        assert!(ran <= INPUT_DELAY);
+    }
+
+    #[test]
+    fn test_different_roms_are_refused() {
+        let (mut host, mut guest) = pair(b"TETRIS");
+        assert!(next(&mut host, [false; 16]).is_err());
+        assert!(next(&mut guest, [false; 16]).is_err());
+    }
+
+    #[test]
+    fn test_key_bits() {
+        let mut keys = [false; 16];
+        keys[0] = true;
+        keys[0xF] = true;
+        assert_eq!(to_bits(keys), 0x8001);
+        assert_eq!(from_bits(0x8001), keys);
+    }
+}
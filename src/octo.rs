@@ -0,0 +1,173 @@
+//! Move interpreter state to and from the Octo IDE.
+//!
+//! Octo has no savestate file of its own, but its emulator keeps all machine
+//! state on a plain JavaScript object. This module converts snapshots to and
+//! from JSON that uses the same field names and layout:
+//!
+//! | field   | meaning                                           |
+//! |---------|---------------------------------------------------|
+//! | `m`     | memory, one number per byte                       |
+//! | `v`     | the 16 registers V0-VF                            |
+//! | `i`     | the index register                                |
+//! | `pc`    | the program counter                               |
+//! | `r`     | the return stack, oldest call first               |
+//! | `dt`    | the delay timer                                   |
+//! | `st`    | the sound timer                                   |
+//! | `p`     | the two display planes, 0/1 per pixel, row-major  |
+//! | `hires` | whether the display is 128x64                     |
+//!
+//! To move a game into Octo, export the state, then in the browser console
+//! of the Octo IDE run `Object.assign(emulator, <exported json>)`. To go the
+//! other way, `copy(JSON.stringify({m: emulator.m, v: emulator.v, ...}))`.
+//!
+//! Only the 64x32 display and the first plane are supported. Octo keeps its
+//! font at a different address, so imported states get this interpreter's
+//! font written back into its usual place.
+
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use chip8_core::{
+    font::FONT,
+    interpreter::{
+        Snapshot, FONT_START, HIRES_SCREEN_HEIGHT, MEMORY_SIZE, PROGRAM_START, SCREEN_HEIGHT,
+        SCREEN_WIDTH,
+    },
+};
+
+#[derive(Serialize, Deserialize)]
+struct OctoState {
+    m: Vec<u8>,
+    v: Vec<u8>,
+    i: u16,
+    pc: u16,
+    r: Vec<u16>,
+    dt: u8,
+    st: u8,
+    p: [Vec<u8>; 2],
+    #[serde(default)]
+    hires: bool,
+}
+
+pub fn export_state(snapshot: &Snapshot) -> String {
+    let state = OctoState {
+        m: snapshot.memory.to_vec(),
+        v: snapshot.registers.to_vec(),
+        i: snapshot.index_register,
+        pc: snapshot.program_counter,
+        // our stack stores return addresses in slots 1..=stack_pointer
+        r: snapshot.stack[1..=snapshot.stack_pointer as usize].to_vec(),
+        dt: snapshot.delay_timer,
+        st: snapshot.sound_timer,
+        // Octo has no 64x64 mode, so only the top half of a hires display is kept
+        p: [
+            snapshot.pixels[..SCREEN_WIDTH * SCREEN_HEIGHT]
+                .iter()
+                .map(|on| *on as u8)
+                .collect(),
+            vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        ],
+        hires: false,
+    };
+
+    serde_json::to_string(&state).expect("state should serialize")
+}
+
+pub fn import_state(json: &str) -> Result<Snapshot, Box<dyn Error>> {
+    let state: OctoState = serde_json::from_str(json)?;
+
+    if state.hires {
+        return Err("hires (128x64) Octo states are not supported".into());
+    }
+    if state.v.len() != 16 {
+        return Err(format!("expected 16 registers, found {}", state.v.len()).into());
+    }
+    if state.r.len() > 15 {
+        return Err(format!("return stack is too deep ({} entries)", state.r.len()).into());
+    }
+    if state.p[0].len() != SCREEN_WIDTH * SCREEN_HEIGHT {
+        return Err(format!("unexpected display size ({} pixels)", state.p[0].len()).into());
+    }
+    if state.m.len() > MEMORY_SIZE && state.m[MEMORY_SIZE..].iter().any(|b| *b != 0) {
+        log::warn!("ignoring Octo memory beyond {:#x}", MEMORY_SIZE);
+    }
+
+    let mut memory = [0; MEMORY_SIZE];
+    let len = state.m.len().min(MEMORY_SIZE);
+    memory[..len].copy_from_slice(&state.m[..len]);
+    memory[FONT_START..FONT_START + FONT.len()].copy_from_slice(&FONT);
+
+    let mut stack = [0; 16];
+    stack[1..=state.r.len()].copy_from_slice(&state.r);
+
+    let mut pixels = [false; SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+    for (pixel, value) in pixels.iter_mut().zip(&state.p[0]) {
+        *pixel = *value != 0;
+    }
+
+    Ok(Snapshot {
+        memory,
+        // Octo doesn't track the program size, so allow running anywhere
+        program_size: MEMORY_SIZE - PROGRAM_START,
+        program_counter: state.pc,
+        stack_pointer: state.r.len() as u8,
+        stack,
+        registers: state.v.try_into().expect("length was checked"),
+        index_register: state.i,
+        delay_timer: state.dt,
+        sound_timer: state.st,
+        keys: [false; 16],
+        key_down_ld_vx_k: None,
+        pixels,
+        hires: false,
+        // Octo has no generator state to carry over
+        rng_state: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::interpreter::Interpreter;
+
+    #[test]
+    fn test_round_trip() -> Result<(), Box<dyn Error>> {
+        let mut interpreter = Interpreter::new();
+        // CALL 0x204; (unused); LD V3, 0x2A
+        interpreter.load_program(&[0x22, 0x04, 0x00, 0x00, 0x63, 0x2A])?;
+        interpreter.step()?;
+        interpreter.step()?;
+
+        let snapshot = interpreter.snapshot();
+        let json = export_state(&snapshot);
+        assert!(json.contains("\"r\":[514]"));
+        assert!(json.contains("\"pc\":518"));
+
+        let imported = import_state(&json)?;
+        assert_eq!(imported.registers, snapshot.registers);
+        assert_eq!(imported.stack, snapshot.stack);
+        assert_eq!(imported.stack_pointer, 1);
+        assert_eq!(imported.memory, snapshot.memory);
+        assert_eq!(imported.pixels, snapshot.pixels);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_rejects_unsupported_states() {
+        let pixels = vec![0; 64 * 32];
+        let state = |v: usize, hires: bool| {
+            serde_json::json!({
+                "m": [0], "v": vec![0; v], "i": 0, "pc": 512, "r": [],
+                "dt": 0, "st": 0, "p": [pixels, pixels], "hires": hires,
+            })
+            .to_string()
+        };
+
+        assert!(import_state(&state(16, false)).is_ok());
+        assert!(import_state(&state(15, false)).is_err());
+        assert!(import_state(&state(16, true)).is_err());
+        assert!(import_state("not json").is_err());
+    }
+}
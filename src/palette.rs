@@ -0,0 +1,120 @@
+//! Display colors. A palette is picked by name on the command line, in the
+//! config file, or by cycling at runtime.
+
+use std::fmt;
+
+use macroquad::color::Color;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Colors for lit and unlit pixels, as 0xRRGGBB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub foreground: u32,
+    pub background: u32,
+}
+
+impl Palette {
+    /// the original dark red on brown
+    pub const DEFAULT: Palette = Palette::new("default", 0x520C1E, 0x7F6F5C);
+
+    pub const PRESETS: [Palette; 5] = [
+        Palette::DEFAULT,
+        Palette::new("green", 0x33FF66, 0x0A1F0F),
+        Palette::new("amber", 0xFFB000, 0x1F1400),
+        Palette::new("bw", 0xFFFFFF, 0x000000),
+        // Octo's defaults
+        Palette::new("octo", 0xFFCC00, 0x996600),
+    ];
+
+    const fn new(name: &'static str, foreground: u32, background: u32) -> Self {
+        Palette {
+            name,
+            foreground,
+            background,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        Self::PRESETS
+            .iter()
+            .copied()
+            .find(|palette| palette.name.eq_ignore_ascii_case(name))
+    }
+
+    /// the preset after this one, wrapping around
+    pub fn next(&self) -> Palette {
+        let idx = Self::PRESETS.iter().position(|p| p == self).unwrap_or(0);
+        Self::PRESETS[(idx + 1) % Self::PRESETS.len()]
+    }
+
+    /// the preset before this one, wrapping around
+    pub fn previous(&self) -> Palette {
+        let idx = Self::PRESETS.iter().position(|p| p == self).unwrap_or(0);
+        Self::PRESETS[(idx + Self::PRESETS.len() - 1) % Self::PRESETS.len()]
+    }
+
+    /// the color of a pixel `brightness` of the way from unlit to lit
+    pub fn blend(&self, brightness: f32) -> Color {
+        let fg = Color::from_hex(self.foreground);
+        let bg = Color::from_hex(self.background);
+        Color::new(
+            bg.r + (fg.r - bg.r) * brightness,
+            bg.g + (fg.g - bg.g) * brightness,
+            bg.b + (fg.b - bg.b) * brightness,
+            1.,
+        )
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::DEFAULT
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Serialize for Palette {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for Palette {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Palette::from_name(&s)
+            .ok_or_else(|| de::Error::custom(format!("unknown palette \"{}\"", s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend() {
+        let bw = Palette::from_name("BW").unwrap();
+        assert_eq!(bw.blend(0.), Color::new(0., 0., 0., 1.));
+        assert_eq!(bw.blend(1.), Color::new(1., 1., 1., 1.));
+        assert_eq!(bw.blend(0.5), Color::new(0.5, 0.5, 0.5, 1.));
+    }
+
+    #[test]
+    fn test_next_cycles_through_presets() {
+        let mut palette = Palette::DEFAULT;
+        for _ in 0..Palette::PRESETS.len() {
+            palette = palette.next();
+        }
+        assert_eq!(palette, Palette::DEFAULT);
+        assert_eq!(Palette::DEFAULT.next().name, "green");
+        assert_eq!(Palette::DEFAULT.previous().name, "octo");
+        assert_eq!(Palette::DEFAULT.next().previous(), Palette::DEFAULT);
+        assert!(Palette::from_name("plaid").is_none());
+    }
+}
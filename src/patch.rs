@@ -0,0 +1,453 @@
+//! Patches applied on top of a ROM before it runs.
+//!
+//! Three formats are understood:
+//!
+//! - IPS, recognized by its `PATCH` header. Offsets are relative to the
+//!   start of the ROM file.
+//! - BPS, recognized by its `BPS1` header. It describes the whole patched
+//!   ROM in terms of the original, and only applies to the ROM it was made
+//!   from, which its checksums make sure of.
+//! - A plain text format with one edit per line, `<address>: <bytes>`,
+//!   where the address is where the bytes end up in CHIP-8 memory (so the
+//!   first ROM byte is at the program start, usually 0x200) and `#` starts
+//!   a comment:
+//!
+//!   ```text
+//!   # start with 5 lives
+//!   0x2A4: 60 05
+//!   ```
+
+use std::error::Error;
+
+use chip8_core::{interpreter::PROGRAM_START, megachip::MEGA_MEMORY_SIZE};
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+const BPS_HEADER: &[u8] = b"BPS1";
+/// the CRC32s of the source, the target, and the patch itself
+const BPS_FOOTER_LEN: usize = 12;
+/// the biggest ROM any memory fits, MegaChip's; patches can't make bigger ones
+const MAX_ROM_SIZE: usize = MEGA_MEMORY_SIZE - PROGRAM_START;
+const TOO_BIG: &str = "patch makes the ROM too big to fit in memory";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Edit {
+    /// offset into the ROM file
+    offset: usize,
+    bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch(Contents);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Contents {
+    /// bytes to write over the ROM, from IPS and text patches
+    Edits(Vec<Edit>),
+    /// a BPS file, checked but kept as it is until applied
+    Bps(Vec<u8>),
+}
+
+impl Patch {
+    /// parse a patch for a ROM loaded at `start`, which text patch addresses count from
+    pub fn parse(data: &[u8], start: u16) -> Result<Self, Box<dyn Error>> {
+        if data.starts_with(IPS_HEADER) {
+            Self::parse_ips(data)
+        } else if data.starts_with(BPS_HEADER) {
+            Self::parse_bps(data)
+        } else {
+            Self::parse_text(std::str::from_utf8(data)?, start as usize)
+        }
+    }
+
+    /// read and parse the patch file at `path`, for a ROM loaded at `start`
+    pub fn read(path: &str, start: u16) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&std::fs::read(path)?, start)
+            .map_err(|e| format!("invalid patch {}: {}", path, e).into())
+    }
+
+    fn parse_ips(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut edits = vec![];
+        let mut pos = IPS_HEADER.len();
+        let mut take = |len: usize| -> Result<&[u8], Box<dyn Error>> {
+            let bytes = pos
+                .checked_add(len)
+                .and_then(|end| data.get(pos..end))
+                .ok_or("IPS patch ends unexpectedly")?;
+            pos += len;
+            Ok(bytes)
+        };
+
+        loop {
+            let offset = take(3)?;
+            if offset == IPS_FOOTER {
+                break;
+            }
+            let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+            let size = take(2)?;
+            let size = u16::from_be_bytes([size[0], size[1]]) as usize;
+
+            let bytes = if size == 0 {
+                // run-length encoded record
+                let count = take(2)?;
+                let count = u16::from_be_bytes([count[0], count[1]]) as usize;
+                vec![take(1)?[0]; count]
+            } else {
+                take(size)?.to_vec()
+            };
+            edits.push(Edit { offset, bytes });
+        }
+
+        Ok(Patch(Contents::Edits(edits)))
+    }
+
+    fn parse_bps(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if data.len() < BPS_HEADER.len() + BPS_FOOTER_LEN {
+            return Err("BPS patch ends unexpectedly".into());
+        }
+        let (body, crc) = data.split_at(data.len() - 4);
+        if crc32fast::hash(body) != read_u32(crc) {
+            return Err("BPS patch is corrupt: its checksum doesn't match".into());
+        }
+        Ok(Patch(Contents::Bps(data.to_vec())))
+    }
+
+    fn parse_text(text: &str, start: usize) -> Result<Self, Box<dyn Error>> {
+        let mut edits = vec![];
+        for (line_idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: &str| format!("line {}: {}", line_idx + 1, msg);
+
+            let (addr, bytes) = line
+                .split_once(':')
+                .ok_or_else(|| error("expected <address>: <bytes>"))?;
+            let addr = parse_hex(addr.trim()).ok_or_else(|| error("invalid address"))?;
+            if addr < start {
+                return Err(error(&format!(
+                    "address is below the program start ({:#05x})",
+                    start
+                ))
+                .into());
+            }
+
+            let digits: String = bytes.chars().filter(|c| !c.is_whitespace()).collect();
+            if digits.is_empty() || !digits.len().is_multiple_of(2) {
+                return Err(error("expected hex bytes").into());
+            }
+            let bytes = (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| error("invalid hex byte"))?;
+
+            edits.push(Edit {
+                offset: addr - start,
+                bytes,
+            });
+        }
+
+        Ok(Patch(Contents::Edits(edits)))
+    }
+
+    /// Apply the patch to `rom`. Edits grow the ROM if they write past its
+    /// end; a BPS patch replaces it, failing if it was made for another ROM.
+    /// Either fails if the ROM would grow too big for any memory.
+    pub fn apply(&self, rom: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        match &self.0 {
+            Contents::Edits(edits) => {
+                for edit in edits {
+                    let end = edit
+                        .offset
+                        .checked_add(edit.bytes.len())
+                        .filter(|end| *end <= MAX_ROM_SIZE)
+                        .ok_or(TOO_BIG)?;
+                    if rom.len() < end {
+                        rom.resize(end, 0);
+                    }
+                    rom[edit.offset..end].copy_from_slice(&edit.bytes);
+                }
+            }
+            Contents::Bps(data) => *rom = apply_bps(data, rom)?,
+        }
+        Ok(())
+    }
+}
+
+/// the ROM `data`, a checked BPS patch, makes out of `source`
+fn apply_bps(data: &[u8], source: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let footer = data.len() - BPS_FOOTER_LEN;
+    if crc32fast::hash(source) != read_u32(&data[footer..]) {
+        return Err("the BPS patch is for a different ROM".into());
+    }
+    let mut reader = BpsReader {
+        data: &data[..footer],
+        pos: BPS_HEADER.len(),
+    };
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.take(metadata_size)?;
+    if source.len() != source_size {
+        return Err("the BPS patch is for a different ROM".into());
+    }
+    if target_size > MAX_ROM_SIZE {
+        return Err(TOO_BIG.into());
+    }
+
+    let mut target: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_pos = 0;
+    let mut target_pos = 0;
+    let past_end = || "BPS patch reads past the end of a ROM";
+    while reader.pos < footer {
+        let action = reader.number()?;
+        let len = (action >> 2) + 1;
+        // never more than the size checked above, so the ROM fits in memory
+        if target.len().saturating_add(len) > target_size {
+            return Err("BPS patch writes past the end of the ROM it describes".into());
+        }
+        match action & 3 {
+            // the source's bytes at the same place
+            0 => target.extend(bytes_at(source, target.len(), len).ok_or_else(past_end)?),
+            // bytes from the patch
+            1 => target.extend(reader.take(len)?),
+            // bytes from anywhere in the source
+            2 => {
+                source_pos = reader.offset(source_pos)?;
+                target.extend(bytes_at(source, source_pos, len).ok_or_else(past_end)?);
+                source_pos += len;
+            }
+            // bytes already written, which can run into the ones being written
+            _ => {
+                target_pos = reader.offset(target_pos)?;
+                for _ in 0..len {
+                    let byte = *target.get(target_pos).ok_or_else(past_end)?;
+                    target.push(byte);
+                    target_pos += 1;
+                }
+            }
+        }
+    }
+    if target.len() != target_size || crc32fast::hash(&target) != read_u32(&data[footer + 4..]) {
+        return Err("the BPS patch didn't produce the ROM it describes".into());
+    }
+    Ok(target)
+}
+
+/// The actions of a BPS patch, read from the front.
+struct BpsReader<'a> {
+    /// the patch without its footer
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BpsReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or("BPS patch ends unexpectedly")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// a number, 7 bits a byte with the last byte's top bit set
+    fn number(&mut self) -> Result<usize, Box<dyn Error>> {
+        let (mut value, mut shift) = (0usize, 1usize);
+        loop {
+            let byte = self.take(1)?[0];
+            value = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|bits| value.checked_add(bits))
+                .ok_or("BPS patch has a number too big")?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift
+                .checked_shl(7)
+                .ok_or("BPS patch has a number too big")?;
+            value += shift;
+        }
+    }
+
+    /// `from` moved by the signed offset that comes next
+    fn offset(&mut self, from: usize) -> Result<usize, Box<dyn Error>> {
+        let value = self.number()?;
+        let moved = if value & 1 == 0 {
+            from.checked_add(value >> 1)
+        } else {
+            from.checked_sub(value >> 1)
+        };
+        Ok(moved.ok_or("BPS patch reads before the start of a ROM")?)
+    }
+}
+
+/// the `len` bytes of `rom` from `at`, if it has them
+fn bytes_at(rom: &[u8], at: usize, len: usize) -> Option<&[u8]> {
+    rom.get(at..at.checked_add(len)?)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    usize::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_patch() -> Result<(), Box<dyn Error>> {
+        let patch = Patch::parse(b"# comment\n0x202: 60 05  # lives\n\n204:FFEE\n", 0x200)?;
+        let mut rom = vec![0x00, 0xE0, 0x00, 0x00];
+        patch.apply(&mut rom)?;
+        assert_eq!(rom, vec![0x00, 0xE0, 0x60, 0x05, 0xFF, 0xEE]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_patch_at_0x600() -> Result<(), Box<dyn Error>> {
+        // an ETI-660 program, whose first byte is at 0x600
+        let patch = Patch::parse(b"0x600: 00\n0x603: 05", 0x600)?;
+        let mut rom = vec![0xFF, 0xE0, 0x60, 0x03];
+        patch.apply(&mut rom)?;
+        assert_eq!(rom, vec![0x00, 0xE0, 0x60, 0x05]);
+
+        assert!(Patch::parse(b"0x200: 00", 0x600).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_patch_errors() {
+        assert!(Patch::parse(b"0x100: 00", 0x200).is_err());
+        assert!(Patch::parse(b"0x200 00", 0x200).is_err());
+        assert!(Patch::parse(b"0x200: 0", 0x200).is_err());
+        assert!(Patch::parse(b"0x200: zz", 0x200).is_err());
+    }
+
+    #[test]
+    fn test_ips_patch() -> Result<(), Box<dyn Error>> {
+        let mut ips = b"PATCH".to_vec();
+        // write AB CD at offset 1
+        ips.extend([0, 0, 1, 0, 2, 0xAB, 0xCD]);
+        // fill 3 bytes with 0x11 from offset 4
+        ips.extend([0, 0, 4, 0, 0, 0, 3, 0x11]);
+        ips.extend(b"EOF");
+
+        let mut rom = vec![0; 4];
+        Patch::parse(&ips, 0x200)?.apply(&mut rom)?;
+        assert_eq!(rom, vec![0, 0xAB, 0xCD, 0, 0x11, 0x11, 0x11]);
+
+        assert!(Patch::parse(b"PATCH\x00\x00", 0x200).is_err());
+        Ok(())
+    }
+
+    /// a number as BPS patches encode them
+    fn bps_number(mut value: usize) -> Vec<u8> {
+        let mut bytes = vec![];
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    /// a BPS patch to make `target_size` bytes out of `source` with `actions`
+    fn make_bps(source: &[u8], target_size: usize, actions: &[u8]) -> Vec<u8> {
+        let mut bps = b"BPS1".to_vec();
+        for size in [source.len(), target_size, 0] {
+            bps.extend(bps_number(size));
+        }
+        bps.extend(actions);
+        bps.extend(crc32fast::hash(source).to_le_bytes());
+        bps.extend([0; 4]);
+        bps.extend(crc32fast::hash(&bps).to_le_bytes());
+        bps
+    }
+
+    #[test]
+    fn test_malformed_bps_patches() -> Result<(), Box<dyn Error>> {
+        let source = [1, 2, 3, 4];
+        let apply = |bps: Vec<u8>| Patch::parse(&bps, 0x200)?.apply(&mut source.to_vec());
+
+        // a ROM bigger than memory is refused before anything is allocated
+        let error = apply(make_bps(&source, usize::MAX, &[])).unwrap_err();
+        assert_eq!(error.to_string(), TOO_BIG);
+
+        // as is writing more than the target's size, which would run for ever
+        let mut actions = bps_number(((usize::MAX >> 3) << 2) | 3);
+        actions.extend(bps_number(0));
+        let error = apply(make_bps(&source, 4, &actions)).unwrap_err();
+        assert!(error.to_string().contains("writes past the end"));
+
+        // and an offset far past the source fails instead of overflowing
+        let mut actions = bps_number(2);
+        actions.extend(bps_number(usize::MAX - 1));
+        let error = apply(make_bps(&source, 4, &actions)).unwrap_err();
+        assert!(error.to_string().contains("past the end of a ROM"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_edits_past_memory() -> Result<(), Box<dyn Error>> {
+        for patch in ["0x1000200: 00", "0xFFFFFFFFFFFFFFFF: 00 00"] {
+            let error = Patch::parse(patch.as_bytes(), 0x200)?
+                .apply(&mut vec![])
+                .unwrap_err();
+            assert_eq!(error.to_string(), TOO_BIG);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bps_patch() -> Result<(), Box<dyn Error>> {
+        let source = [1, 2, 3, 4];
+        let target = [1, 2, 9, 9, 9, 3, 4];
+        let mut bps = b"BPS1".to_vec();
+        for size in [source.len(), target.len(), 0] {
+            bps.extend(bps_number(size));
+        }
+        // source read 2: 1 2
+        bps.extend(bps_number(1 << 2));
+        // target read 1: 9
+        bps.extend(bps_number(1));
+        bps.push(9);
+        // target copy 2 from 2 bytes on: 9 9, the second one just written
+        bps.extend(bps_number((1 << 2) | 3));
+        bps.extend(bps_number(2 << 1));
+        // source copy 2 from 2 bytes on: 3 4
+        bps.extend(bps_number((1 << 2) | 2));
+        bps.extend(bps_number(2 << 1));
+        bps.extend(crc32fast::hash(&source).to_le_bytes());
+        bps.extend(crc32fast::hash(&target).to_le_bytes());
+        bps.extend(crc32fast::hash(&bps).to_le_bytes());
+        assert_eq!(bps_number(300), [0x2C, 0x81]);
+
+        let patch = Patch::parse(&bps, 0x200)?;
+        let mut rom = source.to_vec();
+        patch.apply(&mut rom)?;
+        assert_eq!(rom, target);
+        // it only fits the ROM it was made from
+        assert!(patch.apply(&mut rom).is_err());
+
+        let last = bps.len() - 1;
+        bps[last] ^= 1;
+        assert!(Patch::parse(&bps, 0x200).is_err());
+        Ok(())
+    }
+}
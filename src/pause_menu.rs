@@ -0,0 +1,182 @@
+//! The menu Escape brings up over the paused game, for the controls that
+//! otherwise only have hotkeys.
+
+use macroquad::prelude::*;
+
+use crate::ui_style::{FONT_SIZE, MARGIN, PANEL_BACKGROUND, ROW_HEIGHT};
+
+const WIDTH: f32 = 320.;
+
+/// instructions per second added or taken away by each step of Speed
+pub const SPEED_STEP: u32 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Resume,
+    Reset,
+    OpenRom,
+    KeyBindings,
+    Palette,
+    Speed,
+    Quit,
+}
+
+const ENTRIES: [Entry; 7] = [
+    Entry::Resume,
+    Entry::Reset,
+    Entry::OpenRom,
+    Entry::KeyBindings,
+    Entry::Palette,
+    Entry::Speed,
+    Entry::Quit,
+];
+
+impl Entry {
+    fn label(self) -> &'static str {
+        match self {
+            Entry::Resume => "Resume",
+            Entry::Reset => "Reset",
+            Entry::OpenRom => "Open ROM...",
+            Entry::KeyBindings => "Key bindings...",
+            Entry::Palette => "Palette",
+            Entry::Speed => "Speed",
+            Entry::Quit => "Quit",
+        }
+    }
+}
+
+/// What the user picked in the menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseAction {
+    /// close the menu and carry on
+    Resume,
+    /// start the ROM over
+    Reset,
+    /// choose another ROM from the start menu
+    OpenRom,
+    /// rebind the keypad
+    KeyBindings,
+    NextPalette,
+    PreviousPalette,
+    Faster,
+    Slower,
+    Quit,
+}
+
+/// A list of entries picked with the arrow keys and Enter. Left and Right
+/// change the palette and speed in place, and Escape resumes.
+pub struct PauseMenu {
+    cursor: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        PauseMenu { cursor: 0 }
+    }
+
+    /// Handle this frame's input, returning what was picked if anything.
+    pub fn update(&mut self) -> Option<PauseAction> {
+        let keys = [
+            KeyCode::Up,
+            KeyCode::Down,
+            KeyCode::Left,
+            KeyCode::Right,
+            KeyCode::Enter,
+            KeyCode::Escape,
+        ];
+        keys.into_iter()
+            .filter(|key| is_key_pressed(*key))
+            .find_map(|key| self.handle(key))
+    }
+
+    fn handle(&mut self, key: KeyCode) -> Option<PauseAction> {
+        let entry = ENTRIES[self.cursor];
+        match key {
+            KeyCode::Up => self.cursor = (self.cursor + ENTRIES.len() - 1) % ENTRIES.len(),
+            KeyCode::Down => self.cursor = (self.cursor + 1) % ENTRIES.len(),
+            KeyCode::Escape => return Some(PauseAction::Resume),
+            KeyCode::Left => match entry {
+                Entry::Palette => return Some(PauseAction::PreviousPalette),
+                Entry::Speed => return Some(PauseAction::Slower),
+                _ => (),
+            },
+            KeyCode::Right => match entry {
+                Entry::Palette => return Some(PauseAction::NextPalette),
+                Entry::Speed => return Some(PauseAction::Faster),
+                _ => (),
+            },
+            KeyCode::Enter => {
+                return Some(match entry {
+                    Entry::Resume => PauseAction::Resume,
+                    Entry::Reset => PauseAction::Reset,
+                    Entry::OpenRom => PauseAction::OpenRom,
+                    Entry::KeyBindings => PauseAction::KeyBindings,
+                    Entry::Palette => PauseAction::NextPalette,
+                    Entry::Speed => PauseAction::Faster,
+                    Entry::Quit => PauseAction::Quit,
+                })
+            }
+            _ => (),
+        }
+        None
+    }
+
+    /// draw the menu over the game, with the current `palette` and `speed` beside their entries
+    pub fn draw(&self, palette: &str, speed: &str) {
+        let left = (screen_width() - WIDTH) / 2.;
+        let height = ENTRIES.len() as f32 * ROW_HEIGHT;
+        let top = (screen_height() - height) / 2.;
+        draw_rectangle(
+            left - MARGIN,
+            top - MARGIN - FONT_SIZE,
+            WIDTH + 2. * MARGIN,
+            height + 2. * MARGIN + FONT_SIZE,
+            PANEL_BACKGROUND,
+        );
+        draw_text("Paused", left, top - MARGIN / 2., FONT_SIZE, WHITE);
+
+        for (idx, entry) in ENTRIES.iter().enumerate() {
+            let y = top + idx as f32 * ROW_HEIGHT;
+            let selected = idx == self.cursor;
+            if selected {
+                draw_rectangle(left - 4., y, WIDTH + 8., ROW_HEIGHT, DARKBLUE);
+            }
+            let color = if selected { WHITE } else { LIGHTGRAY };
+            draw_text(entry.label(), left, y + ROW_HEIGHT - 7., FONT_SIZE, color);
+            let value = match entry {
+                Entry::Palette => format!("< {} >", palette),
+                Entry::Speed => format!("< {} >", speed),
+                _ => continue,
+            };
+            let width = measure_text(&value, None, FONT_SIZE as u16, 1.).width;
+            draw_text(
+                &value,
+                left + WIDTH - width,
+                y + ROW_HEIGHT - 7.,
+                FONT_SIZE,
+                GRAY,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let mut menu = PauseMenu::new();
+        assert_eq!(menu.handle(KeyCode::Enter), Some(PauseAction::Resume));
+        // Up wraps around to Quit
+        assert_eq!(menu.handle(KeyCode::Up), None);
+        assert_eq!(menu.handle(KeyCode::Enter), Some(PauseAction::Quit));
+        // Left and Right only do something on Palette and Speed
+        assert_eq!(menu.handle(KeyCode::Left), None);
+        menu.handle(KeyCode::Up);
+        assert_eq!(menu.handle(KeyCode::Left), Some(PauseAction::Slower));
+        menu.handle(KeyCode::Up);
+        assert_eq!(menu.handle(KeyCode::Right), Some(PauseAction::NextPalette));
+        assert_eq!(menu.handle(KeyCode::Escape), Some(PauseAction::Resume));
+    }
+}
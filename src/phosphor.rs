@@ -0,0 +1,157 @@
+//! How quickly pixels light up and fade out, like a CRT's phosphor.
+//! Sprites that games erase and redraw every frame flicker less with more
+//! persistence, at the cost of ghosting behind anything that moves. Set in
+//! the config file as a preset's name or as two rates, or by cycling the
+//! presets at runtime.
+
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Brightness gained per frame by a lit pixel and lost per frame by an
+/// unlit one, where 1 is the difference between unlit and fully lit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Phosphor {
+    pub fade_in: f32,
+    pub fade_out: f32,
+}
+
+impl Phosphor {
+    /// a slight fade, enough to soften most flicker
+    pub const DEFAULT: Phosphor = Phosphor {
+        fade_in: 1.25,
+        fade_out: 0.25,
+    };
+
+    pub const PRESETS: [(&'static str, Phosphor); 3] = [
+        ("default", Self::DEFAULT),
+        // no ghosting: pixels switch in a single frame
+        (
+            "instant",
+            Phosphor {
+                fade_in: 1.,
+                fade_out: 1.,
+            },
+        ),
+        // pixels take 20 frames to fade out
+        (
+            "long",
+            Phosphor {
+                fade_in: 1.25,
+                fade_out: 0.05,
+            },
+        ),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Phosphor> {
+        Self::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, phosphor)| *phosphor)
+    }
+
+    /// the preset's name, if these are a preset's rates
+    pub fn name(&self) -> Option<&'static str> {
+        Self::PRESETS
+            .iter()
+            .find(|(_, phosphor)| phosphor == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// the preset after this one, wrapping around; rates that aren't a preset's go to the first
+    pub fn next(&self) -> Phosphor {
+        let next = Self::PRESETS
+            .iter()
+            .position(|(_, phosphor)| phosphor == self)
+            .map_or(0, |idx| (idx + 1) % Self::PRESETS.len());
+        Self::PRESETS[next].1
+    }
+
+    /// `brightness` one frame later, for a pixel that's lit if `on`
+    pub fn step(&self, brightness: f32, on: bool) -> f32 {
+        let change = if on { self.fade_in } else { -self.fade_out };
+        (brightness + change).clamp(0., 1.)
+    }
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Phosphor::DEFAULT
+    }
+}
+
+impl fmt::Display for Phosphor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "fade in {}, fade out {}", self.fade_in, self.fade_out),
+        }
+    }
+}
+
+/// how a phosphor is written in the config file
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Setting {
+    Preset(String),
+    Rates { fade_in: f32, fade_out: f32 },
+}
+
+impl Serialize for Phosphor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.name() {
+            Some(name) => Setting::Preset(name.to_string()),
+            None => Setting::Rates {
+                fade_in: self.fade_in,
+                fade_out: self.fade_out,
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Phosphor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Setting::deserialize(deserializer)? {
+            Setting::Preset(name) => Phosphor::from_name(&name)
+                .ok_or_else(|| de::Error::custom(format!("unknown phosphor \"{}\"", name))),
+            Setting::Rates { fade_in, fade_out } => {
+                let valid = |rate: f32| rate > 0. && rate.is_finite();
+                if valid(fade_in) && valid(fade_out) {
+                    Ok(Phosphor { fade_in, fade_out })
+                } else {
+                    Err(de::Error::custom("phosphor fade rates must be above 0"))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        let long = Phosphor::from_name("long").unwrap();
+        assert_eq!(long.step(0., true), 1.);
+        assert!((long.step(1., false) - 0.95).abs() < 1e-6);
+        assert_eq!(Phosphor::from_name("instant").unwrap().step(1., false), 0.);
+    }
+
+    #[test]
+    fn test_next_and_names() {
+        let mut phosphor = Phosphor::DEFAULT;
+        for _ in 0..Phosphor::PRESETS.len() {
+            phosphor = phosphor.next();
+        }
+        assert_eq!(phosphor, Phosphor::DEFAULT);
+        let custom = Phosphor {
+            fade_in: 0.5,
+            fade_out: 0.1,
+        };
+        assert_eq!(custom.name(), None);
+        assert_eq!(custom.next(), Phosphor::DEFAULT);
+        assert_eq!(custom.to_string(), "fade in 0.5, fade out 0.1");
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt;
+
+use macroquad::prelude::*;
+
+use chip8_core::Interpreter;
+
+use crate::ui_style::{FONT_SIZE, LINE_HEIGHT, MARGIN, PANEL_BACKGROUND};
+
+const HINT: &str = "V3=2A, I=300, DT=3C, ST=0, or 300=FF 00; Enter sets, Escape cancels";
+
+/// One change to the interpreter's state, typed into the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Register(usize, u8),
+    Index(u16),
+    DelayTimer(u8),
+    SoundTimer(u8),
+    /// bytes to write starting at an address
+    Memory(u16, Vec<u8>),
+}
+
+impl Edit {
+    /// Parse `target=value` with everything in hex: a register `V0`-`VF`,
+    /// `I`, `DT`, `ST`, or an address followed by one or more bytes.
+    pub fn parse(text: &str) -> Result<Edit, String> {
+        let (target, value) = text
+            .split_once('=')
+            .ok_or("expected target=value, e.g. V3=2A")?;
+        let target = target.trim().to_ascii_uppercase();
+        let byte = |value: &str| {
+            u8::from_str_radix(hex_digits(value), 16)
+                .map_err(|_| format!("{} isn't a byte in hex", value.trim()))
+        };
+        match target.as_str() {
+            "I" => u16::from_str_radix(hex_digits(value), 16)
+                .map(Edit::Index)
+                .map_err(|_| format!("{} isn't a 16-bit value in hex", value.trim())),
+            "DT" => byte(value).map(Edit::DelayTimer),
+            "ST" => byte(value).map(Edit::SoundTimer),
+            _ => {
+                if let Some(x) = target.strip_prefix('V') {
+                    let x = usize::from_str_radix(x, 16)
+                        .ok()
+                        .filter(|x| *x < 16)
+                        .ok_or_else(|| format!("no register {}", target))?;
+                    return byte(value).map(|value| Edit::Register(x, value));
+                }
+                let addr = u16::from_str_radix(hex_digits(&target), 16)
+                    .ok()
+                    .filter(|addr| (*addr as usize) < chip8_core::interpreter::MEMORY_SIZE)
+                    .ok_or_else(|| format!("{} isn't a register, timer, or address", target))?;
+                let bytes = value
+                    .split_whitespace()
+                    .map(byte)
+                    .collect::<Result<Vec<u8>, String>>()?;
+                if bytes.is_empty() {
+                    return Err(format!("no bytes to write at {:#05x}", addr));
+                }
+                Ok(Edit::Memory(addr, bytes))
+            }
+        }
+    }
+
+    pub fn apply(&self, interpreter: &mut Interpreter) {
+        let mut poke = interpreter.poke();
+        match self {
+            Edit::Register(x, value) => poke.set_register(*x, *value),
+            Edit::Index(value) => poke.set_index_register(*value),
+            Edit::DelayTimer(value) => poke.set_delay_timer(*value),
+            Edit::SoundTimer(value) => poke.set_sound_timer(*value),
+            Edit::Memory(addr, bytes) => {
+                for (offset, byte) in bytes.iter().enumerate() {
+                    poke.write_memory(*addr as usize + offset, *byte);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Register(x, value) => write!(f, "V{:X} to {:02X}", x, value),
+            Edit::Index(value) => write!(f, "I to {:#05x}", value),
+            Edit::DelayTimer(value) => write!(f, "DT to {}", value),
+            Edit::SoundTimer(value) => write!(f, "ST to {}", value),
+            Edit::Memory(addr, bytes) if bytes.len() == 1 => {
+                write!(f, "{:#05x} to {:02X}", addr, bytes[0])
+            }
+            Edit::Memory(addr, bytes) => write!(f, "{} bytes from {:#05x}", bytes.len(), addr),
+        }
+    }
+}
+
+/// `value` without surrounding space or a `0x` prefix
+fn hex_digits(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value)
+}
+
+/// A line at the bottom of the window for typing an [`Edit`] while paused.
+pub struct PokePrompt {
+    text: String,
+    error: Option<String>,
+}
+
+impl PokePrompt {
+    pub fn new() -> Self {
+        PokePrompt {
+            text: String::new(),
+            error: None,
+        }
+    }
+
+    /// Handle this frame's typing. Returns `Some(None)` when cancelled and
+    /// `Some(Some(edit))` when Enter is pressed on a valid edit.
+    pub fn update(&mut self) -> Option<Option<Edit>> {
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() {
+                self.text.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.text.pop();
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            return Some(None);
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            match Edit::parse(&self.text) {
+                Ok(edit) => return Some(Some(edit)),
+                Err(e) => self.error = Some(e),
+            }
+        }
+        None
+    }
+
+    pub fn draw(&self) {
+        let line = format!("Set: {}_", self.text);
+        let (note, color) = match &self.error {
+            Some(e) => (e.as_str(), ORANGE),
+            None => (HINT, GRAY),
+        };
+        let width = [line.as_str(), note]
+            .iter()
+            .map(|line| measure_text(line, None, FONT_SIZE as u16, 1.).width)
+            .fold(0., f32::max);
+        let y = screen_height() - MARGIN - 2. * LINE_HEIGHT;
+        let x = screen_width() - MARGIN - width;
+        draw_rectangle(
+            x - 6.,
+            y - 6.,
+            width + 12.,
+            2. * LINE_HEIGHT + 12.,
+            PANEL_BACKGROUND,
+        );
+        draw_text(&line, x, y + LINE_HEIGHT - 6., FONT_SIZE, WHITE);
+        draw_text(note, x, y + 2. * LINE_HEIGHT - 6., FONT_SIZE, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Edit::parse("V3=2A"), Ok(Edit::Register(3, 0x2A)));
+        assert_eq!(Edit::parse(" vf = 0x01 "), Ok(Edit::Register(0xF, 1)));
+        assert_eq!(Edit::parse("I=300"), Ok(Edit::Index(0x300)));
+        assert_eq!(Edit::parse("dt=3c"), Ok(Edit::DelayTimer(0x3C)));
+        assert_eq!(Edit::parse("ST=0"), Ok(Edit::SoundTimer(0)));
+        assert_eq!(
+            Edit::parse("0x300=FF 00 12"),
+            Ok(Edit::Memory(0x300, vec![0xFF, 0, 0x12]))
+        );
+
+        assert!(Edit::parse("V3").is_err());
+        assert!(Edit::parse("V10=1").is_err());
+        assert!(Edit::parse("V3=100").is_err());
+        assert!(Edit::parse("1000=FF").is_err());
+        assert!(Edit::parse("300=").is_err());
+        assert!(Edit::parse("PC=200").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut interpreter = Interpreter::new();
+        Edit::Register(3, 0x2A).apply(&mut interpreter);
+        Edit::Index(0x300).apply(&mut interpreter);
+        Edit::Memory(0xFFF, vec![1, 2]).apply(&mut interpreter);
+        assert_eq!(interpreter.registers()[3], 0x2A);
+        assert_eq!(interpreter.index_register(), 0x300);
+        // the second byte wraps around to the start of memory
+        assert_eq!(interpreter.memory()[0xFFF], 1);
+        assert_eq!(interpreter.memory()[0], 2);
+        assert_eq!(
+            Edit::Memory(0x300, vec![1, 2]).to_string(),
+            "2 bytes from 0x300"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Per-instruction statistics for `--profile`: how often each kind of
+//! instruction ran and how long it took, and which addresses ran most, so
+//! both interpreter work and a ROM's own hot loops show up.
+
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use chip8_core::interpreter::decode;
+
+use crate::disassembler::format_op;
+
+/// rows in each table of the report
+const REPORT_ROWS: usize = 10;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct OpStats {
+    count: u64,
+    time: Duration,
+}
+
+/// how often the instruction at an address ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AddrStats {
+    count: u64,
+    /// the instruction there when it last ran, since ROMs can rewrite themselves
+    word: u16,
+}
+
+#[derive(Debug, Default)]
+pub struct Profiler {
+    /// keyed by the instruction form, e.g. "DRW Vx, Vy, nibble"
+    ops: BTreeMap<&'static str, OpStats>,
+    addrs: BTreeMap<u16, AddrStats>,
+    total: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// count one execution of `word` at `addr`, which took `elapsed`
+    pub fn record(&mut self, addr: u16, word: u16, elapsed: Duration) {
+        let op = self.ops.entry(decode(word).name()).or_default();
+        op.count += 1;
+        op.time += elapsed;
+        let at = self
+            .addrs
+            .entry(addr)
+            .or_insert(AddrStats { count: 0, word });
+        at.count += 1;
+        at.word = word;
+        self.total += 1;
+    }
+
+    /// A table of instruction forms by total time, then the most executed
+    /// addresses, each with its share of all instructions run.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let share = |count: u64| 100. * count as f64 / self.total.max(1) as f64;
+        let _ = writeln!(out, "{} instructions executed", self.total);
+
+        let mut ops: Vec<_> = self.ops.iter().collect();
+        ops.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(b.1.count.cmp(&a.1.count)));
+        let _ = writeln!(out, "\nby instruction:");
+        for (name, stats) in ops.into_iter().take(REPORT_ROWS) {
+            let _ = writeln!(
+                out,
+                "  {:<20} {:>10} {:>5.1}%  {:>9.1?} total  {:>7.1?} each",
+                name,
+                stats.count,
+                share(stats.count),
+                stats.time,
+                stats.time / stats.count as u32,
+            );
+        }
+
+        let mut addrs: Vec<_> = self.addrs.iter().collect();
+        addrs.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        let _ = writeln!(out, "\nhottest addresses:");
+        for (addr, stats) in addrs.into_iter().take(REPORT_ROWS) {
+            let _ = writeln!(
+                out,
+                "  {:#05x}  {:04X}  {:<20} {:>10} {:>5.1}%",
+                addr,
+                stats.word,
+                format_op(&decode(stats.word)),
+                stats.count,
+                share(stats.count),
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut profiler = Profiler::new();
+        // a loop of LD V0, 1 at 0x200 and DRW V0, V0, 1 at 0x202, which is slower
+        for _ in 0..3 {
+            profiler.record(0x200, 0x6001, Duration::from_micros(1));
+            profiler.record(0x202, 0xD001, Duration::from_micros(5));
+        }
+        profiler.record(0x200, 0x6001, Duration::from_micros(1));
+
+        let report = profiler.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "7 instructions executed");
+        assert!(lines[3].starts_with("  DRW Vx, Vy, nibble"));
+        assert!(lines[3].contains(" 3  42.9%"));
+        assert!(lines[3].contains("15.0µs total"));
+        assert!(lines[4].starts_with("  LD Vx, byte"));
+        assert_eq!(
+            lines[7].split_whitespace().collect::<Vec<_>>()[..2],
+            ["0x200", "6001"]
+        );
+        assert!(lines[7].ends_with("4  57.1%"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Everything a windowed session is being recorded to: GIF clips started
+//! with the hotkey, and, natively, what `--record-audio`,
+//! `--record-video`, and `--stream-frames` ask for.
+
+use chip8_core::megachip::SoundEvent;
+use macroquad::time::get_frame_time;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{audio_capture::AudioCapture, frame_stream::FrameStream, video_export::VideoRecorder};
+use crate::{
+    buzzer::Tone,
+    cli::RunOptions,
+    keys::Hotkey,
+    machine::FrameOutput,
+    palette::Palette,
+    recording::{self, GifWriter, Recording},
+    toast::Toasts,
+};
+
+pub struct Recorders {
+    /// the GIF being recorded, if any
+    clip: Option<Recording>,
+    gif_writer: GifWriter,
+    #[cfg(not(target_arch = "wasm32"))]
+    audio: Option<AudioCapture<std::fs::File>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    video: Option<VideoRecorder>,
+    #[cfg(not(target_arch = "wasm32"))]
+    frame_stream: Option<FrameStream>,
+}
+
+impl Recorders {
+    /// start the recordings `options` ask for, of a `height`-row display
+    /// with the buzzer sounding like `tone`
+    pub fn start(options: &RunOptions, tone: Tone, height: usize, toasts: &mut Toasts) -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let _ = (options, tone, height, toasts);
+        Recorders {
+            clip: None,
+            gif_writer: GifWriter::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            audio: options.record_audio.as_deref().and_then(|path| {
+                match AudioCapture::create(path, tone) {
+                    Ok(capture) => {
+                        toasts.info(format!("Recording audio to {}", path));
+                        Some(capture)
+                    }
+                    Err(e) => {
+                        toasts.warn(format!("Could not record audio to {}: {}", path, e));
+                        None
+                    }
+                }
+            }),
+            #[cfg(not(target_arch = "wasm32"))]
+            video: options.record_video.as_deref().and_then(|path| {
+                match VideoRecorder::start(path, height, tone) {
+                    Ok(video) => {
+                        toasts.info(format!("Recording video to {}", path));
+                        Some(video)
+                    }
+                    Err(e) => {
+                        toasts.warn(format!("Could not record video: {}", e));
+                        None
+                    }
+                }
+            }),
+            #[cfg(not(target_arch = "wasm32"))]
+            frame_stream: options.stream_frames.as_deref().map(FrameStream::to_path),
+        }
+    }
+
+    /// record the buzzer as `tone` from now on, as for a newly opened ROM
+    pub fn set_tone(&mut self, tone: Tone) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(capture) = &mut self.audio {
+                capture.set_tone(tone);
+            }
+            if let Some(video) = &mut self.video {
+                video.set_tone(tone);
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = tone;
+    }
+
+    /// start or stop MegaChip's digitized sound in the recordings
+    pub fn handle(&mut self, event: &SoundEvent) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(capture) = &mut self.audio {
+                capture.handle(event);
+            }
+            if let Some(video) = &mut self.video {
+                video.handle(event);
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = event;
+    }
+
+    /// Add `output` to the GIF, starting or finishing it if `toggle` (the
+    /// press of `hotkey`) says to, and report the GIFs done writing.
+    pub fn record_gif(
+        &mut self,
+        output: &FrameOutput,
+        toggle: bool,
+        hotkey: &Hotkey,
+        palette: Palette,
+        toasts: &mut Toasts,
+    ) {
+        if let Some(clip) = &mut self.clip {
+            clip.push(&output.rows, get_frame_time());
+            if toggle || clip.is_full() {
+                self.gif_writer.write(self.clip.take().unwrap(), palette);
+                toasts.info("Saving recording...");
+            }
+        } else if toggle && !recording::AVAILABLE {
+            toasts.warn("Recording isn't supported in the browser");
+        } else if toggle {
+            self.clip = Some(Recording::new(output.height));
+            toasts.info(format!("Recording, {} to stop", hotkey));
+        }
+        for result in self.gif_writer.finished() {
+            match result {
+                Ok(path) => toasts.info(format!("Saved recording {}", path)),
+                Err(e) => toasts.warn(format!("Could not save recording: {}", e)),
+            }
+        }
+    }
+
+    /// Add the frame just rendered to the recordings: the sound since the
+    /// last, with the buzzer on if `sounding`, and `output` once per tick
+    /// in `ticks`. Recordings stop and say so when they fail.
+    pub fn push(
+        &mut self,
+        output: &FrameOutput,
+        ticks: u32,
+        palette: &Palette,
+        sounding: bool,
+        toasts: &mut Toasts,
+    ) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(capture) = &mut self.audio {
+                if let Err(e) = capture.push(get_frame_time(), sounding) {
+                    toasts.warn(format!("Stopped recording audio: {}", e));
+                    self.audio = None;
+                }
+            }
+            if let Some(recorder) = &mut self.video {
+                let pushed = (0..ticks).try_for_each(|_| {
+                    recorder.push(&output.rows, output.height, palette, sounding)
+                });
+                if let Err(e) = pushed {
+                    toasts.warn(format!("Stopped recording video: {}", e));
+                    self.video = None;
+                }
+            }
+            if let Some(stream) = &mut self.frame_stream {
+                if !stream.push(&output.rows, output.height) {
+                    toasts.warn("Frame stream closed");
+                    self.frame_stream = None;
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        let _ = (output, ticks, palette, sounding, toasts);
+    }
+
+    /// finish writing the video, if recording one, which can take a while
+    pub fn finish(self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(video) = self.video {
+            println!("Finishing the video...");
+            match video.finish() {
+                Ok(path) => println!("Saved video {}", path),
+                Err(e) => eprintln!("Could not save the video: {}", e),
+            }
+        }
+    }
+}
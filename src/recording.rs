@@ -0,0 +1,206 @@
+//! Recording the display into an animated GIF.
+//!
+//! Natively GIFs are encoded on another thread, since long recordings take
+//! a while, and written to the working directory. The browser has nowhere
+//! to write them, so there recording isn't offered.
+
+use chip8_core::interpreter::Rows;
+
+use crate::palette::Palette;
+
+/// recordings stop by themselves after this many seconds
+pub const MAX_SECONDS: f32 = 300.;
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Browsers show frames with a delay under 2/100 s for 1/10 s instead, so
+/// shorter frames are dropped and their time given to the frame before.
+const MIN_DELAY: u16 = 2;
+
+/// The frames shown since recording started, each with how many seconds
+/// it stayed on screen. Runs of identical frames are kept as one.
+pub struct Recording {
+    frames: Vec<(Rows, f32)>,
+    seconds: f32,
+    /// rows of each frame to record
+    #[cfg(not(target_arch = "wasm32"))]
+    height: usize,
+}
+
+impl Recording {
+    /// a recording of the top `height` rows of the display
+    pub fn new(height: usize) -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let _ = height;
+        Recording {
+            #[cfg(not(target_arch = "wasm32"))]
+            height,
+            frames: vec![],
+            seconds: 0.,
+        }
+    }
+
+    /// add a frame that was shown for `seconds`
+    pub fn push(&mut self, rows: &Rows, seconds: f32) {
+        self.seconds += seconds;
+        match self.frames.last_mut() {
+            Some((last, shown)) if last == rows => *shown += seconds,
+            _ => self.frames.push((*rows, seconds)),
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.seconds >= MAX_SECONDS
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// each frame to write with its delay in hundredths of a second
+    fn delays(&self) -> Vec<(&Rows, u16)> {
+        let mut delays: Vec<(&Rows, u16)> = vec![];
+        let (mut elapsed, mut written) = (0., 0);
+        for (rows, seconds) in &self.frames {
+            elapsed += seconds;
+            let end = (elapsed * 100.).round() as u32;
+            let delay = (end - written).min(u16::MAX.into()) as u16;
+            written = end;
+            match delays.last_mut() {
+                Some((_, last)) if delay < MIN_DELAY => *last += delay,
+                _ => delays.push((rows, delay)),
+            }
+        }
+        delays
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    /// the recording as a looping GIF, at the size of a screenshot
+    pub fn encode_gif(&self, palette: &Palette) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (width, height) = crate::screenshot::image_size(self.height);
+        let mut gif = vec![];
+        let mut encoder = gif::Encoder::new(
+            &mut gif,
+            width as u16,
+            height as u16,
+            &crate::screenshot::rgb_palette(palette),
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for (rows, delay) in self.delays() {
+            encoder.write_frame(&gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                delay,
+                buffer: std::borrow::Cow::Owned(crate::screenshot::upscale(rows, self.height)),
+                ..Default::default()
+            })?;
+        }
+        drop(encoder);
+        Ok(gif)
+    }
+}
+
+/// whether recordings can be saved in this build
+pub const AVAILABLE: bool = cfg!(not(target_arch = "wasm32"));
+
+/// Recordings being encoded and written.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct GifWriter {
+    pending: Vec<std::thread::JoinHandle<Result<String, String>>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GifWriter {
+    pub fn new() -> Self {
+        GifWriter { pending: vec![] }
+    }
+
+    /// start writing `recording` to a timestamped file
+    pub fn write(&mut self, recording: Recording, palette: Palette) {
+        self.pending.push(std::thread::spawn(move || {
+            let path = crate::screenshot::file_name(std::time::SystemTime::now(), "gif");
+            let gif = recording.encode_gif(&palette).map_err(|e| e.to_string())?;
+            std::fs::write(&path, gif).map_err(|e| e.to_string())?;
+            Ok(path)
+        }));
+    }
+
+    /// the path written to, or the error, for each recording done since the last call
+    pub fn finished(&mut self) -> Vec<Result<String, String>> {
+        let (done, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|handle| handle.is_finished());
+        self.pending = pending;
+        done.into_iter()
+            .map(|handle: std::thread::JoinHandle<_>| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(String::from("the encoder panicked")))
+            })
+            .collect()
+    }
+}
+
+/// Stands in where there's nowhere to write recordings; see `AVAILABLE`.
+#[cfg(target_arch = "wasm32")]
+pub struct GifWriter;
+
+#[cfg(target_arch = "wasm32")]
+impl GifWriter {
+    pub fn new() -> Self {
+        GifWriter
+    }
+
+    pub fn write(&mut self, _recording: Recording, _palette: Palette) {}
+
+    pub fn finished(&mut self) -> Vec<Result<String, String>> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::interpreter::{is_lit, set_lit, HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT};
+
+    /// a display with only the pixel at column `lit` of the top row lit
+    fn frame(lit: usize) -> Rows {
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        set_lit(&mut rows, lit, 0, true);
+        rows
+    }
+
+    #[test]
+    fn test_delays() {
+        let mut recording = Recording::new(SCREEN_HEIGHT);
+        // identical frames are merged
+        recording.push(&frame(0), 0.015);
+        recording.push(&frame(0), 0.015);
+        recording.push(&frame(1), 0.04);
+        // a frame too short to show is folded into the one before
+        recording.push(&frame(9), 0.014);
+        recording.push(&frame(2), 0.1);
+
+        let delays: Vec<(usize, u16)> = recording
+            .delays()
+            .into_iter()
+            .map(|(rows, delay)| ((0..64).position(|x| is_lit(rows, x, 0)).unwrap(), delay))
+            .collect();
+        assert_eq!(delays, [(0, 3), (1, 5), (2, 10)]);
+        assert!(!recording.is_full());
+        recording.push(&frame(3), MAX_SECONDS);
+        assert!(recording.is_full());
+    }
+
+    #[test]
+    fn test_encode_gif() {
+        let mut recording = Recording::new(SCREEN_HEIGHT);
+        recording.push(&frame(0), 0.5);
+        recording.push(&frame(1), 0.5);
+        let gif = recording.encode_gif(&Palette::DEFAULT).unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(gif.as_slice()).unwrap();
+        let mut delays = vec![];
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!((frame.width, frame.height), (512, 256));
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays, [50, 50]);
+    }
+}
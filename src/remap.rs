@@ -0,0 +1,124 @@
+use macroquad::prelude::*;
+
+use crate::{
+    keys::{key_name, Key},
+    ui_style::{FONT_SIZE, MARGIN, PANEL_BACKGROUND},
+};
+
+const CELL_WIDTH: f32 = 110.;
+const CELL_HEIGHT: f32 = 56.;
+
+/// CHIP-8 keys in the order they sit on the original hex keypad
+pub const LAYOUT: [usize; 16] = [
+    0x1, 0x2, 0x3, 0xC, //
+    0x4, 0x5, 0x6, 0xD, //
+    0x7, 0x8, 0x9, 0xE, //
+    0xA, 0x0, 0xB, 0xF,
+];
+
+/// A screen laid out like the hex keypad for rebinding its keys. Click a
+/// key (or move to it with the arrow keys and press Enter), then press the
+/// physical key to bind it to; Escape cancels.
+pub struct RemapScreen {
+    /// position in `LAYOUT` of the highlighted key
+    cursor: usize,
+    /// waiting for the physical key to bind the highlighted key to
+    listening: bool,
+}
+
+impl RemapScreen {
+    pub fn new() -> Self {
+        RemapScreen {
+            cursor: 0,
+            listening: false,
+        }
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listening
+    }
+
+    /// Handle this frame's input, returning the CHIP-8 key and the
+    /// physical key it should now be bound to once the user picks one.
+    pub fn update(&mut self) -> Option<(usize, Key)> {
+        if self.listening {
+            let key = get_last_key_pressed()?;
+            self.listening = false;
+            return (key != KeyCode::Escape).then_some((LAYOUT[self.cursor], Key(key)));
+        }
+
+        let (col, row) = (self.cursor % 4, self.cursor / 4);
+        if is_key_pressed(KeyCode::Left) {
+            self.cursor = row * 4 + (col + 3) % 4;
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.cursor = row * 4 + (col + 1) % 4;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.cursor = (row + 3) % 4 * 4 + col;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.cursor = (row + 1) % 4 * 4 + col;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            self.listening = true;
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (x, y) = mouse_position();
+            let (left, top) = origin();
+            let (col, row) = ((x - left) / CELL_WIDTH, (y - top) / CELL_HEIGHT);
+            if (0. ..4.).contains(&col) && (0. ..4.).contains(&row) {
+                self.cursor = row as usize * 4 + col as usize;
+                self.listening = true;
+            }
+        }
+        None
+    }
+
+    pub fn draw(&self, keypad: &[Key; 16]) {
+        let (left, top) = origin();
+        draw_rectangle(
+            left - MARGIN,
+            top - MARGIN - FONT_SIZE,
+            4. * CELL_WIDTH + 2. * MARGIN,
+            4. * CELL_HEIGHT + 2. * MARGIN + FONT_SIZE,
+            PANEL_BACKGROUND,
+        );
+        let title = if self.listening {
+            format!(
+                "Press a key for {:X}, Escape to cancel",
+                LAYOUT[self.cursor]
+            )
+        } else {
+            String::from("Click a key to rebind it")
+        };
+        draw_text(&title, left, top - MARGIN / 2., FONT_SIZE, WHITE);
+
+        for (idx, slot) in LAYOUT.iter().enumerate() {
+            let x = left + (idx % 4) as f32 * CELL_WIDTH;
+            let y = top + (idx / 4) as f32 * CELL_HEIGHT;
+            let color = match (idx == self.cursor, self.listening) {
+                (true, true) => YELLOW,
+                (true, false) => SKYBLUE,
+                _ => GRAY,
+            };
+            draw_rectangle_lines(x + 2., y + 2., CELL_WIDTH - 4., CELL_HEIGHT - 4., 2., color);
+            draw_text(&format!("{:X}", slot), x + 10., y + 24., FONT_SIZE, color);
+            draw_text(
+                &key_name(keypad[*slot].0),
+                x + 10.,
+                y + 46.,
+                FONT_SIZE,
+                WHITE,
+            );
+        }
+    }
+}
+
+/// top-left corner of the grid, which is centered in the window
+fn origin() -> (f32, f32) {
+    (
+        (screen_width() - 4. * CELL_WIDTH) / 2.,
+        (screen_height() - 4. * CELL_HEIGHT) / 2.,
+    )
+}
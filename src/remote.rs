@@ -0,0 +1,185 @@
+//! Who else is taking part in a windowed session: the other player of a
+//! netplay game, and the debug server's clients. Neither is possible in
+//! the browser, so there `Remote` stands in with no one on the line.
+
+use chip8_core::Chip8Error;
+
+use crate::{
+    cli::RunOptions,
+    machine::{Chip8Machine, FrameOutput, Inputs},
+    toast::Toasts,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    debug_server::DebugServer,
+    netplay::{Exchange, Netplay},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Remote {
+    netplay: Option<Netplay>,
+    debug_server: Option<DebugServer>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Remote {
+    /// start the debug server `--debug-server` asks for, if any
+    pub fn new(options: &RunOptions, toasts: &mut Toasts) -> Self {
+        let debug_server = options
+            .debug_server
+            .and_then(|port| match DebugServer::bind(port) {
+                Ok(server) => {
+                    toasts.info(format!("Debug server on ws://127.0.0.1:{}", port));
+                    Some(server)
+                }
+                Err(e) => {
+                    toasts.warn(format!("Could not start the debug server: {}", e));
+                    None
+                }
+            });
+        Remote {
+            netplay: None,
+            debug_server,
+        }
+    }
+
+    /// Start the netplay game `--host` or `--connect` asks for, if either,
+    /// for `rom` running at `speed`; hosting can fail if the port is taken.
+    pub fn start_netplay(
+        &mut self,
+        options: &RunOptions,
+        rom: &[u8],
+        speed: u32,
+        toasts: &mut Toasts,
+    ) {
+        if let Some(addr) = &options.connect {
+            toasts.info(format!("Connecting to {}...", addr));
+            self.netplay = Some(Netplay::connect(addr, rom, speed));
+            return;
+        }
+        let Some(port) = options.host else {
+            return;
+        };
+        match Netplay::host(port, rom, speed) {
+            Ok(netplay) => {
+                toasts.info(format!("Waiting for a player to connect on port {}", port));
+                self.netplay = Some(netplay);
+            }
+            Err(e) => toasts.warn(format!("Could not host on port {}: {}", port, e)),
+        }
+    }
+
+    pub fn in_netplay(&self) -> bool {
+        self.netplay.is_some()
+    }
+
+    /// hang up on the other player, as when another ROM is opened
+    pub fn leave_netplay(&mut self, toasts: &mut Toasts) {
+        if self.netplay.take().is_some() {
+            toasts.warn("Left the netplay game");
+        }
+    }
+
+    /// answer the debug server's requests, and hold down the keys they
+    /// hold on top of `keys`
+    pub fn serve(&mut self, machine: &mut Chip8Machine, paused: &mut bool, keys: &mut [bool; 16]) {
+        if let Some(server) = &mut self.debug_server {
+            server.serve(machine, paused);
+            for (key, held) in keys.iter_mut().zip(server.keys()) {
+                *key |= held;
+            }
+        }
+    }
+
+    /// In a netplay game, the next frame, which only runs once it's due and
+    /// the other player's keys for it are in. Frames can't be skipped, stepped
+    /// or rewound, as that would leave the two players' games out of step.
+    pub fn netplay_frame(
+        &mut self,
+        machine: &mut Chip8Machine,
+        inputs: &Inputs,
+        ticks: u32,
+        paused: bool,
+        toasts: &mut Toasts,
+    ) -> Option<Result<FrameOutput, Chip8Error>> {
+        let game = self.netplay.as_mut()?;
+        if paused || ticks == 0 {
+            return Some(Ok(machine.idle_frame()));
+        }
+        match game.exchange(inputs.keys) {
+            // taps stay local, as the other side never sees them
+            Ok(Exchange::Run(keys)) => Some(machine.frame(&Inputs {
+                keys,
+                ..Default::default()
+            })),
+            Ok(Exchange::Connected) => {
+                toasts.info("Player connected");
+                Some(Ok(machine.idle_frame()))
+            }
+            Ok(Exchange::Waiting | Exchange::Stalled) => Some(Ok(machine.idle_frame())),
+            Err(e) => {
+                toasts.warn(format!("Netplay ended: {}", e));
+                self.netplay = None;
+                Some(Ok(machine.idle_frame()))
+            }
+        }
+    }
+}
+
+/// Stands in where there are no sockets to play or debug over.
+#[cfg(target_arch = "wasm32")]
+pub struct Remote;
+
+#[cfg(target_arch = "wasm32")]
+impl Remote {
+    pub fn new(_options: &RunOptions, _toasts: &mut Toasts) -> Self {
+        Remote
+    }
+
+    pub fn start_netplay(
+        &mut self,
+        _options: &RunOptions,
+        _rom: &[u8],
+        _speed: u32,
+        _toasts: &mut Toasts,
+    ) {
+    }
+
+    pub fn in_netplay(&self) -> bool {
+        false
+    }
+
+    pub fn leave_netplay(&mut self, _toasts: &mut Toasts) {}
+
+    pub fn serve(
+        &mut self,
+        _machine: &mut Chip8Machine,
+        _paused: &mut bool,
+        _keys: &mut [bool; 16],
+    ) {
+    }
+
+    pub fn netplay_frame(
+        &mut self,
+        _machine: &mut Chip8Machine,
+        _inputs: &Inputs,
+        _ticks: u32,
+        _paused: bool,
+        _toasts: &mut Toasts,
+    ) -> Option<Result<FrameOutput, Chip8Error>> {
+        None
+    }
+}
+
+impl Remote {
+    /// In a netplay game, warn that `action` is off, as it would change
+    /// only this player's game and put the two out of step. Returns whether
+    /// it was refused.
+    pub fn refuses(&self, action: &str, toasts: &mut Toasts) -> bool {
+        let in_netplay = self.in_netplay();
+        if in_netplay {
+            toasts.warn(format!("{} is off in a netplay game", action));
+        }
+        in_netplay
+    }
+}
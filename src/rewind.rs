@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+use chip8_core::Snapshot;
+
+/// how far back the rewind hotkey can go
+pub const REWIND_SECONDS: usize = 5;
+
+/// The most recent snapshots, oldest first. Once full, taking a new
+/// snapshot forgets the oldest one.
+pub struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// the most recent snapshot, removing it from the buffer
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::Interpreter;
+
+    #[test]
+    fn test_keeps_most_recent_snapshots() {
+        let snapshot = |pc: u16| Snapshot {
+            program_counter: pc,
+            ..Interpreter::new().snapshot()
+        };
+
+        let mut buffer = RewindBuffer::new(2);
+        for pc in [0x200, 0x202, 0x204] {
+            buffer.push(snapshot(pc));
+        }
+
+        assert_eq!(buffer.pop().map(|s| s.program_counter), Some(0x204));
+        assert_eq!(buffer.pop().map(|s| s.program_counter), Some(0x202));
+        assert_eq!(buffer.pop(), None);
+    }
+}
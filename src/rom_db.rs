@@ -0,0 +1,215 @@
+//! Known ROMs by SHA-1, with the platform, quirks, speed, and controls each
+//! was written for, so they run correctly without trying presets by hand.
+//!
+//! `assets/rom-db.json` follows the community CHIP-8 database's
+//! `programs.json`: programs with a title and their ROMs by hash, each
+//! listing the platforms it runs on in order of preference, quirks that
+//! differ from a platform's usual ones, a tickrate in instructions per
+//! frame, and which keys it uses for each direction and action. Fields
+//! this interpreter has no use for are ignored.
+
+use std::collections::BTreeMap;
+
+use chip8_core::Quirks;
+use macroquad::input::KeyCode;
+use serde::Deserialize;
+
+use crate::{
+    gamepad::{GamepadMapping, PadButton},
+    keys::Key,
+    timing::TIMER_HZ,
+};
+
+const DATABASE: &str = include_str!("../assets/rom-db.json");
+
+#[derive(Debug, Deserialize)]
+struct Program {
+    title: String,
+    roms: BTreeMap<String, Rom>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: BTreeMap<String, QuirkOverrides>,
+    tickrate: Option<u32>,
+    /// CHIP-8 key for each control, e.g. "up" or "a"
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+/// Quirks a ROM needs set differently from its platform's, named as in the
+/// database. Each is true for the behavior that differs from the VIP's.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl QuirkOverrides {
+    fn apply(&self, quirks: &mut Quirks) {
+        if let Some(shift) = self.shift {
+            quirks.shift_uses_vy = !shift;
+        }
+        if let Some(leave_i) = self.memory_leave_i_unchanged {
+            quirks.load_store_increments_i = !leave_i;
+        }
+        if let Some(wrap) = self.wrap {
+            quirks.wrap_sprites = wrap;
+        }
+        if let Some(jump) = self.jump {
+            quirks.jump_uses_vx = jump;
+        }
+        if let Some(vblank) = self.vblank {
+            quirks.display_wait = vblank;
+        }
+        if let Some(logic) = self.logic {
+            quirks.logic_resets_vf = logic;
+        }
+    }
+}
+
+/// How to run a ROM the database knows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub title: String,
+    /// the first of the ROM's platforms that can be emulated, as the database names it
+    pub platform: String,
+    pub quirks: Quirks,
+    pub megachip: bool,
+    pub instructions_per_second: Option<u32>,
+    /// CHIP-8 key for each gamepad button the ROM's controls go on
+    pub buttons: BTreeMap<PadButton, u8>,
+}
+
+impl Profile {
+    /// `mapping` with the ROM's own controls on the D-pad and face buttons
+    pub fn gamepad(&self, mapping: &GamepadMapping) -> GamepadMapping {
+        let mut mapping = mapping.clone();
+        mapping.0.extend(&self.buttons);
+        mapping
+    }
+
+    /// the arrow keys for the ROM's directions, as on the gamepad's D-pad
+    pub fn arrow_keys(&self) -> BTreeMap<Key, u8> {
+        [
+            (PadButton::DPadUp, KeyCode::Up),
+            (PadButton::DPadDown, KeyCode::Down),
+            (PadButton::DPadLeft, KeyCode::Left),
+            (PadButton::DPadRight, KeyCode::Right),
+        ]
+        .into_iter()
+        .filter_map(|(button, key)| Some((Key(key), *self.buttons.get(&button)?)))
+        .collect()
+    }
+}
+
+/// the quirks of a platform in the database, or None for ones that can't be emulated
+fn platform_quirks(platform: &str) -> Option<Quirks> {
+    match platform {
+        "originalChip8" | "hybridVIP" => Some(Quirks::COSMAC_VIP),
+        "modernChip8" | "xochip" => Some(Quirks::default()),
+        "chip48" | "superchip1" | "superchip" | "megachip8" => Some(Quirks::SUPER_CHIP),
+        _ => None,
+    }
+}
+
+/// the gamepad button for a control in the database; the second player's have none
+fn control_button(control: &str) -> Option<PadButton> {
+    match control {
+        "up" => Some(PadButton::DPadUp),
+        "down" => Some(PadButton::DPadDown),
+        "left" => Some(PadButton::DPadLeft),
+        "right" => Some(PadButton::DPadRight),
+        "a" => Some(PadButton::South),
+        "b" => Some(PadButton::East),
+        _ => None,
+    }
+}
+
+/// the SHA-1 of `rom` in lowercase hex, which the database knows ROMs by
+pub fn sha1(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// how to run `rom`, if it's in the database under a platform that can be emulated
+pub fn lookup(rom: &[u8]) -> Option<Profile> {
+    let sha1 = sha1(rom);
+    let programs: Vec<Program> =
+        serde_json::from_str(DATABASE).expect("the ROM database should be valid");
+    programs.into_iter().find_map(|program| {
+        let rom = program.roms.get(&sha1)?;
+        let (platform, mut quirks) = rom
+            .platforms
+            .iter()
+            .find_map(|platform| Some((platform, platform_quirks(platform)?)))?;
+        if let Some(overrides) = rom.quirky_platforms.get(platform) {
+            overrides.apply(&mut quirks);
+        }
+        Some(Profile {
+            title: program.title,
+            platform: platform.clone(),
+            quirks,
+            megachip: platform == "megachip8",
+            instructions_per_second: rom.tickrate.map(|ticks| ticks * TIMER_HZ as u32),
+            buttons: rom
+                .keys
+                .iter()
+                .filter_map(|(control, key)| Some((control_button(control)?, *key)))
+                .collect(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library;
+
+    #[test]
+    fn test_lookup() {
+        let pong = lookup(library::embedded("PONG").unwrap()).unwrap();
+        assert_eq!(pong.title, "Pong");
+        assert_eq!(pong.quirks, Quirks::COSMAC_VIP);
+        assert_eq!(pong.instructions_per_second, Some(900));
+        // only the first player's controls go on the gamepad
+        let mapping = pong.gamepad(&GamepadMapping::default());
+        assert_eq!(mapping.0[&PadButton::DPadUp], 0x1);
+        assert_eq!(mapping.0[&PadButton::DPadDown], 0x4);
+        // and buttons it doesn't use keep their usual keys
+        assert_eq!(mapping.0[&PadButton::DPadLeft], 0x4);
+        assert_eq!(mapping.0[&PadButton::Start], 0xF);
+        // and the same on the arrow keys
+        let arrows: Vec<(Key, u8)> = pong.arrow_keys().into_iter().collect();
+        assert_eq!(arrows, [(Key(KeyCode::Down), 0x4), (Key(KeyCode::Up), 0x1)]);
+
+        let invaders = lookup(library::embedded("INVADERS").unwrap()).unwrap();
+        assert!(!invaders.quirks.shift_uses_vy);
+        assert!(!invaders.quirks.display_wait);
+        assert!(invaders.quirks.logic_resets_vf);
+
+        assert_eq!(lookup(&[0x12, 0x00]), None);
+    }
+
+    #[test]
+    fn test_every_entry_can_run() {
+        let programs: Vec<Program> = serde_json::from_str(DATABASE).unwrap();
+        for program in programs {
+            for (sha1, rom) in &program.roms {
+                assert_eq!(sha1.len(), 40, "{}", program.title);
+                assert!(
+                    rom.platforms.iter().any(|p| platform_quirks(p).is_some()),
+                    "{}",
+                    program.title
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use chip8_core::{
+    interpreter::{decode, Op, MEMORY_SIZE, PROGRAM_START},
+    Quirks,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Chip8,
+    /// 64x64 CHIP-8, recognizable by its 0x1260 boot jump
+    HiresChip8,
+    SuperChip,
+    XoChip,
+    /// recognizable by the 0011 that enters Mega mode
+    MegaChip,
+}
+
+impl Variant {
+    /// the quirks ROMs for this variant usually expect
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Variant::SuperChip | Variant::MegaChip => Quirks::SUPER_CHIP,
+            Variant::Chip8 | Variant::HiresChip8 | Variant::XoChip => Quirks::default(),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Variant::Chip8 => "CHIP-8",
+            Variant::HiresChip8 => "CHIP-8 (hires)",
+            Variant::SuperChip => "SUPER-CHIP",
+            Variant::XoChip => "XO-CHIP",
+            Variant::MegaChip => "MegaChip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// the ROM as a sequence of big-endian instruction words
+fn words(rom: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    rom.chunks(2)
+        .map(|pair| ((pair[0] as u16) << 8) | *pair.get(1).unwrap_or(&0) as u16)
+}
+
+fn is_xo_chip_only(word: u16) -> bool {
+    matches!(word & 0xF00F, 0x5002 | 0x5003) // save/load vx - vy
+        || word == 0xF000 // i := long nnnn
+        || word == 0xF002 // audio
+        || matches!(word & 0xF0FF, 0xF001 | 0xF03A) // plane n, pitch := vx
+        || word & 0xFFF0 == 0x00D0 // scroll-up n
+}
+
+fn is_super_chip_only(word: u16) -> bool {
+    word & 0xFFF0 == 0x00C0 // scroll-down n
+        || (0x00FB..=0x00FF).contains(&word) // scroll, exit, lores/hires
+        || word & 0xF00F == 0xD000 // 16x16 sprite
+        || matches!(word & 0xF0FF, 0xF030 | 0xF075 | 0xF085) // big font, RPL flags
+}
+
+/// the variant `word` needs, if it's an instruction only some variants have
+pub fn extension(word: u16) -> Option<Variant> {
+    if matches!(word, 0x0010 | 0x0011) {
+        // the rest of MegaChip's instructions are SYS calls elsewhere
+        Some(Variant::MegaChip)
+    } else if is_xo_chip_only(word) {
+        Some(Variant::XoChip)
+    } else if is_super_chip_only(word) {
+        Some(Variant::SuperChip)
+    } else {
+        None
+    }
+}
+
+/// Guess which CHIP-8 variant a ROM targets by looking for instructions
+/// that only exist in the extended instruction sets. Data regions are
+/// scanned too, so this is a heuristic rather than a guarantee.
+pub fn detect_variant(rom: &[u8]) -> Variant {
+    if words(rom).any(|word| word == 0x0011) {
+        Variant::MegaChip
+    } else if words(rom).any(is_xo_chip_only) {
+        Variant::XoChip
+    } else if words(rom).any(is_super_chip_only) {
+        Variant::SuperChip
+    } else if words(rom).next() == Some(0x1260) {
+        Variant::HiresChip8
+    } else {
+        Variant::Chip8
+    }
+}
+
+/// Static statistics about a ROM, as printed by the `info` subcommand.
+pub struct RomInfo {
+    pub size: usize,
+    pub sha1: String,
+    pub variant: Variant,
+    /// title and platform from the ROM database, for known ROMs
+    pub known_as: Option<String>,
+    /// how often each instruction form occurs, keyed by `Op::name`
+    pub opcode_histogram: BTreeMap<&'static str, usize>,
+    /// targets of jumps, calls, and `LD I` instructions
+    pub referenced_addresses: BTreeSet<u16>,
+}
+
+impl RomInfo {
+    pub fn analyze(rom: &[u8]) -> Self {
+        let mut opcode_histogram = BTreeMap::new();
+        let mut referenced_addresses = BTreeSet::new();
+        for word in words(rom) {
+            let op = decode(word);
+            *opcode_histogram.entry(op.name()).or_insert(0) += 1;
+            match op {
+                Op::Jp { nnn } | Op::Call { nnn } | Op::LdI { nnn } | Op::JpV0 { nnn } => {
+                    referenced_addresses.insert(nnn);
+                }
+                _ => (),
+            }
+        }
+
+        RomInfo {
+            size: rom.len(),
+            sha1: sha1_smol::Sha1::from(rom).digest().to_string(),
+            variant: detect_variant(rom),
+            known_as: crate::rom_db::lookup(rom)
+                .map(|profile| format!("{} ({})", profile.title, profile.platform)),
+            opcode_histogram,
+            referenced_addresses,
+        }
+    }
+
+    pub fn fits_in_memory(&self) -> bool {
+        self.size <= MEMORY_SIZE - PROGRAM_START
+    }
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "size:     {} bytes (fits in 4 KB: {})",
+            self.size,
+            if self.fits_in_memory() { "yes" } else { "no" }
+        )?;
+        writeln!(f, "sha1:     {}", self.sha1)?;
+        writeln!(f, "variant:  {}", self.variant)?;
+        if let Some(known_as) = &self.known_as {
+            writeln!(f, "known as: {}", known_as)?;
+        }
+
+        writeln!(f, "opcodes:")?;
+        let mut histogram: Vec<_> = self.opcode_histogram.iter().collect();
+        histogram.sort_by(|a, b| b.1.cmp(a.1));
+        for (name, count) in histogram {
+            writeln!(f, "  {:<20} {:>5}", name, count)?;
+        }
+
+        writeln!(
+            f,
+            "referenced addresses ({}):",
+            self.referenced_addresses.len()
+        )?;
+        let addresses: Vec<String> = self
+            .referenced_addresses
+            .iter()
+            .map(|addr| format!("{:#05x}", addr))
+            .collect();
+        for row in addresses.chunks(8) {
+            writeln!(f, "  {}", row.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_variant() {
+        // CLS, JP 0x200
+        assert_eq!(detect_variant(&[0x00, 0xE0, 0x12, 0x00]), Variant::Chip8);
+        // JP 0x260
+        assert_eq!(
+            detect_variant(&[0x12, 0x60, 0x00, 0xE0]),
+            Variant::HiresChip8
+        );
+        // CLS, HIGH
+        assert_eq!(
+            detect_variant(&[0x00, 0xE0, 0x00, 0xFF]),
+            Variant::SuperChip
+        );
+        // HIGH, i := long 0x1234
+        assert_eq!(
+            detect_variant(&[0x00, 0xFF, 0xF0, 0x00, 0x12, 0x34]),
+            Variant::XoChip
+        );
+        // MEGAON, CLS
+        assert_eq!(detect_variant(&[0x00, 0x11, 0x00, 0xE0]), Variant::MegaChip);
+        assert_eq!(Variant::SuperChip.quirks(), Quirks::SUPER_CHIP);
+    }
+
+    #[test]
+    fn test_analyze_counts_opcodes_and_references() {
+        // LD I, 0x20A; CALL 0x208; CALL 0x208; JP 0x206
+        let info = RomInfo::analyze(&[0xA2, 0x0A, 0x22, 0x08, 0x22, 0x08, 0x12, 0x06]);
+        assert_eq!(info.size, 8);
+        assert_eq!(info.opcode_histogram["CALL addr"], 2);
+        assert_eq!(info.opcode_histogram["LD I, addr"], 1);
+        assert_eq!(
+            info.referenced_addresses.into_iter().collect::<Vec<_>>(),
+            vec![0x206, 0x208, 0x20A]
+        );
+    }
+
+    #[test]
+    fn test_sha1_and_size_limit() {
+        let info = RomInfo::analyze(b"abc");
+        assert_eq!(info.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert!(info.fits_in_memory());
+
+        let info = RomInfo::analyze(&[0; MEMORY_SIZE - PROGRAM_START + 1]);
+        assert!(!info.fits_in_memory());
+    }
+}
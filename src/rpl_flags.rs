@@ -0,0 +1,116 @@
+//! SUPER-CHIP's RPL user flags, kept between runs since games use them for
+//! high scores.
+//!
+//! Natively each ROM's flags are a file of 8 bytes under
+//! `$XDG_DATA_HOME/chip8-rust/rpl` (falling back to `~/.local/share`),
+//! named after the ROM. The browser has no filesystem, so there they only
+//! last until the page is closed.
+
+use std::error::Error;
+
+use chip8_core::interpreter::RPL_FLAG_COUNT;
+
+pub struct RplFlags {
+    #[cfg(not(target_arch = "wasm32"))]
+    rom_name: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    dir: Option<std::path::PathBuf>,
+    /// the flags as last loaded or saved
+    saved: [u8; RPL_FLAG_COUNT],
+}
+
+impl RplFlags {
+    pub fn new(rom_name: &str) -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let _ = rom_name;
+        RplFlags {
+            #[cfg(not(target_arch = "wasm32"))]
+            rom_name: rom_name.to_string(),
+            #[cfg(not(target_arch = "wasm32"))]
+            dir: default_dir(),
+            saved: [0; RPL_FLAG_COUNT],
+        }
+    }
+
+    /// the flags the ROM saved in an earlier run, all zero if it never has
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(&mut self) -> Result<[u8; RPL_FLAG_COUNT], Box<dyn Error>> {
+        let bytes = match std::fs::read(self.path()?) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![0; RPL_FLAG_COUNT],
+            Err(e) => return Err(e.into()),
+        };
+        self.saved = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            format!(
+                "expected {} bytes of RPL flags, found {}",
+                RPL_FLAG_COUNT,
+                bytes.len()
+            )
+        })?;
+        Ok(self.saved)
+    }
+
+    /// Write `flags` out if the ROM changed them since the last load or
+    /// save. A failed write isn't retried until they change again.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_if_changed(&mut self, flags: &[u8; RPL_FLAG_COUNT]) -> Result<(), Box<dyn Error>> {
+        if *flags == self.saved {
+            return Ok(());
+        }
+        self.saved = *flags;
+        let path = self.path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, flags)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path(&self) -> Result<std::path::PathBuf, Box<dyn Error>> {
+        let dir = self.dir.as_ref().ok_or("no directory for RPL flags")?;
+        Ok(dir.join(format!("{}.rpl", self.rom_name)))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(&mut self) -> Result<[u8; RPL_FLAG_COUNT], Box<dyn Error>> {
+        Ok(self.saved)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save_if_changed(&mut self, flags: &[u8; RPL_FLAG_COUNT]) -> Result<(), Box<dyn Error>> {
+        self.saved = *flags;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_dir() -> Option<std::path::PathBuf> {
+    Some(crate::config::data_dir()?.join("rpl"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flags_survive_a_restart() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("chip8-rpl-flags-{}", std::process::id()));
+        let open = || RplFlags {
+            rom_name: String::from("TEST"),
+            dir: Some(dir.clone()),
+            saved: [0; RPL_FLAG_COUNT],
+        };
+
+        let mut flags = open();
+        assert_eq!(flags.load()?, [0; RPL_FLAG_COUNT]);
+        flags.save_if_changed(&[1, 2, 3, 4, 5, 6, 7, 8])?;
+        assert_eq!(open().load()?, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        std::fs::write(dir.join("TEST.rpl"), [1, 2])?;
+        assert!(open().load().is_err());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! Numbered save state slots for the running ROM.
+//!
+//! Natively each slot is a file under `$XDG_DATA_HOME/chip8-rust/states`
+//! (falling back to `~/.local/share`), named after the ROM so every game
+//! gets its own slots. The browser has no filesystem, so there slots only
+//! last until the page is closed.
+
+use std::error::Error;
+
+use chip8_core::Snapshot;
+
+pub const SLOT_COUNT: usize = 10;
+
+pub struct SaveSlots {
+    slot: usize,
+    #[cfg(not(target_arch = "wasm32"))]
+    rom_name: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    dir: Option<std::path::PathBuf>,
+    #[cfg(target_arch = "wasm32")]
+    states: Vec<Option<Vec<u8>>>,
+}
+
+impl SaveSlots {
+    pub fn new(rom_name: &str) -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let _ = rom_name;
+        SaveSlots {
+            slot: 0,
+            #[cfg(not(target_arch = "wasm32"))]
+            rom_name: rom_name.to_string(),
+            #[cfg(not(target_arch = "wasm32"))]
+            dir: default_dir(),
+            #[cfg(target_arch = "wasm32")]
+            states: vec![None; SLOT_COUNT],
+        }
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    pub fn next_slot(&mut self) {
+        self.slot = (self.slot + 1) % SLOT_COUNT;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&mut self, snapshot: &Snapshot) -> Result<(), Box<dyn Error>> {
+        let path = self.path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, snapshot.to_bytes())?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(&self) -> Result<Snapshot, Box<dyn Error>> {
+        let path = self.path()?;
+        match std::fs::read(&path) {
+            Ok(bytes) => Snapshot::from_bytes(&bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(format!("slot {} is empty", self.slot).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path(&self) -> Result<std::path::PathBuf, Box<dyn Error>> {
+        let dir = self.dir.as_ref().ok_or("no directory for save states")?;
+        Ok(dir.join(format!("{}.{}.c8st", self.rom_name, self.slot)))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&mut self, snapshot: &Snapshot) -> Result<(), Box<dyn Error>> {
+        self.states[self.slot] = Some(snapshot.to_bytes());
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(&self) -> Result<Snapshot, Box<dyn Error>> {
+        match &self.states[self.slot] {
+            Some(bytes) => Snapshot::from_bytes(bytes),
+            None => Err(format!("slot {} is empty", self.slot).into()),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_dir() -> Option<std::path::PathBuf> {
+    Some(crate::config::data_dir()?.join("states"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::Interpreter;
+
+    #[test]
+    fn test_slots_are_separate() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("chip8-save-slots-{}", std::process::id()));
+        let mut slots = SaveSlots {
+            slot: 0,
+            rom_name: String::from("TEST"),
+            dir: Some(dir.clone()),
+        };
+
+        let mut interpreter = Interpreter::new();
+        // LD V0, 0x2A
+        interpreter.load_program(&[0x60, 0x2A])?;
+        interpreter.step()?;
+        slots.save(&interpreter.snapshot())?;
+        assert_eq!(slots.load()?, interpreter.snapshot());
+
+        slots.next_slot();
+        assert_eq!(slots.slot(), 1);
+        assert!(slots.load().is_err());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+//! PNG screenshots of the display, drawn in a palette's colors.
+
+use std::{error::Error, time::SystemTime};
+
+use chip8_core::interpreter::{is_lit, Rows, SCREEN_WIDTH};
+
+use crate::palette::Palette;
+
+/// window pixels per CHIP-8 pixel, giving 512x256 images, or 512x512 for hires ROMs
+pub const SCALE: usize = 8;
+
+/// width and height of images of a display `height` rows tall, `SCALE` times its size
+pub fn image_size(height: usize) -> (usize, usize) {
+    (SCREEN_WIDTH * SCALE, height * SCALE)
+}
+
+/// the top `height` of `rows` at `image_size`, row by row, as 1 for lit
+/// and 0 for unlit
+pub fn upscale(rows: &Rows, height: usize) -> Vec<u8> {
+    let (width, height) = image_size(height);
+    (0..width * height)
+        .map(|idx| {
+            let (x, y) = (idx % width, idx / width);
+            u8::from(is_lit(rows, x / SCALE, y / SCALE))
+        })
+        .collect()
+}
+
+/// `palette`'s unlit then lit colors as RGB bytes, to index with `upscale`'s output
+pub fn rgb_palette(palette: &Palette) -> [u8; 6] {
+    let [_, r0, g0, b0] = palette.background.to_be_bytes();
+    let [_, r1, g1, b1] = palette.foreground.to_be_bytes();
+    [r0, g0, b0, r1, g1, b1]
+}
+
+/// the top `height` of `rows` as a PNG, at `image_size`
+pub fn encode_png(
+    rows: &Rows,
+    height: usize,
+    palette: &Palette,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let colors = rgb_palette(palette);
+    let data: Vec<u8> = upscale(rows, height)
+        .into_iter()
+        .flat_map(|lit| {
+            let start = usize::from(lit) * 3;
+            colors[start..start + 3].to_vec()
+        })
+        .collect();
+
+    let mut png = vec![];
+    let (width, height) = image_size(height);
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+/// `chip8-YYYYMMDD-HHMMSS.<extension>` for the given time, in UTC
+pub fn file_name(time: SystemTime, extension: &str) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (year, month, day) = civil_date(secs / 86400);
+    let secs = secs % 86400;
+    format!(
+        "chip8-{:04}{:02}{:02}-{:02}{:02}{:02}.{}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        extension
+    )
+}
+
+/// The year, month, and day `days` after 1970-01-01. Howard Hinnant's
+/// `civil_from_days`, which counts in 400 year eras starting in March.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::interpreter::{set_lit, HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT};
+    use std::time::Duration;
+
+    #[test]
+    fn test_encode_png() {
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        set_lit(&mut rows, 1, 1, true);
+        let palette = Palette::from_name("bw").unwrap();
+        let png = encode_png(&rows, SCREEN_HEIGHT, &palette).unwrap();
+
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (512, 256));
+        let rgb = |x: usize, y: usize| &data[(y * 512 + x) * 3..][..3];
+        assert_eq!(rgb(0, 0), [0, 0, 0]);
+        assert_eq!(rgb(SCALE, SCALE), [255, 255, 255]);
+        assert_eq!(rgb(2 * SCALE - 1, 2 * SCALE - 1), [255, 255, 255]);
+        assert_eq!(rgb(2 * SCALE, SCALE), [0, 0, 0]);
+        assert_eq!(image_size(HIRES_SCREEN_HEIGHT), (512, 512));
+    }
+
+    #[test]
+    fn test_file_name() {
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(file_name(at(0), "png"), "chip8-19700101-000000.png");
+        // a leap day
+        assert_eq!(
+            file_name(at(951_827_696), "png"),
+            "chip8-20000229-123456.png"
+        );
+        assert_eq!(
+            file_name(at(1_735_689_599), "gif"),
+            "chip8-20241231-235959.gif"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! A pass/fail runner for the community test ROMs in `assets/test-roms`.
+//!
+//! Each ROM runs headlessly for a fixed number of frames, then regions of
+//! its final screen are compared against a stored screen taken from a
+//! passing run. The regions are the ROM's own result markers, so a failure
+//! names the opcode or behavior that broke. Expected screens are in
+//! `assets/test-roms/expected`, in the `#`/`.` format `test <rom>` prints.
+
+use std::{error::Error, ops::Range};
+
+use chip8_core::{
+    interpreter::{is_lit, set_lit, Rows, HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT, SCREEN_WIDTH},
+    Quirks,
+};
+
+use crate::machine::{Chip8Machine, Inputs};
+
+/// Part of the screen where a ROM reports one result.
+pub struct Group {
+    pub name: &'static str,
+    columns: Range<usize>,
+    rows: Range<usize>,
+}
+
+const fn group(name: &'static str, columns: Range<usize>, rows: Range<usize>) -> Group {
+    Group {
+        name,
+        columns,
+        rows,
+    }
+}
+
+pub struct SuiteRom {
+    pub name: &'static str,
+    rom: &'static [u8],
+    expected: &'static str,
+    frames: u32,
+    quirks: Quirks,
+    /// Timendus' ROMs skip their menus when 0x1FF holds a choice
+    menu_choice: Option<u8>,
+    pub groups: &'static [Group],
+}
+
+/// corax89's opcode test: three columns of six results, each labelled
+/// with its opcode and showing a check or a cross
+const COLUMNS: [Range<usize>; 3] = [0..23, 23..44, 44..64];
+const fn corax(name: &'static str, column: usize, row: usize) -> Group {
+    let top = 1 + row * 5;
+    group(
+        name,
+        COLUMNS[column].start..COLUMNS[column].end,
+        top..top + 4,
+    )
+}
+
+/// Timendus' display wait check reports "SLOW" at the default speed, so
+/// every ROM runs faster than that
+const INSTRUCTIONS_PER_SECOND: u32 = 600;
+
+pub const SUITE: [SuiteRom; 4] = [
+    SuiteRom {
+        name: "corax89 opcode test",
+        rom: include_bytes!("../assets/test-roms/3-test-opcode.ch8"),
+        expected: include_str!("../assets/test-roms/expected/3-test-opcode.txt"),
+        frames: 60,
+        quirks: Quirks::COSMAC_VIP,
+        menu_choice: None,
+        groups: &[
+            corax("3XNN", 0, 0),
+            corax("4XNN", 0, 1),
+            corax("5XY0", 0, 2),
+            corax("7XNN", 0, 3),
+            corax("9XY0", 0, 4),
+            corax("ANNN", 0, 5),
+            corax("00EE", 1, 0),
+            corax("8XY0", 1, 1),
+            corax("8XY1", 1, 2),
+            corax("8XY2", 1, 3),
+            corax("8XY3", 1, 4),
+            corax("8XY4", 1, 5),
+            corax("8XY5", 2, 0),
+            corax("8XY6", 2, 1),
+            corax("8XYE", 2, 2),
+            corax("FX55 and FX65", 2, 3),
+            corax("FX33", 2, 4),
+            corax("1NNN", 2, 5),
+        ],
+    },
+    SuiteRom {
+        name: "Timendus flags test",
+        rom: include_bytes!("../assets/test-roms/4-flags.ch8"),
+        expected: include_str!("../assets/test-roms/expected/4-flags.txt"),
+        frames: 300,
+        quirks: Quirks::COSMAC_VIP,
+        menu_choice: None,
+        groups: &[
+            group("8XYN results and VF without carry", 0..64, 0..15),
+            group("8XYN with carry", 0..64, 15..26),
+            group("FX1E", 0..64, 26..32),
+        ],
+    },
+    SuiteRom {
+        name: "Timendus quirks test, CHIP-8",
+        rom: include_bytes!("../assets/test-roms/5-quirks.ch8"),
+        expected: include_str!("../assets/test-roms/expected/5-quirks.txt"),
+        frames: 600,
+        quirks: Quirks::COSMAC_VIP,
+        menu_choice: Some(1),
+        groups: &[
+            group("VF reset", 0..64, 1..5),
+            group("memory", 0..64, 6..10),
+            group("display wait", 0..64, 11..15),
+            group("clipping", 0..64, 16..20),
+            group("shifting", 0..64, 21..25),
+            group("jumping", 0..64, 26..30),
+        ],
+    },
+    SuiteRom {
+        name: "BC_test",
+        rom: include_bytes!("../assets/test-roms/2-bc-test.ch8"),
+        expected: include_str!("../assets/test-roms/expected/2-bc-test.txt"),
+        frames: 120,
+        // it checks shifts and loads the CHIP-48 way
+        quirks: Quirks::SUPER_CHIP,
+        menu_choice: None,
+        groups: &[group("all opcodes", 0..64, 0..32)],
+    },
+];
+
+pub struct GroupResult {
+    pub name: &'static str,
+    pub passed: bool,
+}
+
+impl SuiteRom {
+    /// run the ROM, returning its final screen
+    pub fn run(&self) -> Result<Rows, Box<dyn Error>> {
+        let mut machine = Chip8Machine::new();
+        machine.set_quirks(self.quirks);
+        machine.set_instructions_per_second(INSTRUCTIONS_PER_SECOND);
+        machine.load(self.rom)?;
+        if let Some(choice) = self.menu_choice {
+            let mut snapshot = machine.snapshot();
+            snapshot.memory[0x1FF] = choice;
+            machine.restore(&snapshot);
+        }
+        Ok(machine.frames(&Inputs::default(), self.frames)?.rows)
+    }
+
+    /// which groups of `screen` match the expected screen
+    pub fn check(&self, screen: &Rows) -> Result<Vec<GroupResult>, Box<dyn Error>> {
+        let expected = parse_screen(self.expected)?;
+        Ok(self
+            .groups
+            .iter()
+            .map(|group| GroupResult {
+                name: group.name,
+                passed: group.rows.clone().all(|y| {
+                    group
+                        .columns
+                        .clone()
+                        .all(|x| is_lit(screen, x, y) == is_lit(&expected, x, y))
+                }),
+            })
+            .collect())
+    }
+}
+
+/// a screen in the format `test <rom>` prints
+pub fn parse_screen(text: &str) -> Result<Rows, Box<dyn Error>> {
+    let rows: Vec<&str> = text.lines().collect();
+    if rows.len() != SCREEN_HEIGHT || rows.iter().any(|row| row.len() != SCREEN_WIDTH) {
+        return Err(format!("expected {} rows of {} pixels", SCREEN_HEIGHT, SCREEN_WIDTH).into());
+    }
+    let mut screen = [0; HIRES_SCREEN_HEIGHT];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let on = match c {
+                '#' => true,
+                '.' => false,
+                _ => return Err(format!("unexpected {:?} in screen", c).into()),
+            };
+            set_lit(&mut screen, x, y, on);
+        }
+    }
+    Ok(screen)
+}
+
+/// the top `height` rows of a screen, in the format `parse_screen` reads
+/// when `height` is `SCREEN_HEIGHT`
+pub fn format_screen(rows: &Rows, height: usize) -> String {
+    (0..height)
+        .map(|y| {
+            let mut line: String = (0..SCREEN_WIDTH)
+                .map(|x| if is_lit(rows, x, y) { '#' } else { '.' })
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_round_trip() {
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        set_lit(&mut rows, 3, 0, true);
+        set_lit(&mut rows, SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1, true);
+        assert_eq!(
+            parse_screen(&format_screen(&rows, SCREEN_HEIGHT)).unwrap(),
+            rows
+        );
+        assert!(parse_screen("#.\n").is_err());
+    }
+
+    #[test]
+    fn test_suite() {
+        let mut failures = vec![];
+        for rom in &SUITE {
+            for result in rom.check(&rom.run().unwrap()).unwrap() {
+                if !result.passed {
+                    failures.push(result.name);
+                }
+            }
+        }
+        assert!(failures.is_empty(), "failed: {:?}", failures);
+    }
+}
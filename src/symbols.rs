@@ -0,0 +1,112 @@
+//! Symbol files, naming addresses in a ROM so the debugger, the history
+//! panel, and `disasm` can say `draw_paddle` rather than `0x2a4`. `asm`
+//! writes one next to the ROM it builds, with a line per label:
+//!
+//! ```text
+//! # comments and blank lines are skipped
+//! 0x2a4 draw_paddle
+//! ```
+//!
+//! A ROM picks up `<rom>.sym` next to it unless `--symbols` names another
+//! file, and `.8o` sources run directly name their labels without one.
+
+use std::{collections::BTreeMap, error::Error, path::Path};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn new(names: BTreeMap<u16, String>) -> Self {
+        Symbols { names }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut names = BTreeMap::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad = || format!("line {}: expected an address and a name", idx + 1);
+            let (addr, name) = line.split_once(char::is_whitespace).ok_or_else(bad)?;
+            let addr = addr.strip_prefix("0x").unwrap_or(addr);
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| bad())?;
+            names.insert(addr, name.trim().to_string());
+        }
+        Ok(Symbols { names })
+    }
+
+    pub fn read(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Symbols::parse(&std::fs::read_to_string(path)?)
+            .map_err(|e| format!("{}: {}", path, e))?)
+    }
+
+    /// where the symbols of the ROM at `path` are looked for without `--symbols`
+    pub fn default_path(path: &str) -> Option<String> {
+        let path = Path::new(path).with_extension("sym");
+        path.exists().then(|| path.display().to_string())
+    }
+
+    pub fn to_text(&self) -> String {
+        self.names
+            .iter()
+            .map(|(addr, name)| format!("{:#05x} {}\n", addr, name))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.names.iter().map(|(addr, name)| (*addr, name.as_str()))
+    }
+
+    /// the name of exactly `addr`
+    pub fn name(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(String::as_str)
+    }
+
+    /// the name of `addr`, or the address in hex
+    pub fn format(&self, addr: u16) -> String {
+        self.name(addr)
+            .map_or_else(|| format!("{:#05x}", addr), String::from)
+    }
+
+    /// `addr` as an offset from the nearest name at or before it, e.g. `draw_paddle+4`
+    pub fn locate(&self, addr: u16) -> Option<String> {
+        let (start, name) = self.names.range(..=addr).next_back()?;
+        Some(match addr - start {
+            0 => name.clone(),
+            offset => format!("{}+{}", name, offset),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let symbols =
+            Symbols::parse("# from asm\n0x2A4 draw_paddle\n\n200 main  # entry\n").unwrap();
+        assert_eq!(symbols.name(0x2A4), Some("draw_paddle"));
+        assert_eq!(symbols.format(0x200), "main");
+        assert_eq!(symbols.format(0x300), "0x300");
+        assert_eq!(symbols.to_text(), "0x200 main\n0x2a4 draw_paddle\n");
+        assert_eq!(Symbols::parse(&symbols.to_text()), Ok(symbols));
+        assert!(Symbols::parse("draw_paddle").is_err());
+        assert!(Symbols::parse("0xZZZ draw_paddle").is_err());
+    }
+
+    #[test]
+    fn test_locate() {
+        let symbols = Symbols::parse("0x200 main\n0x2a4 draw_paddle").unwrap();
+        assert_eq!(symbols.locate(0x1FE), None);
+        assert_eq!(symbols.locate(0x200).as_deref(), Some("main"));
+        assert_eq!(symbols.locate(0x2A8).as_deref(), Some("draw_paddle+4"));
+    }
+}
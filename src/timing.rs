@@ -0,0 +1,199 @@
+/// target rate of the delay/sound timers
+pub const TIMER_HZ: f64 = 60.;
+
+/// a frame taking longer than this many target frame times counts as dropped
+const DROPPED_FRAME_FACTOR: f64 = 1.5;
+
+/// most ticks to run in one render frame; after a longer stall (e.g. the
+/// window being dragged) the emulation falls behind instead of racing to catch up
+const MAX_TICKS_PER_FRAME: f64 = 4.;
+
+/// Turns measured render frame times into how many 60 Hz ticks to run, so
+/// emulation speed doesn't depend on the display's refresh rate.
+pub struct TickClock {
+    /// ticks owed, including a fraction carried over to the next frame
+    pending: f64,
+}
+
+impl TickClock {
+    pub fn new() -> Self {
+        TickClock { pending: 0. }
+    }
+
+    pub fn ticks_due(&mut self, frame_time: f64) -> u32 {
+        self.pending = (self.pending + frame_time * TIMER_HZ).min(MAX_TICKS_PER_FRAME);
+        let ticks = self.pending.floor();
+        self.pending -= ticks;
+        ticks as u32
+    }
+}
+
+/// One second worth of measurements, compared against the configured targets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimingSample {
+    pub instructions_per_sec: f64,
+    pub target_instructions_per_sec: f64,
+    pub timer_hz: f64,
+    pub frames_per_sec: f64,
+    pub dropped_frames: u32,
+}
+
+impl TimingSample {
+    /// relative deviation from the target instruction rate, e.g. -0.25 when running 25% slow
+    pub fn instruction_drift(&self) -> f64 {
+        drift(self.instructions_per_sec, self.target_instructions_per_sec)
+    }
+
+    /// relative deviation from the 60 Hz timer rate
+    pub fn timer_drift(&self) -> f64 {
+        drift(self.timer_hz, TIMER_HZ)
+    }
+}
+
+fn drift(actual: f64, target: f64) -> f64 {
+    if target == 0. {
+        return 0.;
+    }
+    (actual - target) / target
+}
+
+/// Tracks how actual instruction throughput and timer ticks deviate from
+/// their targets. Counts accumulate over a one second window; the last
+/// complete window is available via `last_sample`.
+pub struct TimingStats {
+    target_instructions_per_sec: f64,
+    window_start: f64,
+    instructions: u32,
+    timer_ticks: u32,
+    frames: u32,
+    dropped_frames: u32,
+    last_sample: Option<TimingSample>,
+}
+
+impl TimingStats {
+    pub fn new(target_instructions_per_sec: f64, now: f64) -> Self {
+        TimingStats {
+            target_instructions_per_sec,
+            window_start: now,
+            instructions: 0,
+            timer_ticks: 0,
+            frames: 0,
+            dropped_frames: 0,
+            last_sample: None,
+        }
+    }
+
+    /// the instruction rate to compare against, from the next sample on
+    pub fn set_target_instructions_per_sec(&mut self, target: f64) {
+        self.target_instructions_per_sec = target;
+    }
+
+    pub fn record_instructions(&mut self, count: u32) {
+        self.instructions += count;
+    }
+
+    pub fn record_timer_tick(&mut self) {
+        self.timer_ticks += 1;
+    }
+
+    /// `frame_time` is the duration of the frame in seconds
+    pub fn record_frame(&mut self, frame_time: f64) {
+        self.frames += 1;
+        if frame_time > DROPPED_FRAME_FACTOR / TIMER_HZ {
+            self.dropped_frames += 1;
+        }
+    }
+
+    /// closes the current window once a second has passed, returning the new sample
+    pub fn update(&mut self, now: f64) -> Option<TimingSample> {
+        let elapsed = now - self.window_start;
+        if elapsed < 1. {
+            return None;
+        }
+
+        let sample = TimingSample {
+            instructions_per_sec: self.instructions as f64 / elapsed,
+            target_instructions_per_sec: self.target_instructions_per_sec,
+            timer_hz: self.timer_ticks as f64 / elapsed,
+            frames_per_sec: self.frames as f64 / elapsed,
+            dropped_frames: self.dropped_frames,
+        };
+        log::info!(
+            target: "metrics",
+            "ips: {:.0} ({:+.1}%), timer: {:.1} Hz ({:+.1}%), fps: {:.1}, dropped frames: {}",
+            sample.instructions_per_sec,
+            sample.instruction_drift() * 100.,
+            sample.timer_hz,
+            sample.timer_drift() * 100.,
+            sample.frames_per_sec,
+            sample.dropped_frames,
+        );
+
+        self.window_start = now;
+        self.instructions = 0;
+        self.timer_ticks = 0;
+        self.frames = 0;
+        self.dropped_frames = 0;
+        self.last_sample = Some(sample);
+
+        Some(sample)
+    }
+
+    pub fn last_sample(&self) -> Option<TimingSample> {
+        self.last_sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_reports_drift_from_targets() {
+        let mut stats = TimingStats::new(300., 0.);
+        for _ in 0..30 {
+            stats.record_instructions(5);
+            stats.record_timer_tick();
+            stats.record_frame(1. / 30.);
+        }
+        assert_eq!(stats.update(0.5), None);
+
+        let sample = stats.update(1.).unwrap();
+        assert_eq!(sample.instructions_per_sec, 150.);
+        assert_eq!(sample.instruction_drift(), -0.5);
+        assert_eq!(sample.timer_hz, 30.);
+        assert_eq!(sample.timer_drift(), -0.5);
+        assert_eq!(sample.dropped_frames, 30);
+        assert_eq!(stats.last_sample(), Some(sample));
+    }
+
+    #[test]
+    fn test_update_starts_a_new_window() {
+        let mut stats = TimingStats::new(300., 0.);
+        stats.record_instructions(300);
+        stats.record_frame(1. / 60.);
+        stats.update(1.);
+
+        let sample = stats.update(2.).unwrap();
+        assert_eq!(sample.instructions_per_sec, 0.);
+        assert_eq!(sample.dropped_frames, 0);
+
+        stats.set_target_instructions_per_sec(600.);
+        stats.record_instructions(300);
+        assert_eq!(stats.update(3.).unwrap().instruction_drift(), -0.5);
+    }
+
+    #[test]
+    fn test_tick_clock_follows_frame_time() {
+        let mut clock = TickClock::new();
+        // 120 Hz display: a tick every other frame
+        let ticks: Vec<u32> = (0..4).map(|_| clock.ticks_due(1. / 120.)).collect();
+        assert_eq!(ticks.iter().sum::<u32>(), 2);
+        // 30 Hz display: two ticks per frame
+        clock.ticks_due(1. / 60.);
+        assert_eq!(clock.ticks_due(1. / 30.), 2);
+        // a long stall doesn't fast forward
+        assert_eq!(clock.ticks_due(1.), 4);
+        assert_eq!(clock.ticks_due(0.), 0);
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+
+use crate::ui_style::MARGIN;
+
+/// how long a toast stays on screen, in seconds
+const TOAST_DURATION: f32 = 2.5;
+/// toasts fade out over the last part of their lifetime
+const TOAST_FADE: f32 = 0.5;
+/// older toasts are dropped once there are more than this many
+const MAX_TOASTS: usize = 4;
+
+const FONT_SIZE: f32 = 24.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastLevel {
+    Info,
+    Warning,
+}
+
+struct Toast {
+    message: String,
+    level: ToastLevel,
+    remaining: f32,
+}
+
+/// Short-lived notifications drawn in the bottom-left corner of the window,
+/// so features can give feedback ("State 3 saved") without permanently
+/// covering the display.
+pub struct Toasts {
+    toasts: VecDeque<Toast>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Toasts {
+            toasts: VecDeque::new(),
+        }
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(message.into(), ToastLevel::Info);
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        log::warn!("{}", message);
+        self.push(message, ToastLevel::Warning);
+    }
+
+    fn push(&mut self, message: String, level: ToastLevel) {
+        self.toasts.push_back(Toast {
+            message,
+            level,
+            remaining: TOAST_DURATION,
+        });
+        while self.toasts.len() > MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+    }
+
+    /// advance toast lifetimes by `dt` seconds, dropping expired ones
+    pub fn update(&mut self, dt: f32) {
+        for toast in self.toasts.iter_mut() {
+            toast.remaining -= dt;
+        }
+        self.toasts.retain(|t| t.remaining > 0.);
+    }
+
+    pub fn draw(&self) {
+        // newest toast sits at the bottom, older ones stack upwards
+        let mut y = screen_height() - MARGIN;
+        for toast in self.toasts.iter().rev() {
+            let alpha = clamp(toast.remaining / TOAST_FADE, 0., 1.);
+            let dims = measure_text(&toast.message, None, FONT_SIZE as u16, 1.);
+            let padding = 6.;
+
+            draw_rectangle(
+                MARGIN,
+                y - dims.height - padding * 2.,
+                dims.width + padding * 2.,
+                dims.height + padding * 2.,
+                Color::new(0., 0., 0., 0.6 * alpha),
+            );
+            let color = match toast.level {
+                ToastLevel::Info => Color::new(1., 1., 1., alpha),
+                ToastLevel::Warning => Color::new(1., 0.8, 0.2, alpha),
+            };
+            draw_text(
+                &toast.message,
+                MARGIN + padding,
+                y - padding - (dims.height - dims.offset_y),
+                FONT_SIZE,
+                color,
+            );
+
+            y -= dims.height + padding * 2. + 4.;
+        }
+    }
+}
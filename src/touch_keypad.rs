@@ -0,0 +1,118 @@
+//! The hex keypad as buttons on the screen, for phones and tablets with
+//! no keyboard. It stays hidden until the screen is first touched, then
+//! takes the bottom of the window, with the display fitted above it. Every
+//! finger on a button holds its key, so several can be held at once.
+
+use macroquad::prelude::*;
+
+use crate::remap::LAYOUT;
+
+const FONT_SIZE: f32 = 32.;
+/// most of the window's height the keypad takes
+const MAX_SHARE: f32 = 0.45;
+const MARGIN: f32 = 8.;
+
+pub struct TouchKeypad {
+    visible: bool,
+}
+
+impl TouchKeypad {
+    pub fn new() -> Self {
+        TouchKeypad { visible: false }
+    }
+
+    /// the CHIP-8 keys under a finger this frame
+    pub fn update(&mut self) -> [bool; 16] {
+        let touches = touches();
+        self.visible |= !touches.is_empty();
+        if !self.visible {
+            return [false; 16];
+        }
+        let fingers = touches
+            .iter()
+            .filter(|touch| !matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled))
+            .map(|touch| touch.position);
+        held_keys(grid(screen_width(), screen_height()), fingers)
+    }
+
+    /// height left above the keypad for the display
+    pub fn display_height(&self) -> f32 {
+        if self.visible {
+            grid(screen_width(), screen_height()).y - MARGIN
+        } else {
+            screen_height()
+        }
+    }
+
+    pub fn draw(&self, pressed: &[bool; 16]) {
+        if !self.visible {
+            return;
+        }
+        let grid = grid(screen_width(), screen_height());
+        let cell = grid.w / 4.;
+        for (idx, slot) in LAYOUT.iter().enumerate() {
+            let x = grid.x + (idx % 4) as f32 * cell;
+            let y = grid.y + (idx / 4) as f32 * cell;
+            let fill = if pressed[*slot] {
+                DARKBLUE
+            } else {
+                Color::new(0.2, 0.2, 0.2, 0.8)
+            };
+            draw_rectangle(x + 3., y + 3., cell - 6., cell - 6., fill);
+            let label = format!("{:X}", slot);
+            let dims = measure_text(&label, None, FONT_SIZE as u16, 1.);
+            draw_text(
+                &label,
+                x + (cell - dims.width) / 2.,
+                y + (cell + dims.height) / 2.,
+                FONT_SIZE,
+                WHITE,
+            );
+        }
+    }
+}
+
+/// the square the keypad fills in a window of the given size, centered
+/// along the bottom
+fn grid(window_width: f32, window_height: f32) -> Rect {
+    let size = (window_width - 2. * MARGIN).min(window_height * MAX_SHARE);
+    Rect::new(
+        (window_width - size) / 2.,
+        window_height - size - MARGIN,
+        size,
+        size,
+    )
+}
+
+/// the keys of the buttons in `grid` that any of `fingers` is on
+fn held_keys(grid: Rect, fingers: impl Iterator<Item = Vec2>) -> [bool; 16] {
+    let mut keys = [false; 16];
+    let cell = grid.w / 4.;
+    for finger in fingers.filter(|finger| grid.contains(*finger)) {
+        let col = (((finger.x - grid.x) / cell) as usize).min(3);
+        let row = (((finger.y - grid.y) / cell) as usize).min(3);
+        keys[LAYOUT[row * 4 + col]] = true;
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_held_keys() {
+        let keypad = grid(400., 1000.);
+        assert_eq!(keypad, Rect::new(8., 992. - 384., 384., 384.));
+
+        // one finger on 1 in the top-left corner, one on F in the bottom-right,
+        // and one above the keypad
+        let fingers = [vec2(10., keypad.y + 2.), vec2(390., 990.), vec2(200., 100.)];
+        let keys = held_keys(keypad, fingers.into_iter());
+        let held: Vec<usize> = (0..16).filter(|key| keys[*key]).collect();
+        assert_eq!(held, [0x1, 0xF]);
+
+        // a wide window keeps the keypad to the bottom of it
+        assert_eq!(grid(1000., 400.).h, 180.);
+    }
+}
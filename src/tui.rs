@@ -0,0 +1,450 @@
+//! A terminal frontend for `run --tui`, for playing over SSH or on machines
+//! without a GPU. Each character cell shows two CHIP-8 pixels stacked with
+//! half blocks, so pixels come out roughly square, and only the cells that
+//! changed since the last frame are redrawn. Uses crossterm, behind the
+//! default `tui` feature.
+
+use std::{
+    error::Error,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use chip8_core::interpreter::{dirty_pixels, is_lit, Rows, SCREEN_WIDTH};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{
+        self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+use crate::{
+    cli::TuiRenderer,
+    machine::{Chip8Machine, Inputs},
+    palette::Palette,
+    timing::TickClock,
+};
+
+/// how often the loop wakes up to run ticks and redraw
+const FRAME_TIME: Duration = Duration::from_micros(16_667);
+
+/// Most terminals only report presses, so a key counts as held for this
+/// long after each press or auto-repeat. Long enough to bridge the gaps
+/// between repeats while a key is held down.
+const HOLD_TIME: Duration = Duration::from_millis(200);
+
+/// The characters on screen, row by row, and the rows they show.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cells {
+    columns: usize,
+    chars: Vec<char>,
+    /// the pixels the cells cover, and how many of them
+    rows: Rows,
+    height: usize,
+    renderer: TuiRenderer,
+}
+
+impl Cells {
+    /// the top `height` of `rows` drawn with `renderer`: as half
+    /// blocks, one cell per column and two rows, or as braille, one cell per
+    /// 2x4 pixels
+    fn new(rows: &Rows, height: usize, renderer: TuiRenderer) -> Self {
+        let (cell_width, cell_height) = cell_size(renderer);
+        let columns = SCREEN_WIDTH / cell_width;
+        let mut cells = Cells {
+            columns,
+            chars: vec![],
+            rows: *rows,
+            height,
+            renderer,
+        };
+        cells.chars = (0..columns * (height / cell_height))
+            .map(|idx| cells.char_at(idx))
+            .collect();
+        cells
+    }
+
+    fn rows(&self) -> usize {
+        self.chars.len() / self.columns
+    }
+
+    /// the character for the cell at `idx`, from `self.rows`
+    fn char_at(&self, idx: usize) -> char {
+        let (cell_width, cell_height) = cell_size(self.renderer);
+        let (x, y) = (
+            idx % self.columns * cell_width,
+            idx / self.columns * cell_height,
+        );
+        let pixel = |x: usize, y: usize| is_lit(&self.rows, x, y);
+        match self.renderer {
+            TuiRenderer::HalfBlock => half_block(pixel(x, y), pixel(x, y + 1)),
+            TuiRenderer::Braille => braille(|dx, dy| pixel(x + dx, y + dy)),
+        }
+    }
+
+    /// column, row, and character of every cell, for drawing from scratch
+    fn every_cell(&self) -> Vec<(u16, u16, char)> {
+        (0..self.chars.len()).map(|idx| self.cell(idx)).collect()
+    }
+
+    /// switch to `rows`, recomputing just the cells over pixels that
+    /// changed, and return the column, row, and new character of those
+    /// that look different
+    fn update(&mut self, rows: &Rows) -> Vec<(u16, u16, char)> {
+        let (cell_width, cell_height) = cell_size(self.renderer);
+        let mut touched: Vec<usize> = dirty_pixels(&self.rows, rows)
+            .into_iter()
+            .map(|pixel| (pixel % SCREEN_WIDTH, pixel / SCREEN_WIDTH))
+            .filter(|(_, y)| y / cell_height < self.rows())
+            .map(|(x, y)| y / cell_height * self.columns + x / cell_width)
+            .collect();
+        touched.sort_unstable();
+        touched.dedup();
+        self.rows = *rows;
+        let mut changed = vec![];
+        for idx in touched {
+            let c = self.char_at(idx);
+            if self.chars[idx] != c {
+                self.chars[idx] = c;
+                changed.push(self.cell(idx));
+            }
+        }
+        changed
+    }
+
+    fn cell(&self, idx: usize) -> (u16, u16, char) {
+        (
+            (idx % self.columns) as u16,
+            (idx / self.columns) as u16,
+            self.chars[idx],
+        )
+    }
+}
+
+/// the pixels in each cell with `renderer`, across and down
+fn cell_size(renderer: TuiRenderer) -> (usize, usize) {
+    match renderer {
+        TuiRenderer::HalfBlock => (1, 2),
+        TuiRenderer::Braille => (2, 4),
+    }
+}
+
+fn half_block(top: bool, bottom: bool) -> char {
+    match (top, bottom) {
+        (true, true) => '█',
+        (true, false) => '▀',
+        (false, true) => '▄',
+        (false, false) => ' ',
+    }
+}
+
+/// the braille character with a dot for each lit pixel in a 2x4 block,
+/// given whether the pixel at each offset is lit
+fn braille(lit: impl Fn(usize, usize) -> bool) -> char {
+    // dots 1-3 and 7 go down the left column, 4-6 and 8 down the right
+    const DOTS: [(usize, usize); 8] = [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (0, 3),
+        (1, 3),
+    ];
+    let bits = DOTS
+        .iter()
+        .enumerate()
+        .filter(|(_, (dx, dy))| lit(*dx, *dy))
+        .fold(0, |bits, (bit, _)| bits | 1 << bit);
+    char::from_u32(0x2800 + bits).unwrap_or(' ')
+}
+
+/// Which CHIP-8 keys are held, from terminal key events. With `hold` set,
+/// keys are released that long after their last press; without it the
+/// terminal reports releases itself.
+struct HeldKeys {
+    hold: Option<Duration>,
+    /// when each key was last pressed, if it hasn't been released since
+    pressed_at: [Option<Instant>; 16],
+}
+
+impl HeldKeys {
+    fn new(hold: Option<Duration>) -> Self {
+        HeldKeys {
+            hold,
+            pressed_at: [None; 16],
+        }
+    }
+
+    fn press(&mut self, key: usize, now: Instant) {
+        self.pressed_at[key] = Some(now);
+    }
+
+    fn release(&mut self, key: usize) {
+        self.pressed_at[key] = None;
+    }
+
+    fn keys(&self, now: Instant) -> [bool; 16] {
+        self.pressed_at.map(|pressed_at| {
+            pressed_at.is_some_and(|at| self.hold.is_none_or(|hold| now - at < hold))
+        })
+    }
+}
+
+/// the config file's name for a key, for the keys that can be bound
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(' ') => "Space",
+        KeyCode::Char(c) => return Some(c.to_ascii_uppercase().to_string()),
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Enter => "Enter",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+fn color(rgb: u32) -> Color {
+    Color::Rgb {
+        r: (rgb >> 16) as u8,
+        g: (rgb >> 8) as u8,
+        b: rgb as u8,
+    }
+}
+
+/// Raw mode on the alternate screen, restoring the terminal when dropped,
+/// including on errors.
+struct RawTerminal {
+    /// the terminal reports key releases
+    enhanced: bool,
+}
+
+impl RawTerminal {
+    fn enter(palette: &Palette) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut raw = RawTerminal { enhanced: false };
+        execute!(
+            io::stdout(),
+            EnterAlternateScreen,
+            Hide,
+            SetForegroundColor(color(palette.foreground)),
+            SetBackgroundColor(color(palette.background)),
+            Clear(ClearType::All),
+        )?;
+        if terminal::supports_keyboard_enhancement().unwrap_or(false) {
+            execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+            raw.enhanced = true;
+        }
+        Ok(raw)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        if self.enhanced {
+            let _ = execute!(out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Run the loaded ROM in the terminal until Esc or Ctrl+C. `keypad` names
+/// the key for each CHIP-8 key, as in the config file. P pauses.
+pub fn run(
+    machine: &mut Chip8Machine,
+    keypad: &[String; 16],
+    palette: &Palette,
+    renderer: TuiRenderer,
+) -> Result<(), Box<dyn Error>> {
+    let raw = RawTerminal::enter(palette)?;
+    let mut held = HeldKeys::new((!raw.enhanced).then_some(HOLD_TIME));
+    let mut out = io::stdout();
+    let mut clock = TickClock::new();
+    let mut last_frame = Instant::now();
+    let mut drawn: Option<Cells> = None;
+    let mut drawn_status = None;
+    let mut paused = false;
+
+    loop {
+        let deadline = last_frame + FRAME_TIME;
+        while event::poll(deadline.saturating_duration_since(Instant::now()))? {
+            match event::read()? {
+                Event::Key(key) => {
+                    let pressed = key.kind != KeyEventKind::Release;
+                    let ctrl_c = key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL);
+                    if pressed && (key.code == KeyCode::Esc || ctrl_c) {
+                        return Ok(());
+                    }
+                    if key.kind == KeyEventKind::Press && key.code == KeyCode::Char('p') {
+                        paused = !paused;
+                    }
+                    let name = key_name(key.code);
+                    if let Some(idx) = keypad.iter().position(|k| Some(k) == name.as_ref()) {
+                        if pressed {
+                            held.press(idx, Instant::now());
+                        } else {
+                            held.release(idx);
+                        }
+                    }
+                }
+                Event::Resize(..) => {
+                    queue!(out, Clear(ClearType::All))?;
+                    drawn = None;
+                    drawn_status = None;
+                }
+                _ => {}
+            }
+        }
+
+        let now = Instant::now();
+        let ticks = clock.ticks_due((now - last_frame).as_secs_f64());
+        last_frame = now;
+        let output = if paused {
+            machine.idle_frame()
+        } else {
+            let inputs = Inputs {
+                keys: held.keys(now),
+                ..Default::default()
+            };
+            machine.frames(&inputs, ticks)?
+        };
+        if output.stop.is_some() {
+            paused = true;
+        }
+        if output.sound_changed && output.sound {
+            queue!(out, Print('\x07'))?;
+        }
+
+        let changed = match drawn.as_mut() {
+            Some(cells) if cells.height == output.height => cells.update(&output.rows),
+            _ => {
+                let cells = Cells::new(&output.rows, output.height, renderer);
+                let changed = cells.every_cell();
+                drawn = Some(cells);
+                changed
+            }
+        };
+        for (col, row, c) in changed {
+            queue!(out, MoveTo(col, row), Print(c))?;
+        }
+        let status_row = drawn.as_ref().map_or(0, |cells| cells.rows() as u16);
+        let status = match output.stop {
+            Some(stop) if paused => format!("Stopped at {}, P to continue, Esc to quit", stop),
+            _ if paused => String::from("Paused, P to continue, Esc to quit"),
+            _ => String::from("P to pause, Esc to quit"),
+        };
+        if drawn_status.as_ref() != Some(&status) {
+            queue!(
+                out,
+                MoveTo(0, status_row),
+                Print(&status),
+                Clear(ClearType::UntilNewLine)
+            )?;
+            drawn_status = Some(status);
+        }
+        out.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::interpreter::{set_lit, HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT};
+
+    #[test]
+    fn test_half_block_cells() {
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (2, 1)] {
+            set_lit(&mut rows, x, y, true);
+        }
+        let cells = Cells::new(&rows, SCREEN_HEIGHT, TuiRenderer::HalfBlock);
+        assert_eq!((cells.columns, cells.rows()), (64, 16));
+        assert_eq!(cells.chars[..4], ['█', '▀', '▄', ' ']);
+        assert!(cells.chars[4..].iter().all(|c| *c == ' '));
+    }
+
+    #[test]
+    fn test_braille_cells() {
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        // left column of the first cell, and the bottom right of the second
+        for y in 0..4 {
+            set_lit(&mut rows, 0, y, true);
+        }
+        set_lit(&mut rows, 3, 3, true);
+        let cells = Cells::new(&rows, SCREEN_HEIGHT, TuiRenderer::Braille);
+        assert_eq!((cells.columns, cells.rows()), (32, 8));
+        assert_eq!(cells.chars[..3], ['⡇', '⢀', '⠀']);
+        assert_eq!(
+            Cells::new(
+                &[u64::MAX; HIRES_SCREEN_HEIGHT],
+                SCREEN_HEIGHT,
+                TuiRenderer::Braille
+            )
+            .chars[0],
+            '⣿'
+        );
+    }
+
+    #[test]
+    fn test_changed_cells() {
+        let mut cells = Cells::new(
+            &[0; HIRES_SCREEN_HEIGHT],
+            SCREEN_HEIGHT,
+            TuiRenderer::HalfBlock,
+        );
+        assert_eq!(cells.every_cell().len(), cells.chars.len());
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        set_lit(&mut rows, 3, 2, true);
+        // below the shown rows
+        set_lit(&mut rows, 0, SCREEN_HEIGHT, true);
+        assert_eq!(cells.update(&rows), [(3, 1, '▀')]);
+        assert_eq!(
+            cells,
+            Cells::new(&rows, SCREEN_HEIGHT, TuiRenderer::HalfBlock)
+        );
+        // lighting the other half of a cell redraws it once
+        set_lit(&mut rows, 3, 3, true);
+        set_lit(&mut rows, 4, 3, true);
+        assert_eq!(cells.update(&rows), [(3, 1, '█'), (4, 1, '▄')]);
+        assert_eq!(cells.update(&rows), []);
+    }
+
+    #[test]
+    fn test_held_keys() {
+        let start = Instant::now();
+        let mut held = HeldKeys::new(Some(HOLD_TIME));
+        held.press(5, start);
+        assert!(held.keys(start + HOLD_TIME / 2)[5]);
+        assert!(!held.keys(start + HOLD_TIME)[5]);
+
+        // with release events, keys stay down until released
+        let mut held = HeldKeys::new(None);
+        held.press(5, start);
+        assert!(held.keys(start + HOLD_TIME * 10)[5]);
+        held.release(5);
+        assert_eq!(held.keys(start), [false; 16]);
+    }
+
+    #[test]
+    fn test_key_name() {
+        assert_eq!(key_name(KeyCode::Char('q')).as_deref(), Some("Q"));
+        assert_eq!(key_name(KeyCode::Char('1')).as_deref(), Some("1"));
+        assert_eq!(key_name(KeyCode::Char(' ')).as_deref(), Some("Space"));
+        assert_eq!(key_name(KeyCode::Esc), None);
+    }
+}
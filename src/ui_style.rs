@@ -0,0 +1,15 @@
+//! Sizes and colors shared by the panels drawn over the game, so the menus,
+//! prompts, and debugger panes all look alike.
+
+use macroquad::color::Color;
+
+pub const FONT_SIZE: f32 = 22.;
+pub const MARGIN: f32 = 12.;
+/// between lines of text
+pub const LINE_HEIGHT: f32 = 22.;
+/// between the rows of a menu or list, which leaves room to highlight one
+pub const ROW_HEIGHT: f32 = 26.;
+/// behind panels that take the keyboard, like menus and prompts
+pub const PANEL_BACKGROUND: Color = Color::new(0., 0., 0., 0.8);
+/// behind panels shown while the game goes on, like the HUD
+pub const OVERLAY_BACKGROUND: Color = Color::new(0., 0., 0., 0.6);
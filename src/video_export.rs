@@ -0,0 +1,264 @@
+//! `run --record-video`: the session as a video file, encoded by ffmpeg.
+//!
+//! Frames go to an ffmpeg child process over its stdin at a fixed 60 fps,
+//! one per 60 Hz tick, while the audio is written next to the output as a
+//! WAV by `AudioCapture`. When recording stops, a second ffmpeg run muxes
+//! the two into the file asked for, with whatever codecs ffmpeg picks for
+//! its extension, e.g. H.264 and AAC for `.mp4`. MegaChip's display isn't
+//! recorded, only the CHIP-8 one.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{channel, Sender},
+    thread::JoinHandle,
+};
+
+use chip8_core::{
+    interpreter::{is_lit, Rows, SCREEN_WIDTH},
+    megachip::SoundEvent,
+};
+
+use crate::{
+    audio_capture::AudioCapture,
+    buzzer::Tone,
+    palette::Palette,
+    screenshot::{rgb_palette, SCALE},
+    timing::TIMER_HZ,
+};
+
+pub struct VideoRecorder {
+    path: String,
+    video_path: PathBuf,
+    audio_path: PathBuf,
+    /// rows in every frame, fixed by the display when recording started
+    height: usize,
+    frames: Sender<Vec<u8>>,
+    /// feeds frames to ffmpeg, then waits for it to finish encoding
+    encoder: JoinHandle<io::Result<()>>,
+    audio: AudioCapture<File>,
+}
+
+impl VideoRecorder {
+    /// Start ffmpeg encoding frames that are the top `height` rows of the
+    /// display, and capturing audio in `tone`.
+    pub fn start(path: &str, height: usize, tone: Tone) -> Result<Self, Box<dyn Error>> {
+        let (video_path, audio_path) = temp_paths(Path::new(path));
+        let child = Command::new("ffmpeg")
+            .args(encode_args(height, &video_path))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => String::from("ffmpeg isn't installed"),
+                _ => format!("could not start ffmpeg: {}", e),
+            })?;
+        let audio = AudioCapture::create(&audio_path.to_string_lossy(), tone)?;
+        let (frames, receiver) = channel::<Vec<u8>>();
+        let encoder = std::thread::spawn(move || {
+            let mut child = child;
+            let mut stdin: ChildStdin = child.stdin.take().expect("stdin is piped");
+            for frame in receiver {
+                stdin.write_all(&frame)?;
+            }
+            drop(stdin);
+            wait(child, "encoding")
+        });
+        Ok(VideoRecorder {
+            path: path.to_string(),
+            video_path,
+            audio_path,
+            height,
+            frames,
+            encoder,
+            audio,
+        })
+    }
+
+    /// switch the audio to the buzzer's new tone
+    pub fn set_tone(&mut self, tone: Tone) {
+        self.audio.set_tone(tone);
+    }
+
+    /// start or stop a digitized sound in the audio
+    pub fn handle(&mut self, event: &SoundEvent) {
+        self.audio.handle(event);
+    }
+
+    /// Add one 60 Hz tick of the session: the top `height` of `rows`,
+    /// in `palette`'s colors, with the buzzer sounding if `buzzer` is set.
+    pub fn push(
+        &mut self,
+        rows: &Rows,
+        height: usize,
+        palette: &Palette,
+        buzzer: bool,
+    ) -> io::Result<()> {
+        let frame = frame_bytes(rows, height, self.height, palette);
+        self.frames
+            .send(frame)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "ffmpeg stopped"))?;
+        self.audio.push(1. / TIMER_HZ as f32, buzzer)
+    }
+
+    /// Wait for ffmpeg to encode the frames so far, then mux them with the
+    /// audio into the output and remove the intermediate files. Returns
+    /// the output's path.
+    pub fn finish(self) -> Result<String, Box<dyn Error>> {
+        drop(self.frames);
+        drop(self.audio);
+        self.encoder
+            .join()
+            .map_err(|_| "the encoder thread panicked")??;
+        let child = Command::new("ffmpeg")
+            .args(mux_args(&self.video_path, &self.audio_path, &self.path))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()?;
+        wait(child, "muxing")?;
+        std::fs::remove_file(&self.video_path)?;
+        std::fs::remove_file(&self.audio_path)?;
+        Ok(self.path)
+    }
+}
+
+fn wait(mut child: Child, step: &str) -> io::Result<()> {
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "ffmpeg failed {}: {}",
+            step, status
+        )));
+    }
+    Ok(())
+}
+
+/// where the video and audio go before they're muxed, next to `path`
+fn temp_paths(path: &Path) -> (PathBuf, PathBuf) {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let video = match path.extension() {
+        Some(ext) => format!("{}.video.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.video", stem),
+    };
+    (
+        path.with_file_name(video),
+        path.with_file_name(format!("{}.audio.wav", stem)),
+    )
+}
+
+/// ffmpeg arguments for encoding raw frames `height` rows tall from stdin
+/// into `out`, scaled up to the size of a screenshot without smoothing
+fn encode_args(height: usize, out: &Path) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "-y",
+        "-loglevel",
+        "error",
+        "-f",
+        "rawvideo",
+        "-pixel_format",
+        "rgb24",
+        "-video_size",
+        &format!("{}x{}", SCREEN_WIDTH, height),
+        "-framerate",
+        &format!("{}", TIMER_HZ),
+        "-i",
+        "pipe:0",
+        "-vf",
+        &format!("scale=iw*{0}:ih*{0}:flags=neighbor", SCALE),
+        // the pixel format players expect
+        "-pix_fmt",
+        "yuv420p",
+    ]
+    .map(String::from)
+    .to_vec();
+    args.push(out.to_string_lossy().into_owned());
+    args
+}
+
+/// ffmpeg arguments for putting the encoded `video` and `audio` together in `out`
+fn mux_args(video: &Path, audio: &Path, out: &str) -> Vec<String> {
+    let video = video.to_string_lossy();
+    let audio = audio.to_string_lossy();
+    [
+        "-y",
+        "-loglevel",
+        "error",
+        "-i",
+        &video,
+        "-i",
+        &audio,
+        "-c:v",
+        "copy",
+        "-shortest",
+        out,
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// `frame_height` rows of RGB pixels, from the top `height` of `rows`
+/// padded with unlit rows or cut short to fit
+fn frame_bytes(rows: &Rows, height: usize, frame_height: usize, palette: &Palette) -> Vec<u8> {
+    let colors = rgb_palette(palette);
+    (0..SCREEN_WIDTH * frame_height)
+        .flat_map(|idx| {
+            let (x, y) = (idx % SCREEN_WIDTH, idx / SCREEN_WIDTH);
+            let lit = y < height && is_lit(rows, x, y);
+            let start = usize::from(lit) * 3;
+            [colors[start], colors[start + 1], colors[start + 2]]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::interpreter::{set_lit, HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT};
+
+    use super::*;
+
+    #[test]
+    fn test_temp_paths() {
+        assert_eq!(
+            temp_paths(Path::new("out/run.mp4")),
+            (
+                PathBuf::from("out/run.video.mp4"),
+                PathBuf::from("out/run.audio.wav")
+            )
+        );
+        assert_eq!(temp_paths(Path::new("run")).0, PathBuf::from("run.video"));
+    }
+
+    #[test]
+    fn test_ffmpeg_args() {
+        let args = encode_args(SCREEN_HEIGHT, Path::new("run.video.webm"));
+        assert_eq!(args[args.len() - 1], "run.video.webm");
+        assert!(args.windows(2).any(|pair| pair == ["-video_size", "64x32"]));
+        assert!(args.windows(2).any(|pair| pair == ["-framerate", "60"]));
+
+        let args = mux_args(Path::new("v.mp4"), Path::new("a.wav"), "run.mp4");
+        assert!(args.windows(2).any(|pair| pair == ["-c:v", "copy"]));
+        assert_eq!(args[args.len() - 1], "run.mp4");
+    }
+
+    #[test]
+    fn test_frame_bytes() {
+        let palette = Palette::DEFAULT;
+        let [off, on] = [&rgb_palette(&palette)[..3], &rgb_palette(&palette)[3..]];
+        let mut rows = [0; HIRES_SCREEN_HEIGHT];
+        set_lit(&mut rows, 1, 0, true);
+        set_lit(&mut rows, 0, SCREEN_HEIGHT, true);
+
+        let frame = frame_bytes(&rows, SCREEN_HEIGHT, SCREEN_HEIGHT, &palette);
+        assert_eq!(frame.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 3);
+        assert_eq!((&frame[..3], &frame[3..6]), (off, on));
+
+        // a lores display recorded at the hires size leaves the rows below it unlit
+        let frame = frame_bytes(&rows, SCREEN_HEIGHT, HIRES_SCREEN_HEIGHT, &palette);
+        assert_eq!(frame.len(), SCREEN_WIDTH * HIRES_SCREEN_HEIGHT * 3);
+        let below = SCREEN_WIDTH * SCREEN_HEIGHT * 3;
+        assert_eq!(&frame[below..below + 3], off);
+    }
+}